@@ -0,0 +1,168 @@
+//! Named channels players talk in.
+
+use flecs_ecs::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::Template;
+
+/// The channel players talk in until they switch to another one.
+pub const DEFAULT_CHANNEL: &str = "global";
+
+/// Who reads the messages of a channel.
+#[derive(Clone, Copy, Debug)]
+pub enum Audience {
+    /// Every player.
+    Everyone,
+    /// The players in the same world who see the chunk the sender is in.
+    Local,
+    /// The players who joined the channel with [`Channel::join`]. Only they can talk in it.
+    Members,
+    /// The players the function accepts for a sender, such as their teammates.
+    Filter(fn(world: &World, sender: Entity, receiver: Entity) -> bool),
+}
+
+#[derive(Clone, Debug)]
+pub struct Channel {
+    /// What `{channel}` in the template is replaced with.
+    pub display_name: String,
+    pub template: Template,
+    pub audience: Audience,
+    /// How many ticks players have to wait between messages, `0` for no limit.
+    pub cooldown: i64,
+    members: FxHashSet<Entity>,
+}
+
+impl Channel {
+    #[must_use]
+    pub fn new(display_name: impl Into<String>, template: Template, audience: Audience) -> Self {
+        Self {
+            display_name: display_name.into(),
+            template,
+            audience,
+            cooldown: 0,
+            members: FxHashSet::default(),
+        }
+    }
+
+    pub fn join(&mut self, player: Entity) {
+        self.members.insert(player);
+    }
+
+    pub fn leave(&mut self, player: Entity) {
+        self.members.remove(&player);
+    }
+
+    #[must_use]
+    pub fn is_member(&self, player: Entity) -> bool {
+        self.members.contains(&player)
+    }
+
+    /// Whether `player` can talk in the channel.
+    #[must_use]
+    pub fn can_talk(&self, player: Entity) -> bool {
+        !matches!(self.audience, Audience::Members) || self.is_member(player)
+    }
+
+    /// Whether `receiver` reads what `sender` writes in the channel.
+    #[must_use]
+    pub fn reads(&self, world: &World, sender: Entity, receiver: Entity) -> bool {
+        match self.audience {
+            // who is near the sender is up to the proxy
+            Audience::Everyone | Audience::Local => true,
+            Audience::Members => self.is_member(receiver),
+            Audience::Filter(filter) => filter(world, sender, receiver),
+        }
+    }
+}
+
+/// All channels by name.
+#[derive(Component, Debug, Default)]
+pub struct Channels {
+    channels: FxHashMap<String, Channel>,
+}
+
+impl Channels {
+    /// Adds a channel, replacing the one called `name` if there is one.
+    pub fn insert(&mut self, name: impl Into<String>, channel: Channel) {
+        self.channels.insert(name.into(), channel);
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Channel> {
+        self.channels.remove(name)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Channel> {
+        self.channels.get_mut(name)
+    }
+
+    /// The names of the channels `player` can talk in, sorted.
+    #[must_use]
+    pub fn available(&self, player: Entity) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .channels
+            .iter()
+            .filter(|(_, channel)| channel.can_talk(player))
+            .map(|(name, _)| name.as_str())
+            .collect();
+
+        names.sort_unstable();
+        names
+    }
+
+    /// Removes `player` from the members of every channel.
+    pub fn leave_all(&mut self, player: Entity) {
+        for channel in self.channels.values_mut() {
+            channel.leave(player);
+        }
+    }
+}
+
+/// The channel a player talks in.
+#[derive(Component, Clone, Debug)]
+pub struct ChatChannel(pub String);
+
+impl Default for ChatChannel {
+    fn default() -> Self {
+        Self(DEFAULT_CHANNEL.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+
+    use super::{Audience, Channel, Channels};
+    use crate::Template;
+
+    #[test]
+    fn only_members_use_member_channels() {
+        let world = World::new();
+        let alice = world.entity().id();
+        let bob = world.entity().id();
+
+        let template = Template::parse("{name}: {message}").unwrap();
+
+        let mut channels = Channels::default();
+        channels.insert(
+            "global",
+            Channel::new("Global", template.clone(), Audience::Everyone),
+        );
+        channels.insert("staff", Channel::new("Staff", template, Audience::Members));
+        channels.get_mut("staff").unwrap().join(alice);
+
+        assert_eq!(channels.available(alice), ["global", "staff"]);
+        assert_eq!(channels.available(bob), ["global"]);
+
+        let staff = channels.get("staff").unwrap();
+        assert!(staff.reads(&world, bob, alice));
+        assert!(!staff.reads(&world, alice, bob));
+
+        channels.leave_all(alice);
+        assert_eq!(channels.available(alice), ["global"]);
+    }
+}
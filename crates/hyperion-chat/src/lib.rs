@@ -0,0 +1,418 @@
+//! Chat channels, message formatting and private messages.
+//!
+//! Every chat message is sent in the [`ChatChannel`] of its sender, which decides who reads it and
+//! how it looks through its [`Template`]. Messages are built from [`hyperion_text::Text`], so names
+//! can have hover and click events. Signed messages are relayed with their signature, everything
+//! else as system messages. See [`hyperion::simulation::chat`] for how signed chat is handled.
+
+use anyhow::{Context, bail};
+use flecs_ecs::prelude::*;
+use hyperion::{
+    ingress::{PendingRemove, moderation::Moderation},
+    net::{Compose, ConnectionId, DataBundle, agnostic, packets::GameMessageS2c},
+    simulation::{
+        Name, PacketState, Player, Position, Uuid, chat::LastSeen, event, util::chat_type_id,
+        worlds::WorldId,
+    },
+    storage::{EventBus, EventQueue, Priority},
+};
+use hyperion_text::{Color, NamedColor, Text};
+use tracing::{error, info_span, warn};
+
+mod channel;
+mod template;
+
+pub use channel::{Audience, Channel, Channels, ChatChannel, DEFAULT_CHANNEL};
+pub use template::{Template, player_name};
+
+/// How far local messages reach in chunks, like the local broadcasts of the proxy.
+const LOCAL_RADIUS: i16 = 16;
+
+/// How the name of a player looks in chat.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ChatStyle {
+    /// Shown before the name, such as a rank like `§c[Admin] `.
+    pub prefix: String,
+    pub color: Option<Color>,
+    /// Shown when hovering over the name.
+    pub hover: String,
+}
+
+/// When a player can chat again.
+#[derive(Component, Debug, Default)]
+#[meta]
+pub struct ChatCooldown {
+    pub expires: i64,
+}
+
+/// The player a player last exchanged private messages with, who `/r` replies to.
+#[derive(Component, Debug, Default)]
+pub struct LastMessaged(pub Option<Entity>);
+
+/// Why `uuid` can't chat, if they are muted.
+fn muted(world: &World, uuid: hyperion::uuid::Uuid) -> Option<String> {
+    let mute = world.get::<&Moderation>(|moderation| moderation.mute_of(uuid));
+
+    mute.unwrap_or_else(|e| {
+        error!("failed to check if {uuid} is muted: {e:#}");
+        None
+    })
+    .map(|mute| mute.message("You are muted"))
+}
+
+/// Sends the packets in `bundle` to `player` if they are still online.
+fn unicast(world: &World, bundle: &DataBundle<'_, '_>, player: Entity) {
+    if !world.is_alive(player) {
+        return;
+    }
+
+    let result = world
+        .entity_from_id(player)
+        .try_get::<&ConnectionId>(|stream| bundle.unicast(*stream));
+
+    if let Some(Err(e)) = result {
+        error!("failed to send a chat message: {e}");
+    }
+}
+
+/// A private message as one of the two players sees it, `other` being the name of the other one.
+fn private_message<'a>(
+    direction: &'a str,
+    other: Text<'a>,
+    message: &'a str,
+) -> GameMessageS2c<'a> {
+    let extra = vec![Text::new(direction), other, Text::new(" » "), Text {
+        color: Some(Color::Named(NamedColor::White)),
+        ..Text::new(message)
+    }];
+
+    GameMessageS2c {
+        chat: Text {
+            color: Some(Color::Named(NamedColor::Gray)),
+            extra,
+            ..Text::new("")
+        },
+        overlay: false,
+    }
+}
+
+/// Sends `message` privately from `from` to `to`. Afterwards, `/r` of either of them replies to the
+/// other one.
+pub fn whisper(
+    world: &World,
+    compose: &Compose,
+    system: EntityView<'_>,
+    from: Entity,
+    to: Entity,
+    message: &str,
+) -> anyhow::Result<()> {
+    if from == to {
+        bail!("you can't message yourself");
+    }
+
+    if !world.is_alive(to) {
+        bail!("that player is not online anymore");
+    }
+
+    let from = world.entity_from_id(from);
+    let to = world.entity_from_id(to);
+
+    let (from_name, from_uuid, from_style) = from
+        .try_get::<(&Name, &Uuid, &ChatStyle)>(|(name, uuid, style)| {
+            (name.to_string(), uuid.0, style.clone())
+        })
+        .context("only players can send private messages")?;
+
+    let (to_name, to_style) = to
+        .try_get::<(&Name, &ChatStyle)>(|(name, style)| (name.to_string(), style.clone()))
+        .context("only players can receive private messages")?;
+
+    if let Some(mute) = muted(world, from_uuid) {
+        bail!("{mute}");
+    }
+
+    let mut bundle = DataBundle::new(compose, system);
+    bundle.add_packet(&private_message(
+        "To ",
+        player_name(&to_name, &to_style),
+        message,
+    ))?;
+    unicast(world, &bundle, from.id());
+
+    let mut bundle = DataBundle::new(compose, system);
+    bundle.add_packet(&private_message(
+        "From ",
+        player_name(&from_name, &from_style),
+        message,
+    ))?;
+    unicast(world, &bundle, to.id());
+
+    from.set(LastMessaged(Some(to.id())));
+    to.set(LastMessaged(Some(from.id())));
+
+    Ok(())
+}
+
+/// Sends `message` privately to the player `from` last exchanged private messages with.
+pub fn reply(
+    world: &World,
+    compose: &Compose,
+    system: EntityView<'_>,
+    from: Entity,
+    message: &str,
+) -> anyhow::Result<()> {
+    let to = world
+        .entity_from_id(from)
+        .try_get::<&LastMessaged>(|last| last.0)
+        .flatten()
+        .context("nobody messaged you yet")?;
+
+    whisper(world, compose, system, from, to, message)
+}
+
+/// Sends `notice` to `player`, such as why their message was not sent.
+fn notify(compose: &Compose, stream: ConnectionId, system: EntityView<'_>, notice: String) {
+    if let Err(e) = compose.unicast(&agnostic::chat(notice), stream, system) {
+        error!("failed to send a chat notice: {e}");
+    }
+}
+
+#[derive(Component)]
+pub struct ChatModule;
+
+impl Module for ChatModule {
+    fn module(world: &World) {
+        world.component::<Channels>();
+        world.component::<ChatChannel>();
+        world.component::<ChatStyle>();
+        world.component::<ChatCooldown>().meta();
+        world.component::<LastMessaged>();
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, ChatChannel)>()
+            .add_trait::<(flecs::With, ChatStyle)>()
+            .add_trait::<(flecs::With, ChatCooldown)>()
+            .add_trait::<(flecs::With, LastMessaged)>();
+
+        let mut channels = Channels::default();
+
+        #[expect(clippy::unwrap_used, reason = "the default template is valid")]
+        let template = Template::parse("§8<{prefix}{name}§8>§r {message}").unwrap();
+
+        channels.insert(
+            DEFAULT_CHANNEL,
+            Channel::new("Global", template, Audience::Everyone),
+        );
+
+        world.set(channels);
+
+        world.get::<&mut EventBus>(|bus| {
+            bus.listen(
+                Priority::Monitor,
+                |quit: &mut event::PlayerQuit, context| {
+                    context
+                        .world
+                        .get::<&mut Channels>(|channels| channels.leave_all(quit.player));
+                },
+            );
+        });
+
+        let players = world
+            .query::<&ConnectionId>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        // who reads a signed message has to be known to track it, so it is not left to the proxy
+        let signed_players = world
+            .query::<(&ConnectionId, &Position, &WorldId, &mut LastSeen)>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        system!(
+            "handle_chat_messages",
+            world,
+            &mut EventQueue<event::ChatMessage>($),
+            &Compose($),
+            &Channels($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(move |it, _, (queue, compose, channels)| {
+            let span = info_span!("handle_chat_messages");
+            let _enter = span.enter();
+
+            let world = it.world();
+            let system = it.system();
+            let tick = compose.global().tick;
+
+            for event::ChatMessage { msg, by, signed } in queue.drain() {
+                let msg = msg.get();
+
+                if !world.is_alive(by) {
+                    continue;
+                }
+
+                let sender = world.entity_from_id(by).try_get::<(
+                    &Name,
+                    &Uuid,
+                    &ConnectionId,
+                    &ChatChannel,
+                    &ChatStyle,
+                    &mut ChatCooldown,
+                    &Position,
+                    &WorldId,
+                )>(
+                    |(name, uuid, stream, current, style, cooldown, position, &world_id)| {
+                        if let Some(mute) = muted(&world, uuid.0) {
+                            notify(compose, *stream, system, mute);
+                            return None;
+                        }
+
+                        let Some(channel) = channels.get(&current.0) else {
+                            notify(
+                                compose,
+                                *stream,
+                                system,
+                                format!("§cThe channel {} does not exist anymore", current.0),
+                            );
+                            return None;
+                        };
+
+                        if !channel.can_talk(by) {
+                            notify(
+                                compose,
+                                *stream,
+                                system,
+                                format!("§cYou can't talk in {}", channel.display_name),
+                            );
+                            return None;
+                        }
+
+                        if cooldown.expires > tick {
+                            let remaining_secs = (cooldown.expires - tick) as f32 / 20.0;
+
+                            notify(
+                                compose,
+                                *stream,
+                                system,
+                                format!(
+                                    "§cPlease wait {remaining_secs:.2} seconds before sending \
+                                     another message"
+                                ),
+                            );
+                            return None;
+                        }
+
+                        cooldown.expires = tick + channel.cooldown;
+
+                        Some((
+                            name.to_string(),
+                            current.0.clone(),
+                            style.clone(),
+                            world_id,
+                            position.to_chunk(),
+                        ))
+                    },
+                );
+
+                let Some((name, current, style, world_id, chunk)) = sender.flatten() else {
+                    continue;
+                };
+
+                let Some(channel) = channels.get(&current) else {
+                    continue;
+                };
+
+                let chat = channel
+                    .template
+                    .render(&channel.display_name, &name, &style, msg);
+
+                if let Some(signed) = &signed {
+                    let pkt = signed.packet(msg, chat_type_id(), chat);
+
+                    let mut bundle = DataBundle::new(compose, system);
+
+                    if let Err(e) = bundle.add_packet(&pkt) {
+                        error!("failed to write a chat message: {e}");
+                        continue;
+                    }
+
+                    signed_players.each_entity(
+                        |receiver, (stream, position, &receiver_world, last_seen)| {
+                            let reads = match channel.audience {
+                                Audience::Local => {
+                                    receiver_world == world_id
+                                        && (position.to_chunk() - chunk).abs().max_element()
+                                            <= LOCAL_RADIUS
+                                }
+                                _ => channel.reads(&world, by, receiver.id()),
+                            };
+
+                            if !reads {
+                                return;
+                            }
+
+                            // the client acknowledges the message when it chats next
+                            if let Err(e) = last_seen.track(signed.signature) {
+                                warn!("failed to relay a chat message: {e:#}");
+                                receiver.set(PendingRemove::new(
+                                    "§cToo many chat messages were not acknowledged",
+                                ));
+                                return;
+                            }
+
+                            if let Err(e) = bundle.unicast(*stream) {
+                                error!("failed to send a chat message: {e}");
+                            }
+                        },
+                    );
+
+                    continue;
+                }
+
+                let pkt = GameMessageS2c {
+                    chat,
+                    overlay: false,
+                };
+
+                match channel.audience {
+                    Audience::Everyone => {
+                        if let Err(e) = compose.broadcast(&pkt, system).send() {
+                            error!("failed to broadcast a chat message: {e}");
+                        }
+
+                        continue;
+                    }
+                    Audience::Local => {
+                        if let Err(e) = compose
+                            .broadcast_local(&pkt, world_id, chunk, system)
+                            .send()
+                        {
+                            error!("failed to broadcast a chat message: {e}");
+                        }
+
+                        continue;
+                    }
+                    Audience::Members | Audience::Filter(_) => {}
+                }
+
+                let mut bundle = DataBundle::new(compose, system);
+
+                if let Err(e) = bundle.add_packet(&pkt) {
+                    error!("failed to write a chat message: {e}");
+                    continue;
+                }
+
+                players.each_entity(|receiver, stream| {
+                    if !channel.reads(&world, by, receiver.id()) {
+                        return;
+                    }
+
+                    if let Err(e) = bundle.unicast(*stream) {
+                        error!("failed to send a chat message: {e}");
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,153 @@
+//! How chat messages look.
+
+use std::borrow::Cow;
+
+use anyhow::bail;
+use hyperion_text::{ClickEvent, HoverEvent, Text};
+
+use crate::ChatStyle;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Channel,
+    Prefix,
+    Name,
+    Message,
+}
+
+/// The format of the messages of a channel, such as `"§8[{channel}] {prefix}{name}§8:§r
+/// {message}"`.
+///
+/// `{channel}` is the display name of the channel, `{prefix}` the prefix of the [`ChatStyle`] of
+/// the sender, `{name}` their name in the color of their style and `{message}` what they wrote.
+/// Hovering over the name shows [`ChatStyle::hover`] and clicking it suggests a private message to
+/// the sender.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+
+            let Some(end) = rest[start..].find('}') else {
+                bail!("`{template}` has a `{{` without a `}}`");
+            };
+
+            let part = match &rest[start + 1..start + end] {
+                "channel" => Part::Channel,
+                "prefix" => Part::Prefix,
+                "name" => Part::Name,
+                "message" => Part::Message,
+                unknown => bail!("`{template}` has the unknown placeholder `{{{unknown}}}`"),
+            };
+
+            parts.push(part);
+            rest = &rest[start + end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The message `text` that `name` sent in `channel`.
+    #[must_use]
+    pub fn render<'a>(
+        &'a self,
+        channel: &'a str,
+        name: &'a str,
+        style: &'a ChatStyle,
+        text: &'a str,
+    ) -> Text<'a> {
+        let extra = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => Text::new(literal),
+                Part::Channel => Text::new(channel),
+                Part::Prefix => Text::new(&style.prefix),
+                Part::Name => player_name(name, style),
+                Part::Message => Text::new(text),
+            })
+            .collect();
+
+        Text {
+            extra,
+            ..Text::new("")
+        }
+    }
+}
+
+/// The name of a player in the color of their style, which suggests a private message to them
+/// when clicked.
+#[must_use]
+pub fn player_name<'a>(name: &'a str, style: &'a ChatStyle) -> Text<'a> {
+    let hover = if style.hover.is_empty() {
+        format!("§7Click to message {name}")
+    } else {
+        format!("{}\n§7Click to message {name}", style.hover)
+    };
+
+    Text {
+        color: style.color,
+        hover_event: Some(Box::new(HoverEvent::ShowText(hover.into()))),
+        click_event: Some(Box::new(ClickEvent::SuggestCommand(Cow::Owned(format!(
+            "/msg {name} "
+        ))))),
+        ..Text::new(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperion_text::{ClickEvent, Color, NamedColor, Text};
+
+    use super::{Part, Template};
+    use crate::ChatStyle;
+
+    #[test]
+    fn placeholders_are_parsed() {
+        let template = Template::parse("§8<{prefix}{name}§8>§r {message}").unwrap();
+
+        assert_eq!(template.parts, [
+            Part::Literal("§8<".to_owned()),
+            Part::Prefix,
+            Part::Name,
+            Part::Literal("§8>§r ".to_owned()),
+            Part::Message,
+        ]);
+
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("{rank} {name}").is_err());
+    }
+
+    #[test]
+    fn names_are_styled() {
+        let template = Template::parse("[{channel}] {name}: {message}").unwrap();
+        let style = ChatStyle {
+            color: Some(Color::Named(NamedColor::Red)),
+            ..ChatStyle::default()
+        };
+
+        let text = template.render("Team", "alice", &style, "hi");
+
+        assert_eq!(text.extra[1], Text::new("Team"));
+        assert_eq!(text.extra[3].color, style.color);
+        assert_eq!(
+            text.extra[3].click_event.as_deref(),
+            Some(&ClickEvent::SuggestCommand("/msg alice ".into()))
+        );
+        assert_eq!(text.extra[5], Text::new("hi"));
+    }
+}
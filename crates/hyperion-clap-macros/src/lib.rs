@@ -1,6 +1,6 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{DeriveInput, Error, Ident, Lit, parse_macro_input};
+use syn::{DeriveInput, Error, Expr, Ident, Lit, Token, parse_macro_input};
 
 #[proc_macro_derive(CommandPermission, attributes(command_permission))]
 pub fn derive_command_permission(input: TokenStream) -> TokenStream {
@@ -8,15 +8,37 @@ pub fn derive_command_permission(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = input.ident.clone(); // Clone the Ident to prevent moving
 
-    // Extract the group from the `#[command_permission(group = "Admin")]` attribute
+    // Extract the group and node from the
+    // `#[command_permission(group = "Admin", node = "tag.command.fly")]` attribute
     let mut group = None;
+    let mut node = None;
+    let mut command_name = None;
     for attr in &input.attrs {
+        if attr.path().is_ident("command") {
+            // the name clap gives the command, which the node defaults to. Other clap settings are
+            // skipped, clap reports errors in them itself.
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    if let Ok(Lit::Str(lit)) = meta.value()?.parse::<Lit>() {
+                        command_name = Some(lit.value());
+                    }
+                } else if meta.input.peek(Token![=]) {
+                    meta.value()?.parse::<Expr>()?;
+                }
+                Ok(())
+            });
+        }
+
         if attr.path().is_ident("command_permission") {
             if let Err(err) = attr.parse_nested_meta(|meta| {
                 if meta.path.is_ident("group") {
                     if let Ok(Lit::Str(lit)) = meta.value()?.parse::<Lit>() {
                         group = Some(lit);
                     }
+                } else if meta.path.is_ident("node") {
+                    if let Ok(Lit::Str(lit)) = meta.value()?.parse::<Lit>() {
+                        node = Some(lit);
+                    }
                 }
                 Ok(())
             }) {
@@ -39,19 +61,20 @@ pub fn derive_command_permission(input: TokenStream) -> TokenStream {
         }
     };
 
+    // e.g. `tag.command.fly` for the `fly` command of the `tag` crate
+    let node = match node {
+        Some(node) => quote! { #node },
+        None => {
+            let command_name = command_name.unwrap_or_else(|| name.to_string().to_lowercase());
+            quote! { concat!(env!("CARGO_CRATE_NAME"), ".command.", #command_name) }
+        }
+    };
+
     // Generate the trait implementation
     let expanded = quote! {
         impl CommandPermission for #name {
-            fn has_required_permission(user_group: ::hyperion_permission::Group) -> bool {
-                const REQUIRED_GROUP: ::hyperion_permission::Group = ::hyperion_permission::Group::#group_ident;
-
-                if REQUIRED_GROUP == ::hyperion_permission::Group::Banned {
-                    // When checking for the group "Banned" we don't want to check for higher groups.
-                    return REQUIRED_GROUP == user_group;
-                } else {
-                    return user_group as u32 >= REQUIRED_GROUP as u32
-                }
-            }
+            const PERMISSION: &'static str = #node;
+            const DEFAULT_GROUP: ::hyperion_permission::Group = ::hyperion_permission::Group::#group_ident;
         }
     };
 
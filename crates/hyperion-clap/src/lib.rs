@@ -13,7 +13,7 @@ use hyperion::{
 pub use hyperion_clap_macros::CommandPermission;
 pub use hyperion_command;
 use hyperion_command::{CommandHandler, CommandRegistry};
-use hyperion_permission::Group;
+use hyperion_permission::{Group, GroupStorage, PermissionGroups, PlayerPermissions, is_permitted};
 use valence_protocol::{
     VarInt,
     packets::{
@@ -33,11 +33,12 @@ pub trait MinecraftCommand: Parser + CommandPermission {
         let cmd = Self::command();
         let name = cmd.get_name();
 
-        let has_permissions = |world: &World, caller: Entity| {
-            caller
-                .entity_view(world)
-                .get::<&Group>(|group| Self::has_required_permission(*group))
-        };
+        world.get::<&mut PermissionGroups>(|groups| {
+            groups.grant_default(Self::DEFAULT_GROUP.name(), Self::PERMISSION);
+        });
+
+        let has_permissions =
+            |world: &World, caller: Entity| is_permitted(world, caller, Self::PERMISSION);
 
         let node_to_register =
             hyperion::simulation::command::Command::literal(name, has_permissions);
@@ -65,26 +66,20 @@ pub trait MinecraftCommand: Parser + CommandPermission {
 
             match Self::try_parse_from(input) {
                 Ok(elem) => {
-                    if world.get::<&Compose>(|compose| {
-                        caller.entity_view(world).get::<(&ConnectionId, &Group)>(
-                            |(stream, group)| {
-                                if Self::has_required_permission(*group) {
-                                    true
-                                } else {
-                                    let chat = agnostic::chat(
-                                        "§cYou do not have permission to use this command!",
-                                    );
-
-                                    let mut bundle = DataBundle::new(compose, system);
-                                    bundle.add_packet(&chat).unwrap();
-                                    bundle.unicast(*stream).unwrap();
-
-                                    false
-                                }
-                            },
-                        )
-                    }) {
+                    if is_permitted(world, caller, Self::PERMISSION) {
                         elem.execute(system, caller);
+                    } else {
+                        world.get::<&Compose>(|compose| {
+                            caller.entity_view(world).get::<&ConnectionId>(|stream| {
+                                let chat = agnostic::chat(
+                                    "§cYou do not have permission to use this command!",
+                                );
+
+                                let mut bundle = DataBundle::new(compose, system);
+                                bundle.add_packet(&chat).unwrap();
+                                bundle.unicast(*stream).unwrap();
+                            });
+                        });
                     }
                 }
                 Err(e) => {
@@ -261,7 +256,12 @@ impl MinecraftArg for ClapArg {
 }
 
 pub trait CommandPermission {
-    fn has_required_permission(user_group: hyperion_permission::Group) -> bool;
+    /// The permission node needed to run the command, e.g. `tag.command.fly`.
+    const PERMISSION: &'static str;
+
+    /// The group that has [`Self::PERMISSION`] by default, along with every group inheriting from
+    /// it.
+    const DEFAULT_GROUP: Group;
 }
 
 #[derive(Clone, Debug, ValueEnum, PartialEq, Eq)]
@@ -278,7 +278,7 @@ pub struct ClapCommandModule;
 #[derive(clap::Parser, Debug)]
 pub struct SetCommand {
     player: String,
-    group: Group,
+    group: String,
 }
 
 #[derive(clap::Parser, Debug)]
@@ -286,80 +286,216 @@ pub struct GetCommand {
     player: String,
 }
 
+#[derive(clap::Parser, Debug)]
+pub struct GrantCommand {
+    player: String,
+    /// A permission node like `tag.command.fly` or `tag.*`. Nodes starting with `-` are denied.
+    #[arg(allow_hyphen_values = true)]
+    rule: String,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct RevokeCommand {
+    player: String,
+    #[arg(allow_hyphen_values = true)]
+    rule: String,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct GroupCommand {
+    group: String,
+    action: GroupAction,
+    /// The rule to grant or revoke, or the group to inherit from
+    #[arg(allow_hyphen_values = true)]
+    value: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum GroupAction {
+    Show,
+    Grant,
+    Revoke,
+    Inherit,
+    Disinherit,
+}
+
 #[derive(Parser, CommandPermission, Debug)]
 #[command(name = "perms")]
-#[command_permission(group = "Normal")]
+#[command_permission(group = "Admin", node = "hyperion.command.perms")]
 pub enum PermissionCommand {
     Set(SetCommand),
     Get(GetCommand),
+    Grant(GrantCommand),
+    Revoke(RevokeCommand),
+    Group(GroupCommand),
 }
 
 impl MinecraftCommand for PermissionCommand {
     fn execute(self, system: EntityView<'_>, caller: Entity) {
         let world = system.world();
-        world.get::<&IgnMap>(|ign_map| {
-            match self {
-                Self::Set(cmd) => {
-                    // Handle setting permissions
-                    let Some(entity) = ign_map.get(cmd.player.as_str()) else {
-                        caller.entity_view(world).get::<&ConnectionId>(|stream| {
-                            let msg = format!("§c{} not found", cmd.player);
-                            let chat = hyperion::net::agnostic::chat(msg);
-                            world.get::<&Compose>(|compose| {
-                                compose.unicast(&chat, *stream, system).unwrap();
-                            });
-                        });
-                        return;
-                    };
 
-                    entity.entity_view(world).get::<&mut Group>(|group| {
-                        if *group != cmd.group {
-                            *group = cmd.group;
-                            entity.entity_view(world).modified::<Group>();
-                        }
+        let reply = match self.run(&world) {
+            Ok(reply) => reply,
+            Err(e) => format!("§c{e}"),
+        };
 
-                        caller.entity_view(world).get::<&ConnectionId>(|stream| {
-                            let msg = format!(
-                                "§b{}§r's group has been set to §e{:?}",
-                                cmd.player, cmd.group
-                            );
-                            let chat = hyperion::net::agnostic::chat(msg);
-                            world.get::<&Compose>(|compose| {
-                                compose.unicast(&chat, *stream, system).unwrap();
-                            });
-                        });
-                    });
+        world.get::<&Compose>(|compose| {
+            caller.entity_view(world).get::<&ConnectionId>(|stream| {
+                compose
+                    .unicast(&agnostic::chat(reply), *stream, system)
+                    .unwrap();
+            });
+        });
+    }
+}
+
+impl PermissionCommand {
+    fn run(self, world: &World) -> Result<String, String> {
+        match self {
+            Self::Set(cmd) => {
+                if world.get::<&PermissionGroups>(|groups| groups.get(&cmd.group).is_none()) {
+                    return Err(format!("there is no group {}", cmd.group));
                 }
-                Self::Get(cmd) => {
-                    let Some(entity) = ign_map.get(cmd.player.as_str()) else {
-                        caller.entity_view(world).get::<&ConnectionId>(|stream| {
-                            let msg = format!("§c{} not found", cmd.player);
-                            let chat = hyperion::net::agnostic::chat(msg);
-                            world.get::<&Compose>(|compose| {
-                                compose.unicast(&chat, *stream, system).unwrap();
-                            });
-                        });
-                        return;
-                    };
 
-                    entity.entity_view(world).get::<&Group>(|group| {
-                        caller.entity_view(world).get::<&ConnectionId>(|stream| {
-                            let msg = format!("§b{}§r's group is §e{:?}", cmd.player, group);
-                            let chat = hyperion::net::agnostic::chat(msg);
-                            world.get::<&Compose>(|compose| {
-                                compose.unicast(&chat, *stream, system).unwrap();
-                            });
-                        });
-                    });
+                with_player(world, &cmd.player, |permissions| {
+                    permissions.group.clone_from(&cmd.group);
+                })?;
+
+                Ok(format!(
+                    "§b{}§r's group has been set to §e{}",
+                    cmd.player, cmd.group
+                ))
+            }
+            Self::Get(cmd) => {
+                let permissions =
+                    with_player(world, &cmd.player, |permissions| permissions.clone())?;
+
+                Ok(format!(
+                    "§b{}§r's group is §e{}§r, overrides: [{}]",
+                    cmd.player,
+                    permissions.group,
+                    permissions.overrides.join(", ")
+                ))
+            }
+            Self::Grant(cmd) => {
+                with_player(world, &cmd.player, |permissions| {
+                    permissions.overrides.retain(|rule| *rule != cmd.rule);
+                    permissions.overrides.push(cmd.rule.clone());
+                })?;
+
+                Ok(format!("§b{}§r now has §e{}", cmd.player, cmd.rule))
+            }
+            Self::Revoke(cmd) => {
+                let removed = with_player(world, &cmd.player, |permissions| {
+                    let before = permissions.overrides.len();
+                    permissions.overrides.retain(|rule| *rule != cmd.rule);
+                    permissions.overrides.len() != before
+                })?;
+
+                if !removed {
+                    return Err(format!("{} has no override {}", cmd.player, cmd.rule));
                 }
+
+                Ok(format!("§b{}§r no longer has §e{}", cmd.player, cmd.rule))
             }
-        });
+            Self::Group(cmd) => edit_group(world, cmd),
+        }
+    }
+}
+
+/// Changes the permissions of an online player.
+fn with_player<R>(
+    world: &World,
+    player: &str,
+    f: impl FnOnce(&mut PlayerPermissions) -> R,
+) -> Result<R, String> {
+    let entity = world
+        .get::<&IgnMap>(|ign_map| ign_map.get(player).copied())
+        .ok_or_else(|| format!("{player} not found"))?;
+
+    let entity = entity.entity_view(world);
+
+    let result = entity.get::<&mut PlayerPermissions>(f);
+    entity.modified::<PlayerPermissions>();
+
+    Ok(result)
+}
+
+fn edit_group(world: &World, cmd: GroupCommand) -> Result<String, String> {
+    let GroupCommand {
+        group,
+        action,
+        value,
+    } = cmd;
+
+    let value = || {
+        value
+            .clone()
+            .ok_or_else(|| format!("{action:?} needs a value"))
+    };
+
+    let mut definition = world
+        .get::<&PermissionGroups>(|groups| groups.get(&group).cloned())
+        .unwrap_or_default();
+
+    let reply = match action {
+        GroupAction::Show => {
+            return Ok(format!(
+                "§e{group}§r inherits from [{}], rules: [{}]",
+                definition.parents.join(", "),
+                definition.rules.join(", ")
+            ));
+        }
+        GroupAction::Grant => {
+            let rule = value()?;
+            definition.rules.retain(|existing| *existing != rule);
+            definition.rules.push(rule.clone());
+            format!("§e{group}§r now has §e{rule}")
+        }
+        GroupAction::Revoke => {
+            let rule = value()?;
+            definition.rules.retain(|existing| *existing != rule);
+            format!("§e{group}§r no longer has §e{rule}")
+        }
+        GroupAction::Inherit => {
+            let parent = value()?;
+            if !definition.parents.contains(&parent) {
+                definition.parents.push(parent.clone());
+            }
+            format!("§e{group}§r now inherits from §e{parent}")
+        }
+        GroupAction::Disinherit => {
+            let parent = value()?;
+            definition.parents.retain(|existing| *existing != parent);
+            format!("§e{group}§r no longer inherits from §e{parent}")
+        }
+    };
+
+    world
+        .get::<&GroupStorage>(|storage| storage.save(&group, &definition))
+        .map_err(|e| format!("failed to save {group}: {e}"))?;
+
+    world.get::<&mut PermissionGroups>(|groups| groups.set(group, definition));
+
+    // what players may do changed, so they need new command trees
+    let mut players = Vec::new();
+    world
+        .query::<()>()
+        .with::<PlayerPermissions>()
+        .build()
+        .each_entity(|entity, ()| players.push(entity.id()));
+
+    for player in players {
+        world.entity_from_id(player).modified::<PlayerPermissions>();
     }
+
+    Ok(reply)
 }
 
 impl Module for ClapCommandModule {
     fn module(world: &World) {
         world.import::<hyperion_command::CommandModule>();
+        world.import::<hyperion_permission::PermissionModule>();
 
         world.get::<&mut CommandRegistry>(|registry| {
             PermissionCommand::register(registry, world);
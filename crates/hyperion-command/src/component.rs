@@ -25,6 +25,11 @@ impl CommandRegistry {
         self.commands.insert(name, handler);
     }
 
+    /// Removes the command `name`, returning its handler if it was registered.
+    pub fn unregister(&mut self, name: &str) -> Option<CommandHandler> {
+        self.commands.shift_remove(name)
+    }
+
     pub fn all(&self) -> impl Iterator<Item = &str> {
         self.commands.keys().map(String::as_str)
     }
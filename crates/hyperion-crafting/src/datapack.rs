@@ -0,0 +1,492 @@
+//! Loads recipes from a datapack, e.g. the `data` folder of the vanilla server jar.
+//!
+//! Recipes are read from `data/<namespace>/recipes/**/*.json` and item tags from
+//! `data/<namespace>/tags/items/**/*.json`. See <https://minecraft.wiki/w/Recipe#JSON_format>.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::{
+    CraftingCategory, CraftingRegistry, CraftingShapedData, CraftingShapelessData,
+    CraftingSpecialData, Ingredient, RecipeData, SmeltingCategory, SmeltingData,
+    SmithingTransformData, SmithingTrimData, StonecuttingData,
+};
+
+/// The items in every item tag, e.g. `minecraft:planks`.
+#[derive(Clone, Debug, Default)]
+pub struct ItemTags(HashMap<String, Vec<ItemKind>>);
+
+impl ItemTags {
+    /// Item tags with the raw IDs of their items, like the tags sent to the client.
+    pub fn from_raw_ids(tags: impl IntoIterator<Item = (String, Vec<u16>)>) -> Self {
+        let tags = tags
+            .into_iter()
+            .map(|(name, ids)| {
+                let items = ids.into_iter().filter_map(ItemKind::from_raw).collect();
+                (name, items)
+            })
+            .collect();
+
+        Self(tags)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[ItemKind]> {
+        self.0.get(name).map(Vec::as_slice)
+    }
+
+    /// Adds the item tags of the datapack at `root`. Tags that already exist are extended unless
+    /// the datapack replaces them.
+    pub fn load_datapack(&mut self, root: &Path) -> anyhow::Result<()> {
+        let mut files = HashMap::new();
+
+        for (name, path) in datapack_files(root, "tags/items")? {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let file: TagJson = serde_json::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            files.insert(name, file);
+        }
+
+        let mut resolved = HashMap::new();
+
+        for name in files.keys() {
+            self.resolve(name, &files, &mut resolved, &mut HashSet::new())?;
+        }
+
+        self.0.extend(resolved);
+
+        Ok(())
+    }
+
+    fn resolve(
+        &self,
+        name: &str,
+        files: &HashMap<String, TagJson>,
+        resolved: &mut HashMap<String, Vec<ItemKind>>,
+        visiting: &mut HashSet<String>,
+    ) -> anyhow::Result<Vec<ItemKind>> {
+        if let Some(items) = resolved.get(name) {
+            return Ok(items.clone());
+        }
+
+        let Some(file) = files.get(name) else {
+            return self
+                .get(name)
+                .map(<[ItemKind]>::to_vec)
+                .with_context(|| format!("unknown item tag {name}"));
+        };
+
+        if !visiting.insert(name.to_owned()) {
+            bail!("item tag {name} contains itself");
+        }
+
+        let mut items = Vec::new();
+
+        if !file.replace {
+            items.extend_from_slice(self.get(name).unwrap_or_default());
+        }
+
+        for value in &file.values {
+            let (id, required) = match value {
+                TagValue::Id(id) => (id.as_str(), true),
+                TagValue::Entry { id, required } => (id.as_str(), *required),
+            };
+
+            let result = match id.strip_prefix('#') {
+                Some(tag) => self.resolve(tag, files, resolved, visiting),
+                None => item(id).map(|item| vec![item]),
+            };
+
+            match result {
+                Ok(found) => items.extend(found),
+                Err(e) if required => return Err(e.context(format!("in item tag {name}"))),
+                Err(_) => {}
+            }
+        }
+
+        items.sort_unstable();
+        items.dedup();
+
+        visiting.remove(name);
+        resolved.insert(name.to_owned(), items.clone());
+
+        Ok(items)
+    }
+}
+
+impl CraftingRegistry {
+    /// Adds the recipes of the datapack at `root`. Recipes that can't be loaded are skipped.
+    ///
+    /// Returns how many recipes were added.
+    pub fn load_datapack(&mut self, root: &Path, tags: &ItemTags) -> anyhow::Result<usize> {
+        let mut loaded = 0;
+
+        for (recipe_id, path) in datapack_files(root, "recipes")? {
+            let result = std::fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))
+                .and_then(|contents| parse_recipe(&contents, tags));
+
+            match result {
+                Ok(Some(data)) => {
+                    self.register(recipe_id, data);
+                    loaded += 1;
+                }
+                Ok(None) => debug!("skipping recipe {recipe_id} of an unsupported type"),
+                Err(e) => warn!("skipping recipe {recipe_id}: {e:#}"),
+            }
+        }
+
+        info!("loaded {loaded} recipes from {}", root.display());
+
+        Ok(loaded)
+    }
+}
+
+/// Parses a recipe file. Returns `None` if the recipe type is not supported.
+pub fn parse_recipe(contents: &str, tags: &ItemTags) -> anyhow::Result<Option<RecipeData>> {
+    let json: serde_json::Value = serde_json::from_str(contents)?;
+
+    let kind = json
+        .get("type")
+        .and_then(serde_json::Value::as_str)
+        .context("the recipe has no type")?
+        .to_owned();
+
+    let data = match kind.as_str() {
+        "minecraft:crafting_shaped" => {
+            let recipe: ShapedJson = serde_json::from_value(json)?;
+
+            let mut key = HashMap::new();
+            for (symbol, ingredient) in recipe.key {
+                let mut chars = symbol.chars();
+                let (Some(symbol), None) = (chars.next(), chars.next()) else {
+                    bail!("the key {symbol:?} is not a single character");
+                };
+                key.insert(symbol, ingredient.resolve(tags)?);
+            }
+
+            let rows: Vec<_> = recipe.pattern.iter().map(String::as_str).collect();
+
+            let data = CraftingShapedData::new(&rows, &key, recipe.result.stack()?)?
+                .group(recipe.group)
+                .category(recipe.category)
+                .show_notification(recipe.show_notification);
+
+            RecipeData::CraftingShaped(data)
+        }
+        "minecraft:crafting_shapeless" => {
+            let recipe: ShapelessJson = serde_json::from_value(json)?;
+
+            let mut data = CraftingShapelessData::new(recipe.result.stack()?)
+                .group(recipe.group)
+                .category(recipe.category);
+
+            for ingredient in recipe.ingredients {
+                data = data.ingredient(ingredient.resolve(tags)?);
+            }
+
+            RecipeData::CraftingShapeless(data)
+        }
+        "minecraft:smelting"
+        | "minecraft:blasting"
+        | "minecraft:smoking"
+        | "minecraft:campfire_cooking" => {
+            let recipe: CookingJson = serde_json::from_value(json)?;
+
+            let (variant, default_cooking_time): (fn(SmeltingData) -> RecipeData, i32) =
+                match kind.as_str() {
+                    "minecraft:smelting" => (RecipeData::Smelting, 200),
+                    "minecraft:blasting" => (RecipeData::Blasting, 100),
+                    "minecraft:smoking" => (RecipeData::Smoking, 100),
+                    _ => (RecipeData::CampfireCooking, 100),
+                };
+
+            variant(SmeltingData {
+                group: recipe.group,
+                category: recipe.category,
+                ingredient: recipe.ingredient.resolve(tags)?,
+                result: recipe.result.stack()?,
+                experience: recipe.experience,
+                cooking_time: recipe.cookingtime.unwrap_or(default_cooking_time),
+            })
+        }
+        "minecraft:stonecutting" => {
+            let recipe: StonecuttingJson = serde_json::from_value(json)?;
+
+            RecipeData::Stonecutting(StonecuttingData {
+                group: recipe.group,
+                ingredient: recipe.ingredient.resolve(tags)?,
+                result: ItemStack::new(item(&recipe.result)?, recipe.count, None),
+            })
+        }
+        "minecraft:smithing_transform" => {
+            let recipe: SmithingJson = serde_json::from_value(json)?;
+
+            RecipeData::SmithingTransform(SmithingTransformData {
+                template: recipe.template.resolve(tags)?,
+                base: recipe.base.resolve(tags)?,
+                addition: recipe.addition.resolve(tags)?,
+                result: recipe.result.context("the recipe has no result")?.stack()?,
+            })
+        }
+        "minecraft:smithing_trim" => {
+            let recipe: SmithingJson = serde_json::from_value(json)?;
+
+            RecipeData::SmithingTrim(SmithingTrimData {
+                template: recipe.template.resolve(tags)?,
+                base: recipe.base.resolve(tags)?,
+                addition: recipe.addition.resolve(tags)?,
+            })
+        }
+        kind => {
+            let Some(variant) = special(kind) else {
+                return Ok(None);
+            };
+
+            let recipe: SpecialJson = serde_json::from_value(json)?;
+
+            variant(CraftingSpecialData {
+                category: recipe.category,
+            })
+        }
+    };
+
+    Ok(Some(data))
+}
+
+fn special(kind: &str) -> Option<fn(CraftingSpecialData) -> RecipeData> {
+    let variant: fn(CraftingSpecialData) -> RecipeData = match kind {
+        "minecraft:crafting_special_armordye" => RecipeData::CraftingSpecialArmordye,
+        "minecraft:crafting_special_bookcloning" => RecipeData::CraftingSpecialBookcloning,
+        "minecraft:crafting_special_mapcloning" => RecipeData::CraftingSpecialMapcloning,
+        "minecraft:crafting_special_mapextending" => RecipeData::CraftingSpecialMapextending,
+        "minecraft:crafting_special_firework_rocket" => RecipeData::CraftingSpecialFireworkRocket,
+        "minecraft:crafting_special_firework_star" => RecipeData::CraftingSpecialFireworkStar,
+        "minecraft:crafting_special_firework_star_fade" => {
+            RecipeData::CraftingSpecialFireworkStarFade
+        }
+        "minecraft:crafting_special_repairitem" => RecipeData::CraftingSpecialRepairitem,
+        "minecraft:crafting_special_tippedarrow" => RecipeData::CraftingSpecialTippedarrow,
+        "minecraft:crafting_special_bannerduplicate" => RecipeData::CraftingSpecialBannerduplicate,
+        "minecraft:crafting_special_shielddecoration" => {
+            RecipeData::CraftingSpecialShielddecoration
+        }
+        "minecraft:crafting_special_shulkerboxcoloring" => {
+            RecipeData::CraftingSpecialShulkerboxcoloring
+        }
+        "minecraft:crafting_special_suspiciousstew" => RecipeData::CraftingSpecialSuspiciousstew,
+        "minecraft:crafting_decorated_pot" => RecipeData::CraftingDecoratedPot,
+        _ => return None,
+    };
+
+    Some(variant)
+}
+
+/// Looks up an item by its ID, e.g. `minecraft:stone`.
+fn item(id: &str) -> anyhow::Result<ItemKind> {
+    id.strip_prefix("minecraft:")
+        .and_then(ItemKind::from_str)
+        .with_context(|| format!("unknown item {id}"))
+}
+
+/// The JSON files in `data/<namespace>/<kind>` of the datapack, by their resource location.
+fn datapack_files(root: &Path, kind: &str) -> anyhow::Result<Vec<(String, PathBuf)>> {
+    let data = root.join("data");
+    let mut files = Vec::new();
+
+    let namespaces =
+        std::fs::read_dir(&data).with_context(|| format!("failed to read {}", data.display()))?;
+
+    for namespace in namespaces {
+        let namespace = namespace?;
+        let dir = namespace.path().join(kind);
+
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let namespace = namespace.file_name().to_string_lossy().into_owned();
+        json_files(&dir, &format!("{namespace}:"), &mut files)?;
+    }
+
+    files.sort_unstable();
+
+    Ok(files)
+}
+
+fn json_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let Some(name) = path.file_stem().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+
+        if path.is_dir() {
+            json_files(&path, &format!("{prefix}{name}/"), files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push((format!("{prefix}{name}"), path));
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct TagJson {
+    #[serde(default)]
+    replace: bool,
+    values: Vec<TagValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagValue {
+    Id(String),
+    Entry {
+        id: String,
+        #[serde(default = "default_true")]
+        required: bool,
+    },
+}
+
+/// An ingredient is an item, a tag, or a list of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IngredientJson {
+    One(IngredientEntry),
+    Many(Vec<IngredientEntry>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IngredientEntry {
+    Item { item: String },
+    Tag { tag: String },
+}
+
+impl IngredientJson {
+    fn resolve(self, tags: &ItemTags) -> anyhow::Result<Ingredient> {
+        let entries = match self {
+            Self::One(entry) => vec![entry],
+            Self::Many(entries) => entries,
+        };
+
+        let mut items = Vec::new();
+
+        for entry in entries {
+            match entry {
+                IngredientEntry::Item { item: id } => items.push(item(&id)?),
+                IngredientEntry::Tag { tag } => items.extend_from_slice(
+                    tags.get(&tag)
+                        .with_context(|| format!("unknown item tag {tag}"))?,
+                ),
+            }
+        }
+
+        Ok(items.into_iter().collect())
+    }
+}
+
+/// The result of crafting and smithing recipes is an item and a count, and the result of
+/// cooking recipes is only an item.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ResultJson {
+    Id(String),
+    Stack {
+        item: String,
+        #[serde(default = "default_count")]
+        count: i8,
+    },
+}
+
+impl ResultJson {
+    fn stack(&self) -> anyhow::Result<ItemStack> {
+        let (id, count) = match self {
+            Self::Id(id) => (id, 1),
+            Self::Stack { item, count } => (item, *count),
+        };
+
+        Ok(ItemStack::new(item(id)?, count, None))
+    }
+}
+
+#[derive(Deserialize)]
+struct ShapedJson {
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    category: CraftingCategory,
+    pattern: Vec<String>,
+    key: HashMap<String, IngredientJson>,
+    result: ResultJson,
+    #[serde(default = "default_true")]
+    show_notification: bool,
+}
+
+#[derive(Deserialize)]
+struct ShapelessJson {
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    category: CraftingCategory,
+    ingredients: Vec<IngredientJson>,
+    result: ResultJson,
+}
+
+#[derive(Deserialize)]
+struct CookingJson {
+    #[serde(default)]
+    group: String,
+    #[serde(default)]
+    category: SmeltingCategory,
+    ingredient: IngredientJson,
+    result: ResultJson,
+    #[serde(default)]
+    experience: f32,
+    cookingtime: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct StonecuttingJson {
+    #[serde(default)]
+    group: String,
+    ingredient: IngredientJson,
+    result: String,
+    #[serde(default = "default_count")]
+    count: i8,
+}
+
+#[derive(Deserialize)]
+struct SmithingJson {
+    template: IngredientJson,
+    base: IngredientJson,
+    addition: IngredientJson,
+    result: Option<ResultJson>,
+}
+
+#[derive(Deserialize)]
+struct SpecialJson {
+    #[serde(default)]
+    category: CraftingCategory,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+const fn default_count() -> i8 {
+    1
+}
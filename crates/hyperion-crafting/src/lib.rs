@@ -1,9 +1,13 @@
 use std::{collections::HashMap, io::Write};
 
+use anyhow::{Context, ensure};
 use derive_build::Build;
 use flecs_ecs::macros::Component;
-use slotmap::{SecondaryMap, SlotMap, new_key_type};
-use valence_protocol::{Encode, ItemKind, ItemStack, Packet};
+use serde::Deserialize;
+use slotmap::{SlotMap, new_key_type};
+use valence_protocol::{Encode, ItemKind, ItemStack, Packet, VarInt};
+
+pub mod datapack;
 
 /// Represents a packet sent from the server to the client to synchronize recipes.
 #[derive(Clone, Debug, Encode, Packet)]
@@ -27,56 +31,94 @@ pub struct Recipe {
 #[derive(Clone, Debug)]
 pub enum RecipeData {
     CraftingShapeless(CraftingShapelessData),
-    // CraftingShaped(CraftingShapedData<'a>),
-    // CraftingSpecialArmordye(CraftingSpecialData),
-    // CraftingSpecialBookcloning(CraftingSpecialData),
-    // CraftingSpecialMapcloning(CraftingSpecialData),
-    // CraftingSpecialMapextending(CraftingSpecialData),
-    // CraftingSpecialFireworkRocket(CraftingSpecialData),
-    // CraftingSpecialFireworkStar(CraftingSpecialData),
-    // CraftingSpecialFireworkStarFade(CraftingSpecialData),
-    // CraftingSpecialRepairitem(CraftingSpecialData),
-    // CraftingSpecialTippedarrow(CraftingSpecialData),
-    // CraftingSpecialBannerduplicate(CraftingSpecialData),
-    // CraftingSpecialShielddecoration(CraftingSpecialData),
-    // CraftingSpecialShulkerboxcoloring(CraftingSpecialData),
-    // CraftingSpecialSuspiciousstew(CraftingSpecialData),
-    // CraftingDecoratedPot(CraftingSpecialData),
-    // Smelting(SmeltingData<'a>),
-    // Blasting(SmeltingData<'a>),
-    // Smoking(SmeltingData<'a>),
-    // CampfireCooking(SmeltingData<'a>),
-    // Stonecutting(StonecuttingData<'a>),
-    // SmithingTransform(SmithingTransformData<'a>),
-    // SmithingTrim(SmithingTrimData<'a>),
+    CraftingShaped(CraftingShapedData),
+    CraftingSpecialArmordye(CraftingSpecialData),
+    CraftingSpecialBookcloning(CraftingSpecialData),
+    CraftingSpecialMapcloning(CraftingSpecialData),
+    CraftingSpecialMapextending(CraftingSpecialData),
+    CraftingSpecialFireworkRocket(CraftingSpecialData),
+    CraftingSpecialFireworkStar(CraftingSpecialData),
+    CraftingSpecialFireworkStarFade(CraftingSpecialData),
+    CraftingSpecialRepairitem(CraftingSpecialData),
+    CraftingSpecialTippedarrow(CraftingSpecialData),
+    CraftingSpecialBannerduplicate(CraftingSpecialData),
+    CraftingSpecialShielddecoration(CraftingSpecialData),
+    CraftingSpecialShulkerboxcoloring(CraftingSpecialData),
+    CraftingSpecialSuspiciousstew(CraftingSpecialData),
+    CraftingDecoratedPot(CraftingSpecialData),
+    Smelting(SmeltingData),
+    Blasting(SmeltingData),
+    Smoking(SmeltingData),
+    CampfireCooking(SmeltingData),
+    Stonecutting(StonecuttingData),
+    SmithingTransform(SmithingTransformData),
+    SmithingTrim(SmithingTrimData),
+}
+
+impl RecipeData {
+    /// The recipe type the client knows this recipe by.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::CraftingShapeless(_) => "minecraft:crafting_shapeless",
+            Self::CraftingShaped(_) => "minecraft:crafting_shaped",
+            Self::CraftingSpecialArmordye(_) => "minecraft:crafting_special_armordye",
+            Self::CraftingSpecialBookcloning(_) => "minecraft:crafting_special_bookcloning",
+            Self::CraftingSpecialMapcloning(_) => "minecraft:crafting_special_mapcloning",
+            Self::CraftingSpecialMapextending(_) => "minecraft:crafting_special_mapextending",
+            Self::CraftingSpecialFireworkRocket(_) => "minecraft:crafting_special_firework_rocket",
+            Self::CraftingSpecialFireworkStar(_) => "minecraft:crafting_special_firework_star",
+            Self::CraftingSpecialFireworkStarFade(_) => {
+                "minecraft:crafting_special_firework_star_fade"
+            }
+            Self::CraftingSpecialRepairitem(_) => "minecraft:crafting_special_repairitem",
+            Self::CraftingSpecialTippedarrow(_) => "minecraft:crafting_special_tippedarrow",
+            Self::CraftingSpecialBannerduplicate(_) => "minecraft:crafting_special_bannerduplicate",
+            Self::CraftingSpecialShielddecoration(_) => {
+                "minecraft:crafting_special_shielddecoration"
+            }
+            Self::CraftingSpecialShulkerboxcoloring(_) => {
+                "minecraft:crafting_special_shulkerboxcoloring"
+            }
+            Self::CraftingSpecialSuspiciousstew(_) => "minecraft:crafting_special_suspiciousstew",
+            Self::CraftingDecoratedPot(_) => "minecraft:crafting_decorated_pot",
+            Self::Smelting(_) => "minecraft:smelting",
+            Self::Blasting(_) => "minecraft:blasting",
+            Self::Smoking(_) => "minecraft:smoking",
+            Self::CampfireCooking(_) => "minecraft:campfire_cooking",
+            Self::Stonecutting(_) => "minecraft:stonecutting",
+            Self::SmithingTransform(_) => "minecraft:smithing_transform",
+            Self::SmithingTrim(_) => "minecraft:smithing_trim",
+        }
+    }
 }
 
 impl Encode for RecipeData {
     fn encode(&self, w: impl Write) -> anyhow::Result<()> {
         match self {
             Self::CraftingShapeless(data) => data.encode(w),
-            // RecipeData::CraftingShaped(data) => data.encode(w),
-            // RecipeData::CraftingSpecialArmordye(data) => data.encode(w),
-            // RecipeData::CraftingSpecialBookcloning(data) => data.encode(w),
-            // RecipeData::CraftingSpecialMapcloning(data) => data.encode(w),
-            // RecipeData::CraftingSpecialMapextending(data) => data.encode(w),
-            // RecipeData::CraftingSpecialFireworkRocket(data) => data.encode(w),
-            // RecipeData::CraftingSpecialFireworkStar(data) => data.encode(w),
-            // RecipeData::CraftingSpecialFireworkStarFade(data) => data.encode(w),
-            // RecipeData::CraftingSpecialRepairitem(data) => data.encode(w),
-            // RecipeData::CraftingSpecialTippedarrow(data) => data.encode(w),
-            // RecipeData::CraftingSpecialBannerduplicate(data) => data.encode(w),
-            // RecipeData::CraftingSpecialShielddecoration(data) => data.encode(w),
-            // RecipeData::CraftingSpecialShulkerboxcoloring(data) => data.encode(w),
-            // RecipeData::CraftingSpecialSuspiciousstew(data) => data.encode(w),
-            // RecipeData::CraftingDecoratedPot(data) => data.encode(w),
-            // RecipeData::Smelting(data) => data.encode(w),
-            // RecipeData::Blasting(data) => data.encode(w),
-            // RecipeData::Smoking(data) => data.encode(w),
-            // RecipeData::CampfireCooking(data) => data.encode(w),
-            // RecipeData::Stonecutting(data) => data.encode(w),
-            // RecipeData::SmithingTransform(data) => data.encode(w),
-            // RecipeData::SmithingTrim(data) => data.encode(w),
+            Self::CraftingShaped(data) => data.encode(w),
+            Self::CraftingSpecialArmordye(data)
+            | Self::CraftingSpecialBookcloning(data)
+            | Self::CraftingSpecialMapcloning(data)
+            | Self::CraftingSpecialMapextending(data)
+            | Self::CraftingSpecialFireworkRocket(data)
+            | Self::CraftingSpecialFireworkStar(data)
+            | Self::CraftingSpecialFireworkStarFade(data)
+            | Self::CraftingSpecialRepairitem(data)
+            | Self::CraftingSpecialTippedarrow(data)
+            | Self::CraftingSpecialBannerduplicate(data)
+            | Self::CraftingSpecialShielddecoration(data)
+            | Self::CraftingSpecialShulkerboxcoloring(data)
+            | Self::CraftingSpecialSuspiciousstew(data)
+            | Self::CraftingDecoratedPot(data) => data.encode(w),
+            Self::Smelting(data)
+            | Self::Blasting(data)
+            | Self::Smoking(data)
+            | Self::CampfireCooking(data) => data.encode(w),
+            Self::Stonecutting(data) => data.encode(w),
+            Self::SmithingTransform(data) => data.encode(w),
+            Self::SmithingTrim(data) => data.encode(w),
         }
     }
 }
@@ -102,8 +144,241 @@ pub struct CraftingSpecialData {
     pub category: CraftingCategory,
 }
 
+/// Represents data for a shaped crafting recipe.
+#[derive(Clone, Debug)]
+pub struct CraftingShapedData {
+    /// The width of the pattern, at most 3.
+    width: u8,
+    /// The height of the pattern, at most 3.
+    height: u8,
+    /// Used to group similar recipes together in the recipe book.
+    group: String,
+    /// The category of the recipe.
+    category: CraftingCategory,
+    /// The pattern row by row. Empty ingredients are empty cells.
+    ingredients: Vec<Ingredient>,
+    /// The result of the crafting recipe.
+    result: ItemStack,
+    /// Whether the client shows a toast when the recipe is unlocked.
+    show_notification: bool,
+}
+
+impl CraftingShapedData {
+    /// A recipe with the pattern `rows`, where every character is looked up in `key`. Spaces are
+    /// empty cells.
+    pub fn new(
+        rows: &[&str],
+        key: &HashMap<char, Ingredient>,
+        result: ItemStack,
+    ) -> anyhow::Result<Self> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+
+        ensure!(
+            (1..=3).contains(&width) && (1..=3).contains(&height),
+            "the pattern must be between 1x1 and 3x3, but is {width}x{height}"
+        );
+
+        let mut ingredients = Vec::with_capacity(width * height);
+
+        for row in rows {
+            ensure!(
+                row.chars().count() == width,
+                "all rows of the pattern must be equally wide"
+            );
+
+            for symbol in row.chars() {
+                let ingredient = match symbol {
+                    ' ' => Ingredient::EMPTY,
+                    symbol => key
+                        .get(&symbol)
+                        .with_context(|| format!("{symbol:?} is not in the key"))?
+                        .clone(),
+                };
+
+                ingredients.push(ingredient);
+            }
+        }
+
+        Ok(Self {
+            width: u8::try_from(width)?,
+            height: u8::try_from(height)?,
+            group: String::new(),
+            category: CraftingCategory::default(),
+            ingredients,
+            result,
+            show_notification: true,
+        })
+    }
+
+    #[must_use]
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    #[must_use]
+    pub const fn category(mut self, category: CraftingCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    #[must_use]
+    pub const fn show_notification(mut self, show_notification: bool) -> Self {
+        self.show_notification = show_notification;
+        self
+    }
+
+    #[must_use]
+    pub const fn result(&self) -> &ItemStack {
+        &self.result
+    }
+
+    /// Whether the pattern, or the pattern mirrored horizontally, is in `grid`, which is a square
+    /// grid of items row by row.
+    #[must_use]
+    pub fn matches(&self, grid: &[ItemKind]) -> bool {
+        let Some(bounds) = Bounds::of(grid) else {
+            return false;
+        };
+
+        let width = usize::from(self.width);
+        let height = usize::from(self.height);
+
+        if bounds.width() != width || bounds.height() != height {
+            return false;
+        }
+
+        [false, true].into_iter().any(|mirrored| {
+            (0..height).all(|y| {
+                (0..width).all(|x| {
+                    let pattern_x = if mirrored { width - 1 - x } else { x };
+                    let ingredient = &self.ingredients[y * width + pattern_x];
+                    ingredient.matches(bounds.get(grid, x, y))
+                })
+            })
+        })
+    }
+}
+
+/// Represents data for a smelting, blasting, smoking or campfire cooking recipe.
+#[derive(Clone, Debug)]
+pub struct SmeltingData {
+    /// Used to group similar recipes together in the recipe book.
+    pub group: String,
+    /// The category of the recipe.
+    pub category: SmeltingCategory,
+    pub ingredient: Ingredient,
+    pub result: ItemStack,
+    /// The experience the player gets for taking the result out.
+    pub experience: f32,
+    /// How long the item takes to cook, in ticks.
+    pub cooking_time: i32,
+}
+
+/// Represents data for a stonecutting recipe.
+#[derive(Clone, Debug)]
+pub struct StonecuttingData {
+    /// Used to group similar recipes together in the recipe book.
+    pub group: String,
+    pub ingredient: Ingredient,
+    pub result: ItemStack,
+}
+
+/// Represents data for a smithing recipe that turns the base item into another item, e.g. a
+/// diamond into a netherite sword.
+#[derive(Clone, Debug)]
+pub struct SmithingTransformData {
+    pub template: Ingredient,
+    pub base: Ingredient,
+    pub addition: Ingredient,
+    /// The result, which keeps the NBT of the base item.
+    pub result: ItemStack,
+}
+
+impl SmithingTransformData {
+    #[must_use]
+    pub fn matches(&self, template: ItemKind, base: ItemKind, addition: ItemKind) -> bool {
+        self.template.matches(template)
+            && self.base.matches(base)
+            && self.addition.matches(addition)
+    }
+}
+
+/// Represents data for a smithing recipe that adds an armor trim to the base item.
+#[derive(Clone, Debug)]
+pub struct SmithingTrimData {
+    pub template: Ingredient,
+    pub base: Ingredient,
+    pub addition: Ingredient,
+}
+
+impl SmithingTrimData {
+    #[must_use]
+    pub fn matches(&self, template: ItemKind, base: ItemKind, addition: ItemKind) -> bool {
+        self.template.matches(template)
+            && self.base.matches(base)
+            && self.addition.matches(addition)
+    }
+}
+
+/// The bounding box of the items in a square crafting grid.
+struct Bounds {
+    grid_width: usize,
+    min_x: usize,
+    max_x: usize,
+    min_y: usize,
+    max_y: usize,
+}
+
+impl Bounds {
+    /// Returns `None` if the grid is empty.
+    fn of(grid: &[ItemKind]) -> Option<Self> {
+        let grid_width = grid.len().isqrt();
+
+        let mut bounds: Option<Self> = None;
+
+        for (i, item) in grid.iter().enumerate() {
+            if *item == ItemKind::Air {
+                continue;
+            }
+
+            let (x, y) = (i % grid_width, i / grid_width);
+
+            let bounds = bounds.get_or_insert(Self {
+                grid_width,
+                min_x: x,
+                max_x: x,
+                min_y: y,
+                max_y: y,
+            });
+
+            bounds.min_x = bounds.min_x.min(x);
+            bounds.max_x = bounds.max_x.max(x);
+            bounds.min_y = bounds.min_y.min(y);
+            bounds.max_y = bounds.max_y.max(y);
+        }
+
+        bounds
+    }
+
+    const fn width(&self) -> usize {
+        self.max_x - self.min_x + 1
+    }
+
+    const fn height(&self) -> usize {
+        self.max_y - self.min_y + 1
+    }
+
+    /// The item at `x`, `y` relative to the top left corner of the bounds.
+    fn get(&self, grid: &[ItemKind], x: usize, y: usize) -> ItemKind {
+        grid[(self.min_y + y) * self.grid_width + self.min_x + x]
+    }
+}
+
 /// Represents the categories for crafting recipes.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Default)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CraftingCategory {
     Building,
     Redstone,
@@ -113,16 +388,52 @@ pub enum CraftingCategory {
 }
 
 /// Represents the categories for smelting recipes.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Encode, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SmeltingCategory {
     Food,
     Blocks,
+    #[default]
     Misc,
 }
 
 /// Represents an ingredient in a recipe, which can be multiple possible items.
-#[derive(Encode, Clone, Debug)]
-struct Ingredient(Vec<ItemStack>);
+#[derive(Encode, Clone, Debug, Default)]
+pub struct Ingredient(Vec<ItemStack>);
+
+impl Ingredient {
+    /// An empty cell in a shaped recipe.
+    pub const EMPTY: Self = Self(Vec::new());
+
+    /// Whether `item` can be used as this ingredient. Only [`ItemKind::Air`] can be used as an
+    /// empty ingredient.
+    #[must_use]
+    pub fn matches(&self, item: ItemKind) -> bool {
+        if self.0.is_empty() {
+            return item == ItemKind::Air;
+        }
+
+        self.0.iter().any(|stack| stack.item == item)
+    }
+
+    /// The single item of this ingredient, or `None` if several items can be used.
+    fn single(&self) -> Option<ItemKind> {
+        match self.0.as_slice() {
+            [stack] => Some(stack.item),
+            _ => None,
+        }
+    }
+}
+
+impl FromIterator<ItemKind> for Ingredient {
+    fn from_iter<T: IntoIterator<Item = ItemKind>>(iter: T) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|item| ItemStack::new(item, 1, None))
+                .collect(),
+        )
+    }
+}
 
 impl From<Vec<ItemStack>> for Ingredient {
     fn from(value: Vec<ItemStack>) -> Self {
@@ -158,6 +469,57 @@ impl Encode for CraftingSpecialData {
     }
 }
 
+impl Encode for CraftingShapedData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        VarInt(i32::from(self.width)).encode(&mut w)?;
+        VarInt(i32::from(self.height)).encode(&mut w)?;
+        self.group.encode(&mut w)?;
+        self.category.encode(&mut w)?;
+        // the number of ingredients is width * height, so it is not prefixed
+        for ingredient in &self.ingredients {
+            ingredient.encode(&mut w)?;
+        }
+        self.result.encode(&mut w)?;
+        self.show_notification.encode(w)
+    }
+}
+
+impl Encode for SmeltingData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.group.encode(&mut w)?;
+        self.category.encode(&mut w)?;
+        self.ingredient.encode(&mut w)?;
+        self.result.encode(&mut w)?;
+        self.experience.encode(&mut w)?;
+        VarInt(self.cooking_time).encode(w)
+    }
+}
+
+impl Encode for StonecuttingData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.group.encode(&mut w)?;
+        self.ingredient.encode(&mut w)?;
+        self.result.encode(w)
+    }
+}
+
+impl Encode for SmithingTransformData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.template.encode(&mut w)?;
+        self.base.encode(&mut w)?;
+        self.addition.encode(&mut w)?;
+        self.result.encode(w)
+    }
+}
+
+impl Encode for SmithingTrimData {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.template.encode(&mut w)?;
+        self.base.encode(&mut w)?;
+        self.addition.encode(w)
+    }
+}
+
 #[derive(Debug, Encode, Packet)]
 pub struct UnlockRecipesS2c {
     pub action: Action,
@@ -217,31 +579,50 @@ impl FromIterator<ItemKind> for SortedItemList {
 }
 
 // Define a custom key type
-new_key_type! { struct SortedItemId; }
+new_key_type! { struct RecipeKey; }
+
+/// How an item is cooked.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Cooking {
+    Furnace,
+    BlastFurnace,
+    Smoker,
+    Campfire,
+}
+
+/// A smithing recipe that matched the items in a smithing table.
+#[derive(Copy, Clone, Debug)]
+pub enum Smithing<'a> {
+    Transform(&'a SmithingTransformData),
+    Trim(&'a SmithingTrimData),
+}
 
 #[derive(Component)]
 pub struct CraftingRegistry {
     // changes when the registry is updated
     epoch: u64,
 
-    shapeless_lookup: HashMap<SortedItemList, SortedItemId>,
-    shapeless: SlotMap<SortedItemId, CraftingShapelessData>,
-    shapeless_ids: SecondaryMap<SortedItemId, String>,
+    recipes: SlotMap<RecipeKey, Recipe>,
+    /// Registering a recipe ID again replaces the recipe.
+    ids: HashMap<String, RecipeKey>,
+    /// Shapeless recipes that only have single item ingredients, by their sorted items. Other
+    /// crafting recipes are matched one by one.
+    shapeless_lookup: HashMap<SortedItemList, RecipeKey>,
 }
 
 impl Default for CraftingRegistry {
     fn default() -> Self {
         let mut result = Self {
             epoch: 0,
+            recipes: SlotMap::default(),
+            ids: HashMap::default(),
             shapeless_lookup: HashMap::default(),
-            shapeless: SlotMap::default(),
-            shapeless_ids: SecondaryMap::default(),
         };
 
         let shapeless = CraftingShapelessData::new(ItemStack::new(ItemKind::OakPlanks, 4, None))
             .ingredient(ItemKind::OakLog);
 
-        result.register_shapeless("hyperion:plank".to_string(), shapeless);
+        result.register("hyperion:plank", RecipeData::CraftingShapeless(shapeless));
 
         result
     }
@@ -252,6 +633,41 @@ pub struct ShapelessRecipe<'a> {
     pub data: &'a CraftingShapelessData,
 }
 
+impl CraftingShapelessData {
+    /// Whether every ingredient is used by exactly one of `items`.
+    #[must_use]
+    pub fn matches(&self, items: &[ItemKind]) -> bool {
+        fn assign(ingredients: &[Ingredient], items: &[ItemKind], used: &mut [bool]) -> bool {
+            let Some((ingredient, rest)) = ingredients.split_first() else {
+                return true;
+            };
+
+            for (i, item) in items.iter().enumerate() {
+                if used[i] || !ingredient.matches(*item) {
+                    continue;
+                }
+
+                used[i] = true;
+
+                if assign(rest, items, used) {
+                    return true;
+                }
+
+                used[i] = false;
+            }
+
+            false
+        }
+
+        if items.len() != self.ingredients.len() {
+            return false;
+        }
+
+        let mut used = vec![false; items.len()];
+        assign(&self.ingredients, items, &mut used)
+    }
+}
+
 impl CraftingRegistry {
     fn mark_changed(&mut self) {
         self.epoch = self.epoch.wrapping_add(1);
@@ -264,58 +680,451 @@ impl CraftingRegistry {
             return None;
         }
 
-        let recipes: Vec<_> = self
-            .shapeless
-            .iter()
-            .map(|(id, data)| {
-                let recipe_id = self.shapeless_ids.get(id).unwrap();
-
-                Recipe {
-                    kind: "minecraft:crafting_shapeless",
-                    recipe_id: recipe_id.to_string(),
-                    data: RecipeData::CraftingShapeless(data.clone()),
-                }
-            })
-            .collect();
+        let recipes: Vec<_> = self.recipes.values().cloned().collect();
 
         Some(SynchronizeRecipesS2c { recipes })
     }
 
+    /// The IDs of all recipes, e.g. to unlock them in the recipe book.
+    pub fn recipe_ids(&self) -> impl Iterator<Item = &str> {
+        self.recipes
+            .values()
+            .map(|recipe| recipe.recipe_id.as_str())
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+
+    /// Adds a recipe, replacing the recipe with the same ID if there is one.
+    pub fn register(&mut self, recipe_id: impl Into<String>, data: RecipeData) {
+        let recipe_id = recipe_id.into();
+
+        if let Some(old) = self.ids.remove(&recipe_id) {
+            self.recipes.remove(old);
+            self.shapeless_lookup.retain(|_, key| *key != old);
+        }
+
+        let lookup = match &data {
+            RecipeData::CraftingShapeless(shapeless) if shapeless.ingredients.len() <= 9 => {
+                shapeless
+                    .ingredients
+                    .iter()
+                    .map(Ingredient::single)
+                    .collect::<Option<Vec<_>>>()
+            }
+            _ => None,
+        };
+
+        let key = self.recipes.insert(Recipe {
+            kind: data.kind(),
+            recipe_id: recipe_id.clone(),
+            data,
+        });
+
+        self.ids.insert(recipe_id, key);
+
+        if let Some(items) = lookup {
+            self.shapeless_lookup
+                .insert(items.into_iter().collect(), key);
+        }
+
+        self.mark_changed();
+    }
+
     pub fn get_shapeless(
         &self,
         input: impl IntoIterator<Item = ItemKind>,
     ) -> Option<ShapelessRecipe<'_>> {
-        let list: SortedItemList = input.into_iter().collect();
-        let id = self.shapeless_lookup.get(&list).copied()?;
+        let items: Vec<_> = input
+            .into_iter()
+            .filter(|item| *item != ItemKind::Air)
+            .collect();
 
-        // let recipe_id = self.shapeless_ids.get(id).unwrap();
-        let data = self.shapeless.get(id).unwrap();
+        if items.is_empty() || items.len() > 9 {
+            return None;
+        }
 
-        Some(ShapelessRecipe { data })
-    }
+        let list: SortedItemList = items.iter().copied().collect();
 
-    fn register_shapeless(&mut self, recipe_id: String, data: CraftingShapelessData) {
-        let list: SortedItemList = data
-            .ingredients
-            .iter()
-            .flat_map(|x| &x.0)
-            .map(|x| x.item)
-            .collect();
+        let found = self
+            .shapeless_lookup
+            .get(&list)
+            .and_then(|key| match self.recipes.get(*key) {
+                Some(Recipe {
+                    data: RecipeData::CraftingShapeless(data),
+                    ..
+                }) => Some(ShapelessRecipe { data }),
+                _ => None,
+            });
 
-        let entity_id = self.shapeless.insert(data);
-        self.shapeless_ids.insert(entity_id, recipe_id);
+        if found.is_some() {
+            return found;
+        }
 
-        self.shapeless_lookup.insert(list, entity_id);
+        self.recipes.values().find_map(|recipe| match &recipe.data {
+            RecipeData::CraftingShapeless(data) if data.matches(&items) => {
+                Some(ShapelessRecipe { data })
+            }
+            _ => None,
+        })
+    }
 
-        self.mark_changed();
+    /// The result of crafting the items in a square grid of items, row by row.
+    #[must_use]
+    pub fn get_result(&self, grid: &[ItemKind]) -> Option<&ItemStack> {
+        if let Some(shapeless) = self.get_shapeless(grid.iter().copied()) {
+            return Some(&shapeless.data.result);
+        }
+
+        self.recipes.values().find_map(|recipe| match &recipe.data {
+            RecipeData::CraftingShaped(data) if data.matches(grid) => Some(&data.result),
+            _ => None,
+        })
     }
 
     #[must_use]
     pub fn get_result_2x2(&self, grid: Crafting2x2) -> Option<&ItemStack> {
-        if let Some(shapeless) = self.get_shapeless(grid) {
-            return Some(&shapeless.data.result);
+        self.get_result(&grid)
+    }
+
+    #[must_use]
+    pub fn get_result_3x3(&self, grid: Crafting3x3) -> Option<&ItemStack> {
+        self.get_result(&grid)
+    }
+
+    /// The recipe for cooking `input` with `cooking`.
+    #[must_use]
+    pub fn get_cooking(&self, cooking: Cooking, input: ItemKind) -> Option<&SmeltingData> {
+        self.recipes.values().find_map(|recipe| {
+            let data = match (&recipe.data, cooking) {
+                (RecipeData::Smelting(data), Cooking::Furnace)
+                | (RecipeData::Blasting(data), Cooking::BlastFurnace)
+                | (RecipeData::Smoking(data), Cooking::Smoker)
+                | (RecipeData::CampfireCooking(data), Cooking::Campfire) => data,
+                _ => return None,
+            };
+
+            data.ingredient.matches(input).then_some(data)
+        })
+    }
+
+    /// Everything a stonecutter can make from `input`.
+    pub fn get_stonecutting(&self, input: ItemKind) -> impl Iterator<Item = &StonecuttingData> {
+        self.recipes
+            .values()
+            .filter_map(move |recipe| match &recipe.data {
+                RecipeData::Stonecutting(data) if data.ingredient.matches(input) => Some(data),
+                _ => None,
+            })
+    }
+
+    /// The smithing recipe for the items in a smithing table, if any.
+    #[must_use]
+    pub fn get_smithing(
+        &self,
+        template: ItemKind,
+        base: ItemKind,
+        addition: ItemKind,
+    ) -> Option<Smithing<'_>> {
+        self.recipes.values().find_map(|recipe| match &recipe.data {
+            RecipeData::SmithingTransform(data) if data.matches(template, base, addition) => {
+                Some(Smithing::Transform(data))
+            }
+            RecipeData::SmithingTrim(data) if data.matches(template, base, addition) => {
+                Some(Smithing::Trim(data))
+            }
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use crate::{
+        Cooking, CraftingRegistry, CraftingShapedData, RecipeData, SmeltingCategory, SmeltingData,
+        Smithing, SmithingTransformData, SmithingTrimData, StonecuttingData,
+        datapack::{ItemTags, parse_recipe},
+    };
+
+    const AIR: ItemKind = ItemKind::Air;
+    const STICK: ItemKind = ItemKind::Stick;
+    const STONE: ItemKind = ItemKind::Cobblestone;
+
+    fn pickaxe() -> CraftingShapedData {
+        let key = HashMap::from([('#', STONE.into()), ('|', STICK.into())]);
+        let result = ItemStack::new(ItemKind::StonePickaxe, 1, None);
+        CraftingShapedData::new(&["###", " | ", " | "], &key, result).unwrap()
+    }
+
+    #[test]
+    fn shaped_recipes_match_anywhere_and_mirrored() {
+        let key = HashMap::from([('#', ItemKind::OakPlanks.into())]);
+        let result = ItemStack::new(ItemKind::OakPressurePlate, 1, None);
+        let plate = CraftingShapedData::new(&["##"], &key, result).unwrap();
+
+        let planks = ItemKind::OakPlanks;
+        assert!(plate.matches(&[planks, planks, AIR, AIR]));
+        assert!(plate.matches(&[AIR, AIR, AIR, AIR, AIR, AIR, AIR, planks, planks]));
+        assert!(!plate.matches(&[planks, AIR, planks, AIR]));
+        assert!(!plate.matches(&[AIR; 4]));
+
+        let key = HashMap::from([('#', STONE.into()), ('|', STICK.into())]);
+        let result = ItemStack::new(ItemKind::StoneHoe, 1, None);
+        let hoe = CraftingShapedData::new(&["##", " |", " |"], &key, result).unwrap();
+
+        assert!(hoe.matches(&[STONE, STONE, AIR, AIR, STICK, AIR, AIR, STICK, AIR]));
+        assert!(hoe.matches(&[STONE, STONE, AIR, STICK, AIR, AIR, STICK, AIR, AIR]));
+        assert!(!hoe.matches(&[STONE, STONE, AIR, AIR, STICK, AIR, STICK, AIR, AIR]));
+    }
+
+    #[test]
+    fn registry_crafts_shaped_and_shapeless() {
+        let mut registry = CraftingRegistry::default();
+        registry.register(
+            "minecraft:stone_pickaxe",
+            RecipeData::CraftingShaped(pickaxe()),
+        );
+
+        let grid = [STONE, STONE, STONE, AIR, STICK, AIR, AIR, STICK, AIR];
+        let result = registry.get_result_3x3(grid).unwrap();
+        assert_eq!(result.item, ItemKind::StonePickaxe);
+
+        let grid = [AIR, AIR, ItemKind::OakLog, AIR];
+        let result = registry.get_result_2x2(grid).unwrap();
+        assert_eq!(result.item, ItemKind::OakPlanks);
+        assert_eq!(result.count, 4);
+
+        assert!(registry.get_result_2x2([STICK, AIR, AIR, AIR]).is_none());
+    }
+
+    #[test]
+    fn datapack_recipes_resolve_tags() {
+        let tags = ItemTags::from_raw_ids([("minecraft:stone_tool_materials".to_owned(), vec![
+            STONE.to_raw(),
+            ItemKind::Blackstone.to_raw(),
+        ])]);
+
+        let json = r##"{
+            "type": "minecraft:crafting_shaped",
+            "category": "equipment",
+            "key": {
+                "#": { "item": "minecraft:stick" },
+                "X": { "tag": "minecraft:stone_tool_materials" }
+            },
+            "pattern": ["XXX", " # ", " # "],
+            "result": { "item": "minecraft:stone_pickaxe" }
+        }"##;
+
+        let Some(RecipeData::CraftingShaped(pickaxe)) = parse_recipe(json, &tags).unwrap() else {
+            panic!("expected a shaped recipe");
+        };
+
+        let blackstone = ItemKind::Blackstone;
+        assert!(pickaxe.matches(&[
+            blackstone, STONE, blackstone, AIR, STICK, AIR, AIR, STICK, AIR
+        ]));
+
+        let json = r#"{
+            "type": "minecraft:smelting",
+            "ingredient": [{ "item": "minecraft:cobblestone" }],
+            "result": "minecraft:stone",
+            "experience": 0.1
+        }"#;
+
+        let Some(RecipeData::Smelting(smelting)) = parse_recipe(json, &tags).unwrap() else {
+            panic!("expected a smelting recipe");
+        };
+
+        assert_eq!(smelting.result.item, ItemKind::Stone);
+        assert_eq!(smelting.cooking_time, 200);
+
+        let json = r#"{ "type": "hyperion:unknown" }"#;
+        assert!(parse_recipe(json, &tags).unwrap().is_none());
+    }
+
+    fn cooking(input: ItemKind, result: ItemKind) -> SmeltingData {
+        SmeltingData {
+            group: String::new(),
+            category: SmeltingCategory::Misc,
+            ingredient: input.into(),
+            result: ItemStack::new(result, 1, None),
+            experience: 0.1,
+            cooking_time: 200,
         }
+    }
+
+    #[test]
+    fn cooking_recipes_only_match_their_appliance() {
+        let mut registry = CraftingRegistry::default();
+        registry.register(
+            "minecraft:iron_ingot_from_smelting_raw_iron",
+            RecipeData::Smelting(cooking(ItemKind::RawIron, ItemKind::IronIngot)),
+        );
+        registry.register(
+            "minecraft:iron_ingot_from_blasting_raw_iron",
+            RecipeData::Blasting(cooking(ItemKind::RawIron, ItemKind::IronIngot)),
+        );
+        registry.register(
+            "minecraft:cooked_beef_from_smoking",
+            RecipeData::Smoking(cooking(ItemKind::Beef, ItemKind::CookedBeef)),
+        );
+        registry.register(
+            "minecraft:cooked_cod_from_campfire_cooking",
+            RecipeData::CampfireCooking(cooking(ItemKind::Cod, ItemKind::CookedCod)),
+        );
+
+        let result = |cooking, input| {
+            registry
+                .get_cooking(cooking, input)
+                .map(|data| data.result.item)
+        };
+
+        assert_eq!(
+            result(Cooking::Furnace, ItemKind::RawIron),
+            Some(ItemKind::IronIngot)
+        );
+        assert_eq!(
+            result(Cooking::BlastFurnace, ItemKind::RawIron),
+            Some(ItemKind::IronIngot)
+        );
+        assert_eq!(
+            result(Cooking::Smoker, ItemKind::Beef),
+            Some(ItemKind::CookedBeef)
+        );
+        assert_eq!(
+            result(Cooking::Campfire, ItemKind::Cod),
+            Some(ItemKind::CookedCod)
+        );
+
+        assert_eq!(result(Cooking::Smoker, ItemKind::RawIron), None);
+        assert_eq!(result(Cooking::Campfire, ItemKind::Beef), None);
+        assert_eq!(result(Cooking::Furnace, ItemKind::Beef), None);
+        assert_eq!(result(Cooking::BlastFurnace, ItemKind::Cod), None);
+    }
+
+    #[test]
+    fn stonecutting_lists_every_result() {
+        let mut registry = CraftingRegistry::default();
+        for (id, result, count) in [
+            (
+                "minecraft:stone_slab_from_stonecutting",
+                ItemKind::StoneSlab,
+                2,
+            ),
+            (
+                "minecraft:stone_stairs_from_stonecutting",
+                ItemKind::StoneStairs,
+                1,
+            ),
+            (
+                "minecraft:stone_bricks_from_stonecutting",
+                ItemKind::StoneBricks,
+                1,
+            ),
+        ] {
+            registry.register(
+                id,
+                RecipeData::Stonecutting(StonecuttingData {
+                    group: String::new(),
+                    ingredient: ItemKind::Stone.into(),
+                    result: ItemStack::new(result, count, None),
+                }),
+            );
+        }
+
+        let results: Vec<_> = registry
+            .get_stonecutting(ItemKind::Stone)
+            .map(|data| (data.result.item, data.result.count))
+            .collect();
+
+        assert_eq!(results.len(), 3);
+        assert!(results.contains(&(ItemKind::StoneSlab, 2)));
+        assert!(results.contains(&(ItemKind::StoneStairs, 1)));
+        assert!(results.contains(&(ItemKind::StoneBricks, 1)));
+        assert_eq!(registry.get_stonecutting(ItemKind::Cobblestone).count(), 0);
+    }
+
+    #[test]
+    fn smithing_matches_transforms_and_trims() {
+        let mut registry = CraftingRegistry::default();
+        registry.register(
+            "minecraft:netherite_sword_smithing",
+            RecipeData::SmithingTransform(SmithingTransformData {
+                template: ItemKind::NetheriteUpgradeSmithingTemplate.into(),
+                base: ItemKind::DiamondSword.into(),
+                addition: ItemKind::NetheriteIngot.into(),
+                result: ItemStack::new(ItemKind::NetheriteSword, 1, None),
+            }),
+        );
+        registry.register(
+            "minecraft:coast_armor_trim_smithing_template_smithing_trim",
+            RecipeData::SmithingTrim(SmithingTrimData {
+                template: ItemKind::CoastArmorTrimSmithingTemplate.into(),
+                base: [ItemKind::IronChestplate, ItemKind::DiamondChestplate]
+                    .into_iter()
+                    .collect(),
+                addition: [ItemKind::GoldIngot, ItemKind::Emerald]
+                    .into_iter()
+                    .collect(),
+            }),
+        );
+
+        let Some(Smithing::Transform(transform)) = registry.get_smithing(
+            ItemKind::NetheriteUpgradeSmithingTemplate,
+            ItemKind::DiamondSword,
+            ItemKind::NetheriteIngot,
+        ) else {
+            panic!("expected the netherite upgrade to match");
+        };
+        assert_eq!(transform.result.item, ItemKind::NetheriteSword);
+
+        assert!(matches!(
+            registry.get_smithing(
+                ItemKind::CoastArmorTrimSmithingTemplate,
+                ItemKind::DiamondChestplate,
+                ItemKind::Emerald,
+            ),
+            Some(Smithing::Trim(_))
+        ));
 
-        None
+        // Every slot has to match, and the template decides which recipe applies.
+        assert!(
+            registry
+                .get_smithing(
+                    ItemKind::NetheriteUpgradeSmithingTemplate,
+                    ItemKind::IronSword,
+                    ItemKind::NetheriteIngot,
+                )
+                .is_none()
+        );
+        assert!(
+            registry
+                .get_smithing(
+                    ItemKind::CoastArmorTrimSmithingTemplate,
+                    ItemKind::DiamondChestplate,
+                    ItemKind::NetheriteIngot,
+                )
+                .is_none()
+        );
+        assert!(
+            registry
+                .get_smithing(
+                    ItemKind::NetheriteUpgradeSmithingTemplate,
+                    ItemKind::DiamondChestplate,
+                    ItemKind::Emerald,
+                )
+                .is_none()
+        );
     }
 }
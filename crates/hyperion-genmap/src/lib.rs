@@ -1,10 +1,17 @@
+use std::path::Path;
+
 use flecs_ecs::{
     core::{World, WorldGet},
     macros::Component,
     prelude::Module,
 };
-use hyperion::{runtime::AsyncRuntime, simulation::blocks::Blocks};
+use hyperion::{
+    runtime::AsyncRuntime,
+    simulation::blocks::{Blocks, generator::NoiseGenerator},
+};
 
+/// Downloads the pre-built GenMap and loads it. Use [`GeneratedMapModule`] to play without
+/// downloading anything.
 #[derive(Component)]
 pub struct GenMapModule;
 
@@ -26,3 +33,25 @@ impl Module for GenMapModule {
         });
     }
 }
+
+/// Generates the map from a fixed seed instead of downloading it. Changed chunks are saved to
+/// `world/` in the working directory.
+#[derive(Component)]
+pub struct GeneratedMapModule;
+
+impl GeneratedMapModule {
+    /// "hyperion" in ASCII.
+    pub const SEED: u64 = 0x6879_7065_7269_6f6e;
+}
+
+impl Module for GeneratedMapModule {
+    fn module(world: &World) {
+        world.import::<hyperion::HyperionCore>();
+
+        let generator = NoiseGenerator::new(Self::SEED);
+        let blocks = Blocks::generated(world, Path::new("world"), generator)
+            .unwrap_or_else(|e| panic!("failed to create generated world: {e}"));
+
+        world.set(blocks);
+    }
+}
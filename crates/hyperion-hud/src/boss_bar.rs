@@ -0,0 +1,266 @@
+//! Boss bars shown to a set of players.
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    net::{
+        Compose, ConnectionId, DataBundle,
+        packets::{BossBarAction, BossBarS2c},
+    },
+    uuid::Uuid,
+};
+use hyperion_text::Text;
+use rustc_hash::FxHashSet;
+use tracing::error;
+pub use valence_protocol::packets::play::boss_bar_s2c::{
+    BossBarColor, BossBarDivision, BossBarFlags,
+};
+
+/// A boss bar. Each entity with one is a boss bar shown to its viewers, and deleting the entity
+/// hides it from them.
+#[derive(Component, Clone, Debug)]
+pub struct BossBar {
+    pub title: String,
+    /// How full the bar is, from `0.0` to `1.0`.
+    pub progress: f32,
+    pub color: BossBarColor,
+    pub division: BossBarDivision,
+    pub flags: BossBarFlags,
+    viewers: FxHashSet<Entity>,
+}
+
+impl BossBar {
+    /// A full, pink boss bar called `title` nobody sees yet.
+    #[must_use]
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            progress: 1.0,
+            color: BossBarColor::Pink,
+            division: BossBarDivision::NoDivision,
+            flags: BossBarFlags::default(),
+            viewers: FxHashSet::default(),
+        }
+    }
+
+    /// Shows the boss bar to `player`.
+    pub fn add_viewer(&mut self, player: Entity) {
+        self.viewers.insert(player);
+    }
+
+    pub fn remove_viewer(&mut self, player: Entity) {
+        self.viewers.remove(&player);
+    }
+
+    #[must_use]
+    pub fn is_viewer(&self, player: Entity) -> bool {
+        self.viewers.contains(&player)
+    }
+
+    pub fn viewers(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.viewers.iter().copied()
+    }
+
+    /// The packets that turn `sent` into this boss bar.
+    fn updates(&self, sent: &Self) -> Vec<BossBarAction<'_>> {
+        let mut updates = Vec::new();
+
+        if self.title != sent.title {
+            updates.push(BossBarAction::UpdateTitle(Text::new(&self.title)));
+        }
+
+        if self.progress.to_bits() != sent.progress.to_bits() {
+            updates.push(BossBarAction::UpdateHealth(self.progress));
+        }
+
+        if self.color != sent.color || self.division != sent.division {
+            updates.push(BossBarAction::UpdateStyle(self.color, self.division));
+        }
+
+        if self.flags != sent.flags {
+            updates.push(BossBarAction::UpdateFlags(self.flags));
+        }
+
+        updates
+    }
+
+    fn add(&self) -> BossBarAction<'_> {
+        BossBarAction::Add {
+            title: Text::new(&self.title),
+            health: self.progress,
+            color: self.color,
+            division: self.division,
+            flags: self.flags,
+        }
+    }
+}
+
+/// The boss bar as its viewers were sent it.
+#[derive(Component, Default)]
+struct Sent(Option<BossBar>);
+
+/// The viewers of deleted boss bars, which are sent that the boss bar is gone.
+#[derive(Component, Default)]
+struct Removed(Vec<(Uuid, Vec<Entity>)>);
+
+/// Boss bars are told apart by their entity.
+fn uuid(entity: Entity) -> Uuid {
+    Uuid::from_u64_pair(0, entity.0)
+}
+
+/// Sends the packets in `bundle` to `player`, if they are still online.
+fn unicast(world: &World, bundle: &DataBundle<'_, '_>, player: Entity) {
+    if !world.is_alive(player) {
+        return;
+    }
+
+    let result = world
+        .entity_from_id(player)
+        .try_get::<&ConnectionId>(|stream| bundle.unicast(*stream));
+
+    if let Some(Err(e)) = result {
+        error!("failed to send a boss bar: {e}");
+    }
+}
+
+fn packets<'a, 'b>(
+    compose: &'a Compose,
+    system: EntityView<'b>,
+    id: Uuid,
+    actions: impl IntoIterator<Item = BossBarAction<'a>>,
+) -> DataBundle<'a, 'b> {
+    let mut bundle = DataBundle::new(compose, system);
+
+    for action in actions {
+        if let Err(e) = bundle.add_packet(&BossBarS2c { id, action }) {
+            error!("failed to write a boss bar: {e}");
+        }
+    }
+
+    bundle
+}
+
+#[derive(Component)]
+pub struct BossBarModule;
+
+impl Module for BossBarModule {
+    fn module(world: &World) {
+        world.component::<BossBar>();
+        world.component::<Sent>();
+        world.component::<Removed>();
+
+        world.set(Removed::default());
+
+        world
+            .component::<BossBar>()
+            .add_trait::<(flecs::With, Sent)>();
+
+        observer!(world, flecs::OnRemove, &Sent, &mut Removed($)).each_iter(
+            |it, row, (sent, removed)| {
+                let Some(bar) = &sent.0 else {
+                    return;
+                };
+
+                let id = uuid(it.entity(row).id());
+                removed.0.push((id, bar.viewers().collect()));
+            },
+        );
+
+        system!(
+            "send_boss_bars",
+            world,
+            &Compose($),
+            &mut BossBar,
+            &mut Sent,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, row, (compose, bar, sent)| {
+            let world = it.world();
+            let system = it.system();
+            let id = uuid(it.entity(row).id());
+
+            // players who left do not see anything anymore
+            bar.viewers.retain(|viewer| world.is_alive(*viewer));
+
+            let (updates, left, joined): (Vec<_>, Vec<_>, Vec<_>) = match &sent.0 {
+                Some(sent) => (
+                    bar.updates(sent),
+                    sent.viewers.difference(&bar.viewers).copied().collect(),
+                    bar.viewers.difference(&sent.viewers).copied().collect(),
+                ),
+                None => (Vec::new(), Vec::new(), bar.viewers().collect()),
+            };
+
+            if updates.is_empty() && left.is_empty() && joined.is_empty() {
+                return;
+            }
+
+            if !updates.is_empty() {
+                let bundle = packets(compose, system, id, updates);
+
+                for viewer in bar.viewers().filter(|viewer| !joined.contains(viewer)) {
+                    unicast(&world, &bundle, viewer);
+                }
+            }
+
+            if !left.is_empty() {
+                let bundle = packets(compose, system, id, [BossBarAction::Remove]);
+
+                for viewer in left {
+                    unicast(&world, &bundle, viewer);
+                }
+            }
+
+            if !joined.is_empty() {
+                let bundle = packets(compose, system, id, [bar.add()]);
+
+                for viewer in joined {
+                    unicast(&world, &bundle, viewer);
+                }
+            }
+
+            sent.0 = Some(bar.clone());
+        });
+
+        system!(
+            "send_removed_boss_bars",
+            world,
+            &Compose($),
+            &mut Removed($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, removed)| {
+            let world = it.world();
+            let system = it.system();
+
+            for (id, viewers) in removed.0.drain(..) {
+                let bundle = packets(compose, system, id, [BossBarAction::Remove]);
+
+                for viewer in viewers {
+                    unicast(&world, &bundle, viewer);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BossBar, BossBarAction, BossBarColor};
+
+    #[test]
+    fn only_changes_are_updated() {
+        let sent = BossBar::new("Timer");
+        let mut bar = sent.clone();
+
+        assert!(bar.updates(&sent).is_empty());
+
+        bar.progress = 0.5;
+        bar.color = BossBarColor::Red;
+
+        assert_eq!(bar.updates(&sent), [
+            BossBarAction::UpdateHealth(0.5),
+            BossBarAction::UpdateStyle(BossBarColor::Red, bar.division),
+        ]);
+    }
+}
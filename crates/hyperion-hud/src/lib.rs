@@ -0,0 +1,26 @@
+//! Boss bars, titles, the action bar and the tab list.
+//!
+//! Each of them is a component that is sent to players when it changes, so showing something is
+//! a matter of setting a field. [`BossBar`]s are entities of their own that are shown to a set of
+//! players, while [`Title`], [`ActionBar`] and [`TabList`] belong to a single player.
+
+use flecs_ecs::prelude::*;
+
+mod boss_bar;
+mod tab_list;
+mod title;
+
+pub use boss_bar::{BossBar, BossBarColor, BossBarDivision, BossBarFlags, BossBarModule};
+pub use tab_list::{TabList, TabListModule};
+pub use title::{ActionBar, Fade, Title, TitleModule};
+
+#[derive(Component)]
+pub struct HudModule;
+
+impl Module for HudModule {
+    fn module(world: &World) {
+        world.import::<BossBarModule>();
+        world.import::<TitleModule>();
+        world.import::<TabListModule>();
+    }
+}
@@ -0,0 +1,67 @@
+//! The header and footer of the tab list of a player.
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    net::{Compose, ConnectionId},
+    simulation::{PacketState, Player},
+};
+use tracing::error;
+use valence_protocol::{packets::play, text::IntoText};
+
+/// The text above and below the players in the tab list.
+#[derive(Component, Debug, Default)]
+pub struct TabList {
+    pub header: String,
+    pub footer: String,
+    sent: Option<(String, String)>,
+}
+
+impl TabList {
+    fn outdated(&self) -> bool {
+        match &self.sent {
+            Some((header, footer)) => *header != self.header || *footer != self.footer,
+            // players who never had a header or footer have an empty one
+            None => !self.header.is_empty() || !self.footer.is_empty(),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct TabListModule;
+
+impl Module for TabListModule {
+    fn module(world: &World) {
+        world.component::<TabList>();
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, TabList)>();
+
+        system!(
+            "send_tab_lists",
+            world,
+            &Compose($),
+            &mut TabList,
+            &ConnectionId,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, tab_list, stream)| {
+            if !tab_list.outdated() {
+                return;
+            }
+
+            let pkt = play::PlayerListHeaderS2c {
+                header: tab_list.header.as_str().into_cow_text(),
+                footer: tab_list.footer.as_str().into_cow_text(),
+            };
+
+            if let Err(e) = compose.unicast(&pkt, *stream, it.system()) {
+                error!("failed to send the tab list: {e}");
+            }
+
+            tab_list.sent = Some((tab_list.header.clone(), tab_list.footer.clone()));
+        });
+    }
+}
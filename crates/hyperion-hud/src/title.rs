@@ -0,0 +1,222 @@
+//! Titles and the action bar of a player.
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle},
+    simulation::{PacketState, Player},
+};
+use tracing::error;
+use valence_protocol::{packets::play, text::IntoText};
+
+/// How often an action bar that stays the same is sent again, as the client hides it after about
+/// three seconds.
+const ACTION_BAR_REFRESH: i64 = 40;
+
+/// How long a title fades in, stays and fades out, in ticks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Fade {
+    pub fade_in: i32,
+    pub stay: i32,
+    pub fade_out: i32,
+}
+
+impl Default for Fade {
+    /// The timings of vanilla.
+    fn default() -> Self {
+        Self {
+            fade_in: 10,
+            stay: 70,
+            fade_out: 20,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Shown {
+    title: String,
+    subtitle: String,
+    fade: Fade,
+}
+
+/// The title in the middle of the screen of a player.
+#[derive(Component, Debug, Default)]
+pub struct Title {
+    shown: Option<Shown>,
+    changed: bool,
+}
+
+impl Title {
+    /// Shows `title` with `subtitle` below it. Showing the title that is already shown does
+    /// nothing, so a countdown can show its time every tick. To show the same title again after
+    /// it faded, [`Self::clear`] it first.
+    pub fn show(&mut self, title: impl Into<String>, subtitle: impl Into<String>, fade: Fade) {
+        let shown = Shown {
+            title: title.into(),
+            subtitle: subtitle.into(),
+            fade,
+        };
+
+        if self.shown.as_ref() == Some(&shown) {
+            return;
+        }
+
+        self.shown = Some(shown);
+        self.changed = true;
+    }
+
+    /// Hides the title.
+    pub fn clear(&mut self) {
+        if self.shown.take().is_some() {
+            self.changed = true;
+        }
+    }
+
+    fn write(&self, bundle: &mut DataBundle<'_, '_>) -> anyhow::Result<()> {
+        let Some(shown) = &self.shown else {
+            return bundle.add_packet(&play::ClearTitleS2c { reset: true });
+        };
+
+        bundle.add_packet(&play::TitleFadeS2c {
+            fade_in: shown.fade.fade_in,
+            stay: shown.fade.stay,
+            fade_out: shown.fade.fade_out,
+        })?;
+
+        bundle.add_packet(&play::SubtitleS2c {
+            subtitle_text: shown.subtitle.as_str().into_cow_text(),
+        })?;
+
+        // the title is shown once it is sent, so it goes last
+        bundle.add_packet(&play::TitleS2c {
+            title_text: shown.title.as_str().into_cow_text(),
+        })
+    }
+}
+
+/// The text above the hotbar of a player, which stays until it is set to `None`.
+#[derive(Component, Debug, Default)]
+pub struct ActionBar {
+    pub text: Option<String>,
+    sent: Option<String>,
+    sent_at: i64,
+}
+
+impl ActionBar {
+    /// Whether the action bar has to be sent at `tick`.
+    fn outdated(&self, tick: i64) -> bool {
+        self.text != self.sent || (self.text.is_some() && tick - self.sent_at >= ACTION_BAR_REFRESH)
+    }
+}
+
+#[derive(Component)]
+pub struct TitleModule;
+
+impl Module for TitleModule {
+    fn module(world: &World) {
+        world.component::<Title>();
+        world.component::<ActionBar>();
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Title)>()
+            .add_trait::<(flecs::With, ActionBar)>();
+
+        system!(
+            "send_titles",
+            world,
+            &Compose($),
+            &mut Title,
+            &ConnectionId,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, title, stream)| {
+            if !title.changed {
+                return;
+            }
+
+            title.changed = false;
+
+            let mut bundle = DataBundle::new(compose, it.system());
+
+            if let Err(e) = title
+                .write(&mut bundle)
+                .and_then(|()| bundle.unicast(*stream))
+            {
+                error!("failed to send a title: {e}");
+            }
+        });
+
+        system!(
+            "send_action_bars",
+            world,
+            &Compose($),
+            &mut ActionBar,
+            &ConnectionId,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, action_bar, stream)| {
+            let tick = compose.global().tick;
+
+            if !action_bar.outdated(tick) {
+                return;
+            }
+
+            let pkt = play::GameMessageS2c {
+                chat: action_bar
+                    .text
+                    .as_deref()
+                    .unwrap_or_default()
+                    .into_cow_text(),
+                overlay: true,
+            };
+
+            if let Err(e) = compose.unicast(&pkt, *stream, it.system()) {
+                error!("failed to send an action bar: {e}");
+            }
+
+            action_bar.sent.clone_from(&action_bar.text);
+            action_bar.sent_at = tick;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ACTION_BAR_REFRESH, ActionBar, Fade, Title};
+
+    #[test]
+    fn titles_change_once() {
+        let mut title = Title::default();
+
+        title.show("3", "", Fade::default());
+        assert!(title.changed);
+
+        title.changed = false;
+        title.show("3", "", Fade::default());
+        assert!(!title.changed);
+
+        title.clear();
+        assert!(title.changed);
+    }
+
+    #[test]
+    fn action_bars_stay() {
+        let mut action_bar = ActionBar {
+            text: Some("10s left".to_owned()),
+            ..ActionBar::default()
+        };
+
+        assert!(action_bar.outdated(0));
+
+        action_bar.sent.clone_from(&action_bar.text);
+        assert!(!action_bar.outdated(1));
+        assert!(action_bar.outdated(ACTION_BAR_REFRESH));
+
+        action_bar.text = None;
+        assert!(action_bar.outdated(1));
+    }
+}
@@ -1,6 +1,7 @@
+use flecs_ecs::macros::Component;
 use valence_protocol::ItemStack;
 
-use super::{OFFHAND_SLOT, PlayerInventory, slot_index_from_hand};
+use super::window::Window;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum FullMouseButton {
@@ -41,9 +42,14 @@ pub enum InventoryAction {
         slot: u16,
     },
 
-    /// 'Q' key
-    Drop,
-    CtrlDrop,
+    /// 'Q' key, which drops a single item
+    Drop {
+        slot: u16,
+    },
+    /// 'Q' key while holding control, which drops the whole stack
+    CtrlDrop {
+        slot: u16,
+    },
 
     DragStart {
         button: FullMouseButton,
@@ -64,108 +70,1143 @@ pub enum InventoryAction {
     },
 }
 
-pub struct InventoryAndCursor {
-    pub inventory: PlayerInventory,
-    pub cursor: ItemStack,
+impl InventoryAction {
+    /// Whether the action is part of a drag, which any other click cancels.
+    const fn is_drag(self) -> bool {
+        matches!(
+            self,
+            Self::DragStart { .. } | Self::DragAdd { .. } | Self::DragEnd { .. }
+        )
+    }
+}
+
+/// The slots a player spreads the item held with their mouse over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Drag {
+    button: FullMouseButton,
+    slots: Vec<u16>,
+}
+
+/// What a player holds with their mouse while a window is open.
+#[derive(Component, Debug, Default, PartialEq)]
+pub struct Cursor {
+    pub item: ItemStack,
+    drag: Option<Drag>,
+    /// Counts the times the server corrected the open window. The client sends the last one it got
+    /// with every click, which tells clicks on outdated contents apart.
+    pub state_id: i32,
+}
+
+impl Cursor {
+    /// Moves on to the next state id, wrapping around like vanilla.
+    pub const fn next_state_id(&mut self) -> i32 {
+        self.state_id = (self.state_id + 1) & 0x7FFF;
+        self.state_id
+    }
+
+    /// Forgets the drag in progress, e.g. because the window was closed.
+    pub fn cancel_drag(&mut self) {
+        self.drag = None;
+    }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum Amount {
-    TrySingle,
-    All,
+/// A hotbar slot or the offhand, which number keys and 'F' swap slots with.
+#[derive(Debug, Clone, Copy)]
+enum Held {
+    Hotbar(u8),
+    Offhand,
 }
 
-fn take_one(item: &mut ItemStack) -> ItemStack {
-    if item.is_empty() {
+fn is_same_item(a: &ItemStack, b: &ItemStack) -> bool {
+    a.item == b.item && a.nbt == b.nbt
+}
+
+/// Splits `count` items off `item`.
+fn split(item: &mut ItemStack, count: i8) -> ItemStack {
+    let count = count.min(item.count);
+
+    if count <= 0 {
         return ItemStack::EMPTY;
     }
 
-    item.count -= 1;
+    let taken = item.clone().with_count(count);
+    item.count -= count;
+
+    if item.count <= 0 {
+        *item = ItemStack::EMPTY;
+    }
+
+    taken
+}
 
-    ItemStack::new(item.item, 1, item.nbt.clone())
+/// A window and the cursor of the player clicking in it, which together follow the click rules
+/// of vanilla.
+pub struct InventoryAndCursor<'a, W> {
+    pub inventory: W,
+    pub cursor: &'a mut Cursor,
+    /// The items thrown out of the window, which should be dropped in front of the player.
+    pub dropped: Vec<ItemStack>,
+    /// Uses up the crafting grid after its result was taken and shows what it crafts now.
+    craft: Option<Box<dyn FnMut(&mut W) + 'a>>,
 }
 
-impl InventoryAndCursor {
-    fn swap_cursor(&mut self, slot: u16, mode: Amount) {
-        let in_inventory = self.inventory.get_mut(slot).unwrap();
+impl<'a, W: Window> InventoryAndCursor<'a, W> {
+    pub const fn new(inventory: W, cursor: &'a mut Cursor) -> Self {
+        Self {
+            inventory,
+            cursor,
+            dropped: Vec::new(),
+            craft: None,
+        }
+    }
+
+    /// Calls `craft` whenever the crafting result is taken. It should use up the grid and put what
+    /// the grid crafts now into the result.
+    #[must_use]
+    pub fn with_crafting(mut self, craft: impl FnMut(&mut W) + 'a) -> Self {
+        self.craft = Some(Box::new(craft));
+        self
+    }
+
+    fn craft(&mut self) {
+        if let Some(craft) = &mut self.craft {
+            craft(&mut self.inventory);
+        }
+    }
+
+    /// Puts up to `count` items of the cursor into `slot`.
+    fn insert(&mut self, slot: u16, count: i8) {
+        if !self.inventory.may_place(slot, &self.cursor.item) {
+            return;
+        }
+
+        let max_stack_size = self.inventory.max_stack(slot, &self.cursor.item);
+
+        let cursor = &mut self.cursor.item;
+
+        let Some(item) = self.inventory.slot_mut(slot) else {
+            return;
+        };
+
+        let room = if item.is_empty() {
+            max_stack_size
+        } else if is_same_item(item, cursor) {
+            max_stack_size - item.count
+        } else {
+            return;
+        };
 
-        let cursor = &mut self.cursor;
+        let placed = split(cursor, count.min(room));
+
+        if item.is_empty() {
+            *item = placed;
+        } else {
+            item.count += placed.count;
+        }
+    }
+
+    /// Takes up to `count` items out of `slot`. Slots players cannot put items into are only
+    /// emptied if at most `limit` items are in them, and crafting results only as a whole.
+    fn take(&mut self, slot: u16, count: i8, limit: i8) -> ItemStack {
+        let may_place = self
+            .inventory
+            .slot(slot)
+            .is_some_and(|item| self.inventory.may_place(slot, item));
+        let is_crafting_result = self.inventory.is_crafting_result(slot);
+
+        let Some(item) = self.inventory.slot_mut(slot) else {
+            return ItemStack::EMPTY;
+        };
+
+        if !may_place && limit < item.count {
+            return ItemStack::EMPTY;
+        }
 
-        match mode {
-            Amount::TrySingle => {
-                if in_inventory.is_empty() {
-                    let single_cursor = take_one(cursor);
-                    *in_inventory = single_cursor;
-                } else {
-                    // we must swap as we cannot just put one item down
-                    core::mem::swap(in_inventory, cursor);
+        let count = if is_crafting_result {
+            item.count
+        } else {
+            count.min(limit)
+        };
+
+        let taken = split(item, count);
+
+        if is_crafting_result && !taken.is_empty() {
+            self.craft();
+        }
+
+        taken
+    }
+
+    /// A left or right click on `slot`.
+    fn click(&mut self, slot: u16, button: MouseButton) {
+        let Some(in_slot) = self.inventory.slot(slot).cloned() else {
+            return;
+        };
+
+        let cursor = &self.cursor.item;
+
+        if in_slot.is_empty() {
+            let count = match button {
+                MouseButton::Left => cursor.count,
+                MouseButton::Right => 1,
+            };
+
+            self.insert(slot, count);
+        } else if cursor.is_empty() {
+            // right clicks take the bigger half
+            let count = match button {
+                MouseButton::Left => in_slot.count,
+                MouseButton::Right => in_slot.count / 2 + in_slot.count % 2,
+            };
+
+            self.cursor.item = self.take(slot, count, i8::MAX);
+        } else if self.inventory.may_place(slot, cursor) {
+            if is_same_item(&in_slot, cursor) {
+                let count = match button {
+                    MouseButton::Left => cursor.count,
+                    MouseButton::Right => 1,
+                };
+
+                self.insert(slot, count);
+            } else if cursor.count <= self.inventory.max_stack(slot, cursor) {
+                let cursor = core::mem::replace(&mut self.cursor.item, in_slot);
+                if let Some(item) = self.inventory.slot_mut(slot) {
+                    *item = cursor;
                 }
             }
-            Amount::All => {
-                // swap the cursor and the item in the slot
-                core::mem::swap(in_inventory, cursor);
+        } else if is_same_item(&in_slot, cursor) {
+            // e.g. taking more of a furnace result, but only if all of it fits
+            let room = cursor.item.max_stack() - cursor.count;
+            let taken = self.take(slot, in_slot.count, room);
+            self.cursor.item.count += taken.count;
+        }
+    }
+
+    fn held_mut(&mut self, held: Held) -> Option<&mut ItemStack> {
+        match held {
+            Held::Hotbar(key) => {
+                let index = self.inventory.hotbar_slot(key);
+                self.inventory.slot_mut(index)
             }
+            Held::Offhand => Some(self.inventory.offhand_mut()),
         }
     }
 
-    #[expect(clippy::unused_self, unused, reason = "todo")]
-    const fn drop_cursor(&self, mode: Amount) {
-        // todo: we will also need to figure out a way to create entity spawn events from this
+    /// Swaps `slot` with a hotbar slot or the offhand.
+    fn swap_held(&mut self, slot: u16, held: Held) {
+        let Some(in_slot) = self.inventory.slot(slot).cloned() else {
+            return;
+        };
+
+        let Some(in_hand) = self.held_mut(held).cloned() else {
+            return;
+        };
+
+        if in_slot.is_empty() && in_hand.is_empty() {
+            return;
+        }
+
+        let (to_hand, to_slot) = if in_hand.is_empty() {
+            (self.take(slot, in_slot.count, i8::MAX), ItemStack::EMPTY)
+        } else if self.inventory.may_place(slot, &in_hand)
+            && in_hand.count <= self.inventory.max_stack(slot, &in_hand)
+        {
+            (in_slot, in_hand)
+        } else {
+            return;
+        };
+
+        if let Some(item) = self.inventory.slot_mut(slot) {
+            *item = to_slot;
+        }
+
+        if let Some(item) = self.held_mut(held) {
+            *item = to_hand;
+        }
     }
 
-    pub fn apply(&mut self, action: InventoryAction) {
-        match action {
-            InventoryAction::NormalClick { button, slot } => {
-                let mode = match button {
-                    MouseButton::Left => Amount::All,
-                    MouseButton::Right => Amount::TrySingle,
-                };
+    /// Moves `slot` to the other part of the window, again and again like vanilla as long as
+    /// equal items are left in it. Crafting results are only moved as a whole, so they are crafted
+    /// until the grid runs out, crafts something else or the result does not fit anymore.
+    fn quick_move(&mut self, slot: u16) {
+        let is_crafting_result = self.inventory.is_crafting_result(slot);
+
+        loop {
+            let Some(moving) = self.inventory.slot(slot).cloned() else {
+                return;
+            };
+
+            if moving.is_empty() {
+                return;
+            }
+
+            let before = self.inventory.items();
+
+            self.inventory.quick_move(slot);
+
+            let Some(left) = self.inventory.slot(slot).cloned() else {
+                return;
+            };
+
+            if is_crafting_result {
+                if !left.is_empty() {
+                    for (index, item) in (0..).zip(before) {
+                        if let Some(slot) = self.inventory.slot_mut(index) {
+                            *slot = item;
+                        }
+                    }
+
+                    return;
+                }
+
+                self.craft();
+            } else if left == moving {
+                return;
+            }
+
+            // e.g. carved pumpkins left after one was put on move into the inventory next
+            if !self
+                .inventory
+                .slot(slot)
+                .is_some_and(|item| is_same_item(item, &moving))
+            {
+                return;
+            }
+        }
+    }
+
+    /// Throws out the cursor item, or a single one of it for right clicks.
+    fn throw_cursor(&mut self, button: MouseButton) {
+        let count = match button {
+            MouseButton::Left => self.cursor.item.count,
+            MouseButton::Right => 1,
+        };
+
+        let thrown = split(&mut self.cursor.item, count);
+
+        if !thrown.is_empty() {
+            self.dropped.push(thrown);
+        }
+    }
+
+    /// Throws out a single item of `slot`, or all of them.
+    fn throw_slot(&mut self, slot: u16, all: bool) {
+        if !self.cursor.item.is_empty() {
+            return;
+        }
+
+        let count = if all { i8::MAX } else { 1 };
+        let thrown = self.take(slot, count, i8::MAX);
+
+        if !thrown.is_empty() {
+            self.dropped.push(thrown);
+        }
+    }
+
+    /// Whether a drag may spread the cursor item into `slot`.
+    fn can_drag_to(&self, slot: u16) -> bool {
+        let cursor = &self.cursor.item;
+
+        self.inventory.may_place(slot, cursor)
+            && self.inventory.slot(slot).is_some_and(|item| {
+                item.is_empty()
+                    || (is_same_item(item, cursor)
+                        && item.count <= self.inventory.max_stack(slot, cursor))
+            })
+    }
+
+    fn start_drag(&mut self, button: FullMouseButton) {
+        // starting twice cancels the drag
+        if self.cursor.drag.take().is_some() {
+            return;
+        }
+
+        // middle drags copy items, which only players in creative mode may do
+        if self.cursor.item.is_empty() || button == FullMouseButton::Middle {
+            return;
+        }
+
+        self.cursor.drag = Some(Drag {
+            button,
+            slots: Vec::new(),
+        });
+    }
+
+    fn add_drag_slot(&mut self, slot: u16) {
+        if self.cursor.item.is_empty() {
+            self.cursor.drag = None;
+            return;
+        }
+
+        let can_drag_to = self.can_drag_to(slot);
+        let count = usize::try_from(self.cursor.item.count).unwrap_or_default();
+
+        let Some(drag) = &mut self.cursor.drag else {
+            return;
+        };
+
+        // every slot gets at least one item
+        if can_drag_to && count > drag.slots.len() && !drag.slots.contains(&slot) {
+            drag.slots.push(slot);
+        }
+    }
+
+    /// Spreads the cursor item evenly over the dragged slots for left drags, or puts one into each
+    /// of them for right drags. Whatever is left stays on the cursor.
+    fn end_drag(&mut self) {
+        let Some(drag) = self.cursor.drag.take() else {
+            return;
+        };
+
+        if self.cursor.item.is_empty() {
+            return;
+        }
+
+        let button = match drag.button {
+            FullMouseButton::Left => MouseButton::Left,
+            FullMouseButton::Right | FullMouseButton::Middle => MouseButton::Right,
+        };
+
+        match drag.slots[..] {
+            [] => return,
+            // dragging over a single slot is a normal click
+            [slot] => return self.click(slot, button),
+            _ => {}
+        }
+
+        let per_slot = match button {
+            MouseButton::Left => {
+                self.cursor.item.count / i8::try_from(drag.slots.len()).unwrap_or(i8::MAX)
+            }
+            MouseButton::Right => 1,
+        };
+
+        let mut remaining = self.cursor.item.count;
 
-                self.swap_cursor(slot, mode);
+        for slot in drag.slots {
+            if !self.can_drag_to(slot) {
+                continue;
             }
-            InventoryAction::OutsideClick { button } => {
-                let mode = match button {
-                    MouseButton::Left => Amount::All,
-                    MouseButton::Right => Amount::TrySingle,
+
+            let stack = self.cursor.item.clone();
+            let max_stack_size = self.inventory.max_stack(slot, &stack);
+
+            let Some(item) = self.inventory.slot_mut(slot) else {
+                continue;
+            };
+
+            let before = if item.is_empty() { 0 } else { item.count };
+            let after = (before + per_slot).min(max_stack_size);
+
+            remaining -= after - before;
+            *item = stack.with_count(after);
+        }
+
+        self.cursor.item.count = remaining;
+
+        if remaining <= 0 {
+            self.cursor.item = ItemStack::EMPTY;
+        }
+    }
+
+    /// Fills up the cursor with equal items after a double click on the now empty `slot`. Slots
+    /// with less than a full stack are emptied first.
+    fn collect(&mut self, slot: u16, reverse: bool) {
+        let clicked_empty = self.inventory.slot(slot).is_some_and(ItemStack::is_empty);
+
+        if self.cursor.item.is_empty() || !clicked_empty {
+            return;
+        }
+
+        let max_stack_size = self.cursor.item.item.max_stack();
+
+        let mut order: Vec<u16> = (0..self.inventory.size()).collect();
+        if reverse {
+            order.reverse();
+        }
+
+        for full_stacks in [false, true] {
+            for &index in &order {
+                if self.cursor.item.count >= max_stack_size {
+                    return;
+                }
+
+                if self.inventory.is_crafting_result(index) {
+                    continue;
+                }
+
+                let Some(item) = self.inventory.slot(index) else {
+                    continue;
                 };
 
-                self.drop_cursor(mode);
+                if item.is_empty()
+                    || !is_same_item(item, &self.cursor.item)
+                    || (item.count >= max_stack_size && !full_stacks)
+                {
+                    continue;
+                }
+
+                let count = item.count;
+                let room = max_stack_size - self.cursor.item.count;
+                let taken = self.take(index, count, room);
+                self.cursor.item.count += taken.count;
             }
+        }
+    }
+
+    /// Applies a click the way vanilla does.
+    pub fn apply(&mut self, action: InventoryAction) {
+        if !action.is_drag() {
+            self.cursor.drag = None;
+        }
+
+        match action {
+            InventoryAction::NormalClick { button, slot } => self.click(slot, button),
+            InventoryAction::OutsideClick { button } => self.throw_cursor(button),
             InventoryAction::ShiftClick {
                 button: MouseButton::Left | MouseButton::Right,
-                ..
+                slot,
             } => {
                 // identical behavior so we combine branches
+                self.quick_move(slot);
             }
             InventoryAction::NumberKey { key, slot } => {
-                let other = slot_index_from_hand(key - 1);
-                self.inventory.swap(slot, other);
-            }
-            InventoryAction::OffhandSwap { slot } => {
-                self.inventory.swap(slot, OFFHAND_SLOT);
+                self.swap_held(slot, Held::Hotbar(key - 1));
             }
+            InventoryAction::OffhandSwap { slot } => self.swap_held(slot, Held::Offhand),
             InventoryAction::MiddleClick { .. } => {
-                unimplemented!("Middle click");
+                // copies the item in creative mode only
             }
-            InventoryAction::Drop | InventoryAction::CtrlDrop => {
-                // Implement drop logic here
-                self.drop_cursor(Amount::All);
-            }
-            InventoryAction::DragStart { .. } => {
-                unimplemented!("Drag start");
-            }
-            InventoryAction::DragAdd { .. } => {
-                unimplemented!("Drag add");
+            InventoryAction::Drop { slot } => self.throw_slot(slot, false),
+            InventoryAction::CtrlDrop { slot } => self.throw_slot(slot, true),
+            InventoryAction::DragStart { button } => self.start_drag(button),
+            InventoryAction::DragAdd { slot, .. } => self.add_drag_slot(slot),
+            InventoryAction::DragEnd { .. } => self.end_drag(),
+            InventoryAction::DoubleClick { slot } => self.collect(slot, false),
+            // impossible in vanilla
+            InventoryAction::PickupAllReverse { slot } => self.collect(slot, true),
+        }
+    }
+
+    /// Whether the client predicted the result of the last click right, given the contents of
+    /// the window `before` it and the changed slots and cursor item the client sent.
+    #[must_use]
+    pub fn is_predicted(
+        &self,
+        before: &[ItemStack],
+        changes: &[(u16, ItemStack)],
+        carried: &ItemStack,
+    ) -> bool {
+        if self.cursor.item != *carried {
+            return false;
+        }
+
+        let after = self.inventory.items();
+
+        let changed_as_predicted = changes
+            .iter()
+            .all(|(index, item)| after.get(usize::from(*index)) == Some(item));
+
+        let unchanged_as_predicted = (0..)
+            .zip(before.iter().zip(&after))
+            .filter(|(_, (before, after))| before != after)
+            .all(|(index, _)| changes.iter().any(|(changed, _)| *changed == index));
+
+        changed_as_predicted && unchanged_as_predicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use FullMouseButton::{Left as DragLeft, Right as DragRight};
+    use ItemKind::{
+        CarvedPumpkin, DiamondBoots, Dirt, IronChestplate, IronHelmet, LeatherChestplate, OakLog,
+        OakPlanks, SkeletonSkull, Stone,
+    };
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{Cursor, FullMouseButton, InventoryAction, InventoryAndCursor, MouseButton};
+    use crate::{Inventory, PlayerInventory, window::ContainerWindow};
+
+    type Stacks = &'static [(u16, ItemKind, i8)];
+
+    /// Clicks in the inventory of a player and what vanilla does with them.
+    struct Case {
+        name: &'static str,
+        slots: Stacks,
+        cursor: Option<(ItemKind, i8)>,
+        actions: &'static [InventoryAction],
+        expected_slots: Stacks,
+        expected_cursor: Option<(ItemKind, i8)>,
+        expected_dropped: &'static [(ItemKind, i8)],
+    }
+
+    const fn left(slot: u16) -> InventoryAction {
+        InventoryAction::NormalClick {
+            button: MouseButton::Left,
+            slot,
+        }
+    }
+
+    const fn right(slot: u16) -> InventoryAction {
+        InventoryAction::NormalClick {
+            button: MouseButton::Right,
+            slot,
+        }
+    }
+
+    const fn shift(slot: u16) -> InventoryAction {
+        InventoryAction::ShiftClick {
+            button: MouseButton::Left,
+            slot,
+        }
+    }
+
+    const fn drag_start(button: FullMouseButton) -> InventoryAction {
+        InventoryAction::DragStart { button }
+    }
+
+    const fn drag_add(button: FullMouseButton, slot: u16) -> InventoryAction {
+        InventoryAction::DragAdd { button, slot }
+    }
+
+    const fn drag_end(button: FullMouseButton) -> InventoryAction {
+        InventoryAction::DragEnd { button }
+    }
+
+    const CASES: &[Case] = &[
+        Case {
+            name: "left click picks up the stack",
+            slots: &[(9, Stone, 10)],
+            cursor: None,
+            actions: &[left(9)],
+            expected_slots: &[],
+            expected_cursor: Some((Stone, 10)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "right click picks up the bigger half",
+            slots: &[(9, Stone, 5)],
+            cursor: None,
+            actions: &[right(9)],
+            expected_slots: &[(9, Stone, 2)],
+            expected_cursor: Some((Stone, 3)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "left click puts down the stack",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[left(9)],
+            expected_slots: &[(9, Stone, 10)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "right click puts down one",
+            slots: &[(9, Stone, 3)],
+            cursor: Some((Stone, 10)),
+            actions: &[right(9), right(10)],
+            expected_slots: &[(9, Stone, 4), (10, Stone, 1)],
+            expected_cursor: Some((Stone, 8)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "left click fills up equal items",
+            slots: &[(9, Stone, 60)],
+            cursor: Some((Stone, 10)),
+            actions: &[left(9)],
+            expected_slots: &[(9, Stone, 64)],
+            expected_cursor: Some((Stone, 6)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "clicks swap different items",
+            slots: &[(9, Dirt, 3)],
+            cursor: Some((Stone, 10)),
+            actions: &[right(9)],
+            expected_slots: &[(9, Stone, 10)],
+            expected_cursor: Some((Dirt, 3)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "left drags split the stack evenly",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 9),
+                drag_add(DragLeft, 10),
+                drag_add(DragLeft, 11),
+                drag_add(DragLeft, 10),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[(9, Stone, 3), (10, Stone, 3), (11, Stone, 3)],
+            expected_cursor: Some((Stone, 1)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "right drags put one into each slot",
+            slots: &[(10, Stone, 5)],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragRight),
+                drag_add(DragRight, 9),
+                drag_add(DragRight, 10),
+                drag_end(DragRight),
+            ],
+            expected_slots: &[(9, Stone, 1), (10, Stone, 6)],
+            expected_cursor: Some((Stone, 8)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "drags stop at full stacks",
+            slots: &[(10, Stone, 63)],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 9),
+                drag_add(DragLeft, 10),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[(9, Stone, 5), (10, Stone, 64)],
+            expected_cursor: Some((Stone, 4)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "drags skip different items and the crafting result",
+            slots: &[(10, Dirt, 1)],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 0),
+                drag_add(DragLeft, 9),
+                drag_add(DragLeft, 10),
+                drag_add(DragLeft, 11),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[(9, Stone, 5), (10, Dirt, 1), (11, Stone, 5)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "a drag over a single slot is a click",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragRight),
+                drag_add(DragRight, 9),
+                drag_end(DragRight),
+            ],
+            expected_slots: &[(9, Stone, 1)],
+            expected_cursor: Some((Stone, 9)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "a drag cannot add more slots than items",
+            slots: &[],
+            cursor: Some((Stone, 2)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 9),
+                drag_add(DragLeft, 10),
+                drag_add(DragLeft, 11),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[(9, Stone, 1), (10, Stone, 1)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "starting a drag twice cancels it",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 9),
+                drag_start(DragLeft),
+                drag_add(DragLeft, 10),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[],
+            expected_cursor: Some((Stone, 10)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "other clicks cancel drags",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(DragLeft),
+                drag_add(DragLeft, 9),
+                drag_add(DragLeft, 10),
+                right(11),
+                drag_end(DragLeft),
+            ],
+            expected_slots: &[(11, Stone, 1)],
+            expected_cursor: Some((Stone, 9)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "middle drags are for creative mode",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                drag_start(FullMouseButton::Middle),
+                drag_add(FullMouseButton::Middle, 9),
+                drag_add(FullMouseButton::Middle, 10),
+                drag_end(FullMouseButton::Middle),
+            ],
+            expected_slots: &[],
+            expected_cursor: Some((Stone, 10)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "double clicks collect small stacks first",
+            slots: &[
+                (9, Stone, 64),
+                (10, Stone, 30),
+                (11, Stone, 40),
+                (12, Dirt, 5),
+            ],
+            cursor: Some((Stone, 1)),
+            actions: &[InventoryAction::DoubleClick { slot: 13 }],
+            expected_slots: &[(9, Stone, 64), (11, Stone, 7), (12, Dirt, 5)],
+            expected_cursor: Some((Stone, 64)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "double clicks take full stacks last",
+            slots: &[(9, Stone, 64), (10, Stone, 3)],
+            cursor: Some((Stone, 1)),
+            actions: &[InventoryAction::DoubleClick { slot: 13 }],
+            expected_slots: &[(9, Stone, 4)],
+            expected_cursor: Some((Stone, 64)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "double clicks leave the crafting result",
+            slots: &[(0, OakPlanks, 4), (9, OakPlanks, 2)],
+            cursor: Some((OakPlanks, 1)),
+            actions: &[InventoryAction::DoubleClick { slot: 13 }],
+            expected_slots: &[(0, OakPlanks, 4)],
+            expected_cursor: Some((OakPlanks, 3)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "clicks outside drop the cursor",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                InventoryAction::OutsideClick {
+                    button: MouseButton::Right,
+                },
+                InventoryAction::OutsideClick {
+                    button: MouseButton::Left,
+                },
+            ],
+            expected_slots: &[],
+            expected_cursor: None,
+            expected_dropped: &[(Stone, 1), (Stone, 9)],
+        },
+        Case {
+            name: "q drops one item or the whole stack",
+            slots: &[(9, Stone, 5), (10, Dirt, 7)],
+            cursor: None,
+            actions: &[
+                InventoryAction::Drop { slot: 9 },
+                InventoryAction::CtrlDrop { slot: 10 },
+            ],
+            expected_slots: &[(9, Stone, 4)],
+            expected_cursor: None,
+            expected_dropped: &[(Stone, 1), (Dirt, 7)],
+        },
+        Case {
+            name: "q does nothing while holding an item",
+            slots: &[(9, Stone, 5)],
+            cursor: Some((Dirt, 1)),
+            actions: &[InventoryAction::Drop { slot: 9 }],
+            expected_slots: &[(9, Stone, 5)],
+            expected_cursor: Some((Dirt, 1)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "number keys swap with the hotbar",
+            slots: &[(9, Stone, 5), (36, Dirt, 1), (20, OakPlanks, 3)],
+            cursor: None,
+            actions: &[
+                InventoryAction::NumberKey { key: 1, slot: 9 },
+                InventoryAction::NumberKey { key: 9, slot: 20 },
+            ],
+            expected_slots: &[(9, Dirt, 1), (36, Stone, 5), (44, OakPlanks, 3)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "f swaps with the offhand",
+            slots: &[(9, Stone, 5)],
+            cursor: None,
+            actions: &[InventoryAction::OffhandSwap { slot: 9 }],
+            expected_slots: &[(45, Stone, 5)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "shift clicks move between hotbar and main inventory",
+            slots: &[(36, Stone, 10), (9, Dirt, 2)],
+            cursor: None,
+            actions: &[InventoryAction::ShiftClick {
+                button: MouseButton::Left,
+                slot: 36,
+            }],
+            expected_slots: &[(9, Dirt, 2), (10, Stone, 10)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "the crafting result is taken as a whole",
+            slots: &[(0, OakPlanks, 4)],
+            cursor: None,
+            actions: &[right(0)],
+            expected_slots: &[],
+            expected_cursor: Some((OakPlanks, 4)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "the crafting result is only stacked onto the cursor if it fits",
+            slots: &[(0, OakPlanks, 4)],
+            cursor: Some((OakPlanks, 62)),
+            actions: &[left(0), right(9)],
+            expected_slots: &[(0, OakPlanks, 4), (9, OakPlanks, 1)],
+            expected_cursor: Some((OakPlanks, 61)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "nothing is put into the crafting result",
+            slots: &[],
+            cursor: Some((Stone, 10)),
+            actions: &[left(0), drag_start(DragRight), drag_add(DragRight, 0)],
+            expected_slots: &[],
+            expected_cursor: Some((Stone, 10)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "taking the crafting result uses up the grid",
+            slots: &[(0, OakPlanks, 4), (1, OakLog, 2)],
+            cursor: None,
+            actions: &[left(0)],
+            expected_slots: &[(0, OakPlanks, 4), (1, OakLog, 1)],
+            expected_cursor: Some((OakPlanks, 4)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "shift clicks on the crafting result craft until the grid runs out",
+            slots: &[(0, OakPlanks, 4), (1, OakLog, 3), (44, OakPlanks, 60)],
+            cursor: None,
+            actions: &[shift(0)],
+            expected_slots: &[(43, OakPlanks, 8), (44, OakPlanks, 64)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "only armor is put into armor slots",
+            slots: &[(36, Stone, 5)],
+            cursor: Some((Stone, 10)),
+            actions: &[
+                left(5),
+                right(6),
+                drag_start(DragLeft),
+                drag_add(DragLeft, 7),
+                drag_add(DragLeft, 8),
+                drag_end(DragLeft),
+                InventoryAction::NumberKey { key: 1, slot: 8 },
+            ],
+            expected_slots: &[(36, Stone, 5)],
+            expected_cursor: Some((Stone, 10)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "armor is only put into its own slot",
+            slots: &[],
+            cursor: Some((IronHelmet, 1)),
+            actions: &[left(6), left(8), left(5)],
+            expected_slots: &[(5, IronHelmet, 1)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "the head slot takes a single carved pumpkin",
+            slots: &[],
+            cursor: Some((CarvedPumpkin, 3)),
+            actions: &[left(5)],
+            expected_slots: &[(5, CarvedPumpkin, 1)],
+            expected_cursor: Some((CarvedPumpkin, 2)),
+            expected_dropped: &[],
+        },
+        Case {
+            name: "number keys put skulls on",
+            slots: &[(36, SkeletonSkull, 1)],
+            cursor: None,
+            actions: &[InventoryAction::NumberKey { key: 1, slot: 5 }],
+            expected_slots: &[(5, SkeletonSkull, 1)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "shift clicks put armor on and take it off",
+            slots: &[
+                (5, IronHelmet, 1),
+                (6, LeatherChestplate, 1),
+                (9, IronChestplate, 1),
+                (36, DiamondBoots, 1),
+            ],
+            cursor: None,
+            actions: &[shift(9), shift(36), shift(5)],
+            expected_slots: &[
+                (6, LeatherChestplate, 1),
+                (8, DiamondBoots, 1),
+                (9, IronHelmet, 1),
+                (37, IronChestplate, 1),
+            ],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "shift clicks put on one carved pumpkin and move the rest",
+            slots: &[(9, CarvedPumpkin, 3)],
+            cursor: None,
+            actions: &[shift(9)],
+            expected_slots: &[(5, CarvedPumpkin, 1), (36, CarvedPumpkin, 2)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+        Case {
+            name: "middle clicks are for creative mode",
+            slots: &[(9, Stone, 5)],
+            cursor: None,
+            actions: &[InventoryAction::MiddleClick { slot: 9 }],
+            expected_slots: &[(9, Stone, 5)],
+            expected_cursor: None,
+            expected_dropped: &[],
+        },
+    ];
+
+    fn stack((kind, count): (ItemKind, i8)) -> ItemStack {
+        ItemStack::new(kind, count, None)
+    }
+
+    /// Uses up the crafting grid, in which a single oak log in the first slot crafts four planks.
+    fn craft_planks(inventory: &mut PlayerInventory) {
+        for slot in 1..=4 {
+            let item = inventory.get_mut(slot).unwrap();
+
+            if !item.is_empty() {
+                item.count -= 1;
             }
-            InventoryAction::DragEnd { .. } => {
-                unimplemented!("Drag end");
+
+            if item.count <= 0 {
+                *item = ItemStack::EMPTY;
             }
-            InventoryAction::DoubleClick { .. } => {
-                unimplemented!("Double click");
+        }
+
+        let logs = inventory.get(1).unwrap().item == OakLog
+            && (2..=4).all(|slot| inventory.get(slot).unwrap().is_empty());
+
+        let result = if logs {
+            stack((OakPlanks, 4))
+        } else {
+            ItemStack::EMPTY
+        };
+
+        inventory.set(0, result).unwrap();
+    }
+
+    #[test]
+    fn clicks_match_vanilla() {
+        for case in CASES {
+            let mut inventory = PlayerInventory::default();
+            for &(slot, kind, count) in case.slots {
+                inventory.set(slot, stack((kind, count))).unwrap();
             }
-            InventoryAction::PickupAllReverse { .. } => {
-                unimplemented!("Pickup all reverse"); // impossible in vanilla
+
+            let mut cursor = Cursor {
+                item: case.cursor.map(stack).unwrap_or_default(),
+                ..Cursor::default()
+            };
+
+            let dropped = {
+                let mut window = InventoryAndCursor::new(&mut inventory, &mut cursor)
+                    .with_crafting(|inventory| craft_planks(inventory));
+
+                for &action in case.actions {
+                    window.apply(action);
+                }
+
+                window.dropped
+            };
+
+            let expected_dropped: Vec<_> =
+                case.expected_dropped.iter().copied().map(stack).collect();
+            assert_eq!(dropped, expected_dropped, "{}", case.name);
+
+            for (slot, item) in (0..).zip(inventory.slots()) {
+                let expected = case
+                    .expected_slots
+                    .iter()
+                    .find(|(expected, ..)| *expected == slot)
+                    .map(|&(_, kind, count)| stack((kind, count)))
+                    .unwrap_or_default();
+
+                assert_eq!(*item, expected, "{}: slot {slot}", case.name);
             }
+
+            let expected_cursor = case.expected_cursor.map(stack).unwrap_or_default();
+            assert_eq!(cursor.item, expected_cursor, "{}: cursor", case.name);
         }
     }
+
+    #[test]
+    fn right_clicks_take_half_of_furnace_results() {
+        let mut furnace = Inventory::<3>::default();
+        let mut player = PlayerInventory::default();
+        let mut cursor = Cursor::default();
+
+        furnace.set(2, stack((Stone, 9))).unwrap();
+
+        let window = ContainerWindow::new(&mut furnace, &mut player).with_inputs(0..2);
+        let mut window = InventoryAndCursor::new(window, &mut cursor);
+
+        window.apply(right(2));
+        assert_eq!(window.cursor.item, stack((Stone, 5)));
+
+        // the rest is only taken if it all fits
+        window.cursor.item.count = 61;
+        window.apply(left(2));
+        assert_eq!(window.cursor.item.count, 61);
+
+        window.cursor.item.count = 60;
+        window.apply(left(2));
+        assert_eq!(window.cursor.item.count, 64);
+
+        drop(window);
+        assert!(furnace.get(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn predictions_are_checked() {
+        let mut inventory = PlayerInventory::default();
+        inventory.set(9, stack((Stone, 5))).unwrap();
+
+        let mut cursor = Cursor::default();
+        let mut window = InventoryAndCursor::new(&mut inventory, &mut cursor);
+
+        let before = crate::window::Window::items(&window.inventory);
+        window.apply(right(9));
+
+        let carried = stack((Stone, 3));
+        assert!(window.is_predicted(&before, &[(9, stack((Stone, 2)))], &carried));
+        assert!(!window.is_predicted(&before, &[(9, stack((Stone, 3)))], &carried));
+        assert!(!window.is_predicted(&before, &[], &carried));
+        assert!(!window.is_predicted(&before, &[(9, stack((Stone, 2)))], &stack((Stone, 2))));
+    }
 }
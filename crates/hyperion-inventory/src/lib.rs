@@ -6,9 +6,13 @@ use valence_protocol::{ItemKind, ItemStack};
 
 pub mod action;
 pub mod parser;
+pub mod window;
 
 pub type PlayerInventory = Inventory<46>;
 
+/// The items in a crafting table window: the result in slot 0 and the 3x3 grid in slots 1 to 9.
+pub type CraftingTableInventory = Inventory<10>;
+
 /// Placeholder; this will be added later.
 #[derive(Component, Debug, PartialEq)]
 pub struct Inventory<const T: usize> {
@@ -34,7 +38,7 @@ impl<const T: usize> Default for Inventory<T> {
     }
 }
 
-use hyperion_crafting::{Crafting2x2, CraftingRegistry};
+use hyperion_crafting::{Crafting2x2, Crafting3x3, CraftingRegistry};
 use snafu::prelude::*;
 
 #[derive(Debug, Snafu)]
@@ -219,6 +223,26 @@ impl<const N: usize> Inventory<N> {
     }
 }
 
+impl CraftingTableInventory {
+    #[must_use]
+    pub fn crafting_result(&self, registry: &CraftingRegistry) -> ItemStack {
+        let items: Crafting3x3 = core::array::from_fn(|i| {
+            let stack = self.get(u16::try_from(i).unwrap() + 1).unwrap();
+
+            if stack.is_empty() {
+                return ItemKind::Air;
+            }
+
+            stack.item
+        });
+
+        registry
+            .get_result_3x3(items)
+            .cloned()
+            .unwrap_or(ItemStack::EMPTY)
+    }
+}
+
 impl PlayerInventory {
     pub const BOOTS_SLOT: u16 = 8;
     pub const CHESTPLATE_SLOT: u16 = 6;
@@ -375,5 +399,6 @@ pub struct InventoryModule;
 impl Module for InventoryModule {
     fn module(world: &World) {
         world.component::<PlayerInventory>();
+        world.component::<action::Cursor>();
     }
 }
@@ -122,19 +122,24 @@ pub fn create_inventory_action(mode: u8, button: u8, slot: i16) -> InventoryActi
         0 if slot == -999 => handle_outside_click(button),
         0 => handle_normal_click(button, slot),
         1 => handle_shift_click(button, slot.try_into().context(NegativeSlotSnafu)?),
+        // the offhand is swapped like a hotbar slot
+        2 if button == 40 => Ok(InventoryAction::OffhandSwap {
+            slot: slot.try_into().context(NegativeSlotSnafu)?,
+        }),
         2 => handle_number_key(button, slot.try_into().context(NegativeSlotSnafu)?),
         3 => match button {
-            40 => Ok(InventoryAction::OffhandSwap {
-                slot: slot.try_into().context(NegativeSlotSnafu)?,
-            }),
             2 => Ok(InventoryAction::MiddleClick {
                 slot: slot.try_into().context(NegativeSlotSnafu)?,
             }),
             _ => InvalidButtonSnafu { mode, button }.fail(),
         },
         4 => match button {
-            0 => Ok(InventoryAction::Drop),
-            1 => Ok(InventoryAction::CtrlDrop),
+            0 => Ok(InventoryAction::Drop {
+                slot: slot.try_into().context(NegativeSlotSnafu)?,
+            }),
+            1 => Ok(InventoryAction::CtrlDrop {
+                slot: slot.try_into().context(NegativeSlotSnafu)?,
+            }),
             _ => InvalidButtonSnafu { mode, button }.fail(),
         },
         5 => handle_drag(button, slot),
@@ -187,6 +192,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_offhand_swap() {
+        assert_eq!(
+            create_inventory_action(2, 40, 12).unwrap(),
+            InventoryAction::OffhandSwap { slot: 12 }
+        );
+
+        assert_eq!(
+            create_inventory_action(2, 8, 12).unwrap(),
+            InventoryAction::NumberKey { key: 9, slot: 12 }
+        );
+    }
+
+    #[test]
+    fn test_drop() {
+        assert_eq!(
+            create_inventory_action(4, 1, 20).unwrap(),
+            InventoryAction::CtrlDrop { slot: 20 }
+        );
+    }
+
     #[test]
     fn test_drag() {
         assert_eq!(
@@ -0,0 +1,443 @@
+//! The slots of the windows players click in, numbered like the client numbers them.
+
+use std::ops::Range;
+
+use valence_protocol::{ItemKind, ItemStack};
+
+use super::{Inventory, OFFHAND_SLOT, PlayerInventory, slot_index_from_hand};
+
+/// The first slot of the main inventory of a player, after the crafting grid and armor.
+const MAIN_START_SLOT: u16 = 9;
+
+/// The number of slots of the main inventory and the hotbar, which are shown below every
+/// container.
+const PLAYER_SLOTS: u16 = 36;
+
+/// An open window, e.g. the inventory of a player or a chest.
+pub trait Window {
+    /// The number of slots.
+    fn size(&self) -> u16;
+
+    /// The item in slot `index`, or `None` if the window has no such slot.
+    fn slot(&self, index: u16) -> Option<&ItemStack>;
+
+    fn slot_mut(&mut self, index: u16) -> Option<&mut ItemStack>;
+
+    /// The item in the offhand of the player looking at the window.
+    fn offhand_mut(&mut self) -> &mut ItemStack;
+
+    /// The window slot of hotbar slot `key`, from 0 to 8.
+    fn hotbar_slot(&self, key: u8) -> u16;
+
+    /// The slots an item shift-clicked in slot `index` moves to, in the order they are filled.
+    fn quick_move_targets(&self, index: u16) -> Vec<u16>;
+
+    /// Whether players may put `item` into slot `index`, which is not the case for e.g. the result
+    /// of a furnace or anything but boots in the boots slot.
+    fn may_place(&self, _index: u16, _item: &ItemStack) -> bool {
+        true
+    }
+
+    /// The most items of `item` slot `index` holds.
+    fn max_stack(&self, _index: u16, item: &ItemStack) -> i8 {
+        item.item.max_stack()
+    }
+
+    /// Whether slot `index` shows what a crafting grid crafts, which can only be taken as a whole.
+    fn is_crafting_result(&self, _index: u16) -> bool {
+        false
+    }
+
+    /// The items in all slots.
+    fn items(&self) -> Vec<ItemStack> {
+        (0..self.size())
+            .filter_map(|index| self.slot(index).cloned())
+            .collect()
+    }
+
+    fn swap(&mut self, a: u16, b: u16) {
+        if a == b {
+            return;
+        }
+
+        let Some(first) = self.slot_mut(a).map(core::mem::take) else {
+            return;
+        };
+
+        let first = match self.slot_mut(b) {
+            Some(second) => core::mem::replace(second, first),
+            // put it back
+            None => first,
+        };
+
+        if let Some(slot) = self.slot_mut(a) {
+            *slot = first;
+        }
+    }
+
+    /// Moves the item in slot `index` to the slots of [`Self::quick_move_targets`], stacking it
+    /// onto equal items before filling empty slots. What does not fit stays in `index`.
+    fn quick_move(&mut self, index: u16) {
+        let Some(mut moving) = self.slot_mut(index).map(core::mem::take) else {
+            return;
+        };
+
+        let targets = self.quick_move_targets(index);
+
+        for fill_empty in [false, true] {
+            for &target in &targets {
+                if moving.is_empty() {
+                    return;
+                }
+
+                let max_stack_size = self.max_stack(target, &moving);
+
+                if let Some(slot) = self.slot_mut(target) {
+                    merge(slot, &mut moving, fill_empty, max_stack_size);
+                }
+            }
+        }
+
+        if let Some(slot) = self.slot_mut(index) {
+            *slot = moving;
+        }
+    }
+}
+
+/// Moves as much of `from` into `into` as fits. Empty slots are only filled if `fill_empty`.
+fn merge(into: &mut ItemStack, from: &mut ItemStack, fill_empty: bool, max_stack_size: i8) {
+    if into.is_empty() {
+        if fill_empty {
+            let count = from.count.min(max_stack_size);
+            *into = from.clone().with_count(count);
+            from.count -= count;
+        }
+    } else if into.item == from.item && into.nbt == from.nbt && into.count < max_stack_size {
+        let count = from.count.min(max_stack_size - into.count);
+        into.count += count;
+        from.count -= count;
+    }
+
+    if from.count <= 0 {
+        *from = ItemStack::EMPTY;
+    }
+}
+
+impl<W: Window + ?Sized> Window for &mut W {
+    fn size(&self) -> u16 {
+        (**self).size()
+    }
+
+    fn slot(&self, index: u16) -> Option<&ItemStack> {
+        (**self).slot(index)
+    }
+
+    fn slot_mut(&mut self, index: u16) -> Option<&mut ItemStack> {
+        (**self).slot_mut(index)
+    }
+
+    fn offhand_mut(&mut self) -> &mut ItemStack {
+        (**self).offhand_mut()
+    }
+
+    fn hotbar_slot(&self, key: u8) -> u16 {
+        (**self).hotbar_slot(key)
+    }
+
+    fn quick_move_targets(&self, index: u16) -> Vec<u16> {
+        (**self).quick_move_targets(index)
+    }
+
+    fn may_place(&self, index: u16, item: &ItemStack) -> bool {
+        (**self).may_place(index, item)
+    }
+
+    fn max_stack(&self, index: u16, item: &ItemStack) -> i8 {
+        (**self).max_stack(index, item)
+    }
+
+    fn is_crafting_result(&self, index: u16) -> bool {
+        (**self).is_crafting_result(index)
+    }
+}
+
+impl Window for PlayerInventory {
+    fn size(&self) -> u16 {
+        u16::try_from(self.slots().len()).unwrap()
+    }
+
+    fn slot(&self, index: u16) -> Option<&ItemStack> {
+        self.get(index).ok()
+    }
+
+    fn slot_mut(&mut self, index: u16) -> Option<&mut ItemStack> {
+        self.get_mut(index).ok()
+    }
+
+    fn offhand_mut(&mut self) -> &mut ItemStack {
+        self.get_mut(OFFHAND_SLOT).unwrap()
+    }
+
+    fn hotbar_slot(&self, key: u8) -> u16 {
+        slot_index_from_hand(key)
+    }
+
+    fn quick_move_targets(&self, index: u16) -> Vec<u16> {
+        let hotbar = Self::HOTBAR_START_SLOT;
+
+        // armor is put on if nothing is worn there yet
+        let armor = self
+            .slot(index)
+            .and_then(|item| armor_slot(item.item))
+            .filter(|&armor| self.slot(armor).is_some_and(ItemStack::is_empty));
+
+        match (index, armor) {
+            // like vanilla, starting at the end of the hotbar
+            (0, _) => (MAIN_START_SLOT..hotbar + 9).rev().collect(),
+            (1..MAIN_START_SLOT, _) => (MAIN_START_SLOT..hotbar + 9).collect(),
+            (_, Some(armor)) => vec![armor],
+            (MAIN_START_SLOT..Self::HOTBAR_START_SLOT, None) => (hotbar..hotbar + 9).collect(),
+            (Self::HOTBAR_START_SLOT..OFFHAND_SLOT, None) => (MAIN_START_SLOT..hotbar).collect(),
+            _ => (MAIN_START_SLOT..hotbar + 9).collect(),
+        }
+    }
+
+    fn may_place(&self, index: u16, item: &ItemStack) -> bool {
+        match index {
+            _ if self.is_crafting_result(index) => false,
+            Self::HELMET_SLOT..=Self::BOOTS_SLOT => armor_slot(item.item) == Some(index),
+            _ => true,
+        }
+    }
+
+    fn max_stack(&self, index: u16, item: &ItemStack) -> i8 {
+        match index {
+            // e.g. carved pumpkins are worn one at a time
+            Self::HELMET_SLOT..=Self::BOOTS_SLOT => 1,
+            _ => item.item.max_stack(),
+        }
+    }
+
+    fn is_crafting_result(&self, index: u16) -> bool {
+        index == 0
+    }
+}
+
+/// The armor slot of the player inventory `item` is worn in, if it is worn at all.
+const fn armor_slot(item: ItemKind) -> Option<u16> {
+    match item {
+        ItemKind::LeatherHelmet
+        | ItemKind::ChainmailHelmet
+        | ItemKind::IronHelmet
+        | ItemKind::GoldenHelmet
+        | ItemKind::DiamondHelmet
+        | ItemKind::NetheriteHelmet
+        | ItemKind::TurtleHelmet
+        | ItemKind::CarvedPumpkin
+        | ItemKind::SkeletonSkull
+        | ItemKind::WitherSkeletonSkull
+        | ItemKind::PlayerHead
+        | ItemKind::ZombieHead
+        | ItemKind::CreeperHead
+        | ItemKind::DragonHead
+        | ItemKind::PiglinHead => Some(PlayerInventory::HELMET_SLOT),
+        ItemKind::LeatherChestplate
+        | ItemKind::ChainmailChestplate
+        | ItemKind::IronChestplate
+        | ItemKind::GoldenChestplate
+        | ItemKind::DiamondChestplate
+        | ItemKind::NetheriteChestplate
+        | ItemKind::Elytra => Some(PlayerInventory::CHESTPLATE_SLOT),
+        ItemKind::LeatherLeggings
+        | ItemKind::ChainmailLeggings
+        | ItemKind::IronLeggings
+        | ItemKind::GoldenLeggings
+        | ItemKind::DiamondLeggings
+        | ItemKind::NetheriteLeggings => Some(PlayerInventory::LEGGINGS_SLOT),
+        ItemKind::LeatherBoots
+        | ItemKind::ChainmailBoots
+        | ItemKind::IronBoots
+        | ItemKind::GoldenBoots
+        | ItemKind::DiamondBoots
+        | ItemKind::NetheriteBoots => Some(PlayerInventory::BOOTS_SLOT),
+        _ => None,
+    }
+}
+
+/// A container like a chest above the inventory of the player looking at it.
+///
+/// Slots `0..N` are the container, followed by the 27 slots of the main inventory and the 9
+/// slots of the hotbar of the player.
+pub struct ContainerWindow<'a, const N: usize> {
+    pub container: &'a mut Inventory<N>,
+    pub player: &'a mut PlayerInventory,
+    /// The container slots players may put items into.
+    pub inputs: Range<u16>,
+    /// The container slot that shows what a crafting grid crafts, if there is one.
+    pub crafting_result: Option<u16>,
+}
+
+impl<'a, const N: usize> ContainerWindow<'a, N> {
+    #[must_use]
+    pub fn new(container: &'a mut Inventory<N>, player: &'a mut PlayerInventory) -> Self {
+        Self {
+            container,
+            player,
+            inputs: 0..Self::container_size(),
+            crafting_result: None,
+        }
+    }
+
+    /// Only lets players put items into the container slots in `inputs`.
+    #[must_use]
+    pub const fn with_inputs(mut self, inputs: Range<u16>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    /// Makes container slot `index` a crafting result, which can only be taken as a whole.
+    #[must_use]
+    pub const fn with_crafting_result(mut self, index: u16) -> Self {
+        self.crafting_result = Some(index);
+        self
+    }
+
+    /// The number of container slots.
+    fn container_size() -> u16 {
+        u16::try_from(N).unwrap()
+    }
+
+    /// The slot of the player inventory shown in window slot `index`.
+    fn player_slot(index: u16) -> Option<u16> {
+        let index = index.checked_sub(Self::container_size())?;
+        (index < PLAYER_SLOTS).then_some(index + MAIN_START_SLOT)
+    }
+}
+
+impl<const N: usize> Window for ContainerWindow<'_, N> {
+    fn size(&self) -> u16 {
+        Self::container_size() + PLAYER_SLOTS
+    }
+
+    fn slot(&self, index: u16) -> Option<&ItemStack> {
+        if index < Self::container_size() {
+            return self.container.get(index).ok();
+        }
+
+        self.player.get(Self::player_slot(index)?).ok()
+    }
+
+    fn slot_mut(&mut self, index: u16) -> Option<&mut ItemStack> {
+        if index < Self::container_size() {
+            return self.container.get_mut(index).ok();
+        }
+
+        self.player.get_mut(Self::player_slot(index)?).ok()
+    }
+
+    fn offhand_mut(&mut self) -> &mut ItemStack {
+        self.player.offhand_mut()
+    }
+
+    fn hotbar_slot(&self, key: u8) -> u16 {
+        Self::container_size() + 27 + u16::from(key)
+    }
+
+    fn quick_move_targets(&self, index: u16) -> Vec<u16> {
+        if index < Self::container_size() {
+            // like vanilla, starting at the end of the hotbar
+            (Self::container_size()..Self::container_size() + PLAYER_SLOTS)
+                .rev()
+                .collect()
+        } else {
+            self.inputs.clone().collect()
+        }
+    }
+
+    fn may_place(&self, index: u16, _item: &ItemStack) -> bool {
+        index >= Self::container_size() || self.inputs.contains(&index)
+    }
+
+    fn is_crafting_result(&self, index: u16) -> bool {
+        self.crafting_result == Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::{ItemKind, ItemStack};
+
+    use super::{ContainerWindow, Window};
+    use crate::{CraftingTableInventory, Inventory, PlayerInventory};
+
+    #[test]
+    fn quick_move_between_container_and_player() {
+        let mut chest = Inventory::<27>::default();
+        let mut player = PlayerInventory::default();
+
+        chest
+            .set(0, ItemStack::new(ItemKind::Stone, 40, None))
+            .unwrap();
+        player
+            .set(44, ItemStack::new(ItemKind::Stone, 60, None))
+            .unwrap();
+
+        let mut window = ContainerWindow::new(&mut chest, &mut player);
+
+        // the last hotbar slot is stacked onto first, the rest goes into the last empty slot
+        window.quick_move(0);
+        assert!(window.slot_mut(0).unwrap().is_empty());
+        assert_eq!(window.slot_mut(27 + 35).unwrap().count, 64);
+        assert_eq!(window.slot_mut(27 + 34).unwrap().count, 36);
+
+        window.quick_move(27 + 34);
+        assert_eq!(window.slot_mut(0).unwrap().count, 36);
+
+        window.swap(0, window.hotbar_slot(0));
+        assert_eq!(player.get(36).unwrap().count, 36);
+        assert!(chest.get(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn furnace_results_cannot_be_filled() {
+        let mut furnace = Inventory::<3>::default();
+        let mut player = PlayerInventory::default();
+
+        player
+            .set(36, ItemStack::new(ItemKind::Coal, 10, None))
+            .unwrap();
+
+        let mut window = ContainerWindow::new(&mut furnace, &mut player).with_inputs(0..2);
+
+        let coal = ItemStack::new(ItemKind::Coal, 1, None);
+        assert!(window.may_place(1, &coal));
+        assert!(!window.may_place(2, &coal));
+
+        window.quick_move(3 + 27);
+        assert_eq!(window.slot_mut(0).unwrap().count, 10);
+        assert!(window.slot_mut(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn crafting_table_results_move_to_the_player() {
+        let mut table = CraftingTableInventory::default();
+        let mut player = PlayerInventory::default();
+
+        table
+            .set(0, ItemStack::new(ItemKind::OakPlanks, 4, None))
+            .unwrap();
+
+        let mut window = ContainerWindow::new(&mut table, &mut player)
+            .with_inputs(1..10)
+            .with_crafting_result(0);
+
+        let planks = ItemStack::new(ItemKind::OakPlanks, 1, None);
+        assert!(window.is_crafting_result(0));
+        assert!(!window.may_place(0, &planks));
+        assert!(window.may_place(9, &planks));
+
+        window.quick_move(0);
+        assert!(window.slot_mut(0).unwrap().is_empty());
+        assert_eq!(player.get(44).unwrap().count, 4);
+    }
+}
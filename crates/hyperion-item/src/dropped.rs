@@ -0,0 +1,590 @@
+//! Items lying in the world, which players pick up by walking into them.
+//!
+//! Every [`event::ItemDropEvent`] spawns an item entity. Items fall and slide like in vanilla,
+//! merge with items of the same kind next to them and disappear after five minutes.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use flecs_ecs::{
+    core::{
+        Builder, Entity, EntityView, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI,
+        TermBuilderImpl, World, flecs,
+    },
+    macros::{Component, system},
+    prelude::Module,
+};
+use geometry::aabb::Aabb;
+use hyperion::{
+    glam::Vec3,
+    net::{Compose, ConnectionId, DataBundle},
+    simulation::{
+        EntitySize, PacketState, Pitch, Player, Position, Uuid, Velocity, Yaw, aabb,
+        entity_kind::EntityKind, event, worlds::WorldId,
+    },
+    storage::EventQueue,
+    valence_protocol::{ByteAngle, Encode, ItemStack, RawBytes, VarInt, packets::play},
+};
+use hyperion_inventory::PlayerInventory;
+use hyperion_utils::EntityExt;
+use spatial::{Spatial, SpatialModule, with_index};
+use tracing::{error, info_span};
+
+/// Items disappear after five minutes.
+const DESPAWN_AGE: u16 = 6000;
+
+/// Items that fall below the bottom of the world are gone.
+const VOID_Y: f32 = -64.0;
+
+/// Items are picked up by players whose bounding box is this close to them.
+const PICKUP_REACH: Vec3 = Vec3::new(1.0, 0.5, 1.0);
+
+/// Items merge with items this close to them.
+const MERGE_REACH: Vec3 = Vec3::new(0.5, 0.0, 0.5);
+
+const ITEM_SIZE: EntitySize = EntitySize {
+    half_width: 0.125,
+    height: 0.25,
+};
+
+/// An item lying in the world.
+#[derive(Component, Debug)]
+pub struct DroppedItem {
+    pub stack: ItemStack,
+    /// The ticks until the item can be picked up.
+    pub pickup_delay: u16,
+    /// The ticks since the item was dropped.
+    pub age: u16,
+}
+
+/// The world whose items were last sent to a player.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+struct ShownItems(WorldId);
+
+/// An item that may merge with the items next to it.
+struct MergeCandidate {
+    entity: Entity,
+    world: WorldId,
+    position: Position,
+    reach: Aabb,
+    stack: ItemStack,
+    changed: bool,
+}
+
+impl MergeCandidate {
+    /// Moves as much of `other` into this item as fits.
+    fn merge(&mut self, other: &mut Self) {
+        let (stack, other_stack) = (&mut self.stack, &mut other.stack);
+
+        if stack.is_empty() || other_stack.is_empty() {
+            return;
+        }
+
+        if stack.item != other_stack.item || stack.nbt != other_stack.nbt {
+            return;
+        }
+
+        let moved = (stack.item.max_stack() - stack.count).min(other_stack.count);
+
+        if moved <= 0 {
+            return;
+        }
+
+        stack.count += moved;
+        other_stack.count -= moved;
+
+        if other_stack.count <= 0 {
+            *other_stack = ItemStack::EMPTY;
+        }
+
+        self.changed = true;
+        other.changed = true;
+    }
+}
+
+fn inflate(aabb: Aabb, by: Vec3) -> Aabb {
+    Aabb::new(aabb.min - by, aabb.max + by)
+}
+
+/// The metadata of an item entity, which is the item it shows.
+fn item_metadata(stack: &ItemStack) -> Vec<u8> {
+    // https://wiki.vg/Entity_metadata#Item_Entity
+    // 8 = Item, type = slot
+    let mut bytes = vec![8_u8];
+
+    // writing to a vec never fails
+    VarInt(7).encode(&mut bytes).unwrap();
+    stack.encode(&mut bytes).unwrap();
+
+    bytes.push(0xff);
+    bytes
+}
+
+/// Adds the packets showing an item entity to `bundle`.
+fn add_spawn_packets(
+    bundle: &mut DataBundle<'_, '_>,
+    entity: Entity,
+    uuid: &Uuid,
+    position: &Position,
+    velocity: &Velocity,
+    item: &DroppedItem,
+) -> anyhow::Result<()> {
+    let entity_id = VarInt(entity.minecraft_id());
+
+    bundle.add_packet(&play::EntitySpawnS2c {
+        entity_id,
+        object_uuid: uuid.0,
+        kind: VarInt(EntityKind::Item as i32),
+        position: position.as_dvec3(),
+        pitch: ByteAngle::from_degrees(0.0),
+        yaw: ByteAngle::from_degrees(0.0),
+        head_yaw: ByteAngle::from_degrees(0.0),
+        data: VarInt(1),
+        velocity: velocity.to_packet_units(),
+    })?;
+
+    let metadata = item_metadata(&item.stack);
+    bundle.add_packet(&play::EntityTrackerUpdateS2c {
+        entity_id,
+        tracked_values: RawBytes(&metadata),
+    })?;
+
+    Ok(())
+}
+
+/// Tells everyone in the world that the item entity shows `stack` now.
+fn update_stack(
+    compose: &Compose,
+    system: EntityView<'_>,
+    entity: Entity,
+    world_id: WorldId,
+    position: &Position,
+    stack: &ItemStack,
+) {
+    let metadata = item_metadata(stack);
+    let pkt = play::EntityTrackerUpdateS2c {
+        entity_id: VarInt(entity.minecraft_id()),
+        tracked_values: RawBytes(&metadata),
+    };
+
+    if let Err(e) = compose
+        .broadcast_local(&pkt, world_id, position.to_chunk(), system)
+        .send()
+    {
+        error!("failed to update dropped item: {e}");
+    }
+}
+
+/// Removes the item entity from the world.
+fn despawn(compose: &Compose, system: EntityView<'_>, entity: EntityView<'_>, world_id: WorldId) {
+    let pkt = play::EntitiesDestroyS2c {
+        entity_ids: Cow::Borrowed(&[VarInt(entity.minecraft_id())]),
+    };
+
+    if let Err(e) = compose.broadcast(&pkt, system).world(world_id).send() {
+        error!("failed to despawn dropped item: {e}");
+    }
+
+    entity.destruct();
+}
+
+#[derive(Component)]
+pub struct DroppedItemModule;
+
+impl Module for DroppedItemModule {
+    fn module(world: &World) {
+        world.import::<SpatialModule>();
+
+        world
+            .component::<DroppedItem>()
+            .add_trait::<(flecs::With, Spatial)>();
+        world.component::<ShownItems>();
+
+        system!(
+            "spawn_dropped_items",
+            world,
+            &Compose($),
+            &mut EventQueue<event::ItemDropEvent>($),
+        )
+        .each_iter(|it, _, (compose, event_queue)| {
+            let span = info_span!("spawn_dropped_items");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+
+            for event in event_queue.drain() {
+                if event.item.is_empty() {
+                    continue;
+                }
+
+                let uuid = Uuid::new_v4();
+                let position = Position::from(event.location);
+                let velocity = Velocity(event.velocity);
+                let item = DroppedItem {
+                    stack: event.item,
+                    pickup_delay: event.pickup_delay,
+                    age: 0,
+                };
+
+                let entity = world.entity();
+
+                let mut bundle = DataBundle::new(compose, system);
+                if let Err(e) =
+                    add_spawn_packets(&mut bundle, entity.id(), &uuid, &position, &velocity, &item)
+                {
+                    error!("failed to spawn dropped item: {e}");
+                    entity.destruct();
+                    continue;
+                }
+
+                if let Err(e) = bundle.broadcast_local(event.world, position.to_chunk()) {
+                    error!("failed to spawn dropped item: {e}");
+                }
+
+                // the uuid is set first, so the entity does not get another one
+                entity
+                    .set(uuid)
+                    .add_enum(EntityKind::Item)
+                    .set(position)
+                    .set(velocity)
+                    .set(Yaw::default())
+                    .set(Pitch::default())
+                    .set(ITEM_SIZE)
+                    .set(event.world)
+                    .set(item);
+            }
+        });
+
+        // items are moved by the physics of their entity kind
+        system!(
+            "age_dropped_items",
+            world,
+            &Compose($),
+            &mut DroppedItem,
+            &Position,
+            ?&WorldId,
+        )
+        .each_iter(|it, row, (compose, item, position, world_id)| {
+            let system = it.system();
+            let entity = it.entity(row);
+            let world_id = world_id.copied().unwrap_or_default();
+
+            item.age = item.age.saturating_add(1);
+            item.pickup_delay = item.pickup_delay.saturating_sub(1);
+
+            if item.age >= DESPAWN_AGE || position.y < VOID_Y {
+                // so it can no longer be picked up this tick
+                item.stack = ItemStack::EMPTY;
+                despawn(compose, system, entity, world_id);
+            }
+        });
+
+        system!(
+            "pick_up_dropped_items",
+            world,
+            &Compose($),
+            &mut DroppedItem,
+            &Position,
+            &EntitySize,
+            ?&WorldId,
+        )
+        .each_iter(|it, row, (compose, item, position, size, world_id)| {
+            if item.pickup_delay > 0 || item.stack.is_empty() {
+                return;
+            }
+
+            let system = it.system();
+            let world = it.world();
+            let entity = it.entity(row);
+            let world_id = world_id.copied().unwrap_or_default();
+
+            let reach = inflate(aabb(**position, *size), PICKUP_REACH);
+            let nearby = with_index(&world, world_id, |index| {
+                index.get_collisions(reach, &world).collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+            let mut picked_up = false;
+
+            for player in nearby {
+                if item.stack.is_empty() {
+                    break;
+                }
+
+                let player = world.entity_from_id(player);
+
+                let count = player
+                    .try_get::<&mut PlayerInventory>(|inventory| {
+                        let before = item.stack.count;
+                        let remaining = inventory.try_add_item(item.stack.clone()).remaining;
+                        item.stack = remaining.unwrap_or(ItemStack::EMPTY);
+
+                        before - item.stack.count
+                    })
+                    .unwrap_or_default();
+
+                if count == 0 {
+                    continue;
+                }
+
+                picked_up = true;
+
+                let pkt = play::ItemPickupAnimationS2c {
+                    collected_entity_id: VarInt(entity.minecraft_id()),
+                    collector_entity_id: VarInt(player.minecraft_id()),
+                    pickup_item_count: VarInt(i32::from(count)),
+                };
+
+                if let Err(e) = compose
+                    .broadcast_local(&pkt, world_id, position.to_chunk(), system)
+                    .send()
+                {
+                    error!("failed to send item pickup: {e}");
+                }
+            }
+
+            if item.stack.is_empty() {
+                despawn(compose, system, entity, world_id);
+            } else if picked_up {
+                update_stack(
+                    compose,
+                    system,
+                    entity.id(),
+                    world_id,
+                    position,
+                    &item.stack,
+                );
+            }
+        });
+
+        let items = world
+            .query::<(&DroppedItem, &Position, &EntitySize, Option<&WorldId>)>()
+            .build();
+
+        system!("merge_dropped_items", world, &Compose($)).each_iter(move |it, _, compose| {
+            let span = info_span!("merge_dropped_items");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+
+            // the items are merged on a copy, as merging changes two items at once
+            let mut candidates = Vec::new();
+            items.each_entity(|entity, (item, position, size, world_id)| {
+                if item.stack.is_empty() || item.stack.count >= item.stack.item.max_stack() {
+                    return;
+                }
+
+                candidates.push(MergeCandidate {
+                    entity: entity.id(),
+                    world: world_id.copied().unwrap_or_default(),
+                    position: *position,
+                    reach: inflate(aabb(**position, *size), MERGE_REACH),
+                    stack: item.stack.clone(),
+                    changed: false,
+                });
+            });
+
+            let indices: HashMap<_, _> = candidates
+                .iter()
+                .enumerate()
+                .map(|(idx, candidate)| (candidate.entity, idx))
+                .collect();
+
+            for idx in 0..candidates.len() {
+                let MergeCandidate {
+                    world: id, reach, ..
+                } = candidates[idx];
+
+                let nearby = with_index(&world, id, |index| {
+                    index.get_collisions(reach, &world).collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+                for other in nearby {
+                    let Some(&other) = indices.get(&other) else {
+                        continue;
+                    };
+
+                    if other == idx {
+                        continue;
+                    }
+
+                    // the smaller stack goes into the larger one
+                    let (into, from) =
+                        if candidates[idx].stack.count >= candidates[other].stack.count {
+                            (idx, other)
+                        } else {
+                            (other, idx)
+                        };
+
+                    let [into, from] = candidates.get_disjoint_mut([into, from]).unwrap();
+                    into.merge(from);
+                }
+            }
+
+            for candidate in candidates {
+                if !candidate.changed {
+                    continue;
+                }
+
+                let MergeCandidate {
+                    entity,
+                    world: id,
+                    position,
+                    stack,
+                    ..
+                } = candidate;
+
+                let entity = world.entity_from_id(entity);
+
+                if stack.is_empty() {
+                    entity.get::<&mut DroppedItem>(|item| item.stack = ItemStack::EMPTY);
+                    despawn(compose, system, entity, id);
+                } else {
+                    update_stack(compose, system, entity.id(), id, &position, &stack);
+                    entity.get::<&mut DroppedItem>(|item| item.stack = stack);
+                }
+            }
+        });
+
+        let shown = world
+            .query::<(&DroppedItem, &Uuid, &Position, &Velocity, Option<&WorldId>)>()
+            .build();
+
+        // players who join or change worlds see the items already lying there
+        system!(
+            "show_dropped_items",
+            world,
+            &Compose($),
+            &ConnectionId,
+            ?&WorldId,
+            ?&ShownItems,
+        )
+        .with::<Player>()
+        .with_enum(PacketState::Play)
+        .each_iter(move |it, row, (compose, &stream, world_id, shown_items)| {
+            let world_id = world_id.copied().unwrap_or_default();
+
+            if shown_items == Some(&ShownItems(world_id)) {
+                return;
+            }
+
+            let system = it.system();
+            let entity = it.entity(row);
+
+            let mut bundle = DataBundle::new(compose, system);
+
+            shown.each_entity(
+                |item_entity, (item, uuid, position, velocity, item_world)| {
+                    if item_world.copied().unwrap_or_default() != world_id || item.stack.is_empty()
+                    {
+                        return;
+                    }
+
+                    let result = add_spawn_packets(
+                        &mut bundle,
+                        item_entity.id(),
+                        uuid,
+                        position,
+                        velocity,
+                        item,
+                    );
+
+                    if let Err(e) = result {
+                        error!("failed to show dropped item: {e}");
+                    }
+                },
+            );
+
+            if let Err(e) = bundle.unicast(stream) {
+                error!("failed to show dropped items: {e}");
+            }
+
+            entity.set(ShownItems(world_id));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::World;
+    use geometry::aabb::Aabb;
+    use hyperion::{
+        glam::{IVec3, Vec3},
+        simulation::{Position, event, worlds::WorldId},
+        valence_protocol::{Decode, ItemKind, ItemStack, VarInt},
+    };
+
+    use super::{MergeCandidate, item_metadata};
+
+    fn candidate(world: &World, stack: ItemStack) -> MergeCandidate {
+        MergeCandidate {
+            entity: world.entity().id(),
+            world: WorldId::DEFAULT,
+            position: Position::new(0.0, 0.0, 0.0),
+            reach: Aabb::new(Vec3::ZERO, Vec3::ONE),
+            stack,
+            changed: false,
+        }
+    }
+
+    #[test]
+    fn metadata_holds_the_item() {
+        let stack = ItemStack::new(ItemKind::Diamond, 3, None);
+        let bytes = item_metadata(&stack);
+
+        let mut rest = &bytes[1..];
+        assert_eq!(bytes[0], 8);
+        assert_eq!(VarInt::decode(&mut rest).unwrap(), VarInt(7));
+        assert_eq!(ItemStack::decode(&mut rest).unwrap(), stack);
+        assert_eq!(rest, [0xff]);
+    }
+
+    #[test]
+    fn merging_fills_up_to_a_full_stack() {
+        let world = World::new();
+
+        let mut first = candidate(&world, ItemStack::new(ItemKind::Stone, 60, None));
+        let mut second = candidate(&world, ItemStack::new(ItemKind::Stone, 10, None));
+
+        first.merge(&mut second);
+        assert_eq!(first.stack.count, 64);
+        assert_eq!(second.stack.count, 6);
+        assert!(first.changed && second.changed);
+
+        let mut third = candidate(&world, ItemStack::new(ItemKind::Stone, 6, None));
+        second.merge(&mut third);
+        assert_eq!(second.stack.count, 12);
+        assert!(third.stack.is_empty());
+    }
+
+    #[test]
+    fn different_items_do_not_merge() {
+        let world = World::new();
+
+        let mut stone = candidate(&world, ItemStack::new(ItemKind::Stone, 1, None));
+        let mut dirt = candidate(&world, ItemStack::new(ItemKind::Dirt, 1, None));
+
+        stone.merge(&mut dirt);
+        assert_eq!(stone.stack.count, 1);
+        assert_eq!(dirt.stack.count, 1);
+        assert!(!stone.changed && !dirt.changed);
+    }
+
+    #[test]
+    fn block_drops_pop_out_of_the_block() {
+        let position = IVec3::new(3, 64, -2);
+        let stack = ItemStack::new(ItemKind::CoalOre, 1, None);
+
+        for _ in 0..100 {
+            let drop = event::ItemDropEvent::from_block(stack.clone(), position, WorldId::DEFAULT);
+
+            let min = position.as_vec3();
+            assert!(drop.location.cmpge(min).all());
+            assert!(drop.location.cmple(min + Vec3::ONE).all());
+            assert!(drop.velocity.y > 0.0);
+            assert_eq!(drop.item, stack);
+        }
+    }
+}
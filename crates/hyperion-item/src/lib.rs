@@ -12,6 +12,7 @@ use hyperion_utils::LifetimeHandle;
 use valence_protocol::nbt;
 
 pub mod builder;
+pub mod dropped;
 
 #[derive(Component)]
 pub struct ItemModule;
@@ -24,6 +25,7 @@ pub struct Handler {
 impl Module for ItemModule {
     fn module(world: &World) {
         world.import::<hyperion_inventory::InventoryModule>();
+        world.import::<dropped::DroppedItemModule>();
         world.component::<Handler>();
 
         world.get::<&mut HandlerRegistry>(|registry| {
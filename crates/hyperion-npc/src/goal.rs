@@ -0,0 +1,361 @@
+//! What NPCs want to do.
+//!
+//! Every NPC with [`Goals`] pursues the first of its goals it can pursue each tick, so goals
+//! earlier in the list take over from later ones. A zombie that attacks players in range and
+//! otherwise wanders around has the goals `[Attack, Wander]`.
+
+use flecs_ecs::prelude::*;
+use geometry::aabb::Aabb;
+use hyperion::{
+    glam::Vec3,
+    net::{Compose, ConnectionId},
+    simulation::{AiTargetable, Position, event, metadata::living_entity::Health, worlds::WorldId},
+    storage::EventBus,
+};
+use spatial::with_index;
+use tracing::warn;
+use valence_protocol::{VarInt, packets::play};
+
+use crate::navigation::Navigation;
+
+/// NPCs wander somewhere else after waiting this many ticks and up to twice as long.
+const WANDER_DELAY: i64 = 60;
+
+const WANDER_SPEED: f32 = 0.6;
+const FOLLOW_SPEED: f32 = 1.0;
+const ATTACK_SPEED: f32 = 1.2;
+const FLEE_SPEED: f32 = 1.5;
+
+/// Something an NPC wants to do.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Goal {
+    /// Walk to random places within `radius` blocks.
+    Wander { radius: f32 },
+    /// Walk after the closest targetable entity within `range` and stay `distance` away from it.
+    Follow { range: f32, distance: f32 },
+    /// Walk after the closest targetable entity within `range` and hit it for `damage` every
+    /// `cooldown` ticks while it is within `reach`.
+    Attack {
+        range: f32,
+        reach: f32,
+        damage: f32,
+        cooldown: i64,
+    },
+    /// Run away from targetable entities within `range`.
+    Flee { range: f32 },
+}
+
+/// The goals of an NPC, in the order of their priority.
+#[derive(Component, Debug, Default)]
+pub struct Goals {
+    goals: Vec<Goal>,
+    active: Option<usize>,
+    target: Option<Entity>,
+    last_attack: i64,
+    wander_at: i64,
+}
+
+impl Goals {
+    #[must_use]
+    pub fn new(goals: impl IntoIterator<Item = Goal>) -> Self {
+        Self {
+            goals: goals.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The goal the NPC pursues.
+    #[must_use]
+    pub fn active(&self) -> Option<Goal> {
+        self.active.map(|index| self.goals[index])
+    }
+
+    /// The entity the NPC follows, attacks or flees from.
+    #[must_use]
+    pub const fn target(&self) -> Option<Entity> {
+        self.target
+    }
+}
+
+/// The closest entity to `position` within `range` that NPCs target, other than `entity`.
+fn closest_target(
+    world: &World,
+    world_id: WorldId,
+    entity: Entity,
+    position: Vec3,
+    range: f32,
+) -> Option<(Entity, Vec3)> {
+    let area = Aabb::new(position - Vec3::splat(range), position + Vec3::splat(range));
+
+    let closest = with_index(world, world_id, |index| {
+        index
+            .get_collisions(area, world)
+            .filter(|&candidate| candidate != entity)
+            .map(|candidate| world.entity_from_id(candidate))
+            .filter(|candidate| candidate.has::<AiTargetable>())
+            .filter_map(|candidate| {
+                candidate.try_get::<&Position>(|target| (candidate.id(), **target))
+            })
+            .filter(|(_, target)| target.distance_squared(position) <= range * range)
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+    });
+
+    closest.flatten()
+}
+
+/// Deals `damage` to `target` on behalf of `attacker`. Returns the health the target has left,
+/// or `None` if it cannot take damage or a listener cancelled the damage.
+///
+/// The attacks of players are handled by the game mode, which knows about their weapons and
+/// armor, so NPCs hit on their own.
+fn hit(world: &World, attacker: Entity, target: Entity, damage: f32) -> Option<f32> {
+    let mut damage = event::Damage {
+        target,
+        source: Some(attacker),
+        amount: damage,
+    };
+
+    if !EventBus::dispatch(world, &mut damage) {
+        return None;
+    }
+
+    let target = world.entity_from_id(target);
+
+    let dead = target.try_get::<&mut Health>(|health| {
+        health.damage(damage.amount);
+        health.is_dead()
+    })?;
+
+    if dead {
+        let mut death = event::Death {
+            entity: target.id(),
+            killer: Some(attacker),
+        };
+
+        if !EventBus::dispatch(world, &mut death) {
+            target.get::<&mut Health>(|health| health.heal(1.0));
+        }
+    }
+
+    target.try_get::<&Health>(|health| **health)
+}
+
+struct Context<'a> {
+    world: &'a World,
+    compose: &'a Compose,
+    system: EntityView<'a>,
+    world_id: WorldId,
+    entity: Entity,
+    position: Vec3,
+    tick: i64,
+}
+
+impl Context<'_> {
+    /// Pursues `goal`. Returns whether the NPC could.
+    fn pursue(&self, goal: Goal, goals: &mut Goals, navigation: &mut Navigation) -> bool {
+        match goal {
+            Goal::Wander { radius } => {
+                goals.target = None;
+                navigation.look_at(None);
+
+                if navigation.is_idle() && self.tick >= goals.wander_at {
+                    let offset = Vec3::new(
+                        fastrand::f32() * 2.0 - 1.0,
+                        0.0,
+                        fastrand::f32() * 2.0 - 1.0,
+                    );
+                    navigation.go_to(self.position + offset * radius, WANDER_SPEED);
+                    goals.wander_at = self.tick + WANDER_DELAY + fastrand::i64(0..WANDER_DELAY);
+                }
+
+                true
+            }
+            Goal::Follow { range, distance } => {
+                let Some((target, position)) = self.target(range) else {
+                    return false;
+                };
+
+                goals.target = Some(target);
+                navigation.look_at(Some(position));
+
+                if self.position.distance(position) > distance {
+                    navigation.go_to(position, FOLLOW_SPEED);
+                } else {
+                    navigation.stop();
+                }
+
+                true
+            }
+            Goal::Attack {
+                range,
+                reach,
+                damage,
+                cooldown,
+            } => {
+                let Some((target, position)) = self.target(range) else {
+                    return false;
+                };
+
+                goals.target = Some(target);
+                navigation.look_at(Some(position));
+                navigation.go_to(position, ATTACK_SPEED);
+
+                if self.position.distance(position) <= reach
+                    && self.tick - goals.last_attack >= cooldown
+                {
+                    goals.last_attack = self.tick;
+                    self.attack(target, damage);
+                }
+
+                true
+            }
+            Goal::Flee { range } => {
+                let Some((target, position)) = self.target(range) else {
+                    return false;
+                };
+
+                goals.target = Some(target);
+                navigation.look_at(None);
+
+                let away = self.position - position;
+                let away = Vec3::new(away.x, 0.0, away.z)
+                    .try_normalize()
+                    .unwrap_or(Vec3::X);
+                navigation.go_to(self.position + away * range, FLEE_SPEED);
+
+                true
+            }
+        }
+    }
+
+    /// Hits `target` and shows players their new health.
+    fn attack(&self, target: Entity, damage: f32) {
+        let Some(health) = hit(self.world, self.entity, target, damage) else {
+            return;
+        };
+
+        let Some(connection) = self
+            .world
+            .entity_from_id(target)
+            .try_get::<&ConnectionId>(|connection| *connection)
+        else {
+            return;
+        };
+
+        let pkt = play::HealthUpdateS2c {
+            health,
+            food: VarInt(20),
+            food_saturation: 5.0,
+        };
+
+        if let Err(e) = self.compose.unicast(&pkt, connection, self.system) {
+            warn!("failed to send health update: {e}");
+        }
+    }
+
+    fn target(&self, range: f32) -> Option<(Entity, Vec3)> {
+        closest_target(self.world, self.world_id, self.entity, self.position, range)
+    }
+}
+
+#[derive(Component)]
+pub struct GoalModule;
+
+impl Module for GoalModule {
+    fn module(world: &World) {
+        world
+            .component::<Goals>()
+            .add_trait::<(flecs::With, Navigation)>();
+
+        // not multi-threaded because the spatial index is queried from the main thread
+        system!(
+            "select_goals",
+            world,
+            &Compose($),
+            &mut Goals,
+            &mut Navigation,
+            &Position,
+            ?&WorldId,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(
+            |it, row, (compose, goals, navigation, position, world_id)| {
+                let world = it.world();
+
+                let context = Context {
+                    world: &world,
+                    compose,
+                    system: it.system(),
+                    world_id: world_id.copied().unwrap_or_default(),
+                    entity: it.entity(row).id(),
+                    position: **position,
+                    tick: compose.global().tick,
+                };
+
+                let previous = goals.active;
+                goals.active = None;
+
+                for index in 0..goals.goals.len() {
+                    let goal = goals.goals[index];
+
+                    // wandering starts where the previous goal left the NPC
+                    if matches!(goal, Goal::Wander { .. }) && previous != Some(index) {
+                        navigation.stop();
+                    }
+
+                    if context.pursue(goal, goals, navigation) {
+                        goals.active = Some(index);
+                        break;
+                    }
+                }
+
+                // whatever the NPC did before is over
+                if previous.is_some() && goals.active.is_none() {
+                    goals.target = None;
+                    navigation.look_at(None);
+                    navigation.stop();
+                }
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+    use hyperion::{
+        simulation::{event, metadata::living_entity::Health},
+        storage::{EventBus, Priority},
+    };
+
+    use super::hit;
+
+    #[test]
+    fn attacks_damage_the_target() {
+        let world = World::new();
+        world.set(EventBus::default());
+
+        let zombie = world.entity().id();
+        let target = world.entity().set(Health::default()).id();
+
+        let left = hit(&world, zombie, target, 3.0).unwrap();
+        assert!((left - 17.0).abs() < f32::EPSILON);
+
+        // entities without health are not hurt
+        assert!(hit(&world, zombie, world.entity().id(), 3.0).is_none());
+
+        world.get::<&mut EventBus>(|bus| {
+            bus.listen(Priority::Normal, |_: &mut event::Damage, context| {
+                context.cancel();
+            });
+        });
+
+        assert!(hit(&world, zombie, target, 3.0).is_none());
+        world
+            .entity_from_id(target)
+            .get::<&Health>(|health| assert!((**health - 17.0).abs() < f32::EPSILON));
+    }
+}
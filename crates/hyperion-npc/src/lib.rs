@@ -0,0 +1,25 @@
+//! NPCs that find their way through the world on their own.
+//!
+//! [`path`] finds paths through the blocks of a world, [`navigation`] walks NPCs along them and
+//! [`goal`] decides where NPCs walk to and what they do there.
+
+#![feature(let_chains)]
+
+use flecs_ecs::prelude::*;
+
+pub mod goal;
+pub mod navigation;
+pub mod path;
+
+#[derive(Component)]
+pub struct NpcModule;
+
+impl Module for NpcModule {
+    fn module(world: &World) {
+        world.import::<spatial::SpatialModule>();
+
+        // goals are selected before the paths to them are searched
+        world.import::<goal::GoalModule>();
+        world.import::<navigation::NavigationModule>();
+    }
+}
@@ -0,0 +1,316 @@
+//! Walking NPCs along paths.
+//!
+//! Setting a destination on [`Navigation`] makes `find_paths` look for a path to it. The
+//! searches of all NPCs run in parallel and are spread over ticks, so thousands of NPCs stay
+//! affordable. `follow_paths` then sets the [`Velocity`] of the NPC towards the next
+//! [`Waypoint`], jumps and opens doors on the way, and turns the NPC to where it looks.
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    glam::Vec3,
+    net::Compose,
+    simulation::{
+        Pitch, Position, RunningSpeed, Velocity, Yaw, event,
+        physics::PhysicsState,
+        worlds::{WorldId, with_blocks},
+    },
+    storage::Events,
+    valence_protocol::{
+        block::{PropName, PropValue},
+        math::IVec3,
+    },
+};
+
+use crate::path::{Abilities, STEP_HEIGHT, Waypoint, find_path};
+
+/// Ticks between searches while an NPC walks to the same destination, so it notices blocks that
+/// changed. A random part of this is added on top so the searches of NPCs spread over ticks.
+const REPATH_INTERVAL: i64 = 40;
+
+/// Ticks at least between searches when the destination keeps moving, like a walking player.
+const MIN_REPATH_INTERVAL: i64 = 10;
+
+/// How far a destination has to move before the path to it is searched again.
+const REPATH_DISTANCE: f32 = 1.0;
+
+/// How close an NPC has to get to a waypoint to go on to the next one.
+const WAYPOINT_REACH: f32 = 0.25;
+
+/// The vertical velocity of a jump, which is the one of vanilla mobs.
+const JUMP_VELOCITY: f32 = 0.42;
+
+/// How far above their feet NPCs and the players they look at have their eyes.
+const EYE_HEIGHT: f32 = 1.6;
+
+/// How close an NPC has to be to a door to open it.
+const DOOR_REACH: f32 = 1.5;
+
+/// Where an NPC walks to and how it gets there.
+#[derive(Component, Debug, Default)]
+pub struct Navigation {
+    destination: Option<Vec3>,
+    /// Multiplied with the [`RunningSpeed`] of the NPC.
+    speed: f32,
+    path: Vec<Waypoint>,
+    next: usize,
+    /// Whether the destination moved since the last search.
+    stale: bool,
+    searched_at: i64,
+    repath_at: i64,
+    look_at: Option<Vec3>,
+    pub abilities: Abilities,
+}
+
+impl Navigation {
+    #[must_use]
+    pub const fn new(abilities: Abilities) -> Self {
+        Self {
+            destination: None,
+            speed: 0.0,
+            path: Vec::new(),
+            next: 0,
+            stale: false,
+            searched_at: 0,
+            repath_at: 0,
+            look_at: None,
+            abilities,
+        }
+    }
+
+    /// Walks to `destination` at `speed` times the [`RunningSpeed`] of the NPC.
+    pub fn go_to(&mut self, destination: Vec3, speed: f32) {
+        self.speed = speed;
+
+        if let Some(current) = self.destination
+            && current.distance_squared(destination) < REPATH_DISTANCE * REPATH_DISTANCE
+        {
+            return;
+        }
+
+        self.destination = Some(destination);
+        self.stale = true;
+    }
+
+    /// Stops walking.
+    pub fn stop(&mut self) {
+        self.destination = None;
+        self.path.clear();
+        self.next = 0;
+        self.stale = false;
+    }
+
+    /// Turns the NPC towards `target`, or where it walks if `None`.
+    pub const fn look_at(&mut self, target: Option<Vec3>) {
+        self.look_at = target;
+    }
+
+    #[must_use]
+    pub const fn destination(&self) -> Option<Vec3> {
+        self.destination
+    }
+
+    /// Whether the NPC has nowhere to go.
+    #[must_use]
+    pub const fn is_idle(&self) -> bool {
+        self.destination.is_none()
+    }
+
+    /// The waypoints the NPC has yet to walk through.
+    #[must_use]
+    pub fn path(&self) -> &[Waypoint] {
+        self.path.get(self.next..).unwrap_or_default()
+    }
+}
+
+/// The block the feet of an entity at `position` are in. Entities standing on slabs or other
+/// blocks lower than a full block are in the block above them.
+fn feet(position: Vec3) -> IVec3 {
+    (position + Vec3::Y * 0.5).floor().as_ivec3()
+}
+
+/// The yaw and pitch of an entity at `from` looking at `to`, in degrees.
+#[must_use]
+pub fn look_rotation(from: Vec3, to: Vec3) -> (f32, f32) {
+    let delta = to - from;
+    let horizontal = delta.x.hypot(delta.z);
+
+    let yaw = (-delta.x).atan2(delta.z).to_degrees();
+    let pitch = (-delta.y).atan2(horizontal).to_degrees();
+
+    (yaw, pitch)
+}
+
+#[derive(Component)]
+pub struct NavigationModule;
+
+impl Module for NavigationModule {
+    fn module(world: &World) {
+        world.component::<Navigation>();
+
+        system!(
+            "find_paths",
+            world,
+            &Compose($),
+            &mut Navigation,
+            &Position,
+            ?&WorldId,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(|it, _, (compose, navigation, position, world_id)| {
+            let Some(destination) = navigation.destination else {
+                return;
+            };
+
+            let tick = compose.global().tick;
+
+            let moved = navigation.stale && tick - navigation.searched_at >= MIN_REPATH_INTERVAL;
+
+            if !moved && tick < navigation.repath_at {
+                return;
+            }
+
+            let world = it.world();
+            let world_id = world_id.copied().unwrap_or_default();
+            let abilities = navigation.abilities;
+
+            let path = with_blocks(&world, world_id, |blocks| {
+                find_path(blocks, feet(**position), feet(destination), abilities)
+            });
+
+            // the NPC is in the air or its world is gone, so it tries again next tick
+            let Some(Some(path)) = path else {
+                navigation.repath_at = tick + 1;
+                return;
+            };
+
+            navigation.path = path;
+            navigation.next = 0;
+            navigation.stale = false;
+            navigation.searched_at = tick;
+            navigation.repath_at = tick + REPATH_INTERVAL + fastrand::i64(0..REPATH_INTERVAL);
+        });
+
+        system!(
+            "follow_paths",
+            world,
+            &Events($),
+            &mut Navigation,
+            &Position,
+            &mut Velocity,
+            &mut Yaw,
+            &mut Pitch,
+            &PhysicsState,
+            ?&RunningSpeed,
+            ?&WorldId,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(
+            |it, row, (events, navigation, position, velocity, yaw, pitch, state, speed, world_id)| {
+                let world = it.world();
+                let entity = it.entity(row).id();
+                let world_id = world_id.copied().unwrap_or_default();
+
+                let waypoint = follow(navigation, **position);
+
+                // NPCs without a path slow down by friction
+                if let Some(waypoint) = waypoint {
+                    let speed = speed.copied().unwrap_or_default().0 * navigation.speed;
+                    walk(velocity, state, **position, waypoint, speed);
+
+                    if let Some(door) = waypoint.door
+                        && position.distance(waypoint.position) < DOOR_REACH
+                    {
+                        open_door(&world, events, entity, world_id, door);
+
+                        // the door is only opened once
+                        navigation.path[navigation.next].door = None;
+                    }
+                }
+
+                let walking_to = waypoint.map(|waypoint| waypoint.position);
+
+                let Some(target) = navigation.look_at.or(walking_to) else {
+                    return;
+                };
+
+                // NPCs look from their eyes at the eyes of others, or straight ahead while walking
+                let eyes = Vec3::Y * EYE_HEIGHT;
+                let (new_yaw, new_pitch) = look_rotation(**position + eyes, target + eyes);
+
+                **yaw = new_yaw;
+                **pitch = new_pitch;
+            },
+        );
+    }
+}
+
+/// Moves on to the next waypoint once the NPC at `position` reached the current one, and returns
+/// the waypoint to walk to.
+fn follow(navigation: &mut Navigation, position: Vec3) -> Option<Waypoint> {
+    while let Some(waypoint) = navigation.path.get(navigation.next) {
+        let delta = waypoint.position - position;
+        let horizontal = delta.x.hypot(delta.z);
+
+        // the NPC is still below a waypoint it jumps up to
+        if horizontal > WAYPOINT_REACH || delta.y > STEP_HEIGHT {
+            return Some(*waypoint);
+        }
+
+        navigation.next += 1;
+    }
+
+    // the path led as close to the destination as it could
+    if let Some(destination) = navigation.destination
+        && position.distance_squared(destination) < REPATH_DISTANCE * REPATH_DISTANCE
+    {
+        navigation.stop();
+    }
+
+    None
+}
+
+/// Sets `velocity` to walk towards `waypoint` at `speed`, jumping if it has to.
+fn walk(
+    velocity: &mut Velocity,
+    state: &PhysicsState,
+    position: Vec3,
+    waypoint: Waypoint,
+    speed: f32,
+) {
+    let delta = waypoint.position - position;
+    let direction = Vec3::new(delta.x, 0.0, delta.z).normalize_or_zero();
+
+    velocity.0.x = direction.x * speed;
+    velocity.0.z = direction.z * speed;
+
+    let close = delta.x.hypot(delta.z) < 1.5;
+
+    if waypoint.jump && state.on_ground && (close || state.horizontal_collision) {
+        velocity.0.y = JUMP_VELOCITY;
+    }
+}
+
+/// Opens the door at `position` unless someone else already did.
+fn open_door(world: &World, events: &Events, entity: Entity, world_id: WorldId, position: IVec3) {
+    let closed = with_blocks(world, world_id, |blocks| {
+        blocks
+            .get_block(position)
+            .and_then(|block| block.get(PropName::Open))
+            == Some(PropValue::False)
+    });
+
+    if closed != Some(true) {
+        return;
+    }
+
+    events.push(
+        event::ToggleDoor {
+            position,
+            from: entity,
+            sequence: 0,
+        },
+        world,
+    );
+}
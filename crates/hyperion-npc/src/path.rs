@@ -0,0 +1,472 @@
+//! A* over the block grid.
+//!
+//! A node is the block the feet of an NPC are in. It can stand there if its feet and head fit and
+//! there is something to stand on below. NPCs walk up small steps like slabs, jump up to one block,
+//! fall down a few blocks and walk through doors they can open.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, hash_map::Entry},
+};
+
+use hyperion::{glam::Vec3, simulation::blocks::Blocks};
+use rustc_hash::FxHashMap;
+use valence_protocol::{
+    BlockState,
+    block::{BlockKind, PropName, PropValue},
+    math::IVec3,
+};
+
+/// How high NPCs walk up without jumping, like onto slabs.
+pub const STEP_HEIGHT: f32 = 0.6;
+
+/// How high NPCs get with a jump.
+pub const JUMP_HEIGHT: f32 = 1.25;
+
+/// How far NPCs are willing to fall.
+const MAX_FALL: i32 = 3;
+
+/// How many nodes a search looks at before it gives up and returns the path to the node closest
+/// to the goal. This keeps a single search cheap, however far away the goal is.
+const MAX_NODES: usize = 1024;
+
+const JUMP_COST: f32 = 1.0;
+const FALL_COST: f32 = 0.5;
+const DOOR_COST: f32 = 1.0;
+
+/// The blocks a path is found in.
+pub trait Terrain {
+    /// The block at `position`, or `None` if it is not loaded.
+    fn block(&self, position: IVec3) -> Option<BlockState>;
+}
+
+impl Terrain for Blocks {
+    fn block(&self, position: IVec3) -> Option<BlockState> {
+        self.get_block(position)
+    }
+}
+
+/// A point on a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Waypoint {
+    /// Where the feet of the NPC should be.
+    pub position: Vec3,
+    /// Whether the NPC has to jump to get here.
+    pub jump: bool,
+    /// The closed door the NPC has to open to get here.
+    pub door: Option<IVec3>,
+}
+
+/// What an NPC can do on its way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Abilities {
+    /// Whether the NPC opens wooden doors, like villagers.
+    pub opens_doors: bool,
+}
+
+fn is_door(kind: BlockKind) -> bool {
+    kind.to_str().ends_with("_door")
+}
+
+fn is_open(block: BlockState) -> bool {
+    block.get(PropName::Open) == Some(PropValue::True)
+}
+
+/// Blocks NPCs do not walk into, although nothing stops them.
+const fn is_dangerous(kind: BlockKind) -> bool {
+    matches!(
+        kind,
+        BlockKind::Lava
+            | BlockKind::Fire
+            | BlockKind::SoulFire
+            | BlockKind::Cactus
+            | BlockKind::MagmaBlock
+            | BlockKind::SweetBerryBush
+            | BlockKind::PowderSnow
+    )
+}
+
+/// How tall the collision box of `block` is, from `0` for blocks without one.
+fn height(block: BlockState) -> f32 {
+    block
+        .collision_shapes()
+        .map(|shape| shape.max().as_vec3().y)
+        .fold(0.0, f32::max)
+}
+
+struct Search<'a, T> {
+    terrain: &'a T,
+    abilities: Abilities,
+}
+
+impl<T: Terrain> Search<'_, T> {
+    /// Whether an NPC fits through `position`. Returns the door in the way, if it needs to be
+    /// opened.
+    fn passable(&self, position: IVec3) -> Option<Option<IVec3>> {
+        let block = self.terrain.block(position)?;
+        let kind = block.to_kind();
+
+        if is_dangerous(kind) {
+            return None;
+        }
+
+        if is_door(kind) {
+            if is_open(block) {
+                return Some(None);
+            }
+
+            let opens = self.abilities.opens_doors && kind != BlockKind::IronDoor;
+            return opens.then_some(Some(position));
+        }
+
+        block.collision_shapes().next().is_none().then_some(None)
+    }
+
+    /// Where the feet of an NPC standing in `node` are, if it can stand there.
+    fn stand(&self, node: IVec3) -> Option<(f32, Option<IVec3>)> {
+        let feet = self.passable(node)?;
+        let head = self.passable(node + IVec3::Y)?;
+
+        let below = self.terrain.block(node - IVec3::Y)?;
+
+        if is_dangerous(below.to_kind()) {
+            return None;
+        }
+
+        let ground = height(below);
+
+        if ground <= 0.0 {
+            return None;
+        }
+
+        let y = (node.y - 1) as f32 + ground;
+
+        Some((y, feet.or(head)))
+    }
+
+    /// The nodes reachable from `node`, where the NPC stands at `y`, with their cost.
+    fn neighbors(&self, node: IVec3, y: f32) -> Vec<(IVec3, Waypoint, f32)> {
+        let mut neighbors = Vec::with_capacity(8);
+
+        let directions = [
+            IVec3::X,
+            IVec3::NEG_X,
+            IVec3::Z,
+            IVec3::NEG_Z,
+            IVec3::new(1, 0, 1),
+            IVec3::new(1, 0, -1),
+            IVec3::new(-1, 0, 1),
+            IVec3::new(-1, 0, -1),
+        ];
+
+        for direction in directions {
+            let diagonal = direction.x != 0 && direction.z != 0;
+
+            // diagonals must not cut corners
+            if diagonal
+                && (self
+                    .passable(node + IVec3::new(direction.x, 0, 0))
+                    .is_none()
+                    || self
+                        .passable(node + IVec3::new(0, 0, direction.z))
+                        .is_none())
+            {
+                continue;
+            }
+
+            let distance = if diagonal {
+                core::f32::consts::SQRT_2
+            } else {
+                1.0
+            };
+
+            if let Some(neighbor) = self.step(node, y, direction, distance) {
+                neighbors.push(neighbor);
+            }
+        }
+
+        neighbors
+    }
+
+    /// Where the NPC standing in `node` at `y` ends up when it moves one block in `direction`.
+    fn step(
+        &self,
+        node: IVec3,
+        y: f32,
+        direction: IVec3,
+        distance: f32,
+    ) -> Option<(IVec3, Waypoint, f32)> {
+        let next = node + direction;
+
+        let waypoint = |target: IVec3, stand_y: f32, door: Option<IVec3>| {
+            let position = Vec3::new(target.x as f32 + 0.5, stand_y, target.z as f32 + 0.5);
+
+            Waypoint {
+                position,
+                jump: stand_y - y > STEP_HEIGHT,
+                door,
+            }
+        };
+
+        // the same height, or a step up like a slab
+        if let Some((stand_y, door)) = self.stand(next) {
+            let rise = stand_y - y;
+
+            if rise <= JUMP_HEIGHT {
+                let mut cost = distance;
+
+                if rise > STEP_HEIGHT {
+                    cost += JUMP_COST;
+                }
+
+                if door.is_some() {
+                    cost += DOOR_COST;
+                }
+
+                return Some((next, waypoint(next, stand_y, door), cost));
+            }
+        }
+
+        // jumping up a block, which needs room above the NPC
+        let up = next + IVec3::Y;
+        if self.passable(node + IVec3::Y * 2).is_some()
+            && let Some((stand_y, door)) = self.stand(up)
+            && stand_y - y <= JUMP_HEIGHT
+        {
+            let cost = distance + JUMP_COST;
+            return Some((up, waypoint(up, stand_y, door), cost));
+        }
+
+        // falling down, as long as nothing is in the way of the fall
+        self.passable(next)?;
+        self.passable(next + IVec3::Y)?;
+
+        for fall in 1..=MAX_FALL {
+            let down = next - IVec3::Y * fall;
+
+            if let Some((stand_y, door)) = self.stand(down) {
+                let cost = distance + fall as f32 * FALL_COST;
+                return Some((down, waypoint(down, stand_y, door), cost));
+            }
+
+            self.passable(down)?;
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Visited {
+    cost: f32,
+    parent: Option<IVec3>,
+    waypoint: Waypoint,
+}
+
+/// A node to look at, ordered so that the [`BinaryHeap`] pops the most promising one first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Open {
+    estimate: f32,
+    node: IVec3,
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+fn heuristic(from: IVec3, to: IVec3) -> f32 {
+    (to - from).as_vec3().length()
+}
+
+/// Finds a path from `start` to `goal`, which are the blocks the feet are in. If the goal cannot
+/// be reached, the path leads to the reachable node closest to it. Returns `None` if the NPC
+/// cannot stand at `start`.
+pub fn find_path(
+    terrain: &impl Terrain,
+    start: IVec3,
+    goal: IVec3,
+    abilities: Abilities,
+) -> Option<Vec<Waypoint>> {
+    let search = Search { terrain, abilities };
+
+    let (start_y, _) = search.stand(start)?;
+
+    let start_waypoint = Waypoint {
+        position: Vec3::new(start.x as f32 + 0.5, start_y, start.z as f32 + 0.5),
+        jump: false,
+        door: None,
+    };
+
+    let mut visited = FxHashMap::default();
+    visited.insert(start, Visited {
+        cost: 0.0,
+        parent: None,
+        waypoint: start_waypoint,
+    });
+
+    let mut open = BinaryHeap::new();
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        node: start,
+    });
+
+    let mut closest = (heuristic(start, goal), start);
+
+    while let Some(Open { node, .. }) = open.pop() {
+        if node == goal || visited.len() >= MAX_NODES {
+            break;
+        }
+
+        let Visited { cost, waypoint, .. } = visited[&node];
+        let remaining = heuristic(node, goal);
+
+        if remaining < closest.0 {
+            closest = (remaining, node);
+        }
+
+        for (neighbor, next_waypoint, step_cost) in search.neighbors(node, waypoint.position.y) {
+            let cost = cost + step_cost;
+
+            let better = match visited.entry(neighbor) {
+                Entry::Occupied(entry) => entry.get().cost > cost,
+                Entry::Vacant(_) => true,
+            };
+
+            if !better {
+                continue;
+            }
+
+            visited.insert(neighbor, Visited {
+                cost,
+                parent: Some(node),
+                waypoint: next_waypoint,
+            });
+
+            open.push(Open {
+                estimate: cost + heuristic(neighbor, goal),
+                node: neighbor,
+            });
+        }
+    }
+
+    let end = if visited.contains_key(&goal) {
+        goal
+    } else {
+        closest.1
+    };
+
+    let mut path = Vec::new();
+    let mut node = Some(end);
+
+    while let Some(current) = node {
+        let Visited {
+            parent, waypoint, ..
+        } = visited[&current];
+
+        // the NPC is already at the start
+        if parent.is_some() {
+            path.push(waypoint);
+        }
+
+        node = parent;
+    }
+
+    path.reverse();
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hash::FxHashMap;
+    use valence_protocol::{BlockState, math::IVec3};
+
+    use super::{Abilities, Terrain, find_path};
+
+    /// A floor of stone at `y = 0` with blocks on top of it.
+    struct Flat(FxHashMap<IVec3, BlockState>);
+
+    impl Terrain for Flat {
+        fn block(&self, position: IVec3) -> Option<BlockState> {
+            if let Some(block) = self.0.get(&position) {
+                return Some(*block);
+            }
+
+            Some(if position.y <= 0 {
+                BlockState::STONE
+            } else {
+                BlockState::AIR
+            })
+        }
+    }
+
+    const WALKER: Abilities = Abilities { opens_doors: false };
+
+    #[test]
+    fn walks_straight_on_flat_ground() {
+        let terrain = Flat(FxHashMap::default());
+
+        let path = find_path(&terrain, IVec3::new(0, 1, 0), IVec3::new(5, 1, 0), WALKER).unwrap();
+
+        assert_eq!(path.len(), 5);
+        assert!(path.iter().all(|waypoint| !waypoint.jump));
+        assert!((path[4].position.x - 5.5).abs() < f32::EPSILON);
+        assert!((path[4].position.y - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn jumps_onto_blocks_and_walks_around_walls() {
+        let mut blocks = FxHashMap::default();
+        blocks.insert(IVec3::new(1, 1, 0), BlockState::STONE);
+
+        // a wall two blocks high from z = -3 to z = 3 at x = 3
+        for z in -3..=3 {
+            blocks.insert(IVec3::new(3, 1, z), BlockState::STONE);
+            blocks.insert(IVec3::new(3, 2, z), BlockState::STONE);
+        }
+
+        let terrain = Flat(blocks);
+
+        let onto = find_path(&terrain, IVec3::new(0, 1, 0), IVec3::new(1, 2, 0), WALKER).unwrap();
+        assert_eq!(onto.len(), 1);
+        assert!(onto[0].jump);
+
+        let around = find_path(&terrain, IVec3::new(2, 1, 0), IVec3::new(4, 1, 0), WALKER).unwrap();
+        let end = around.last().unwrap();
+        assert!((end.position.x - 4.5).abs() < f32::EPSILON);
+
+        // nothing walks through the wall
+        assert!(
+            around.iter().all(|waypoint| waypoint.position.z.abs() > 3.0
+                || (waypoint.position.x - 3.5).abs() > 0.5)
+        );
+    }
+
+    #[test]
+    fn unreachable_goals_lead_close_by() {
+        let mut blocks = FxHashMap::default();
+
+        // the goal is on a pillar too high to jump onto
+        for y in 1..=3 {
+            blocks.insert(IVec3::new(3, y, 0), BlockState::STONE);
+        }
+
+        let terrain = Flat(blocks);
+
+        let path = find_path(&terrain, IVec3::new(0, 1, 0), IVec3::new(3, 4, 0), WALKER).unwrap();
+        let end = path.last().unwrap();
+
+        assert!((end.position.y - 1.0).abs() < f32::EPSILON);
+        assert!((end.position.x - 2.5).abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use flecs_ecs::macros::Component;
+use hyperion::storage::Persistent;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::{Group, node};
+
+/// How many groups are looked at for a single node, which stops long or circular inheritance.
+const MAX_GROUPS: usize = 32;
+
+/// A group of players with the same permissions.
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupDefinition {
+    /// The groups this one inherits rules from. Its own rules take priority.
+    pub parents: Vec<String>,
+    /// The rules of the group, see [`node`].
+    pub rules: Vec<String>,
+}
+
+/// Every group and what it permits.
+#[derive(Component, Debug, Default)]
+pub struct PermissionGroups {
+    /// The groups as configured by the server staff.
+    groups: HashMap<String, GroupDefinition>,
+    /// Rules every group has unless its own rules say otherwise, e.g. the commands a group can
+    /// run by default. These are not saved.
+    defaults: HashMap<String, Vec<String>>,
+}
+
+impl PermissionGroups {
+    /// The built-in groups, overridden by `groups`.
+    #[must_use]
+    pub fn new(groups: HashMap<String, GroupDefinition>) -> Self {
+        let builtin = |parent: Option<Group>| GroupDefinition {
+            parents: parent
+                .map(|parent| parent.name().to_owned())
+                .into_iter()
+                .collect(),
+            rules: Vec::new(),
+        };
+
+        let mut all = HashMap::from([
+            (Group::Banned.name().to_owned(), builtin(None)),
+            (Group::Normal.name().to_owned(), builtin(None)),
+            (
+                Group::Moderator.name().to_owned(),
+                builtin(Some(Group::Normal)),
+            ),
+            (
+                Group::Admin.name().to_owned(),
+                builtin(Some(Group::Moderator)),
+            ),
+        ]);
+
+        all.extend(groups);
+
+        Self {
+            groups: all,
+            defaults: HashMap::new(),
+        }
+    }
+
+    /// Grants `rule` to `group` by default.
+    pub fn grant_default(&mut self, group: &str, rule: impl Into<String>) {
+        self.defaults
+            .entry(group.to_owned())
+            .or_default()
+            .push(rule.into());
+    }
+
+    #[must_use]
+    pub fn get(&self, group: &str) -> Option<&GroupDefinition> {
+        self.groups.get(group)
+    }
+
+    pub fn set(&mut self, group: impl Into<String>, definition: GroupDefinition) {
+        self.groups.insert(group.into(), definition);
+    }
+
+    /// The names of all groups.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.groups.keys().map(String::as_str)
+    }
+
+    /// Whether a player may do what `node` stands for.
+    ///
+    /// The overrides of the player are checked first, then their group, then the groups it
+    /// inherits from, nearest first. The first of them with a rule matching `node` decides.
+    #[must_use]
+    pub fn is_permitted(&self, player: &PlayerPermissions, node: &str) -> bool {
+        let overrides = player.overrides.iter().map(String::as_str);
+        if let Some(granted) = node::decide(overrides, node) {
+            return granted;
+        }
+
+        let mut queue = VecDeque::from([player.group.as_str()]);
+        let mut seen = HashSet::new();
+
+        while let Some(group) = queue.pop_front() {
+            if seen.len() >= MAX_GROUPS || !seen.insert(group) {
+                continue;
+            }
+
+            let definition = self.groups.get(group);
+
+            let own = definition
+                .into_iter()
+                .flat_map(|definition| &definition.rules);
+            let defaults = self.defaults.get(group).into_iter().flatten();
+
+            // a group can take away a default by denying it
+            if let Some(granted) = node::decide(defaults.chain(own).map(String::as_str), node) {
+                return granted;
+            }
+
+            if let Some(definition) = definition {
+                queue.extend(definition.parents.iter().map(String::as_str));
+            }
+        }
+
+        false
+    }
+}
+
+/// The group and individual rules of a player.
+#[derive(
+    Component,
+    Archive,
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq
+)]
+pub struct PlayerPermissions {
+    pub group: String,
+    /// Rules that take priority over those of the group, see [`node`].
+    pub overrides: Vec<String>,
+}
+
+impl Default for PlayerPermissions {
+    fn default() -> Self {
+        Self::new(Group::Normal.name())
+    }
+}
+
+impl PlayerPermissions {
+    #[must_use]
+    pub fn new(group: impl Into<String>) -> Self {
+        Self {
+            group: group.into(),
+            overrides: Vec::new(),
+        }
+    }
+}
+
+impl Persistent for PlayerPermissions {
+    type Record = Self;
+
+    const KEY: &'static str = "permissions";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        self.clone()
+    }
+
+    fn from_record(record: Self::Record) -> Self {
+        record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{GroupDefinition, PermissionGroups, PlayerPermissions};
+
+    #[test]
+    fn groups_inherit_rules() {
+        let mut groups =
+            PermissionGroups::new(HashMap::from([("builder".to_owned(), GroupDefinition {
+                parents: vec!["normal".to_owned()],
+                rules: vec!["tag.command.*".to_owned(), "-tag.command.ban".to_owned()],
+            })]));
+
+        groups.grant_default("normal", "tag.command.spawn");
+        groups.grant_default("moderator", "tag.command.ban");
+        groups.grant_default("admin", "tag.command.config");
+
+        let admin = PlayerPermissions::new("admin");
+        assert!(groups.is_permitted(&admin, "tag.command.config"));
+        assert!(groups.is_permitted(&admin, "tag.command.ban"));
+        assert!(groups.is_permitted(&admin, "tag.command.spawn"));
+
+        let normal = PlayerPermissions::default();
+        assert!(groups.is_permitted(&normal, "tag.command.spawn"));
+        assert!(!groups.is_permitted(&normal, "tag.command.ban"));
+
+        let mut builder = PlayerPermissions::new("builder");
+        assert!(groups.is_permitted(&builder, "tag.command.fly"));
+        assert!(!groups.is_permitted(&builder, "tag.command.ban"));
+
+        builder.overrides.push("tag.command.ban".to_owned());
+        builder.overrides.push("-tag.command.fly".to_owned());
+        assert!(groups.is_permitted(&builder, "tag.command.ban"));
+        assert!(!groups.is_permitted(&builder, "tag.command.fly"));
+
+        assert!(!groups.is_permitted(&PlayerPermissions::new("banned"), "tag.command.spawn"));
+    }
+}
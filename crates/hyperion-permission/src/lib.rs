@@ -1,72 +1,128 @@
 use clap::ValueEnum;
 use flecs_ecs::{
-    core::{EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet},
+    core::{
+        Entity, EntityViewGet, QueryBuilderImpl, SystemAPI, TermBuilderImpl, World, WorldGet, flecs,
+    },
     macros::{Component, observer},
-    prelude::{Module, flecs},
+    prelude::Module,
 };
 use hyperion::{
+    ingress::PendingRemove,
     net::{Compose, ConnectionId},
     simulation::{Player, Uuid, command::get_command_packet},
-    storage::LocalDb,
+    storage::{LocalDb, PlayerDataStore, persist},
 };
-use num_derive::{FromPrimitive, ToPrimitive};
+use num_derive::FromPrimitive;
 
 #[derive(Component)]
 pub struct PermissionModule;
 
+mod groups;
+pub mod node;
 mod storage;
 
-#[derive(
-    Default,
-    Component,
-    FromPrimitive,
-    ToPrimitive,
-    Copy,
-    Clone,
-    Debug,
-    PartialEq,
-    ValueEnum,
-    Eq
-)]
+pub use groups::{GroupDefinition, PermissionGroups, PlayerPermissions};
+pub use storage::GroupStorage;
+
+/// The groups every server has. Their permissions can be changed like those of any other group.
+#[derive(FromPrimitive, Copy, Clone, Debug, PartialEq, ValueEnum, Eq)]
 #[repr(C)]
 pub enum Group {
+    /// Players in this group can't join.
     Banned,
-    #[default]
+    /// The group of new players.
     Normal,
+    /// Inherits from [`Group::Normal`].
     Moderator,
+    /// Inherits from [`Group::Moderator`].
     Admin,
 }
 
-// todo:
+impl Group {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Banned => "banned",
+            Self::Normal => "normal",
+            Self::Moderator => "moderator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+/// Whether `player` may do what `node` stands for, e.g. `tag.command.fly`.
+#[must_use]
+pub fn is_permitted(world: &World, player: Entity, node: &str) -> bool {
+    world.get::<&PermissionGroups>(|groups| {
+        player
+            .entity_view(world)
+            .try_get::<&PlayerPermissions>(|permissions| groups.is_permitted(permissions, node))
+            .unwrap_or(false)
+    })
+}
 
 impl Module for PermissionModule {
     fn module(world: &World) {
-        world.component::<Group>();
+        world.component::<PlayerPermissions>();
+        world.component::<PermissionGroups>();
+        world.component::<GroupStorage>();
         world.component::<storage::PermissionStorage>();
 
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, PlayerPermissions)>();
+
         world.get::<&LocalDb>(|db| {
             let storage = storage::PermissionStorage::new(db).unwrap();
+            let group_storage = GroupStorage::new(db).unwrap();
+            let groups = PermissionGroups::new(group_storage.load().unwrap());
+
             world.set(storage);
+            world.set(group_storage);
+            world.set(groups);
         });
 
-        observer!(world, flecs::OnSet, &Uuid, &storage::PermissionStorage($))
-            .with::<Player>()
-            .each_entity(|entity, (uuid, permissions)| {
-                let group = permissions.get(**uuid);
-                entity.set(group);
-            });
+        // players who only have a group from before permission nodes get it as their group; this
+        // runs before their saved permissions are restored
+        observer!(
+            world,
+            flecs::OnSet,
+            &Uuid,
+            &storage::PermissionStorage($),
+            &PlayerDataStore($),
+        )
+        .with::<Player>()
+        .each_entity(|entity, (uuid, legacy, store)| {
+            let group = match legacy.take(**uuid) {
+                Ok(Some(group)) => group,
+                Ok(None) => return,
+                Err(e) => {
+                    tracing::error!("failed to read the old group of {}: {e:#}", **uuid);
+                    return;
+                }
+            };
 
-        observer!(world, flecs::OnRemove, &Uuid, &Group, &storage::PermissionStorage($))
-            .with::<Player>()
-            .each(|(uuid, group, permissions)| {
-                permissions.set(**uuid, *group).unwrap();
-            });
+            let permissions = PlayerPermissions::new(group.name());
 
-        observer!(world, flecs::OnSet, &Group).each_iter(|it, row, _group| {
+            if let Err(e) = store.save(**uuid, &permissions) {
+                tracing::error!("failed to save the permissions of {}: {e:#}", **uuid);
+            }
+
+            entity.set(permissions);
+        });
+
+        persist::<PlayerPermissions>(world);
+
+        observer!(world, flecs::OnSet, &PlayerPermissions).each_iter(|it, row, permissions| {
             let system = it.system();
             let world = it.world();
             let entity = it.entity(row);
 
+            if permissions.group == Group::Banned.name() {
+                entity.set(PendingRemove::new("§cYou are banned from this server"));
+                return;
+            }
+
             let root_command = hyperion::simulation::command::get_root_command_entity();
 
             let cmd_pkt = get_command_packet(&world, root_command, Some(*entity));
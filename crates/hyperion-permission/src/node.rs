@@ -0,0 +1,77 @@
+//! Permission nodes like `tag.command.fly` and the rules that grant them.
+//!
+//! A rule is a node, a node ending in `.*` which covers everything below it, or `*` which covers
+//! every node. Rules starting with `-` deny instead of grant.
+
+/// How closely `pattern` matches `node`, or `None` if it does not match. Higher is more specific.
+#[must_use]
+pub fn specificity(pattern: &str, node: &str) -> Option<usize> {
+    let segments = |node: &str| node.split('.').count();
+
+    if pattern == "*" {
+        return Some(0);
+    }
+
+    if let Some(prefix) = pattern.strip_suffix(".*") {
+        let covered = node
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'));
+
+        return covered.then(|| 2 * segments(prefix));
+    }
+
+    // an exact match beats a wildcard with the same prefix
+    (pattern == node).then(|| 2 * segments(node) + 1)
+}
+
+/// Whether `rules` grant `node`, or `None` if none of them match it.
+///
+/// The most specific matching rule decides. If a granting and a denying rule are equally
+/// specific, the node is denied.
+pub fn decide<'a>(rules: impl IntoIterator<Item = &'a str>, node: &str) -> Option<bool> {
+    let mut decision: Option<(usize, bool)> = None;
+
+    for rule in rules {
+        let (pattern, granted) = match rule.strip_prefix('-') {
+            Some(pattern) => (pattern, false),
+            None => (rule, true),
+        };
+
+        let Some(specificity) = specificity(pattern, node) else {
+            continue;
+        };
+
+        let replace = match decision {
+            None => true,
+            Some((best, best_granted)) => {
+                specificity > best || (specificity == best && best_granted && !granted)
+            }
+        };
+
+        if replace {
+            decision = Some((specificity, granted));
+        }
+    }
+
+    decision.map(|(_, granted)| granted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decide;
+
+    #[test]
+    fn most_specific_rule_decides() {
+        let rules = ["tag.*", "-tag.command.*", "tag.command.fly"];
+
+        assert_eq!(decide(rules, "tag.command.fly"), Some(true));
+        assert_eq!(decide(rules, "tag.command.speed"), Some(false));
+        assert_eq!(decide(rules, "tag.bypass.cooldown"), Some(true));
+        assert_eq!(decide(rules, "tag"), Some(true));
+        assert_eq!(decide(rules, "tagged"), None);
+        assert_eq!(decide(rules, "other.command.fly"), None);
+
+        assert_eq!(decide(["*", "-*"], "tag.command.fly"), Some(false));
+        assert_eq!(decide(["*"], "anything"), Some(true));
+    }
+}
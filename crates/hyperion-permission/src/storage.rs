@@ -1,52 +1,82 @@
+use std::collections::HashMap;
+
 use flecs_ecs::macros::Component;
-use heed::{Database, Env, byteorder::NativeEndian, types};
-use hyperion::storage::LocalDb;
-use num_traits::{FromPrimitive, ToPrimitive};
+use heed::{Database, byteorder::NativeEndian, types};
+use hyperion::storage::{LocalDb, Record};
+use num_traits::FromPrimitive;
 
-use crate::Group;
+use crate::{Group, GroupDefinition};
 
+/// The groups players had before permission nodes, which are moved into their
+/// [`PlayerPermissions`](crate::PlayerPermissions) when they join.
 #[derive(Component)]
 pub struct PermissionStorage {
-    env: Env,
+    db: LocalDb,
     perms: Database<types::U128<NativeEndian>, types::U8>,
 }
 
 impl PermissionStorage {
     pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
-        // We open the default unnamed database
-        let perms = {
-            let mut wtxn = db.write_txn()?;
-            let db = db.create_database(&mut wtxn, Some("uuid-to-perms"))?;
-            wtxn.commit()?;
-            db
-        };
+        let perms = db.write(|wtxn| Ok(db.create_database(wtxn, Some("uuid-to-perms"))?))?;
 
         Ok(Self {
-            env: (**db).clone(),
+            db: db.clone(),
             perms,
         })
     }
 
-    pub fn get(&self, uuid: uuid::Uuid) -> Group {
+    /// Removes the old group of a player and returns it.
+    pub fn take(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<Group>> {
         let uuid = uuid.as_u128();
-        let rtxn = self.env.read_txn().unwrap();
-        let Some(perms) = self.perms.get(&rtxn, &uuid).unwrap() else {
-            return Group::default();
+
+        let Some(perms) = self.db.read(|rtxn| Ok(self.perms.get(rtxn, &uuid)?))? else {
+            return Ok(None);
         };
 
+        self.db.write(|wtxn| Ok(self.perms.delete(wtxn, &uuid)?))?;
+
         let Some(group) = Group::from_u8(perms) else {
             tracing::error!("invalid group {perms:?}");
-            return Group::default();
+            return Ok(None);
         };
 
-        group
+        Ok(Some(group))
     }
+}
 
-    pub fn set(&self, uuid: uuid::Uuid, group: Group) -> anyhow::Result<()> {
-        let uuid = uuid.as_u128();
-        let mut wtxn = self.env.write_txn()?;
-        self.perms.put(&mut wtxn, &uuid, &group.to_u8().unwrap())?;
-        wtxn.commit()?;
-        Ok(())
+/// The [`GroupDefinition`]s by name.
+#[derive(Component)]
+pub struct GroupStorage {
+    db: LocalDb,
+    groups: Database<types::Str, types::Bytes>,
+}
+
+impl GroupStorage {
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let groups = db.write(|wtxn| Ok(db.create_database(wtxn, Some("permission-groups"))?))?;
+
+        Ok(Self {
+            db: db.clone(),
+            groups,
+        })
+    }
+
+    pub fn load(&self) -> anyhow::Result<HashMap<String, GroupDefinition>> {
+        self.db.read(|rtxn| {
+            let mut groups = HashMap::new();
+
+            for entry in self.groups.iter(rtxn)? {
+                let (name, definition) = entry?;
+                groups.insert(name.to_owned(), GroupDefinition::from_bytes(definition)?);
+            }
+
+            Ok(groups)
+        })
+    }
+
+    pub fn save(&self, name: &str, definition: &GroupDefinition) -> anyhow::Result<()> {
+        let definition = rkyv::to_bytes::<rkyv::rancor::Error>(definition)?;
+        self.db
+            .write(|wtxn| Ok(self.groups.put(wtxn, name, &definition)?))
     }
 }
@@ -0,0 +1,472 @@
+//! The interface between the server and plugins.
+//!
+//! A plugin is a WebAssembly module. Strings are UTF-8 and passed as a pointer into the memory of
+//! the plugin and a length. Entities are passed as their `i64` id. Events are passed as JSON.
+//!
+//! A plugin exports
+//!
+//! - `memory`
+//! - `hyperion_abi_version() -> i32`, which returns [`ABI_VERSION`]
+//! - `alloc(len: i32) -> i32`, which returns a buffer of `len` bytes the server writes events and
+//!   commands into. The plugin owns the buffer and frees it when it is done with it.
+//! - optionally `on_load()` and `on_unload()`
+//! - optionally `on_event(kind: i32, ptr: i32, len: i32) -> i32`, which is called with the events
+//!   the plugin subscribed to. Returning anything but `0` cancels the event.
+//! - optionally `on_command(caller: i64, ptr: i32, len: i32)`, which is called with the commands
+//!   the plugin registered, without the leading `/`.
+//!
+//! and may import these functions from the module `hyperion`
+//!
+//! - `log(level: i32, ptr: i32, len: i32)`, where the level is `0` for errors up to `4` for traces
+//! - `subscribe(kind: i32) -> i32`, which returns `-1` for unknown kinds of events
+//! - `register_command(ptr: i32, len: i32)`
+//! - `set_event(ptr: i32, len: i32)`, which replaces the event `on_event` was called with. Only
+//!   the fields documented as changeable take effect.
+//! - `players(ptr: i32, cap: i32) -> i32`, which writes up to `cap` little endian `i64` player ids
+//!   and returns the number of players
+//! - `player_name(player: i64, ptr: i32, cap: i32) -> i32`, which writes up to `cap` bytes of the
+//!   name and returns its length, or `-1` if there is no such player
+//! - `player_position(player: i64, ptr: i32) -> i32`, which writes the `x`, `y` and `z` of the
+//!   feet as little endian `f32`, or returns `-1` if there is no such player
+//! - `send_chat(player: i64, ptr: i32, len: i32)` and `send_action_bar(player: i64, ptr: i32,
+//!   len: i32)`
+//! - `broadcast_chat(ptr: i32, len: i32)`
+
+use anyhow::{Context, bail};
+use flecs_ecs::prelude::*;
+use hyperion::{
+    simulation::{Name, Player, Position, event},
+    valence_protocol::BlockState,
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{debug, error, info, trace, warn};
+use wasmtime::{Caller, Extern, Linker, Memory};
+
+use crate::plugin::{HostState, Message};
+
+/// The version of the interface. It changes whenever a change would break existing plugins.
+pub const ABI_VERSION: i32 = 1;
+
+/// The kinds of events plugins can subscribe to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(i32)]
+pub enum EventKind {
+    Join = 0,
+    Quit = 1,
+    Chat = 2,
+    PlaceBlock = 3,
+    BreakBlock = 4,
+    Damage = 5,
+    Death = 6,
+}
+
+impl EventKind {
+    pub const ALL: [Self; 7] = [
+        Self::Join,
+        Self::Quit,
+        Self::Chat,
+        Self::PlaceBlock,
+        Self::BreakBlock,
+        Self::Damage,
+        Self::Death,
+    ];
+
+    #[must_use]
+    pub fn from_i32(kind: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|other| *other as i32 == kind)
+    }
+}
+
+/// How an event is shown to plugins.
+pub trait Payload: Serialize + DeserializeOwned {
+    type Event: Send + Sync + 'static;
+
+    const KIND: EventKind;
+
+    fn of(event: &Self::Event) -> Self;
+
+    /// Copies the fields plugins may change back into `event`.
+    fn apply(self, _event: &mut Self::Event) {}
+}
+
+/// A player joins. `kick_reason` can be changed.
+#[derive(Serialize, Deserialize)]
+pub struct Join {
+    pub player: u64,
+    pub kick_reason: String,
+}
+
+impl Payload for Join {
+    type Event = event::PlayerJoin;
+
+    const KIND: EventKind = EventKind::Join;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            player: event.player.0,
+            kick_reason: event.kick_reason.clone(),
+        }
+    }
+
+    fn apply(self, event: &mut Self::Event) {
+        event.kick_reason = self.kick_reason;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Quit {
+    pub player: u64,
+    pub reason: String,
+}
+
+impl Payload for Quit {
+    type Event = event::PlayerQuit;
+
+    const KIND: EventKind = EventKind::Quit;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            player: event.player.0,
+            reason: event.reason.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Chat {
+    pub player: u64,
+    pub message: String,
+}
+
+impl Payload for Chat {
+    type Event = event::ChatMessage;
+
+    const KIND: EventKind = EventKind::Chat;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            player: event.by.0,
+            message: (*event.msg.get()).to_owned(),
+        }
+    }
+}
+
+/// A player places a block. `block` is the id of the block state and can be changed.
+#[derive(Serialize, Deserialize)]
+pub struct PlaceBlock {
+    pub player: u64,
+    pub position: [i32; 3],
+    pub block: u16,
+}
+
+impl Payload for PlaceBlock {
+    type Event = event::PlaceBlock;
+
+    const KIND: EventKind = EventKind::PlaceBlock;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            player: event.from.0,
+            position: event.position.to_array(),
+            block: event.block.to_raw(),
+        }
+    }
+
+    fn apply(self, event: &mut Self::Event) {
+        if let Some(block) = BlockState::from_raw(self.block) {
+            event.block = block;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BreakBlock {
+    pub player: u64,
+    pub position: [i32; 3],
+}
+
+impl Payload for BreakBlock {
+    type Event = event::DestroyBlock;
+
+    const KIND: EventKind = EventKind::BreakBlock;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            player: event.from.0,
+            position: event.position.to_array(),
+        }
+    }
+}
+
+/// An entity takes damage. `amount` can be changed.
+#[derive(Serialize, Deserialize)]
+pub struct Damage {
+    pub target: u64,
+    pub source: Option<u64>,
+    pub amount: f32,
+}
+
+impl Payload for Damage {
+    type Event = event::Damage;
+
+    const KIND: EventKind = EventKind::Damage;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            target: event.target.0,
+            source: event.source.map(|source| source.0),
+            amount: event.amount,
+        }
+    }
+
+    fn apply(self, event: &mut Self::Event) {
+        event.amount = self.amount;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Death {
+    pub entity: u64,
+    pub killer: Option<u64>,
+}
+
+impl Payload for Death {
+    type Event = event::Death;
+
+    const KIND: EventKind = EventKind::Death;
+
+    fn of(event: &Self::Event) -> Self {
+        Self {
+            entity: event.entity.0,
+            killer: event.killer.map(|killer| killer.0),
+        }
+    }
+}
+
+fn memory(caller: &mut Caller<'_, HostState>) -> anyhow::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => bail!("the plugin does not export its memory"),
+    }
+}
+
+/// The `len` bytes at `ptr` in the memory of the plugin.
+fn read(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> anyhow::Result<Vec<u8>> {
+    let memory = memory(caller)?;
+    let start = usize::try_from(ptr).context("negative pointer")?;
+    let len = usize::try_from(len).context("negative length")?;
+
+    let bytes = memory
+        .data(&caller)
+        .get(start..start + len)
+        .context("out of bounds")?;
+
+    Ok(bytes.to_vec())
+}
+
+fn read_string(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> anyhow::Result<String> {
+    let bytes = read(caller, ptr, len)?;
+    String::from_utf8(bytes).context("not UTF-8")
+}
+
+fn write(caller: &mut Caller<'_, HostState>, ptr: i32, bytes: &[u8]) -> anyhow::Result<()> {
+    let memory = memory(caller)?;
+    let start = usize::try_from(ptr).context("negative pointer")?;
+    memory.write(caller, start, bytes).context("out of bounds")
+}
+
+/// The world the plugin is called from.
+fn world<'a>(caller: &Caller<'_, HostState>) -> anyhow::Result<&'a World> {
+    caller
+        .data()
+        .world()
+        .context("the plugin called the server outside of a call from the server")
+}
+
+fn player(world: &World, id: i64) -> Option<Entity> {
+    #[expect(clippy::cast_sign_loss, reason = "ids are passed as i64 to plugins")]
+    let id = id as u64;
+
+    if !world.is_alive(id) {
+        return None;
+    }
+
+    let entity = world.entity_from_id(id);
+    entity.has::<Player>().then(|| entity.id())
+}
+
+/// Adds the functions plugins import to `linker`.
+pub fn link(linker: &mut Linker<HostState>) -> anyhow::Result<()> {
+    linker.func_wrap(
+        "hyperion",
+        "log",
+        |mut caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32| {
+            let message = read_string(&mut caller, ptr, len)?;
+            let plugin = caller.data().name();
+
+            match level {
+                0 => error!("[{plugin}] {message}"),
+                1 => warn!("[{plugin}] {message}"),
+                2 => info!("[{plugin}] {message}"),
+                3 => debug!("[{plugin}] {message}"),
+                _ => trace!("[{plugin}] {message}"),
+            }
+
+            anyhow::Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "subscribe",
+        |mut caller: Caller<'_, HostState>, kind: i32| {
+            let Some(kind) = EventKind::from_i32(kind) else {
+                return -1;
+            };
+
+            caller.data_mut().subscribe(kind);
+            0
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "register_command",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let name = read_string(&mut caller, ptr, len)?;
+            caller.data_mut().register_command(name);
+            anyhow::Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "set_event",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let event = read_string(&mut caller, ptr, len)?;
+            caller.data_mut().set_event(event);
+            anyhow::Ok(())
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "players",
+        |mut caller: Caller<'_, HostState>, ptr: i32, cap: i32| {
+            let world = world(&caller)?;
+
+            let mut players = Vec::new();
+            world
+                .query::<()>()
+                .with::<Player>()
+                .build()
+                .each_entity(|entity, ()| players.push(entity.id().0));
+
+            let cap = usize::try_from(cap).unwrap_or_default();
+            let bytes: Vec<u8> = players
+                .iter()
+                .take(cap)
+                .flat_map(|id| id.to_le_bytes())
+                .collect();
+
+            write(&mut caller, ptr, &bytes)?;
+
+            anyhow::Ok(i32::try_from(players.len()).unwrap_or(i32::MAX))
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "player_name",
+        |mut caller: Caller<'_, HostState>, id: i64, ptr: i32, cap: i32| {
+            let world = world(&caller)?;
+
+            let Some(player) = player(world, id) else {
+                return Ok(-1);
+            };
+
+            let name = world
+                .entity_from_id(player)
+                .try_get::<&Name>(|name| name.to_string());
+
+            let Some(name) = name else {
+                return Ok(-1);
+            };
+
+            let cap = usize::try_from(cap).unwrap_or_default();
+            let bytes = &name.as_bytes()[..name.len().min(cap)];
+            write(&mut caller, ptr, bytes)?;
+
+            anyhow::Ok(i32::try_from(name.len()).unwrap_or(i32::MAX))
+        },
+    )?;
+
+    linker.func_wrap(
+        "hyperion",
+        "player_position",
+        |mut caller: Caller<'_, HostState>, id: i64, ptr: i32| {
+            let world = world(&caller)?;
+
+            let Some(player) = player(world, id) else {
+                return Ok(-1);
+            };
+
+            let position = world
+                .entity_from_id(player)
+                .try_get::<&Position>(|position| **position);
+
+            let Some(position) = position else {
+                return Ok(-1);
+            };
+
+            let bytes: Vec<u8> = position
+                .to_array()
+                .into_iter()
+                .flat_map(f32::to_le_bytes)
+                .collect();
+
+            write(&mut caller, ptr, &bytes)?;
+
+            anyhow::Ok(0)
+        },
+    )?;
+
+    for (name, overlay) in [("send_chat", false), ("send_action_bar", true)] {
+        linker.func_wrap(
+            "hyperion",
+            name,
+            move |mut caller: Caller<'_, HostState>, id: i64, ptr: i32, len: i32| {
+                let text = read_string(&mut caller, ptr, len)?;
+                let world = world(&caller)?;
+
+                if let Some(player) = player(world, id) {
+                    caller.data_mut().send(Message {
+                        to: Some(player),
+                        text,
+                        overlay,
+                    });
+                }
+
+                anyhow::Ok(())
+            },
+        )?;
+    }
+
+    linker.func_wrap(
+        "hyperion",
+        "broadcast_chat",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            let text = read_string(&mut caller, ptr, len)?;
+
+            caller.data_mut().send(Message {
+                to: None,
+                text,
+                overlay: false,
+            });
+
+            anyhow::Ok(())
+        },
+    )?;
+
+    Ok(())
+}
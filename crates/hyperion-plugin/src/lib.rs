@@ -0,0 +1,508 @@
+//! Game logic loaded from WebAssembly modules while the server runs.
+//!
+//! Every `.wasm` file in the `plugins/` directory is a plugin. The directory is checked every
+//! second: new plugins are loaded, changed ones reloaded and removed ones unloaded. Plugins see
+//! the server through the functions in [`abi`] only, and each call into a plugin is limited by
+//! the [`Limits`] in the `<name>.toml` next to it. A plugin that fails to load, traps or exceeds
+//! its limits is unloaded and only loaded again once its file changes.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    net::{Compose, ConnectionId},
+    simulation::command::{Command, get_root_command_entity},
+    storage::{EventBus, Priority},
+};
+use hyperion_command::{CommandHandler, CommandRegistry};
+use parking_lot::Mutex;
+use rustc_hash::FxHashMap;
+use tracing::{error, info, warn};
+use valence_protocol::{packets::play, text::IntoText};
+use wasmtime::{Config, Engine, Linker};
+
+pub mod abi;
+mod plugin;
+
+pub use plugin::{HostState, Limits, Message, Plugin};
+
+use crate::abi::Payload;
+
+/// How often the plugin directory is checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The plugins that are loaded.
+#[derive(Component)]
+pub struct PluginHost {
+    engine: Engine,
+    linker: Linker<HostState>,
+    directory: PathBuf,
+    plugins: Vec<Mutex<Plugin>>,
+    /// The plugin and the node in the command tree of every command plugins registered.
+    commands: FxHashMap<String, (String, Entity)>,
+    /// The plugins that failed with the modification time of their file back then. They are not
+    /// loaded again until the file changes.
+    failed: FxHashMap<PathBuf, Option<SystemTime>>,
+    /// Stops the thread counting epochs.
+    stop: Arc<AtomicBool>,
+    epochs: Option<JoinHandle<()>>,
+}
+
+impl PluginHost {
+    /// A host for the plugins in `directory`, without loading them yet.
+    pub fn new(directory: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+
+        let engine = Engine::new(&config)?;
+
+        let mut linker = Linker::new(&engine);
+        abi::link(&mut linker)?;
+
+        // time limits are counted in epochs
+        let ticker = engine.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let epochs = std::thread::Builder::new()
+            .name("plugin-epoch".to_owned())
+            .spawn(move || {
+                while !stopped.load(Ordering::Relaxed) {
+                    std::thread::sleep(plugin::EPOCH);
+                    ticker.increment_epoch();
+                }
+            })?;
+
+        Ok(Self {
+            engine,
+            linker,
+            directory: directory.into(),
+            plugins: Vec::new(),
+            commands: FxHashMap::default(),
+            failed: FxHashMap::default(),
+            stop,
+            epochs: Some(epochs),
+        })
+    }
+
+    #[must_use]
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The names of the loaded plugins.
+    pub fn names(&self) -> impl Iterator<Item = String> + '_ {
+        self.plugins
+            .iter()
+            .map(|plugin| plugin.lock().name().to_owned())
+    }
+
+    /// Hands `event` to every plugin. Returns whether a plugin cancelled it.
+    fn fire<P: Payload>(&self, event: &mut P::Event, world: &World) -> bool {
+        let mut cancelled = false;
+
+        for plugin in &self.plugins {
+            cancelled |= plugin.lock().fire::<P>(event, world);
+        }
+
+        cancelled
+    }
+
+    /// Loads `path`, and registers the commands of the plugin.
+    fn load(&mut self, path: &Path, world: &World) {
+        let plugin = match Plugin::load(&self.engine, &self.linker, path, world) {
+            Ok(plugin) => plugin,
+            Err(e) => {
+                error!("failed to load the plugin {}: {e:#}", path.display());
+                self.failed
+                    .insert(path.to_path_buf(), plugin::modified(path));
+                return;
+            }
+        };
+
+        info!("loaded the plugin {}", plugin.name());
+
+        for name in plugin.commands() {
+            if let Some((owner, _)) = self.commands.get(name) {
+                warn!(
+                    "the plugin {} registered /{name}, which the plugin {owner} already registered",
+                    plugin.name()
+                );
+                continue;
+            }
+
+            let node = world
+                .entity()
+                .set(Command::literal(name, |_, _| true))
+                .child_of_id(get_root_command_entity())
+                .id();
+
+            world.get::<&mut CommandRegistry>(|registry| {
+                registry.register(name, CommandHandler {
+                    on_execute: run_command,
+                    on_tab_complete: Box::new(|_, _| {}),
+                    has_permissions: |_, _| true,
+                });
+            });
+
+            self.commands
+                .insert(name.clone(), (plugin.name().to_owned(), node));
+        }
+
+        self.plugins.push(Mutex::new(plugin));
+    }
+
+    /// Unloads the plugin at `index` and unregisters its commands.
+    fn unload(&mut self, index: usize, world: &World) {
+        let plugin = self.plugins.swap_remove(index).into_inner();
+        let name = plugin.name().to_owned();
+
+        self.commands.retain(|command, (owner, node)| {
+            if *owner != name {
+                return true;
+            }
+
+            world.get::<&mut CommandRegistry>(|registry| {
+                registry.unregister(command);
+            });
+            world.entity_from_id(*node).destruct();
+
+            false
+        });
+
+        plugin.unload(world);
+
+        info!("unloaded the plugin {name}");
+    }
+
+    /// Loads new plugins, reloads changed ones and unloads removed or failed ones.
+    fn sync(&mut self, world: &World) {
+        let mut index = 0;
+
+        while index < self.plugins.len() {
+            let plugin = self.plugins[index].get_mut();
+
+            if !plugin.failed() && !plugin.changed() {
+                index += 1;
+                continue;
+            }
+
+            // changed plugins are loaded again below, failed ones only once they change
+            if !plugin.changed() {
+                let path = plugin.path().to_path_buf();
+                let modified = plugin.modified();
+                self.failed.insert(path, modified);
+            }
+
+            self.unload(index, world);
+        }
+
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) => {
+                // a server without plugins has no directory for them
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("failed to read {}: {e}", self.directory.display());
+                }
+                return;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.extension().is_none_or(|extension| extension != "wasm") {
+                continue;
+            }
+
+            let loaded = self
+                .plugins
+                .iter_mut()
+                .any(|plugin| plugin.get_mut().path() == path);
+
+            let failed = self
+                .failed
+                .get(&path)
+                .is_some_and(|&modified| modified == plugin::modified(&path));
+
+            if !loaded && !failed {
+                self.failed.remove(&path);
+                self.load(&path, world);
+            }
+        }
+
+        // forget about failed plugins that were removed
+        self.failed.retain(|path, _| path.exists());
+    }
+}
+
+impl Drop for PluginHost {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if self
+            .epochs
+            .take()
+            .is_some_and(|epochs| epochs.join().is_err())
+        {
+            error!("the plugin epoch thread panicked");
+        }
+    }
+}
+
+/// Runs a command a plugin registered.
+fn run_command(input: &str, system: EntityView<'_>, caller: Entity) {
+    let world = system.world();
+
+    let Some(name) = input.split_whitespace().next() else {
+        return;
+    };
+
+    world.get::<&PluginHost>(|host| {
+        let Some((owner, _)) = host.commands.get(name) else {
+            return;
+        };
+
+        for plugin in &host.plugins {
+            let mut plugin = plugin.lock();
+
+            if plugin.name() == owner {
+                plugin.run_command(input, caller, &world);
+                break;
+            }
+        }
+    });
+}
+
+/// Hands events of type `P` to the plugins subscribed to them.
+fn listen<P: Payload>(bus: &mut EventBus) {
+    bus.listen(Priority::Normal, |event: &mut P::Event, context| {
+        let world = context.world;
+
+        if world.get::<&PluginHost>(|host| host.fire::<P>(event, world)) {
+            context.cancel();
+        }
+    });
+}
+
+#[derive(Component)]
+pub struct PluginModule;
+
+impl Module for PluginModule {
+    fn module(world: &World) {
+        world.component::<PluginHost>();
+
+        let host = match PluginHost::new("plugins") {
+            Ok(host) => host,
+            Err(e) => {
+                error!("plugins are disabled: {e:#}");
+                return;
+            }
+        };
+
+        world.set(host);
+
+        world.get::<&mut EventBus>(|bus| {
+            listen::<abi::Join>(bus);
+            listen::<abi::Quit>(bus);
+            listen::<abi::Chat>(bus);
+            listen::<abi::PlaceBlock>(bus);
+            listen::<abi::BreakBlock>(bus);
+            listen::<abi::Damage>(bus);
+            listen::<abi::Death>(bus);
+        });
+
+        let mut last_check: Option<Instant> = None;
+
+        system!("reload_plugins", world, &mut PluginHost($))
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each_iter(move |it, _, host| {
+                if last_check.is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL) {
+                    return;
+                }
+
+                last_check = Some(Instant::now());
+
+                host.sync(&it.world());
+            });
+
+        system!(
+            "send_plugin_messages",
+            world,
+            &Compose($),
+            &PluginHost($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, host)| {
+            let world = it.world();
+            let system = it.system();
+
+            for plugin in &host.plugins {
+                for message in plugin.lock().take_messages() {
+                    let packet = play::GameMessageS2c {
+                        chat: message.text.into_cow_text(),
+                        overlay: message.overlay,
+                    };
+
+                    let result = match message.to {
+                        Some(player) => world
+                            .entity_from_id(player)
+                            .try_get::<&ConnectionId>(|stream| {
+                                compose.unicast(&packet, *stream, system)
+                            })
+                            .unwrap_or(Ok(())),
+                        None => compose.broadcast(&packet, system).send(),
+                    };
+
+                    if let Err(e) = result {
+                        error!("failed to send a message of a plugin: {e}");
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use flecs_ecs::core::{Entity, World};
+    use hyperion::simulation::event;
+    use wasmtime::{Config, Engine, Linker};
+
+    use super::{Limits, Plugin, PluginHost, abi};
+
+    /// Subscribes to damage, sets it to 2.5 and cancels damage with a source, which serializes to
+    /// more than 40 bytes.
+    const HALVE_DAMAGE: &str = r#"
+        (module
+            (import "hyperion" "subscribe" (func $subscribe (param i32) (result i32)))
+            (import "hyperion" "set_event" (func $set_event (param i32 i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{\"target\":1,\"source\":null,\"amount\":2.5}")
+            (func (export "hyperion_abi_version") (result i32) i32.const 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "on_load") i32.const 5 call $subscribe drop)
+            (func (export "on_event") (param i32 i32 i32) (result i32)
+                i32.const 0 i32.const 39 call $set_event
+                local.get 2 i32.const 40 i32.gt_s))
+    "#;
+
+    const LOOP: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "hyperion_abi_version") (result i32) i32.const 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "on_command") (param i64 i32 i32) (loop $forever br $forever)))
+    "#;
+
+    fn load(source: &str, world: &World) -> Plugin {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+
+        let engine = Engine::new(&config).unwrap();
+        let mut linker = Linker::new(&engine);
+        abi::link(&mut linker).unwrap();
+
+        let limits = Limits {
+            fuel: 100_000,
+            time_limit_ms: u64::MAX,
+        };
+
+        Plugin::new(
+            &engine,
+            &linker,
+            "test".to_owned(),
+            source.as_bytes(),
+            limits,
+            world,
+        )
+        .unwrap()
+    }
+
+    fn damage(source: Option<Entity>) -> event::Damage {
+        event::Damage {
+            target: Entity(1),
+            source,
+            amount: 5.0,
+        }
+    }
+
+    #[test]
+    fn plugins_change_and_cancel_events() {
+        let world = World::new();
+        let mut plugin = load(HALVE_DAMAGE, &world);
+
+        let mut fall = damage(None);
+        assert!(!plugin.fire::<abi::Damage>(&mut fall, &world));
+        assert!((fall.amount - 2.5).abs() < f32::EPSILON);
+
+        let mut attack = damage(Some(Entity(1_234_567_890)));
+        assert!(plugin.fire::<abi::Damage>(&mut attack, &world));
+
+        // the plugin did not subscribe to deaths
+        let mut death = event::Death {
+            entity: Entity(1),
+            killer: None,
+        };
+        assert!(!plugin.fire::<abi::Death>(&mut death, &world));
+    }
+
+    #[test]
+    fn plugins_out_of_fuel_fail() {
+        let world = World::new();
+        let mut plugin = load(LOOP, &world);
+
+        plugin.run_command("loop", Entity(1), &world);
+        assert!(plugin.failed());
+    }
+
+    #[test]
+    fn failed_plugins_load_again_once_they_change() {
+        let directory =
+            std::env::temp_dir().join(format!("hyperion-plugins-{}", std::process::id()));
+        drop(std::fs::remove_dir_all(&directory));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let world = World::new();
+        let mut host = PluginHost::new(&directory).unwrap();
+
+        let path = directory.join("loop.wasm");
+        std::fs::write(&path, "not a module").unwrap();
+
+        host.sync(&world);
+        assert_eq!(host.names().count(), 0);
+        assert!(host.failed.contains_key(&path));
+
+        std::fs::write(&path, LOOP).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        host.sync(&world);
+        assert_eq!(host.names().collect::<Vec<_>>(), ["loop"]);
+        assert!(host.failed.is_empty());
+
+        // the plugin fails and stays unloaded while its file is the same
+        host.plugins[0]
+            .get_mut()
+            .run_command("loop", Entity(1), &world);
+        host.sync(&world);
+        host.sync(&world);
+        assert_eq!(host.names().count(), 0);
+        assert!(host.failed.contains_key(&path));
+
+        drop(std::fs::remove_dir_all(&directory));
+    }
+}
@@ -0,0 +1,368 @@
+//! A loaded plugin and the state the server keeps for it.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, bail};
+use flecs_ecs::core::{Entity, World};
+use serde::Deserialize;
+use tracing::{error, warn};
+use wasmtime::{Engine, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::abi::{ABI_VERSION, EventKind, Payload};
+
+/// How often the epoch of the engine is incremented, which is what time limits are counted in.
+pub const EPOCH: Duration = Duration::from_millis(1);
+
+/// What a plugin may use for a single call, read from the `<name>.toml` next to the plugin.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Roughly the number of WebAssembly instructions.
+    pub fuel: u64,
+    pub time_limit_ms: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            fuel: 10_000_000,
+            time_limit_ms: 50,
+        }
+    }
+}
+
+impl Limits {
+    /// The limits next to the plugin at `path`, or the defaults if there are none.
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let path = path.with_extension("toml");
+
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents).with_context(|| format!("invalid limits in {}", path.display()))
+    }
+}
+
+/// A chat message a plugin sent, which is sent to players at the end of the tick.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    /// `None` for all players.
+    pub to: Option<Entity>,
+    pub text: String,
+    /// Whether the message is shown above the hotbar.
+    pub overlay: bool,
+}
+
+struct WorldPtr(*const World);
+
+// SAFETY: the pointer is only set for the duration of a call into the plugin, during which the
+// world outlives it
+unsafe impl Send for WorldPtr {}
+unsafe impl Sync for WorldPtr {}
+
+/// What the server knows about a plugin while it runs.
+pub struct HostState {
+    name: String,
+    world: Option<WorldPtr>,
+    subscriptions: Vec<EventKind>,
+    commands: Vec<String>,
+    messages: Vec<Message>,
+    event: Option<String>,
+}
+
+impl HostState {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The world the server called the plugin from, if it is in a call.
+    #[must_use]
+    pub(crate) fn world<'a>(&self) -> Option<&'a World> {
+        // SAFETY: see `WorldPtr`
+        self.world.as_ref().map(|world| unsafe { &*world.0 })
+    }
+
+    pub fn subscribe(&mut self, kind: EventKind) {
+        if !self.subscriptions.contains(&kind) {
+            self.subscriptions.push(kind);
+        }
+    }
+
+    pub fn register_command(&mut self, name: String) {
+        if !self.commands.contains(&name) {
+            self.commands.push(name);
+        }
+    }
+
+    pub fn set_event(&mut self, event: String) {
+        self.event = Some(event);
+    }
+
+    pub fn send(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+}
+
+/// A plugin loaded from a WebAssembly module.
+pub struct Plugin {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    limits: Limits,
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_event: Option<TypedFunc<(i32, i32, i32), i32>>,
+    on_command: Option<TypedFunc<(i64, i32, i32), ()>>,
+    on_unload: Option<TypedFunc<(), ()>>,
+    /// Set once the plugin trapped or ran out of fuel or time. It is unloaded at the next check.
+    failed: bool,
+}
+
+impl Plugin {
+    /// Loads the plugin at `path`.
+    pub fn load(
+        engine: &Engine,
+        linker: &Linker<HostState>,
+        path: &Path,
+        world: &World,
+    ) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let limits = Limits::read(path)?;
+        let name = path
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut plugin = Self::new(engine, linker, name, &bytes, limits, world)?;
+        plugin.path = path.to_path_buf();
+        plugin.modified = modified(path);
+
+        Ok(plugin)
+    }
+
+    /// Instantiates the plugin compiled from `bytes`, which may also be in the text format, and
+    /// calls its `on_load`.
+    pub fn new(
+        engine: &Engine,
+        linker: &Linker<HostState>,
+        name: String,
+        bytes: &[u8],
+        limits: Limits,
+        world: &World,
+    ) -> anyhow::Result<Self> {
+        let module = Module::new(engine, bytes)?;
+
+        let state = HostState {
+            name,
+            world: None,
+            subscriptions: Vec::new(),
+            commands: Vec::new(),
+            messages: Vec::new(),
+            event: None,
+        };
+
+        let mut store = Store::new(engine, state);
+        store.set_fuel(limits.fuel)?;
+        store.set_epoch_deadline(limits.time_limit_ms);
+
+        let instance = linker.instantiate(&mut store, &module)?;
+
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, "hyperion_abi_version")?
+            .call(&mut store, ())?;
+
+        if version != ABI_VERSION {
+            bail!(
+                "the plugin needs version {version} of the plugin interface, but the server has \
+                 version {ABI_VERSION}"
+            );
+        }
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("the plugin does not export its memory")?;
+
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        let on_load = instance
+            .get_typed_func::<(), ()>(&mut store, "on_load")
+            .ok();
+        let on_event = instance.get_typed_func(&mut store, "on_event").ok();
+        let on_command = instance.get_typed_func(&mut store, "on_command").ok();
+        let on_unload = instance.get_typed_func(&mut store, "on_unload").ok();
+
+        let mut plugin = Self {
+            path: PathBuf::new(),
+            modified: None,
+            limits,
+            store,
+            memory,
+            alloc,
+            on_event,
+            on_command,
+            on_unload,
+            failed: false,
+        };
+
+        if let Some(on_load) = on_load {
+            plugin.call(world, |plugin| on_load.call(&mut plugin.store, ()))?;
+        }
+
+        Ok(plugin)
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.store.data().name()
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// When the file of the plugin was modified, as of loading it.
+    #[must_use]
+    pub const fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// Whether the file of the plugin changed since it was loaded.
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        modified(&self.path) != self.modified
+    }
+
+    #[must_use]
+    pub const fn failed(&self) -> bool {
+        self.failed
+    }
+
+    #[must_use]
+    pub fn subscribes_to(&self, kind: EventKind) -> bool {
+        self.store.data().subscriptions.contains(&kind)
+    }
+
+    #[must_use]
+    pub fn commands(&self) -> &[String] {
+        &self.store.data().commands
+    }
+
+    /// The messages the plugin sent since the last call.
+    pub fn take_messages(&mut self) -> Vec<Message> {
+        core::mem::take(&mut self.store.data_mut().messages)
+    }
+
+    /// Runs `f` within the limits of the plugin. If it fails, the plugin is marked as failed.
+    fn call<R>(
+        &mut self,
+        world: &World,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        if self.failed {
+            bail!("the plugin failed before");
+        }
+
+        self.store.set_fuel(self.limits.fuel)?;
+        self.store.set_epoch_deadline(self.limits.time_limit_ms);
+        self.store.data_mut().world = Some(WorldPtr(std::ptr::from_ref(world)));
+
+        let result = f(self);
+
+        self.store.data_mut().world = None;
+
+        if let Err(e) = &result {
+            error!("plugin {} failed and will be unloaded: {e:#}", self.name());
+            self.failed = true;
+        }
+
+        result
+    }
+
+    /// Copies `bytes` into a buffer the plugin allocates.
+    fn pass(&mut self, bytes: &[u8]) -> anyhow::Result<(i32, i32)> {
+        let len = i32::try_from(bytes.len()).context("too long to pass to a plugin")?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        let start = usize::try_from(ptr).context("the plugin allocated at a negative address")?;
+
+        self.memory.write(&mut self.store, start, bytes)?;
+
+        Ok((ptr, len))
+    }
+
+    /// Hands `event` to the plugin, which may change it. Returns whether the plugin cancelled it.
+    pub fn fire<P: Payload>(&mut self, event: &mut P::Event, world: &World) -> bool {
+        if !self.subscribes_to(P::KIND) {
+            return false;
+        }
+
+        let Some(on_event) = self.on_event.clone() else {
+            return false;
+        };
+
+        let result = self.call(world, |plugin| {
+            let json = serde_json::to_vec(&P::of(event))?;
+            let (ptr, len) = plugin.pass(&json)?;
+
+            let cancelled = on_event.call(&mut plugin.store, (P::KIND as i32, ptr, len))? != 0;
+
+            if let Some(changed) = plugin.store.data_mut().event.take() {
+                match serde_json::from_str::<P>(&changed) {
+                    Ok(changed) => changed.apply(event),
+                    Err(e) => warn!(
+                        "plugin {} changed an event to something invalid: {e}",
+                        plugin.name()
+                    ),
+                }
+            }
+
+            Ok(cancelled)
+        });
+
+        result.unwrap_or(false)
+    }
+
+    /// Runs the command `input` of the plugin for `caller`.
+    pub fn run_command(&mut self, input: &str, caller: Entity, world: &World) {
+        let Some(on_command) = self.on_command.clone() else {
+            return;
+        };
+
+        #[expect(
+            clippy::cast_possible_wrap,
+            reason = "ids are passed as i64 to plugins"
+        )]
+        let caller = caller.0 as i64;
+
+        // errors are logged and unload the plugin
+        let _result = self.call(world, |plugin| {
+            let (ptr, len) = plugin.pass(input.as_bytes())?;
+            on_command.call(&mut plugin.store, (caller, ptr, len))
+        });
+    }
+
+    /// Calls `on_unload` of the plugin.
+    pub fn unload(mut self, world: &World) {
+        let Some(on_unload) = self.on_unload.clone() else {
+            return;
+        };
+
+        if self.failed {
+            return;
+        }
+
+        // errors are logged by `call`
+        let _result = self.call(world, |plugin| on_unload.call(&mut plugin.store, ()));
+    }
+}
+
+pub(crate) fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
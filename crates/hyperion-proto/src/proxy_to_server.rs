@@ -1,5 +1,7 @@
 use rkyv::{Archive, Deserialize, Serialize, with::InlineAsBox};
 
+use crate::{PeerAddress, TransferredPlayer};
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub struct PlayerPackets<'a> {
     pub stream: u64,
@@ -8,9 +10,16 @@ pub struct PlayerPackets<'a> {
     pub data: &'a [u8],
 }
 
-#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Debug)]
 pub struct PlayerConnect {
     pub stream: u64,
+    /// The address of the client. If the proxy is behind a load balancer speaking the PROXY
+    /// protocol, this is the address the load balancer reported. `None` if the client is
+    /// connected through a unix socket.
+    pub address: Option<PeerAddress>,
+    /// Set if the player was moved here from another backend. The player is already logged in
+    /// and in the play state, so the server skips the handshake and login.
+    pub transfer: Option<TransferredPlayer>,
 }
 
 #[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
@@ -25,6 +34,9 @@ pub enum PlayerDisconnectReason<'a> {
     /// If cannot receive packets fast enough
     CouldNotKeepUp,
     LostConnection,
+    /// The player was moved to another backend. The server should drop the player without
+    /// sending a disconnect packet.
+    Transferred,
 
     Other(#[rkyv(with = InlineAsBox)] &'a str),
 }
@@ -1,6 +1,6 @@
 use rkyv::{Archive, Deserialize, Serialize, with::InlineAsBox};
 
-use crate::ChunkPosition;
+use crate::{ChunkPosition, PlayerInfo};
 
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 #[rkyv(derive(Debug))]
@@ -15,10 +15,50 @@ pub struct SetReceiveBroadcasts {
     pub stream: u64,
 }
 
+/// Enables AES/CFB8 stream encryption for a player connection.
+///
+/// Everything the proxy flushes to the player after receiving this message is encrypted, and
+/// everything the player sends afterwards is decrypted before being forwarded to the server.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[rkyv(derive(Debug))]
+pub struct SetEncryption {
+    pub stream: u64,
+    /// The shared secret, which vanilla uses as both the key and the IV.
+    pub key: [u8; 16],
+}
+
+/// Sent once a player is logged in and in the play state.
+///
+/// The proxy uses this to keep the player connected while the server restarts: it sends
+/// keep-alives itself and reconnects the player once the server is back.
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
+#[rkyv(derive(Debug))]
+pub struct SetPlayerInfo {
+    pub stream: u64,
+    pub info: PlayerInfo,
+}
+
+/// Moves a player to another backend server of the proxy without disconnecting the client.
+///
+/// The proxy flushes everything sent so far, disconnects the player from this server with
+/// [`crate::PlayerDisconnectReason::Transferred`] and connects them to `backend` with
+/// [`crate::PlayerConnect::transfer`] set to what it got in [`SetPlayerInfo`]. The new backend is
+/// responsible for making the client load its world. Backends must use the same compression
+/// threshold, since the client keeps the one it got during login.
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Transfer<'a> {
+    pub stream: u64,
+    /// The name of the backend as configured in the proxy.
+    #[rkyv(with = InlineAsBox)]
+    pub backend: &'a str,
+}
+
 #[derive(Archive, Deserialize, Serialize, Clone, PartialEq)]
 pub struct BroadcastGlobal<'a> {
     pub exclude: u64,
     pub order: u32,
+    /// If set, only players in this world receive the broadcast.
+    pub world: Option<u16>,
 
     #[rkyv(with = InlineAsBox)]
     pub data: &'a [u8],
@@ -54,5 +94,8 @@ pub enum ServerToProxyMessage<'a> {
     BroadcastLocal(BroadcastLocal<'a>),
     Unicast(Unicast<'a>),
     SetReceiveBroadcasts(SetReceiveBroadcasts),
+    SetEncryption(SetEncryption),
+    SetPlayerInfo(SetPlayerInfo),
+    Transfer(Transfer<'a>),
     Flush(Flush),
 }
@@ -1,25 +1,79 @@
-use glam::I16Vec2;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
 #[rkyv(derive(Debug))]
 pub struct ChunkPosition {
+    /// The id of the world the chunk is in. Players only receive local broadcasts for their own
+    /// world.
+    pub world: u16,
     pub x: i16,
     pub z: i16,
 }
 
 impl ChunkPosition {
     #[must_use]
-    pub const fn new(x: i16, z: i16) -> Self {
-        Self { x, z }
+    pub const fn new(world: u16, x: i16, z: i16) -> Self {
+        Self { world, x, z }
     }
 }
 
-impl From<I16Vec2> for ChunkPosition {
-    fn from(value: I16Vec2) -> Self {
+/// The socket address of a client connected to the proxy.
+///
+/// IPv4 addresses are stored as IPv4-mapped IPv6 addresses.
+#[derive(Archive, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[rkyv(derive(Debug))]
+pub struct PeerAddress {
+    pub ip: [u8; 16],
+    pub port: u16,
+}
+
+impl From<SocketAddr> for PeerAddress {
+    fn from(value: SocketAddr) -> Self {
+        let ip = match value.ip() {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        };
+
         Self {
-            x: value.x,
-            z: value.y,
+            ip: ip.octets(),
+            port: value.port(),
         }
     }
 }
+
+impl From<PeerAddress> for SocketAddr {
+    fn from(value: PeerAddress) -> Self {
+        let ip = Ipv6Addr::from(value.ip).to_canonical();
+        Self::new(ip, value.port)
+    }
+}
+
+/// What a backend needs to know about a player it did not log in itself.
+///
+/// Part of the [`PlayerInfo`] the proxy passes on to a new backend in
+/// [`crate::PlayerConnect::transfer`].
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+#[rkyv(derive(Debug))]
+pub struct TransferredPlayer {
+    pub uuid: u128,
+    pub username: String,
+    /// The protocol version from the handshake.
+    pub protocol_version: i32,
+    /// The hostname from the handshake.
+    pub server_address: String,
+    /// The port from the handshake.
+    pub server_port: u16,
+}
+
+/// What the proxy needs to know to connect a logged in player to a backend on its own.
+#[derive(Archive, Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+#[rkyv(derive(Debug))]
+pub struct PlayerInfo {
+    pub address: Option<PeerAddress>,
+    /// The compression threshold the client agreed to during login. Negative if compression is
+    /// disabled.
+    pub compression_threshold: i32,
+    pub player: TransferredPlayer,
+}
@@ -0,0 +1,318 @@
+//! The game servers behind the proxy and how players are assigned to them.
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use hyperion_proto::{ChunkPosition, PlayerConnect, PlayerInfo, ProxyToServerMessage};
+use rustc_hash::FxBuildHasher;
+use tokio::sync::watch;
+
+use crate::{data::PlayerHandle, server_sender::ServerSender};
+
+/// The live connection to a backend. A new one is created every time the proxy (re)connects.
+pub struct BackendConnection {
+    pub name: Arc<str>,
+    pub server_sender: ServerSender,
+    pub player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
+    // todo: remove positions when player leaves
+    pub player_positions: &'static papaya::HashMap<u64, ChunkPosition, FxBuildHasher>,
+}
+
+impl BackendConnection {
+    /// Creates a connection with empty registries. The connection is leaked since players and
+    /// egress tasks reference it for as long as the proxy runs.
+    #[must_use]
+    pub fn leak(name: Arc<str>, server_sender: ServerSender) -> &'static Self {
+        let player_registry = Box::leak(Box::new(papaya::HashMap::default()));
+        let player_positions = Box::leak(Box::new(papaya::HashMap::default()));
+
+        Box::leak(Box::new(Self {
+            name,
+            server_sender,
+            player_registry,
+            player_positions,
+        }))
+    }
+
+    /// Connects an already logged in player to this backend, which then makes the client load
+    /// its world. The player's packets are forwarded here from now on.
+    ///
+    /// Returns `false` if the backend cannot take the player, in which case nothing changed.
+    pub fn attach(&'static self, stream: u64, player: &PlayerHandle, info: PlayerInfo) -> bool {
+        let connect = rkyv::to_bytes::<rkyv::rancor::Error>(&ProxyToServerMessage::PlayerConnect(
+            PlayerConnect {
+                stream,
+                address: info.address,
+                transfer: Some(info.player),
+            },
+        ))
+        .unwrap();
+
+        // the backend must know about the player before the reader forwards packets to it
+        self.player_registry.pin().insert(stream, player.transfer());
+
+        if !matches!(self.server_sender.try_send(connect), Ok(true)) {
+            self.player_registry.pin().remove(&stream);
+            return false;
+        }
+
+        player.set_backend(self);
+        true
+    }
+
+    /// The number of players currently routed to this connection.
+    #[must_use]
+    pub fn player_count(&self) -> usize {
+        self.player_registry.len()
+    }
+}
+
+impl std::fmt::Debug for BackendConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackendConnection")
+            .field("name", &self.name)
+            .field("players", &self.player_count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The backend a player's packets are currently forwarded to. Updated when the player is
+/// transferred.
+pub type Route = Arc<watch::Sender<&'static BackendConnection>>;
+
+/// A game server the proxy forwards players to.
+#[derive(Debug)]
+pub struct Backend {
+    name: Arc<str>,
+    address: SocketAddr,
+    connection: watch::Sender<Option<&'static BackendConnection>>,
+}
+
+impl Backend {
+    #[must_use]
+    pub fn new(name: impl Into<Arc<str>>, address: SocketAddr) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            connection: watch::Sender::new(None),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &Arc<str> {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The current connection, or `None` while the proxy is (re)connecting.
+    #[must_use]
+    pub fn connection(&self) -> Option<&'static BackendConnection> {
+        *self.connection.borrow()
+    }
+
+    pub(crate) fn set_connection(&self, connection: Option<&'static BackendConnection>) {
+        self.connection.send_replace(connection);
+    }
+}
+
+/// All backends of the proxy in the order they were configured.
+#[derive(Debug)]
+pub struct Backends {
+    backends: Vec<Backend>,
+}
+
+impl Backends {
+    #[must_use]
+    pub const fn new(backends: Vec<Backend>) -> Self {
+        Self { backends }
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Backend> {
+        self.backends.iter().find(|backend| &*backend.name == name)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Backend> {
+        self.backends.iter()
+    }
+
+    /// The backends the proxy is currently connected to.
+    pub fn connected(&self) -> impl Iterator<Item = &'static BackendConnection> + '_ {
+        self.backends.iter().filter_map(Backend::connection)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.backends.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Backends {
+    type IntoIter = std::slice::Iter<'a, Backend>;
+    type Item = &'a Backend;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Decides which backend a newly connected player is sent to.
+pub trait RoutingPolicy: Send + Sync + 'static {
+    /// Picks one of the connected backends, or `None` if no backend is available.
+    fn route(&self, backends: &Backends) -> Option<&'static BackendConnection>;
+}
+
+impl<T: RoutingPolicy + ?Sized> RoutingPolicy for Box<T> {
+    fn route(&self, backends: &Backends) -> Option<&'static BackendConnection> {
+        (**self).route(backends)
+    }
+}
+
+/// Sends every player to the first configured backend that is connected. This makes the first
+/// backend the default, e.g. a lobby, and the others fallbacks.
+#[derive(Debug, Default)]
+pub struct FirstAvailable;
+
+impl RoutingPolicy for FirstAvailable {
+    fn route(&self, backends: &Backends) -> Option<&'static BackendConnection> {
+        backends.connected().next()
+    }
+}
+
+/// Cycles through the connected backends.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl RoutingPolicy for RoundRobin {
+    fn route(&self, backends: &Backends) -> Option<&'static BackendConnection> {
+        let connected = backends.connected().count();
+
+        if connected == 0 {
+            return None;
+        }
+
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % connected;
+        backends.connected().nth(index)
+    }
+}
+
+/// Sends every player to the connected backend with the fewest players.
+#[derive(Debug, Default)]
+pub struct LeastPlayers;
+
+impl RoutingPolicy for LeastPlayers {
+    fn route(&self, backends: &Backends) -> Option<&'static BackendConnection> {
+        backends
+            .connected()
+            .min_by_key(|connection| connection.player_count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        Backend, BackendConnection, Backends, FirstAvailable, LeastPlayers, RoundRobin,
+        RoutingPolicy,
+    };
+    use crate::data::PlayerHandle;
+
+    fn backends(names: &[&str]) -> Backends {
+        let backends = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let port = u16::try_from(35565 + i).unwrap();
+                Backend::new(*name, ([127, 0, 0, 1], port).into())
+            })
+            .collect();
+
+        Backends::new(backends)
+    }
+
+    fn connect(backend: &Backend) -> &'static BackendConnection {
+        let (server_sender, _) = kanal::bounded_async(1);
+        let connection = BackendConnection::leak(backend.name().clone(), server_sender);
+        backend.set_connection(Some(connection));
+        connection
+    }
+
+    fn add_player(connection: &'static BackendConnection, id: u64) {
+        let (writer, _) = kanal::bounded_async(1);
+        let route = Arc::new(tokio::sync::watch::Sender::new(connection));
+        connection
+            .player_registry
+            .pin()
+            .insert(id, PlayerHandle::new(writer, route));
+    }
+
+    fn routed(policy: &impl RoutingPolicy, backends: &Backends) -> Option<Arc<str>> {
+        policy
+            .route(backends)
+            .map(|connection| connection.name.clone())
+    }
+
+    #[test]
+    fn no_backend_connected() {
+        let backends = backends(&["lobby", "game"]);
+
+        assert_eq!(routed(&FirstAvailable, &backends), None);
+        assert_eq!(routed(&RoundRobin::default(), &backends), None);
+        assert_eq!(routed(&LeastPlayers, &backends), None);
+    }
+
+    #[test]
+    fn first_available_falls_back_in_order() {
+        let backends = backends(&["lobby", "game", "fallback"]);
+
+        connect(backends.get("fallback").unwrap());
+        assert_eq!(
+            routed(&FirstAvailable, &backends).as_deref(),
+            Some("fallback")
+        );
+
+        connect(backends.get("lobby").unwrap());
+        assert_eq!(routed(&FirstAvailable, &backends).as_deref(), Some("lobby"));
+    }
+
+    #[test]
+    fn round_robin_skips_disconnected() {
+        let backends = backends(&["a", "b", "c"]);
+        connect(backends.get("a").unwrap());
+        connect(backends.get("c").unwrap());
+
+        let policy = RoundRobin::default();
+        let routes: Vec<_> = (0..4)
+            .map(|_| routed(&policy, &backends).unwrap())
+            .collect();
+
+        assert_eq!(routes.iter().map(|name| &**name).collect::<Vec<_>>(), [
+            "a", "c", "a", "c"
+        ]);
+    }
+
+    #[test]
+    fn least_players() {
+        let backends = backends(&["a", "b"]);
+        let a = connect(backends.get("a").unwrap());
+        let b = connect(backends.get("b").unwrap());
+
+        add_player(a, 1);
+        add_player(a, 2);
+        add_player(b, 3);
+
+        assert_eq!(routed(&LeastPlayers, &backends).as_deref(), Some("b"));
+    }
+}
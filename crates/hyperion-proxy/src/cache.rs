@@ -1,4 +1,4 @@
-use std::{ops::Range, sync::Arc};
+use std::{collections::BTreeMap, ops::Range, sync::Arc};
 
 use bvh::{Bvh, Data, Point};
 use glam::I16Vec2;
@@ -155,6 +155,7 @@ impl Iterator for ExclusionIterator<'_> {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 struct LocalBroadcastData {
+    world: u16,
     position: I16Vec2,
     range_start: usize,
     range_end: usize,
@@ -197,6 +198,8 @@ pub struct BufferedEgress {
     egress: Egress,
     /// Tracks the current broadcast order.
     current_broadcast_order: Option<u32>,
+    /// The world the buffered global broadcasts are restricted to.
+    current_broadcast_world: Option<u16>,
     local_flush_counter: u32,
 }
 
@@ -211,6 +214,7 @@ impl BufferedEgress {
             exclusion_manager: ExclusionsManager::default(),
             egress,
             current_broadcast_order: None,
+            current_broadcast_world: None,
             local_flush_counter: 0,
         }
     }
@@ -224,15 +228,17 @@ impl BufferedEgress {
             }
             ArchivedServerToProxyMessage::BroadcastGlobal(packet) => {
                 let Ok(packet_order) = rkyv::deserialize::<u32, !>(&packet.order);
+                let Ok(packet_world) = rkyv::deserialize::<Option<u16>, !>(&packet.world);
 
                 if let Some(order) = self.current_broadcast_order
-                    && order != packet_order
+                    && (order != packet_order || self.current_broadcast_world != packet_world)
                 {
                     // send the current broadcasts to all players
                     self.flush_broadcast(order);
                 }
 
                 self.current_broadcast_order = Some(packet_order);
+                self.current_broadcast_world = packet_world;
 
                 let current_len = self.global_broadcast_buffer.len();
                 self.global_broadcast_buffer.extend_from_slice(&packet.data);
@@ -250,6 +256,7 @@ impl BufferedEgress {
                 // to optimize cache usage.
             }
             ArchivedServerToProxyMessage::BroadcastLocal(packet) => {
+                let Ok(world) = rkyv::deserialize::<u16, !>(&packet.center.world);
                 let Ok(center_x) = rkyv::deserialize::<i16, !>(&packet.center.x);
                 let Ok(center_z) = rkyv::deserialize::<i16, !>(&packet.center.z);
                 let Ok(player_id_to_exclude) = rkyv::deserialize::<u64, !>(&packet.exclude);
@@ -262,6 +269,7 @@ impl BufferedEgress {
                 let after_len = self.raw_local_broadcast_data.len();
 
                 self.local_broadcast_buffer.push(LocalBroadcastData {
+                    world,
                     // todo: checked
                     position,
                     range_start: before_len,
@@ -275,6 +283,15 @@ impl BufferedEgress {
             ArchivedServerToProxyMessage::SetReceiveBroadcasts(pkt) => {
                 self.egress.handle_set_receive_broadcasts(pkt);
             }
+            ArchivedServerToProxyMessage::SetEncryption(pkt) => {
+                self.egress.handle_set_encryption(pkt);
+            }
+            ArchivedServerToProxyMessage::SetPlayerInfo(pkt) => {
+                self.egress.handle_set_player_info(pkt);
+            }
+            ArchivedServerToProxyMessage::Transfer(pkt) => {
+                self.egress.handle_transfer(pkt);
+            }
             ArchivedServerToProxyMessage::Flush(_) => {
                 if let Some(order) = self.current_broadcast_order.take() {
                     self.flush_broadcast(order);
@@ -287,41 +304,46 @@ impl BufferedEgress {
                     return;
                 }
 
-                let bvh = Bvh::build(
-                    &mut self.local_broadcast_buffer,
-                    &self.raw_local_broadcast_data,
-                );
+                // positions in different worlds must not end up in the same bvh
+                let mut by_world: BTreeMap<u16, Vec<LocalBroadcastData>> = BTreeMap::new();
+                for packet in self.local_broadcast_buffer.drain(..) {
+                    by_world.entry(packet.world).or_default().push(packet);
+                }
 
-                let mut exclusions = ExclusionsManager::default();
-                let mut idx_on = 0;
+                for (world, mut packets) in by_world {
+                    let bvh = Bvh::build(&mut packets, &self.raw_local_broadcast_data);
 
-                for packet in &self.local_broadcast_buffer {
-                    // todo: is there a more idiomatic way to do this?
-                    let packet_len = packet.len();
-                    let range = idx_on..idx_on + packet_len;
+                    let mut exclusions = ExclusionsManager::default();
+                    let mut idx_on = 0;
 
-                    if packet.player_id_to_exclude != 0 {
-                        exclusions.append_exclusion(packet.player_id_to_exclude, range);
-                    }
+                    for packet in &packets {
+                        // todo: is there a more idiomatic way to do this?
+                        let packet_len = packet.len();
+                        let range = idx_on..idx_on + packet_len;
 
-                    idx_on += packet_len;
-                }
+                        if packet.player_id_to_exclude != 0 {
+                            exclusions.append_exclusion(packet.player_id_to_exclude, range);
+                        }
 
-                self.local_broadcast_buffer.clear();
-                self.raw_local_broadcast_data.clear();
+                        idx_on += packet_len;
+                    }
 
-                let egress = self.egress;
-                tokio::spawn(async move {
-                    let bvh = bvh.into_bytes();
+                    let egress = self.egress;
+                    tokio::spawn(async move {
+                        let bvh = bvh.into_bytes();
 
-                    let instruction = BroadcastLocalInstruction {
-                        order: 0,
-                        bvh: Arc::new(bvh),
-                        exclusions: Arc::new(exclusions),
-                    };
+                        let instruction = BroadcastLocalInstruction {
+                            order: 0,
+                            world,
+                            bvh: Arc::new(bvh),
+                            exclusions: Arc::new(exclusions),
+                        };
 
-                    egress.handle_broadcast_local(instruction);
-                });
+                        egress.handle_broadcast_local(instruction);
+                    });
+                }
+
+                self.raw_local_broadcast_data.clear();
             }
         }
     }
@@ -333,6 +355,7 @@ impl BufferedEgress {
             data,
             exclude: 0,
             order,
+            world: self.current_broadcast_world,
         };
 
         let exclusions = self.exclusion_manager.take();
@@ -1,10 +1,15 @@
-use std::sync::{Arc, atomic, atomic::AtomicBool};
+use std::sync::{Arc, Mutex, atomic, atomic::AtomicBool};
 
 use anyhow::bail;
 use bytes::Bytes;
+use hyperion_proto::PlayerInfo;
 use slotmap::{KeyData, new_key_type};
 
-use crate::cache::ExclusionsManager;
+use crate::{
+    backend::{BackendConnection, Route},
+    cache::ExclusionsManager,
+    encryption::SharedKey,
+};
 
 new_key_type! {
     pub struct PlayerId;
@@ -47,6 +52,18 @@ impl OrderedBytes {
         exclusions: None,
     };
 
+    /// Sentinel telling the writer to encrypt everything it flushes from now on. The key is
+    /// stored in [`Self::data`].
+    #[must_use]
+    pub fn enable_encryption(key: [u8; 16]) -> Self {
+        Self {
+            order: u32::MAX - 2,
+            offset: 0,
+            data: Bytes::copy_from_slice(&key),
+            exclusions: None,
+        }
+    }
+
     pub const fn is_flush(&self) -> bool {
         self.order == u32::MAX
     }
@@ -55,6 +72,10 @@ impl OrderedBytes {
         self.order == u32::MAX - 1
     }
 
+    pub const fn is_enable_encryption(&self) -> bool {
+        self.order == u32::MAX - 2
+    }
+
     pub const fn no_order(data: Bytes) -> Self {
         Self {
             order: 0,
@@ -108,15 +129,82 @@ pub struct PlayerHandle {
     /// they will get packets that it deems are invalid because the broadcasts are using the play
     /// state and play IDs.
     can_receive_broadcasts: AtomicBool,
+
+    /// Set once the server enables encryption for this player.
+    encryption_key: SharedKey,
+
+    /// The backend the player's packets are forwarded to.
+    route: Route,
+
+    /// Set by the backend once the player is in the play state.
+    info: Mutex<Option<PlayerInfo>>,
 }
 
 impl PlayerHandle {
     #[must_use]
-    pub const fn new(writer: kanal::AsyncSender<OrderedBytes>) -> Self {
+    pub fn new(writer: kanal::AsyncSender<OrderedBytes>, route: Route) -> Self {
         Self {
             writer,
             can_receive_broadcasts: AtomicBool::new(false),
+            encryption_key: SharedKey::default(),
+            route,
+            info: Mutex::new(None),
+        }
+    }
+
+    /// A handle to the same connection for another backend.
+    ///
+    /// The new backend has to enable broadcasts again once the player is in its world.
+    #[must_use]
+    pub fn transfer(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            can_receive_broadcasts: AtomicBool::new(false),
+            encryption_key: self.encryption_key.clone(),
+            route: self.route.clone(),
+            info: Mutex::new(self.info()),
+        }
+    }
+
+    /// Who the player is, or `None` if they are not in the play state yet.
+    #[must_use]
+    pub fn info(&self) -> Option<PlayerInfo> {
+        self.info
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set_info(&self, info: PlayerInfo) {
+        *self
+            .info
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(info);
+    }
+
+    /// The backend the player is currently connected to.
+    #[must_use]
+    pub fn backend(&self) -> &'static BackendConnection {
+        *self.route.borrow()
+    }
+
+    /// Forwards the player's packets to `backend` from now on.
+    pub fn set_backend(&self, backend: &'static BackendConnection) {
+        self.route.send_replace(backend);
+    }
+
+    /// The key the reader half uses to decrypt incoming bytes.
+    #[must_use]
+    pub fn encryption_key(&self) -> SharedKey {
+        self.encryption_key.clone()
+    }
+
+    pub fn enable_encryption(&self, key: [u8; 16]) -> anyhow::Result<()> {
+        if self.encryption_key.set(key).is_err() {
+            bail!("encryption is already enabled");
         }
+
+        self.send(OrderedBytes::enable_encryption(key))
     }
 
     pub fn shutdown(&self) {
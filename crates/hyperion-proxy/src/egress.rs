@@ -4,19 +4,27 @@ use bvh::{Aabb, Bvh};
 use bytes::Bytes;
 use glam::I16Vec2;
 use hyperion_proto::{
-    ArchivedSetReceiveBroadcasts, ArchivedUnicast, ArchivedUpdatePlayerChunkPositions,
-    ChunkPosition,
+    ArchivedSetEncryption, ArchivedSetPlayerInfo, ArchivedSetReceiveBroadcasts, ArchivedTransfer,
+    ArchivedUnicast, ArchivedUpdatePlayerChunkPositions, ChunkPosition, PlayerDisconnect,
+    PlayerDisconnectReason, PlayerInfo, ProxyToServerMessage,
 };
 use rustc_hash::FxBuildHasher;
 use tracing::{Instrument, debug, error, info_span, instrument, warn};
 
 use crate::{
+    backend::{Backend, BackendConnection, Backends},
     cache::ExclusionsManager,
     data::{OrderedBytes, PlayerHandle},
 };
 
 #[derive(Copy, Clone)]
 pub struct Egress {
+    /// The backend whose messages this handles.
+    connection: &'static BackendConnection,
+
+    /// All backends, to look up the target of a transfer.
+    backends: &'static Backends,
+
     // todo: can we do some type of EntityId and SlotMap
     player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
 
@@ -26,19 +34,20 @@ pub struct Egress {
 
 pub struct BroadcastLocalInstruction {
     pub order: u32,
+    /// Only players in this world receive the broadcast.
+    pub world: u16,
     pub bvh: Arc<Bvh<Bytes>>,
     pub exclusions: Arc<ExclusionsManager>,
 }
 
 impl Egress {
     #[must_use]
-    pub const fn new(
-        player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
-        positions: &'static papaya::HashMap<u64, ChunkPosition, FxBuildHasher>,
-    ) -> Self {
+    pub const fn new(connection: &'static BackendConnection, backends: &'static Backends) -> Self {
         Self {
-            player_registry,
-            positions,
+            connection,
+            backends,
+            player_registry: connection.player_registry,
+            positions: connection.player_positions,
         }
     }
 
@@ -52,10 +61,12 @@ impl Egress {
             let Ok(stream) = rkyv::deserialize::<u64, !>(stream);
 
             // todo: can I just grab the whole thing as Infallible?
+            let Ok(world) = rkyv::deserialize::<_, !>(&position.world);
             let Ok(position_x) = rkyv::deserialize::<_, !>(&position.x);
             let Ok(position_z) = rkyv::deserialize::<_, !>(&position.z);
 
             let position = ChunkPosition {
+                world,
                 x: position_x,
                 z: position_z,
             };
@@ -72,6 +83,8 @@ impl Egress {
     ) {
         // todo: why cannot I pin_owned inside the spawn
         let players = self.player_registry.pin_owned();
+        let positions = self.positions.pin_owned();
+        let world = pkt.world;
         let data = pkt.data;
         let data = Bytes::copy_from_slice(data);
 
@@ -86,6 +99,14 @@ impl Egress {
                         continue;
                     }
 
+                    if let Some(world) = world
+                        && positions
+                            .get(player_id)
+                            .is_none_or(|position| position.world != world)
+                    {
+                        continue;
+                    }
+
                     let to_send =
                         OrderedBytes::with_exclusions(pkt.order, data.clone(), exclusions.clone());
 
@@ -123,6 +144,7 @@ impl Egress {
     #[instrument(skip_all)]
     pub fn handle_broadcast_local(self, instruction: BroadcastLocalInstruction) {
         let order = instruction.order;
+        let world = instruction.world;
         let bvh = instruction.bvh;
         let exclusions = instruction.exclusions;
 
@@ -136,6 +158,10 @@ impl Egress {
                 let players = self.player_registry.pin();
 
                 for (id, &position) in &positions {
+                    if position.world != world {
+                        continue;
+                    }
+
                     let Some(player) = players.get(id) else {
                         // expected to still happen infrequently
                         debug!("Player not found for id {id:?}");
@@ -230,4 +256,103 @@ impl Egress {
 
         player.enable_receive_broadcasts();
     }
+
+    #[instrument(skip_all)]
+    pub fn handle_set_encryption(&self, pkt: &ArchivedSetEncryption) {
+        let players = self.player_registry.pin();
+        let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
+        let Ok(key) = rkyv::deserialize::<[u8; 16], !>(&pkt.key);
+
+        let Some(player) = players.get(&stream) else {
+            error!("Player not found for stream {stream:?}");
+            return;
+        };
+
+        if let Err(e) = player.enable_encryption(key) {
+            warn!("Failed to enable encryption for player: {e:?}");
+            if let Some(result) = players.remove(&stream) {
+                result.shutdown();
+            }
+        }
+    }
+
+    #[instrument(skip_all)]
+    pub fn handle_set_player_info(&self, pkt: &ArchivedSetPlayerInfo) {
+        let players = self.player_registry.pin();
+        let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
+        let Ok(info) = rkyv::deserialize::<PlayerInfo, !>(&pkt.info);
+
+        let Some(player) = players.get(&stream) else {
+            error!("Player not found for stream {stream:?}");
+            return;
+        };
+
+        player.set_info(info);
+    }
+
+    /// Moves a player to another backend.
+    ///
+    /// If the target backend is unknown or not connected, the player stays where they are.
+    #[instrument(skip_all)]
+    pub fn handle_transfer(&self, pkt: &ArchivedTransfer<'_>) {
+        let Ok(stream) = rkyv::deserialize::<u64, !>(&pkt.stream);
+        let backend = &*pkt.backend;
+
+        let Some(target) = self.backends.get(backend).and_then(Backend::connection) else {
+            warn!("Cannot transfer player {stream:?} to unavailable backend {backend:?}");
+            return;
+        };
+
+        if std::ptr::eq(target, self.connection) {
+            warn!("Player {stream:?} is already connected to backend {backend:?}");
+            return;
+        }
+
+        let players = self.player_registry.pin();
+
+        let Some(player) = players.get(&stream) else {
+            error!("Player not found for stream {stream:?}");
+            return;
+        };
+
+        let Some(info) = player.info() else {
+            warn!("Cannot transfer player {stream:?} before they are in the play state");
+            return;
+        };
+
+        // everything this backend sent so far must reach the player before the new backend
+        // starts sending
+        if let Err(e) = player.send(OrderedBytes::FLUSH) {
+            warn!("Failed to send data to player: {:?}", e);
+            if let Some(result) = players.remove(&stream) {
+                result.shutdown();
+            }
+            return;
+        }
+
+        if !target.attach(stream, player, info) {
+            warn!("Failed to send player connect to backend {backend:?}");
+            return;
+        }
+
+        players.remove(&stream);
+        self.positions.pin().remove(&stream);
+
+        let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
+            &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
+                stream,
+                reason: PlayerDisconnectReason::Transferred,
+            }),
+        )
+        .unwrap();
+
+        if !matches!(self.connection.server_sender.try_send(disconnect), Ok(true)) {
+            warn!("Failed to send player disconnect to server");
+        }
+
+        debug!(
+            "Transferred player {stream:?} from {} to {}",
+            self.connection.name, target.name
+        );
+    }
 }
@@ -0,0 +1,88 @@
+//! AES/CFB8 stream encryption for online-mode player connections.
+//!
+//! The game server performs the key exchange. Once it knows the shared secret it sends a
+//! [`hyperion_proto::SetEncryption`] message and all encryption happens here, because broadcast
+//! bytes are shared between players and can only be encrypted once they are split per player.
+
+use std::sync::{Arc, OnceLock};
+
+use aes::cipher::{
+    BlockDecryptMut, BlockEncryptMut, KeyIvInit, generic_array::GenericArray, typenum::U1,
+};
+
+type Encryptor = cfb8::Encryptor<aes::Aes128>;
+type Decryptor = cfb8::Decryptor<aes::Aes128>;
+
+/// The shared secret of a connection, set once the server has enabled encryption.
+///
+/// The writer half learns about the key in order through [`crate::data::OrderedBytes`]; the reader
+/// half polls this instead since it does not receive anything from the server.
+pub type SharedKey = Arc<OnceLock<[u8; 16]>>;
+
+/// Encrypts bytes sent to the player.
+pub struct PacketEncryptor {
+    cipher: Encryptor,
+}
+
+impl PacketEncryptor {
+    #[must_use]
+    pub fn new(key: &[u8; 16]) -> Self {
+        Self {
+            cipher: Encryptor::new(key.into(), key.into()),
+        }
+    }
+
+    pub fn encrypt(&mut self, bytes: &mut [u8]) {
+        self.cipher.encrypt_blocks_mut(as_blocks(bytes));
+    }
+}
+
+/// Decrypts bytes received from the player.
+pub struct PacketDecryptor {
+    cipher: Decryptor,
+}
+
+impl PacketDecryptor {
+    #[must_use]
+    pub fn new(key: &[u8; 16]) -> Self {
+        Self {
+            cipher: Decryptor::new(key.into(), key.into()),
+        }
+    }
+
+    pub fn decrypt(&mut self, bytes: &mut [u8]) {
+        self.cipher.decrypt_blocks_mut(as_blocks(bytes));
+    }
+}
+
+/// CFB8 has a block size of one byte, so every byte is its own block.
+fn as_blocks(bytes: &mut [u8]) -> &mut [GenericArray<u8, U1>] {
+    // SAFETY: `GenericArray<u8, U1>` is `repr(transparent)` over `[u8; 1]`
+    unsafe { std::slice::from_raw_parts_mut(bytes.as_mut_ptr().cast(), bytes.len()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PacketDecryptor, PacketEncryptor};
+
+    #[test]
+    fn round_trip_across_chunk_boundaries() {
+        let key = [7; 16];
+        let plain: Vec<u8> = (0..=255).collect();
+
+        let mut encryptor = PacketEncryptor::new(&key);
+        let mut cipher_text = plain.clone();
+        let (first, second) = cipher_text.split_at_mut(100);
+        encryptor.encrypt(first);
+        encryptor.encrypt(second);
+
+        assert_ne!(cipher_text, plain);
+
+        let mut decryptor = PacketDecryptor::new(&key);
+        let (first, second) = cipher_text.split_at_mut(37);
+        decryptor.decrypt(first);
+        decryptor.decrypt(second);
+
+        assert_eq!(cipher_text, plain);
+    }
+}
@@ -16,12 +16,11 @@
     clippy::future_not_send
 )]
 
-use std::fmt::Debug;
+use std::{fmt::Debug, net::SocketAddr, sync::Arc};
 
-use anyhow::Context;
+use anyhow::{Context, ensure};
 use colored::Colorize;
-use hyperion_proto::{ArchivedServerToProxyMessage, ChunkPosition};
-use rustc_hash::FxBuildHasher;
+use hyperion_proto::ArchivedServerToProxyMessage;
 use tokio::{
     io::{AsyncReadExt, BufReader},
     net::{TcpStream, ToSocketAddrs},
@@ -30,7 +29,12 @@ use tokio_util::net::Listener;
 use tracing::{Instrument, debug, error, info, info_span, instrument, trace, warn};
 
 use crate::{
-    cache::BufferedEgress, data::PlayerHandle, egress::Egress, player::initiate_player_connection,
+    backend::{Backend, BackendConnection, Backends, RoutingPolicy},
+    cache::BufferedEgress,
+    data::PlayerHandle,
+    egress::Egress,
+    limbo::Limbo,
+    player::initiate_player_connection,
     server_sender::launch_server_writer,
 };
 
@@ -42,10 +46,14 @@ const DEFAULT_BUFFER_SIZE: usize = 4 * 1024;
 /// memory exhaustion from slow or unresponsive clients.
 const MAX_PLAYER_PENDING_MESSAGES: usize = 1_024;
 
+pub mod backend;
 pub mod cache;
 pub mod data;
 pub mod egress;
+pub mod encryption;
+pub mod limbo;
 pub mod player;
+pub mod proxy_protocol;
 pub mod server_sender;
 pub mod util;
 
@@ -60,18 +68,19 @@ pub async fn connect(addr: impl ToSocketAddrs + Debug + Clone) -> TcpStream {
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum ShutdownType {
-    Reconnect,
-    Full,
-}
-
 #[tracing::instrument(level = "trace", skip_all)]
 pub async fn run_proxy(
     mut listener: impl HyperionListener,
-    server_addr: impl ToSocketAddrs + Debug + Clone,
+    backends: Backends,
+    routing: impl RoutingPolicy,
+    proxy_protocol: bool,
 ) -> anyhow::Result<()> {
-    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(None);
+    ensure!(
+        !backends.is_empty(),
+        "at least one backend server is required"
+    );
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
 
     #[cfg(unix)]
     let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
@@ -88,139 +97,149 @@ pub async fn run_proxy(
             tokio::select! {
                 _ = sigterm.recv() => {
                     warn!("SIGTERM received, shutting down");
-                    shutdown_tx.send(Some(ShutdownType::Full)).unwrap();
+                    shutdown_tx.send(true).unwrap();
                 }
                 _ = sigquit.recv() => {
                     warn!("SIGQUIT received, shutting down");
-                    shutdown_tx.send(Some(ShutdownType::Full)).unwrap();
+                    shutdown_tx.send(true).unwrap();
                 }
             }
         }
     });
 
+    let backends: &'static Backends = Box::leak(Box::new(backends));
+
+    for backend in backends {
+        tokio::spawn(
+            maintain_backend(backend, backends, shutdown_rx.clone())
+                .instrument(info_span!("backend", name = &**backend.name())),
+        );
+    }
+
+    // 0 is reserved for "None" value
+    let mut player_id_on = 1;
+
     loop {
-        let mut shutdown_rx2 = shutdown_rx.clone();
+        let mut shutdown_rx = shutdown_rx.clone();
 
-        if *shutdown_rx2.borrow() == Some(ShutdownType::Full) {
-            break Ok(());
+        // like with a single server, clients wait in the accept queue until a backend is up
+        while backends.connected().next().is_none() {
+            tokio::select! {
+                _ = shutdown_rx.wait_for(|shutdown| *shutdown) => return Ok(()),
+                () = tokio::time::sleep(std::time::Duration::from_millis(100)) => {}
+            }
         }
 
-        tokio::select! {
-            _ = shutdown_rx2.wait_for(|value| *value == Some(ShutdownType::Full)) => {
+        let (socket, addr) = tokio::select! {
+            _ = shutdown_rx.wait_for(|shutdown| *shutdown) => {
                 warn!("Received shutdown signal, exiting proxy loop");
-                break Ok(());
+                return Ok(())
             }
-            () = async {
+            Ok((socket, addr)) = listener.accept() => {
+                info!("New client connection from {addr:?}");
+                (socket, addr.socket_addr())
+            }
+        };
 
-                // clear shutdown channel
-                shutdown_tx.send(None).unwrap();
+        let Some(backend) = routing.route(backends) else {
+            warn!("No backend available for {addr:?}, closing connection");
+            continue;
+        };
 
-                let binding_help = "~ Make sure the event server is running".dimmed();
-                info!("⏳ Binding to server... {binding_help}");
+        let registry = backend.player_registry.pin();
 
-                let server_socket = connect(server_addr.clone()).await;
-                server_socket.set_nodelay(true).unwrap();
+        // todo: re-add bounding but issues if have MASSIVE number of packets
+        let (tx, rx) = kanal::bounded_async(MAX_PLAYER_PENDING_MESSAGES);
+        let route = Arc::new(tokio::sync::watch::Sender::new(backend));
+        let handle = PlayerHandle::new(tx, route.clone());
+        let encryption_key = handle.encryption_key();
+        registry.insert(player_id_on, handle);
 
-                if let Err(e) = connect_to_server_and_run_proxy(&mut listener, server_socket, shutdown_rx.clone(), shutdown_tx.clone()).await {
-                    error!("Error connecting to server: {e:?}");
-                }
+        // todo: some SlotMap like thing
+        debug!("got player with id {player_id_on:?} on {}", backend.name);
 
+        initiate_player_connection(
+            socket,
+            addr,
+            proxy_protocol,
+            shutdown_rx,
+            player_id_on,
+            rx,
+            encryption_key,
+            route,
+        );
 
-            } => {}
-        }
+        player_id_on += 1;
     }
 }
 
+/// Keeps the proxy connected to `backend`, reconnecting whenever the connection is lost.
+///
+/// Players in the world of the backend wait in its [`Limbo`] while it is down and are reattached
+/// once it is back; players on other backends are not affected.
 #[tracing::instrument(level = "trace", skip_all)]
-async fn connect_to_server_and_run_proxy(
-    listener: &mut impl HyperionListener,
-    server_socket: TcpStream,
-    shutdown_rx: tokio::sync::watch::Receiver<Option<ShutdownType>>,
-    shutdown_tx: tokio::sync::watch::Sender<Option<ShutdownType>>,
-) -> anyhow::Result<()> {
-    info!("🔗 Connected to server, accepting connections");
-    let (server_read, server_write) = server_socket.into_split();
-    let server_sender = launch_server_writer(server_write);
-
-    let player_registry = papaya::HashMap::default();
-    let player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher> =
-        Box::leak(Box::new(player_registry));
+async fn maintain_backend(
+    backend: &'static Backend,
+    backends: &'static Backends,
+    mut shutdown_rx: tokio::sync::watch::Receiver<bool>,
+) {
+    let limbo = Limbo::new(backend.name());
 
-    let player_positions = papaya::HashMap::default();
-    let player_positions: &'static papaya::HashMap<u64, ChunkPosition, FxBuildHasher> =
-        Box::leak(Box::new(player_positions));
+    loop {
+        let binding_help = "~ Make sure the event server is running".dimmed();
+        info!(
+            "⏳ Binding to server {} at {}... {binding_help}",
+            backend.name(),
+            backend.address()
+        );
 
-    let egress = Egress::new(player_registry, player_positions);
+        let server_socket = tokio::select! {
+            _ = shutdown_rx.wait_for(|shutdown| *shutdown) => return,
+            server_socket = connect(backend.address()) => server_socket,
+            () = limbo.wait() => unreachable!("limbo never stops waiting"),
+        };
 
-    let egress = BufferedEgress::new(egress);
+        server_socket.set_nodelay(true).unwrap();
 
-    let mut handler = IngressHandler::new(BufReader::new(server_read), egress);
+        info!(
+            "🔗 Connected to server {}, accepting connections",
+            backend.name()
+        );
+        let (server_read, server_write) = server_socket.into_split();
+        let server_sender = launch_server_writer(server_write);
 
-    tokio::spawn({
-        let mut shutdown_rx = shutdown_rx.clone();
+        let connection = BackendConnection::leak(backend.name().clone(), server_sender);
+        backend.set_connection(Some(connection));
 
-        async move {
-                loop {
-                    tokio::select! {
-                    _ = shutdown_rx.wait_for(Option::is_some) => return,
-                    result = handler.handle_next() => {
-                        match result {
-                            Ok(()) => {},
-                            Err(e) => {
-                                error!(
-                                    "Error reading next packet: {e:?}. Are you connected to a valid \
-                                     hyperion server? If you are connected to a vanilla server, \
-                                     hyperion-proxy will not work."
-                                );
-                                break;
-                            }
-                        }
-                    }
-                }
-                }
+        limbo.leave(connection);
 
-                debug!("Sending shutdown to all players");
+        let egress = Egress::new(connection, backends);
 
-                shutdown_tx.send(Some(ShutdownType::Reconnect)).unwrap();
-            }
-                .instrument(info_span!("server_reader_loop"))
-    });
+        let egress = BufferedEgress::new(egress);
 
-    // 0 is reserved for "None" value
-    let mut player_id_on = 1;
+        let mut handler = IngressHandler::new(BufReader::new(server_read), egress);
 
-    loop {
-        let mut shutdown_rx = shutdown_rx.clone();
-        let socket = tokio::select! {
-            _ = shutdown_rx.wait_for(Option::is_some) => {
-                return Ok(())
-            }
-            Ok((socket, addr)) = listener.accept() => {
-                info!("New client connection from {addr:?}");
-                socket
+        loop {
+            tokio::select! {
+                _ = shutdown_rx.wait_for(|shutdown| *shutdown) => return,
+                result = handler.handle_next() => {
+                    if let Err(e) = result {
+                        error!(
+                            "Error reading next packet: {e:?}. Are you connected to a valid \
+                             hyperion server? If you are connected to a vanilla server, \
+                             hyperion-proxy will not work."
+                        );
+                        break;
+                    }
+                }
             }
-        };
-
-        let registry = player_registry.pin();
-
-        // todo: re-add bounding but issues if have MASSIVE number of packets
-        let (tx, rx) = kanal::bounded_async(MAX_PLAYER_PENDING_MESSAGES);
-        registry.insert(player_id_on, PlayerHandle::new(tx));
+        }
 
-        // todo: some SlotMap like thing
-        debug!("got player with id {player_id_on:?}");
+        backend.set_connection(None);
 
-        initiate_player_connection(
-            socket,
-            shutdown_rx.clone(),
-            player_id_on,
-            rx,
-            server_sender.clone(),
-            player_registry,
-            player_positions,
-        );
+        debug!("Moving players of {} to limbo", backend.name());
 
-        player_id_on += 1;
+        limbo.enter(connection);
     }
 }
 
@@ -290,6 +309,25 @@ impl IngressHandler {
     }
 }
 
-trait HyperionListener: Listener<Io: Send, Addr: Debug> + 'static {}
+trait HyperionListener: Listener<Io: Send, Addr: PeerAddr> + 'static {}
+
+impl<L: Listener<Io: Send, Addr: PeerAddr> + 'static> HyperionListener for L {}
 
-impl<L: Listener<Io: Send, Addr: Debug> + 'static> HyperionListener for L {}
+/// The address of an accepted connection.
+pub trait PeerAddr: Debug {
+    /// The address of the peer, or `None` if it is not an IP socket.
+    fn socket_addr(&self) -> Option<SocketAddr>;
+}
+
+impl PeerAddr for SocketAddr {
+    fn socket_addr(&self) -> Option<SocketAddr> {
+        Some(*self)
+    }
+}
+
+#[cfg(unix)]
+impl PeerAddr for tokio::net::unix::SocketAddr {
+    fn socket_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
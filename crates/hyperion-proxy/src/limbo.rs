@@ -0,0 +1,259 @@
+//! Keeps players connected while their backend is down, e.g. while it is being redeployed.
+//!
+//! Players that are in the world when the connection to their backend is lost are moved to the
+//! backend's [`Limbo`]. The proxy answers for the backend there: it sends keep-alives so the client
+//! does not time out and shows a waiting message. Once the proxy reconnects to the backend, the
+//! players are attached to it again like after a transfer, so the backend makes them load the
+//! world.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::{Instrument, info, info_span, warn};
+use valence_protocol::{CompressionThreshold, Encode, Packet, PacketEncoder, packets::play};
+use valence_text::{Color, IntoText};
+
+use crate::{
+    backend::BackendConnection,
+    data::{OrderedBytes, PlayerHandle},
+};
+
+/// How often players in limbo get a keep-alive and the waiting message.
+const TICK: Duration = Duration::from_secs(1);
+
+/// How long players wait for the backend before they are disconnected.
+const TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Where the players of one backend wait while it is down.
+pub struct Limbo {
+    /// Stands in for the backend. Its registry holds the waiting players, and everything they send
+    /// is discarded.
+    connection: &'static BackendConnection,
+}
+
+impl Limbo {
+    #[must_use]
+    pub fn new(backend: &str) -> Self {
+        let (server_sender, discarded) = kanal::bounded_async(1024);
+
+        tokio::spawn(
+            async move {
+                // keep-alive responses and anything else the client sends while waiting
+                while discarded.recv().await.is_ok() {}
+            }
+            .instrument(info_span!("limbo_discard")),
+        );
+
+        let connection =
+            BackendConnection::leak(format!("{backend} (limbo)").into(), server_sender);
+
+        Self { connection }
+    }
+
+    /// Moves the players of a lost connection here. Players that are not in the world yet are
+    /// disconnected, since the restarted backend cannot continue their login.
+    pub fn enter(&self, lost: &'static BackendConnection) {
+        let waiting = self.connection.player_registry.pin();
+        let players = lost.player_registry.pin();
+
+        for (&stream, player) in &players {
+            if player.info().is_none() {
+                player.shutdown();
+                continue;
+            }
+
+            let message = "Server is restarting. Please wait...".color(Color::YELLOW);
+
+            if let Err(e) = send(player, &play::GameMessageS2c {
+                chat: message.into_cow_text(),
+                overlay: false,
+            }) {
+                warn!("Failed to send data to player: {e:?}");
+                player.shutdown();
+                continue;
+            }
+
+            waiting.insert(stream, player.transfer());
+            player.set_backend(self.connection);
+        }
+
+        players.clear();
+
+        info!("{} players are waiting in limbo", waiting.len());
+    }
+
+    /// Attaches all waiting players to the reconnected backend.
+    pub fn leave(&self, connection: &'static BackendConnection) {
+        let waiting = self.connection.player_registry.pin();
+
+        for (&stream, player) in &waiting {
+            let Some(info) = player.info() else {
+                player.shutdown();
+                continue;
+            };
+
+            if !connection.attach(stream, player, info) {
+                warn!(
+                    "Failed to reattach player {stream:?} to {}",
+                    connection.name
+                );
+                player.shutdown();
+            }
+        }
+
+        if !waiting.is_empty() {
+            info!("{} players left limbo", waiting.len());
+        }
+
+        waiting.clear();
+    }
+
+    /// Keeps the waiting players connected. Never returns; cancel it once the backend is back.
+    pub async fn wait(&self) {
+        let start = Instant::now();
+        let mut interval = tokio::time::interval(TICK);
+        let mut keep_alive_id = 0_u64;
+
+        loop {
+            interval.tick().await;
+
+            let waiting = self.connection.player_registry.pin();
+
+            if waiting.is_empty() {
+                continue;
+            }
+
+            if start.elapsed() > TIMEOUT {
+                warn!("Backend did not come back in time, disconnecting players in limbo");
+
+                for player in waiting.values() {
+                    let reason = "The server did not come back in time".color(Color::RED);
+                    let _unused = send(player, &play::DisconnectS2c {
+                        reason: reason.into_cow_text(),
+                    });
+                    player.shutdown();
+                }
+
+                waiting.clear();
+                continue;
+            }
+
+            keep_alive_id += 1;
+
+            let seconds = start.elapsed().as_secs();
+            let message = format!("Waiting for the server to restart... ({seconds}s)");
+
+            for (stream, player) in &waiting {
+                let keep_alive = send(player, &play::KeepAliveS2c { id: keep_alive_id });
+
+                let message = send(player, &play::GameMessageS2c {
+                    chat: message.as_str().color(Color::GRAY).into_cow_text(),
+                    overlay: true,
+                });
+
+                if let Err(e) = keep_alive.and(message) {
+                    warn!("Failed to send data to player: {e:?}");
+                    if let Some(player) = waiting.remove(stream) {
+                        player.shutdown();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Encodes `pkt` like the backend would and flushes it to the player.
+fn send<P: Packet + Encode>(player: &PlayerHandle, pkt: &P) -> anyhow::Result<()> {
+    let Some(info) = player.info() else {
+        anyhow::bail!("player is not in the play state");
+    };
+
+    let mut encoder = PacketEncoder::new();
+    encoder.set_compression(CompressionThreshold(info.compression_threshold));
+    encoder.append_packet(pkt)?;
+
+    player.send(OrderedBytes::no_order(encoder.take().freeze()))?;
+    player.send(OrderedBytes::FLUSH)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use hyperion_proto::{PlayerInfo, TransferredPlayer};
+    use rkyv::util::AlignedVec;
+
+    use super::Limbo;
+    use crate::{
+        backend::BackendConnection,
+        data::{OrderedBytes, PlayerHandle},
+    };
+
+    fn connection(name: &str) -> (&'static BackendConnection, kanal::AsyncReceiver<AlignedVec>) {
+        let (server_sender, server_receiver) = kanal::bounded_async(16);
+        (
+            BackendConnection::leak(name.into(), server_sender),
+            server_receiver,
+        )
+    }
+
+    fn add_player(
+        connection: &'static BackendConnection,
+        id: u64,
+        in_play: bool,
+    ) -> kanal::AsyncReceiver<OrderedBytes> {
+        let (writer, reader) = kanal::bounded_async(16);
+        let route = Arc::new(tokio::sync::watch::Sender::new(connection));
+        let player = PlayerHandle::new(writer, route);
+
+        if in_play {
+            player.set_info(PlayerInfo {
+                address: None,
+                compression_threshold: 256,
+                player: TransferredPlayer {
+                    uuid: u128::from(id),
+                    username: format!("player{id}"),
+                    protocol_version: 763,
+                    server_address: "localhost".to_owned(),
+                    server_port: 25565,
+                },
+            });
+        }
+
+        connection.player_registry.pin().insert(id, player);
+        reader
+    }
+
+    #[tokio::test]
+    async fn players_wait_and_are_reattached() {
+        let limbo = Limbo::new("lobby");
+        let (lost, _) = connection("lobby");
+
+        let waiting_reader = add_player(lost, 1, true);
+        let _login_reader = add_player(lost, 2, false);
+
+        limbo.enter(lost);
+
+        // the waiting message and a flush
+        assert_eq!(waiting_reader.len(), 2);
+
+        assert_eq!(lost.player_count(), 0);
+        assert_eq!(limbo.connection.player_count(), 1);
+
+        let waiting = limbo.connection.player_registry.pin();
+        let player = waiting.get(&1).unwrap();
+        assert!(std::ptr::eq(player.backend(), limbo.connection));
+        drop(waiting);
+
+        let (restarted, server_receiver) = connection("lobby");
+        limbo.leave(restarted);
+
+        assert_eq!(limbo.connection.player_count(), 0);
+        assert_eq!(restarted.player_count(), 1);
+        assert_eq!(server_receiver.len(), 1);
+
+        let registry = restarted.player_registry.pin();
+        let player = registry.get(&1).unwrap();
+        assert!(std::ptr::eq(player.backend(), restarted));
+    }
+}
@@ -1,7 +1,10 @@
 use std::{fmt::Debug, net::SocketAddr, path::PathBuf};
 
-use clap::Parser;
-use hyperion_proxy::run_proxy;
+use clap::{Parser, ValueEnum};
+use hyperion_proxy::{
+    backend::{Backend, Backends, FirstAvailable, LeastPlayers, RoundRobin, RoutingPolicy},
+    run_proxy,
+};
 use tokio::net::TcpListener;
 #[cfg(unix)]
 use tokio::net::UnixListener;
@@ -20,9 +23,31 @@ struct Params {
     /// - A Unix domain socket path like "/tmp/minecraft.sock" (Unix only)
     proxy_addr: String,
 
-    /// The address of the target Minecraft game server to proxy from/to
+    /// The address of a game server to proxy from/to, optionally prefixed with a name, e.g.
+    /// `lobby=127.0.0.1:35565`. Can be given multiple times; servers use the name to transfer
+    /// players to each other. Unnamed servers are named after their address.
     #[clap(short, long, default_value = "127.0.0.1:35565")]
-    server: String,
+    server: Vec<String>,
+
+    /// How new players are assigned to one of the servers
+    #[clap(long, value_enum, default_value_t = Routing::FirstAvailable)]
+    routing: Routing,
+
+    /// Expect a PROXY protocol (v1 or v2) header on every client connection, e.g. when running
+    /// behind `HAProxy`. The address in the header is forwarded to the game server instead of
+    /// the address of the load balancer.
+    #[clap(long)]
+    proxy_protocol: bool,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Routing {
+    /// The first server in the order given that is up, e.g. a lobby with fallbacks
+    FirstAvailable,
+    /// Cycle through all servers that are up
+    RoundRobin,
+    /// The server that is up and has the fewest players
+    LeastPlayers,
 }
 
 #[derive(Debug)]
@@ -83,32 +108,60 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let proxy_addr = ProxyAddress::parse(&params.proxy_addr)?;
 
-    let server_addr: SocketAddr = tokio::net::lookup_host(&params.server)
-        .await?
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("Could not resolve hostname: {}", params.server))?;
+    let mut backends = Vec::new();
+
+    for server in &params.server {
+        let (name, address) = server.split_once('=').unwrap_or((server, server));
+
+        let server_addr: SocketAddr = tokio::net::lookup_host(address)
+            .await?
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Could not resolve hostname: {address}"))?;
+
+        backends.push(Backend::new(name, server_addr));
+    }
 
     let login_help = "~ The address to connect to".dimmed();
 
     info!("Starting Hyperion Proxy");
     info!("📡 Public proxy address: {proxy_addr} {login_help}",);
 
+    let proxy_protocol = params.proxy_protocol;
+
     let server_help = "~ The event server internal address".dimmed();
-    info!("👾 Internal server address: tcp://{server_addr} {server_help}");
+    for backend in &backends {
+        info!(
+            "👾 Internal server address: {} tcp://{} {server_help}",
+            backend.name(),
+            backend.address()
+        );
+    }
+
+    let backends = Backends::new(backends);
+
+    let routing: Box<dyn RoutingPolicy> = match params.routing {
+        Routing::FirstAvailable => Box::new(FirstAvailable),
+        Routing::RoundRobin => Box::new(RoundRobin::default()),
+        Routing::LeastPlayers => Box::new(LeastPlayers),
+    };
 
     let handle = tokio::spawn(async move {
         match &proxy_addr {
             ProxyAddress::Tcp(addr) => {
                 let listener = TcpListener::bind(addr).await.unwrap();
                 let socket = NoDelayTcpListener { listener };
-                run_proxy(socket, server_addr).await.unwrap();
+                run_proxy(socket, backends, routing, proxy_protocol)
+                    .await
+                    .unwrap();
             }
             #[cfg(unix)]
             ProxyAddress::Unix(path) => {
                 // remove file if already exists
                 let _unused = tokio::fs::remove_file(path).await;
                 let listener = UnixListener::bind(path).unwrap();
-                run_proxy(listener, server_addr).await.unwrap();
+                run_proxy(listener, backends, routing, proxy_protocol)
+                    .await
+                    .unwrap();
             }
         }
     });
@@ -1,24 +1,24 @@
 //! Player connection handling and packet processing.
 
-use std::io::IoSlice;
+use std::{io::IoSlice, net::SocketAddr};
 
 use hyperion_proto::{
-    ChunkPosition, PlayerConnect, PlayerDisconnect, PlayerDisconnectReason, PlayerPackets,
+    PeerAddress, PlayerConnect, PlayerDisconnect, PlayerDisconnectReason, PlayerPackets,
     ProxyToServerMessage,
 };
 use rkyv::ser::allocator::Arena;
-use rustc_hash::FxBuildHasher;
 use tokio::{
-    io::{AsyncReadExt, AsyncWrite},
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
     task::JoinHandle,
 };
 use tracing::{info, info_span, instrument, warn};
 
 use crate::{
-    ShutdownType,
+    backend::Route,
     cache::ExclusionsManager,
-    data::{OrderedBytes, PlayerHandle},
-    server_sender::ServerSender,
+    data::OrderedBytes,
+    encryption::{PacketDecryptor, PacketEncryptor, SharedKey},
+    proxy_protocol,
     util::AsyncWriteVectoredExt,
 };
 
@@ -32,15 +32,23 @@ const DEFAULT_READ_BUFFER_SIZE: usize = 8 * 1024;
 /// 2. A writer task that sends outgoing packets to the player.
 ///
 /// It also handles player disconnection and shutdown scenarios.
+///
+/// Packets from the player are forwarded to the backend in `route`, which changes when the player
+/// is transferred.
+///
+/// If `proxy_protocol` is set, the connection must start with a PROXY protocol header, whose
+/// source address replaces `peer_address` when telling the server about the player.
 #[instrument(skip_all, fields(player_id = player_id))]
+#[expect(clippy::too_many_arguments, reason = "todo; refactor")]
 pub fn initiate_player_connection(
     socket: impl tokio::io::AsyncRead + AsyncWrite + Send + 'static,
-    mut shutdown_signal: tokio::sync::watch::Receiver<Option<ShutdownType>>,
+    peer_address: Option<SocketAddr>,
+    proxy_protocol: bool,
+    mut shutdown_signal: tokio::sync::watch::Receiver<bool>,
     player_id: u64,
     incoming_packet_receiver: kanal::AsyncReceiver<OrderedBytes>,
-    server_sender: ServerSender,
-    player_registry: &'static papaya::HashMap<u64, PlayerHandle, FxBuildHasher>,
-    player_positions: &'static papaya::HashMap<u64, ChunkPosition, FxBuildHasher>,
+    encryption_key: SharedKey,
+    route: Route,
 ) -> JoinHandle<()> {
     let span = info_span!("player_connection", player_id);
     let _enter = span.enter();
@@ -53,24 +61,42 @@ pub fn initiate_player_connection(
 
     // Task for handling incoming packets (player -> proxy)
     let mut packet_reader_task = tokio::spawn({
-        let server_sender = server_sender.clone();
+        let route = route.clone();
         async move {
             let mut read_buffer = Vec::new();
             let player_stream_id = player_id;
 
+            let address = if proxy_protocol {
+                match proxy_protocol::read_header(&mut socket_reader).await {
+                    // fall back to the load balancer's address for LOCAL connections
+                    Ok(address) => address.or(peer_address),
+                    Err(e) => {
+                        warn!("invalid PROXY protocol header from {peer_address:?}: {e:?}");
+                        return;
+                    }
+                }
+            } else {
+                peer_address
+            };
+
             let connect = rkyv::to_bytes::<rkyv::rancor::Error>(
                 &ProxyToServerMessage::PlayerConnect(PlayerConnect {
                     stream: player_stream_id,
+                    address: address.map(PeerAddress::from),
+                    transfer: None,
                 }),
             )
             .unwrap();
 
-            if let Err(e) = server_sender.send(connect).await {
+            let backend = *route.borrow();
+
+            if let Err(e) = backend.server_sender.send(connect).await {
                 warn!("failed to send player connect to server: {e}");
                 return;
             }
 
             let mut arena = Arena::new();
+            let mut decryptor = None;
 
             loop {
                 // Ensure the buffer has enough capacity
@@ -89,6 +115,19 @@ pub fn initiate_player_connection(
                     return;
                 }
 
+                // the client sends nothing after its encryption response until it got the login
+                // success, and the server sets the key before sending that. So the read with the
+                // encryption response is the last plain one and every later read is encrypted.
+                if decryptor.is_none()
+                    && let Some(key) = encryption_key.get()
+                {
+                    decryptor = Some(PacketDecryptor::new(key));
+                }
+
+                if let Some(decryptor) = &mut decryptor {
+                    decryptor.decrypt(&mut read_buffer);
+                }
+
                 let player_packets = ProxyToServerMessage::PlayerPackets(PlayerPackets {
                     stream: player_id,
                     data: &read_buffer,
@@ -102,7 +141,9 @@ pub fn initiate_player_connection(
 
                 read_buffer.clear();
 
-                if let Err(e) = server_sender.send(aligned_vec).await {
+                let backend = *route.borrow();
+
+                if let Err(e) = backend.server_sender.send(aligned_vec).await {
                     warn!("Error forwarding player packets to server: {e:?}");
                     return;
                 }
@@ -119,6 +160,15 @@ pub fn initiate_player_connection(
                 return;
             }
 
+            if outgoing_packet.is_enable_encryption() {
+                let Ok(key) = <[u8; 16]>::try_from(outgoing_packet.data.as_ref()) else {
+                    warn!("Received invalid encryption key for player");
+                    return;
+                };
+                packet_writer.enable_encryption(&key);
+                continue;
+            }
+
             if outgoing_packet.is_flush() {
                 let time_start = std::time::Instant::now();
                 if let Err(e) = packet_writer.flush_pending_packets().await {
@@ -137,7 +187,10 @@ pub fn initiate_player_connection(
 
     tokio::task::spawn(async move {
         let shutdown_received = async move {
-            shutdown_signal.wait_for(Option::is_some).await.unwrap();
+            shutdown_signal
+                .wait_for(|shutdown| *shutdown)
+                .await
+                .unwrap();
         };
 
         tokio::select! {
@@ -150,6 +203,8 @@ pub fn initiate_player_connection(
                 info!("Player disconnected because writer task finished: {player_id:?}");
                 packet_reader_task.abort();
 
+                let backend = *route.borrow();
+
                 let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
                     &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
                         stream: player_id,
@@ -157,7 +212,7 @@ pub fn initiate_player_connection(
                     }),
                 ).unwrap();
 
-                if let Err(e) = server_sender.send(disconnect).await {
+                if let Err(e) = backend.server_sender.send(disconnect).await {
                     warn!("failed to send player disconnect to server: {e}");
                 }
             },
@@ -165,6 +220,7 @@ pub fn initiate_player_connection(
                 info!("Player disconnected because reader task finished: {player_id:?}");
                 packet_writer_task.abort();
 
+                let backend = *route.borrow();
 
                 let disconnect = rkyv::to_bytes::<rkyv::rancor::Error>(
                     &ProxyToServerMessage::PlayerDisconnect(PlayerDisconnect {
@@ -172,14 +228,14 @@ pub fn initiate_player_connection(
                         reason: PlayerDisconnectReason::LostConnection,
                     })).unwrap();
 
-                if let Err(e) = server_sender.send(disconnect).await {
+                if let Err(e) = backend.server_sender.send(disconnect).await {
                     warn!("failed to send player disconnect to server: {e}");
                 }
 
-                let map_ref = player_registry.pin();
+                let map_ref = backend.player_registry.pin();
                 map_ref.remove(&player_id);
 
-                let map_ref = player_positions.pin();
+                let map_ref = backend.player_positions.pin();
                 map_ref.remove(&player_id);
 
             }
@@ -193,6 +249,9 @@ struct PlayerPacketWriter<W> {
     player_id: u64,
     pending_packets: Vec<OrderedBytes>,
     io_vecs: Vec<IoSlice<'static>>,
+    encryptor: Option<PacketEncryptor>,
+    /// Buffer the packets are copied into when they need to be encrypted.
+    encrypted: Vec<u8>,
 }
 
 impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
@@ -203,9 +262,16 @@ impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
             player_id,
             pending_packets: Vec::new(),
             io_vecs: vec![],
+            encryptor: None,
+            encrypted: Vec::new(),
         }
     }
 
+    /// Encrypts all packets flushed from now on.
+    fn enable_encryption(&mut self, key: &[u8; 16]) {
+        self.encryptor = Some(PacketEncryptor::new(key));
+    }
+
     /// Adds a packet to the queue for writing.
     fn enqueue_packet(&mut self, packet: OrderedBytes) {
         self.pending_packets.push(packet);
@@ -233,7 +299,20 @@ impl<W: AsyncWrite + Unpin> PlayerPacketWriter<W> {
             }
         }
 
-        self.writer.write_vectored_all(&mut self.io_vecs).await?;
+        if let Some(encryptor) = &mut self.encryptor {
+            // encryption is stateful, so the shared broadcast bytes have to be copied
+            for iovec in &self.io_vecs {
+                self.encrypted.extend_from_slice(iovec);
+            }
+            self.io_vecs.clear();
+
+            encryptor.encrypt(&mut self.encrypted);
+            self.writer.write_all(&self.encrypted).await?;
+            self.encrypted.clear();
+        } else {
+            self.writer.write_vectored_all(&mut self.io_vecs).await?;
+        }
+
         self.pending_packets.clear();
         self.io_vecs.clear();
 
@@ -0,0 +1,225 @@
+//! Parsing of the [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt)
+//! header load balancers such as `HAProxy` put in front of a forwarded connection.
+//!
+//! Both the human-readable v1 and the binary v2 format are supported.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{Context, bail, ensure};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The signature every v2 header starts with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// The maximum length of a v1 header including the trailing CRLF.
+const V1_MAX_LENGTH: usize = 107;
+
+/// Reads a PROXY protocol header from the start of `reader`, consuming exactly the header.
+///
+/// Returns the source address of the original connection, or `None` if the header does not
+/// carry one (`UNKNOWN` in v1, `LOCAL` or an unsupported address family in v2). Errors if the
+/// connection does not start with a valid header, since a load balancer is expected to always
+/// send one.
+pub async fn read_header(
+    reader: &mut (impl AsyncRead + Unpin),
+) -> anyhow::Result<Option<SocketAddr>> {
+    let mut signature = [0; 12];
+    reader
+        .read_exact(&mut signature)
+        .await
+        .context("failed to read PROXY protocol header")?;
+
+    if signature == V2_SIGNATURE {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header).await?;
+
+        let [version_command, family, len @ ..] = header;
+        let len = usize::from(u16::from_be_bytes(len));
+
+        let mut addresses = vec![0; len];
+        reader.read_exact(&mut addresses).await?;
+
+        return parse_v2(version_command, family, &addresses);
+    }
+
+    ensure!(
+        signature.starts_with(b"PROXY "),
+        "connection does not start with a PROXY protocol header"
+    );
+
+    let mut line = signature.to_vec();
+
+    while !line.ends_with(b"\r\n") {
+        ensure!(
+            line.len() < V1_MAX_LENGTH,
+            "PROXY protocol v1 header is too long"
+        );
+        line.push(reader.read_u8().await?);
+    }
+
+    let line = std::str::from_utf8(&line).context("PROXY protocol v1 header is not ascii")?;
+    parse_v1(line)
+}
+
+/// Parses a v1 header such as `PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n`.
+fn parse_v1(line: &str) -> anyhow::Result<Option<SocketAddr>> {
+    let line = line
+        .strip_suffix("\r\n")
+        .context("PROXY protocol v1 header must end with CRLF")?;
+
+    let mut parts = line.split(' ');
+
+    ensure!(
+        parts.next() == Some("PROXY"),
+        "invalid PROXY protocol v1 header"
+    );
+
+    match parts.next() {
+        Some("TCP4" | "TCP6") => {}
+        Some("UNKNOWN") => return Ok(None),
+        protocol => bail!("unsupported PROXY protocol v1 protocol {protocol:?}"),
+    }
+
+    let (Some(source_ip), Some(_destination_ip), Some(source_port), Some(_destination_port), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        bail!("PROXY protocol v1 header must have exactly four addresses and ports");
+    };
+
+    let ip: IpAddr = source_ip.parse().context("invalid source address")?;
+    let port: u16 = source_port.parse().context("invalid source port")?;
+
+    Ok(Some(SocketAddr::new(ip, port)))
+}
+
+/// Parses the part of a v2 header following the signature.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> anyhow::Result<Option<SocketAddr>> {
+    ensure!(
+        version_command >> 4 == 2,
+        "unsupported PROXY protocol version {}",
+        version_command >> 4
+    );
+
+    match version_command & 0x0F {
+        // LOCAL: health checks from the load balancer itself
+        0x0 => return Ok(None),
+        // PROXY
+        0x1 => {}
+        command => bail!("unsupported PROXY protocol v2 command {command}"),
+    }
+
+    match family {
+        // TCP over IPv4
+        0x11 => {
+            let Some((ip, rest)) = addresses.split_first_chunk::<4>() else {
+                bail!("PROXY protocol v2 IPv4 addresses are truncated");
+            };
+            let Some((_destination_ip, rest)) = rest.split_first_chunk::<4>() else {
+                bail!("PROXY protocol v2 IPv4 addresses are truncated");
+            };
+            let Some((port, _)) = rest.split_first_chunk::<2>() else {
+                bail!("PROXY protocol v2 IPv4 addresses are truncated");
+            };
+
+            let ip = Ipv4Addr::from(*ip);
+            Ok(Some(SocketAddr::new(ip.into(), u16::from_be_bytes(*port))))
+        }
+        // TCP over IPv6
+        0x21 => {
+            let Some((ip, rest)) = addresses.split_first_chunk::<16>() else {
+                bail!("PROXY protocol v2 IPv6 addresses are truncated");
+            };
+            let Some((_destination_ip, rest)) = rest.split_first_chunk::<16>() else {
+                bail!("PROXY protocol v2 IPv6 addresses are truncated");
+            };
+            let Some((port, _)) = rest.split_first_chunk::<2>() else {
+                bail!("PROXY protocol v2 IPv6 addresses are truncated");
+            };
+
+            let ip = Ipv6Addr::from(*ip);
+            Ok(Some(SocketAddr::new(ip.into(), u16::from_be_bytes(*port))))
+        }
+        // UNSPEC, UDP or unix sockets; the spec says to fall back to the real connection
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::{V2_SIGNATURE, read_header};
+
+    async fn read(header: &[u8]) -> anyhow::Result<Option<SocketAddr>> {
+        let mut reader = header;
+        let result = read_header(&mut reader).await;
+
+        // the header must be consumed exactly, everything after belongs to the client
+        assert!(result.is_err() || reader == b"rest");
+
+        result
+    }
+
+    #[tokio::test]
+    async fn v1_tcp4() {
+        let addr = read(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nrest")
+            .await
+            .unwrap();
+        assert_eq!(addr, Some("192.168.0.1:56324".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v1_tcp6() {
+        let addr = read(b"PROXY TCP6 ::1 ::2 1234 25565\r\nrest")
+            .await
+            .unwrap();
+        assert_eq!(addr, Some("[::1]:1234".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v1_unknown() {
+        let addr = read(b"PROXY UNKNOWN\r\nrest").await.unwrap();
+        assert_eq!(addr, None);
+    }
+
+    #[tokio::test]
+    async fn v1_invalid() {
+        assert!(read(b"PROXY TCP4 192.168.0.1 56324\r\nrest").await.is_err());
+        assert!(
+            read(b"\x10\x00\xfb\x05\x09localhost\x63\xdd\x02")
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn v2_tcp4() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        header.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        header.extend_from_slice(&25000_u16.to_be_bytes());
+        header.extend_from_slice(&25565_u16.to_be_bytes());
+        header.extend_from_slice(b"rest");
+
+        let addr = read(&header).await.unwrap();
+        assert_eq!(addr, Some("10.0.0.1:25000".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn v2_local() {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+        header.extend_from_slice(b"rest");
+
+        let addr = read(&header).await.unwrap();
+        assert_eq!(addr, None);
+    }
+}
@@ -89,6 +89,10 @@ impl Module for RespawnModule {
                             bundle.add_packet(&pkt_xp).unwrap();
 
                             bundle.unicast(*connection).unwrap();
+
+                            let tick = query.compose.global().tick;
+                            query.anticheat.teleported(**position, tick);
+
                             query
                                 .compose
                                 .broadcast(&pkt_add_player, query.system)
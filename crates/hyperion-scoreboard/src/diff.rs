@@ -0,0 +1,324 @@
+//! The packets that turn what a player was sent into what they should see.
+
+use std::borrow::Cow;
+
+use valence_protocol::{
+    PacketEncoder, VarInt,
+    packets::play::{
+        self,
+        scoreboard_display_s2c::ScoreboardPosition,
+        scoreboard_objective_update_s2c::ObjectiveMode,
+        scoreboard_player_update_s2c::ScoreboardPlayerUpdateAction,
+        team_s2c::{Mode, TeamFlags},
+    },
+    text::IntoText,
+};
+
+use crate::{DisplaySlot, Objective, Scoreboard, Team};
+
+#[derive(Debug, PartialEq)]
+pub enum Change<'a> {
+    CreateObjective(&'a str, &'a Objective),
+    UpdateObjective(&'a str, &'a Objective),
+    RemoveObjective(&'a str),
+    SetScore {
+        objective: &'a str,
+        entry: &'a str,
+        score: i32,
+    },
+    RemoveScore {
+        objective: &'a str,
+        entry: &'a str,
+    },
+    /// An empty objective shows nothing.
+    Display(DisplaySlot, &'a str),
+    /// Also adds the members of the team.
+    CreateTeam(&'a str, &'a Team, Vec<&'a str>),
+    UpdateTeam(&'a str, &'a Team),
+    RemoveTeam(&'a str),
+    AddMembers(&'a str, Vec<&'a str>),
+    RemoveMembers(&'a str, Vec<&'a str>),
+}
+
+/// The changes from `old` to `new`, in the order they have to be sent in.
+pub fn diff<'a>(old: &'a Scoreboard, new: &'a Scoreboard) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+
+    for name in old.objectives.keys() {
+        if !new.objectives.contains_key(name) {
+            changes.push(Change::RemoveObjective(name));
+        }
+    }
+
+    for (name, objective) in &new.objectives {
+        let Some(previous) = old.objectives.get(name) else {
+            changes.push(Change::CreateObjective(name, objective));
+
+            for (entry, score) in objective.scores() {
+                changes.push(Change::SetScore {
+                    objective: name,
+                    entry,
+                    score,
+                });
+            }
+
+            continue;
+        };
+
+        if previous.display_name != objective.display_name
+            || previous.render_type != objective.render_type
+        {
+            changes.push(Change::UpdateObjective(name, objective));
+        }
+
+        for (entry, _) in previous.scores() {
+            if objective.score(entry).is_none() {
+                changes.push(Change::RemoveScore {
+                    objective: name,
+                    entry,
+                });
+            }
+        }
+
+        for (entry, score) in objective.scores() {
+            if previous.score(entry) != Some(score) {
+                changes.push(Change::SetScore {
+                    objective: name,
+                    entry,
+                    score,
+                });
+            }
+        }
+    }
+
+    for slot in DisplaySlot::ALL {
+        let displayed = new.displayed(slot);
+
+        // removing an objective already removes it from where it is shown
+        let cleared = old
+            .displayed(slot)
+            .is_some_and(|objective| !new.objectives.contains_key(objective));
+
+        if displayed != old.displayed(slot) && !(displayed.is_none() && cleared) {
+            changes.push(Change::Display(slot, displayed.unwrap_or_default()));
+        }
+    }
+
+    diff_teams(old, new, &mut changes);
+
+    changes
+}
+
+/// The changes to the teams from `old` to `new`. Entries have to leave their team before they
+/// join another one, so members are removed before any are added.
+fn diff_teams<'a>(old: &'a Scoreboard, new: &'a Scoreboard, changes: &mut Vec<Change<'a>>) {
+    for name in old.teams.keys() {
+        if new.teams.contains_key(name) {
+            let removed: Vec<&str> = old
+                .members(name)
+                .filter(|entry| new.team_of(entry) != Some(name))
+                .collect();
+
+            if !removed.is_empty() {
+                changes.push(Change::RemoveMembers(name, removed));
+            }
+        } else {
+            changes.push(Change::RemoveTeam(name));
+        }
+    }
+
+    for (name, team) in &new.teams {
+        let Some(previous) = old.teams.get(name) else {
+            changes.push(Change::CreateTeam(name, team, new.members(name).collect()));
+            continue;
+        };
+
+        if previous != team {
+            changes.push(Change::UpdateTeam(name, team));
+        }
+
+        let added: Vec<&str> = new
+            .members(name)
+            .filter(|entry| old.team_of(entry) != Some(name))
+            .collect();
+
+        if !added.is_empty() {
+            changes.push(Change::AddMembers(name, added));
+        }
+    }
+}
+
+fn text(text: &str) -> Cow<'static, valence_protocol::Text> {
+    text.to_owned().into_cow_text()
+}
+
+/// Adds the packet for `change` to `encoder`.
+pub fn write(change: &Change<'_>, encoder: &mut PacketEncoder) -> anyhow::Result<()> {
+    match change {
+        Change::CreateObjective(name, objective) | Change::UpdateObjective(name, objective) => {
+            let objective_display_name = objective.display_name.clone().into_text();
+            let render_type = objective.render_type;
+
+            let mode = if matches!(change, Change::CreateObjective(..)) {
+                ObjectiveMode::Create {
+                    objective_display_name,
+                    render_type,
+                }
+            } else {
+                ObjectiveMode::Update {
+                    objective_display_name,
+                    render_type,
+                }
+            };
+
+            encoder.append_packet(&play::ScoreboardObjectiveUpdateS2c {
+                objective_name: name,
+                mode,
+            })
+        }
+        Change::RemoveObjective(name) => {
+            encoder.append_packet(&play::ScoreboardObjectiveUpdateS2c {
+                objective_name: name,
+                mode: ObjectiveMode::Remove,
+            })
+        }
+        Change::SetScore {
+            objective,
+            entry,
+            score,
+        } => encoder.append_packet(&play::ScoreboardPlayerUpdateS2c {
+            entity_name: entry,
+            action: ScoreboardPlayerUpdateAction::Update {
+                objective_name: objective,
+                objective_score: VarInt(*score),
+            },
+        }),
+        Change::RemoveScore { objective, entry } => {
+            encoder.append_packet(&play::ScoreboardPlayerUpdateS2c {
+                entity_name: entry,
+                action: ScoreboardPlayerUpdateAction::Remove {
+                    objective_name: objective,
+                },
+            })
+        }
+        Change::Display(slot, objective) => {
+            let position = match slot {
+                DisplaySlot::List => ScoreboardPosition::List,
+                DisplaySlot::Sidebar => ScoreboardPosition::Sidebar,
+                DisplaySlot::BelowName => ScoreboardPosition::BelowName,
+            };
+
+            encoder.append_packet(&play::ScoreboardDisplayS2c {
+                position,
+                score_name: objective,
+            })
+        }
+        Change::CreateTeam(name, team, members) => encoder.append_packet(&play::TeamS2c {
+            team_name: name,
+            mode: Mode::CreateTeam {
+                team_display_name: text(&team.display_name),
+                friendly_flags: flags(team),
+                name_tag_visibility: team.name_tag_visibility,
+                collision_rule: team.collision_rule,
+                team_color: team.color,
+                team_prefix: text(&team.prefix),
+                team_suffix: text(&team.suffix),
+                entities: members.clone(),
+            },
+        }),
+        Change::UpdateTeam(name, team) => encoder.append_packet(&play::TeamS2c {
+            team_name: name,
+            mode: Mode::UpdateTeamInfo {
+                team_display_name: text(&team.display_name),
+                friendly_flags: flags(team),
+                name_tag_visibility: team.name_tag_visibility,
+                collision_rule: team.collision_rule,
+                team_color: team.color,
+                team_prefix: text(&team.prefix),
+                team_suffix: text(&team.suffix),
+            },
+        }),
+        Change::RemoveTeam(name) => encoder.append_packet(&play::TeamS2c {
+            team_name: name,
+            mode: Mode::RemoveTeam,
+        }),
+        Change::AddMembers(name, members) => encoder.append_packet(&play::TeamS2c {
+            team_name: name,
+            mode: Mode::AddEntities {
+                entities: members.clone(),
+            },
+        }),
+        Change::RemoveMembers(name, members) => encoder.append_packet(&play::TeamS2c {
+            team_name: name,
+            mode: Mode::RemoveEntities {
+                entities: members.clone(),
+            },
+        }),
+    }
+}
+
+fn flags(team: &Team) -> TeamFlags {
+    TeamFlags::new()
+        .with_friendly_fire(team.friendly_fire)
+        .with_see_invisible_teammates(team.see_invisible_teammates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, diff};
+    use crate::{DisplaySlot, ObjectiveRenderType, Scoreboard, Team};
+
+    #[test]
+    fn only_differences_are_sent() {
+        let mut old = Scoreboard::default();
+        old.set_objective("kills", "Kills", ObjectiveRenderType::Integer);
+        old.set_score("kills", "alice", 1);
+        old.set_score("kills", "bob", 2);
+        old.display(DisplaySlot::List, Some("kills"));
+
+        let mut new = old.clone();
+        new.set_score("kills", "alice", 5);
+        new.remove_score("kills", "bob");
+
+        assert_eq!(diff(&old, &new), [
+            Change::RemoveScore {
+                objective: "kills",
+                entry: "bob",
+            },
+            Change::SetScore {
+                objective: "kills",
+                entry: "alice",
+                score: 5,
+            },
+        ]);
+
+        assert!(diff(&new, &new).is_empty());
+
+        // removing the objective also clears the list
+        new.remove_objective("kills");
+        assert_eq!(diff(&old, &new), [Change::RemoveObjective("kills")]);
+    }
+
+    #[test]
+    fn members_leave_before_they_join() {
+        let mut old = Scoreboard::default();
+        old.set_team("blue", Team::new("Blue"));
+        old.set_team("red", Team::new("Red"));
+        old.join_team("red", "alice");
+
+        let mut new = old.clone();
+        new.join_team("blue", "alice");
+
+        assert_eq!(diff(&old, &new), [
+            Change::RemoveMembers("red", vec!["alice"]),
+            Change::AddMembers("blue", vec!["alice"]),
+        ]);
+
+        let new_player = diff(&Scoreboard::default(), &new);
+        assert_eq!(new_player.len(), 2);
+        assert!(matches!(
+            &new_player[0],
+            Change::CreateTeam("blue", _, members) if *members == ["alice"]
+        ));
+    }
+}
@@ -0,0 +1,554 @@
+//! Scoreboard objectives, sidebars and teams.
+//!
+//! The [`Scoreboard`] holds the objectives and teams every player sees, and a [`Sidebar`] on a
+//! player shows lines only that player sees. Each tick the scoreboard is compared with the one of
+//! the tick before once, and the packets for what changed are sent to every player. Only the
+//! sidebars are compared for each player on their own.
+
+use std::collections::BTreeMap;
+
+use flecs_ecs::prelude::*;
+use hyperion::{
+    net::{Compose, ConnectionId, DataBundle},
+    simulation::{PacketState, Player},
+};
+use tracing::error;
+pub use valence_protocol::packets::play::{
+    scoreboard_objective_update_s2c::ObjectiveRenderType,
+    team_s2c::{CollisionRule, NameTagVisibility, TeamColor},
+};
+use valence_protocol::{CompressionThreshold, PacketEncoder};
+
+mod diff;
+
+/// The most lines a sidebar can show.
+pub const MAX_SIDEBAR_LINES: usize = 15;
+
+/// The objective the [`Sidebar`] of a player is shown with.
+const SIDEBAR_OBJECTIVE: &str = "__sidebar";
+
+/// Where an objective is shown.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DisplaySlot {
+    /// Next to names in the tab list.
+    List,
+    Sidebar,
+    /// Below the name tags of players.
+    BelowName,
+}
+
+impl DisplaySlot {
+    pub const ALL: [Self; 3] = [Self::List, Self::Sidebar, Self::BelowName];
+}
+
+/// Scores of entries, which are usually player names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Objective {
+    pub display_name: String,
+    pub render_type: ObjectiveRenderType,
+    scores: BTreeMap<String, i32>,
+}
+
+impl Objective {
+    #[must_use]
+    pub fn score(&self, entry: &str) -> Option<i32> {
+        self.scores.get(entry).copied()
+    }
+
+    pub fn scores(&self) -> impl Iterator<Item = (&str, i32)> {
+        self.scores
+            .iter()
+            .map(|(entry, score)| (entry.as_str(), *score))
+    }
+}
+
+/// How the members of a team are shown and how they interact.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Team {
+    pub display_name: String,
+    /// Shown before the names of members.
+    pub prefix: String,
+    /// Shown after the names of members.
+    pub suffix: String,
+    pub color: TeamColor,
+    pub name_tag_visibility: NameTagVisibility,
+    pub collision_rule: CollisionRule,
+    pub friendly_fire: bool,
+    pub see_invisible_teammates: bool,
+}
+
+impl Team {
+    /// A team like a new vanilla team called `display_name`.
+    #[must_use]
+    pub fn new(display_name: impl Into<String>) -> Self {
+        Self {
+            display_name: display_name.into(),
+            prefix: String::new(),
+            suffix: String::new(),
+            color: TeamColor::White,
+            name_tag_visibility: NameTagVisibility::Always,
+            collision_rule: CollisionRule::Always,
+            friendly_fire: true,
+            see_invisible_teammates: false,
+        }
+    }
+}
+
+/// The objectives and teams all players see.
+///
+/// Only changes that make a difference count as changes, so setting a score to the score it
+/// already has each tick does not send anything.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct Scoreboard {
+    objectives: BTreeMap<String, Objective>,
+    displays: BTreeMap<DisplaySlot, String>,
+    teams: BTreeMap<String, Team>,
+    /// The team of every entry in a team.
+    members: BTreeMap<String, String>,
+    /// Incremented with every change.
+    revision: u64,
+}
+
+impl Scoreboard {
+    #[must_use]
+    pub fn objective(&self, name: &str) -> Option<&Objective> {
+        self.objectives.get(name)
+    }
+
+    /// Adds the objective `name`, or changes how it is shown if it exists.
+    pub fn set_objective(
+        &mut self,
+        name: impl Into<String>,
+        display_name: impl Into<String>,
+        render_type: ObjectiveRenderType,
+    ) {
+        let name = name.into();
+        let display_name = display_name.into();
+
+        if let Some(objective) = self.objectives.get_mut(&name) {
+            if objective.display_name == display_name && objective.render_type == render_type {
+                return;
+            }
+
+            objective.display_name = display_name;
+            objective.render_type = render_type;
+        } else {
+            self.objectives.insert(name, Objective {
+                display_name,
+                render_type,
+                scores: BTreeMap::new(),
+            });
+        }
+
+        self.revision += 1;
+    }
+
+    /// Removes the objective `name` with its scores and from where it is shown.
+    pub fn remove_objective(&mut self, name: &str) {
+        if self.objectives.remove(name).is_none() {
+            return;
+        }
+
+        self.displays.retain(|_, objective| objective != name);
+        self.revision += 1;
+    }
+
+    /// Sets the score of `entry` in the objective `objective`, if there is such an objective.
+    pub fn set_score(&mut self, objective: &str, entry: &str, score: i32) {
+        let Some(objective) = self.objectives.get_mut(objective) else {
+            return;
+        };
+
+        if objective.scores.get(entry) == Some(&score) {
+            return;
+        }
+
+        objective.scores.insert(entry.to_owned(), score);
+        self.revision += 1;
+    }
+
+    pub fn remove_score(&mut self, objective: &str, entry: &str) {
+        let Some(objective) = self.objectives.get_mut(objective) else {
+            return;
+        };
+
+        if objective.scores.remove(entry).is_some() {
+            self.revision += 1;
+        }
+    }
+
+    /// Shows the objective `objective` in `slot`, or nothing if `None`.
+    pub fn display(&mut self, slot: DisplaySlot, objective: Option<&str>) {
+        let current = self.displays.get(&slot).map(String::as_str);
+
+        if current == objective {
+            return;
+        }
+
+        match objective {
+            Some(objective) => self.displays.insert(slot, objective.to_owned()),
+            None => self.displays.remove(&slot),
+        };
+
+        self.revision += 1;
+    }
+
+    /// The objective shown in `slot`.
+    #[must_use]
+    pub fn displayed(&self, slot: DisplaySlot) -> Option<&str> {
+        self.displays.get(&slot).map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn team(&self, name: &str) -> Option<&Team> {
+        self.teams.get(name)
+    }
+
+    /// Adds the team `name`, or changes it if it exists.
+    pub fn set_team(&mut self, name: impl Into<String>, team: Team) {
+        let name = name.into();
+
+        if self.teams.get(&name) == Some(&team) {
+            return;
+        }
+
+        self.teams.insert(name, team);
+        self.revision += 1;
+    }
+
+    /// Removes the team `name`, whose members are then in no team.
+    pub fn remove_team(&mut self, name: &str) {
+        if self.teams.remove(name).is_none() {
+            return;
+        }
+
+        self.members.retain(|_, team| team != name);
+        self.revision += 1;
+    }
+
+    /// Puts `entry` in the team `team`, taking it out of the team it was in before. Does
+    /// nothing if there is no such team.
+    pub fn join_team(&mut self, team: &str, entry: &str) {
+        if !self.teams.contains_key(team) || self.team_of(entry) == Some(team) {
+            return;
+        }
+
+        self.members.insert(entry.to_owned(), team.to_owned());
+        self.revision += 1;
+    }
+
+    pub fn leave_team(&mut self, entry: &str) {
+        if self.members.remove(entry).is_some() {
+            self.revision += 1;
+        }
+    }
+
+    /// The name of the team `entry` is in.
+    #[must_use]
+    pub fn team_of(&self, entry: &str) -> Option<&str> {
+        self.members.get(entry).map(String::as_str)
+    }
+
+    /// The members of the team `team`.
+    pub fn members<'a>(&'a self, team: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.members
+            .iter()
+            .filter(move |(_, other)| *other == team)
+            .map(|(entry, _)| entry.as_str())
+    }
+
+    /// Removes the scores of `entry` and takes it out of its team, like when a player leaves.
+    pub fn remove_entry(&mut self, entry: &str) {
+        let objectives: Vec<String> = self
+            .objectives
+            .iter()
+            .filter(|(_, objective)| objective.scores.contains_key(entry))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for objective in objectives {
+            self.remove_score(&objective, entry);
+        }
+
+        self.leave_team(entry);
+    }
+}
+
+/// Lines in the sidebar of a single player, from the top down. Only the first
+/// [`MAX_SIDEBAR_LINES`] lines are shown.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sidebar {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+impl Sidebar {
+    /// A scoreboard with only the objective the sidebar is shown with.
+    fn scoreboard(&self) -> Scoreboard {
+        let mut scoreboard = Scoreboard::default();
+
+        scoreboard.set_objective(
+            SIDEBAR_OBJECTIVE,
+            self.title.clone(),
+            ObjectiveRenderType::Integer,
+        );
+
+        let lines = self.lines.iter().take(MAX_SIDEBAR_LINES);
+        let len = lines.len();
+
+        for (index, line) in lines.enumerate() {
+            // entries have to be unique, so each line ends with a different invisible color code
+            let entry = format!("{line}§{index:x}");
+
+            #[expect(
+                clippy::cast_possible_truncation,
+                clippy::cast_possible_wrap,
+                reason = "there are at most 15 lines"
+            )]
+            let score = (len - index) as i32;
+
+            scoreboard.set_score(SIDEBAR_OBJECTIVE, &entry, score);
+        }
+
+        scoreboard
+    }
+}
+
+/// The packets that turn `old` into `new`, without what is shown in the sidebar, which depends on
+/// the player.
+fn encode(old: &Scoreboard, new: &Scoreboard, threshold: CompressionThreshold) -> Vec<u8> {
+    let mut encoder = PacketEncoder::new();
+    encoder.set_compression(threshold);
+
+    for change in diff::diff(old, new) {
+        if matches!(change, diff::Change::Display(DisplaySlot::Sidebar, _)) {
+            continue;
+        }
+
+        if let Err(e) = diff::write(&change, &mut encoder) {
+            error!("failed to write a scoreboard change: {e}");
+        }
+    }
+
+    encoder.take().to_vec()
+}
+
+/// The [`Scoreboard`] as players were sent it, updated once per tick.
+#[derive(Component, Default)]
+struct Published {
+    scoreboard: Scoreboard,
+    /// The revision of the [`Scoreboard`] that was published.
+    revision: Option<u64>,
+    /// The packets for the changes since the tick before.
+    changes: Vec<u8>,
+    /// The packets for the whole scoreboard, for players who were sent nothing yet.
+    full: Vec<u8>,
+}
+
+/// What a player was sent so far.
+#[derive(Component, Default)]
+struct Sent {
+    /// Whether the player was sent the [`Published`] scoreboard.
+    scoreboard: bool,
+    sidebar: Option<Sidebar>,
+    /// The objective shown in the sidebar of the player.
+    slot: Option<String>,
+}
+
+#[derive(Component)]
+pub struct ScoreboardModule;
+
+impl Module for ScoreboardModule {
+    fn module(world: &World) {
+        world.component::<Scoreboard>();
+        world.component::<Sidebar>();
+        world.component::<Sent>();
+        world.component::<Published>();
+
+        world.set(Scoreboard::default());
+        world.set(Published::default());
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Sent)>();
+
+        system!(
+            "publish_scoreboard",
+            world,
+            &Compose($),
+            &Scoreboard($),
+            &mut Published($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each(|(compose, scoreboard, published)| {
+            published.changes.clear();
+
+            if published.revision == Some(scoreboard.revision) {
+                return;
+            }
+
+            let threshold = compose.global().shared.compression_threshold;
+
+            published.changes = encode(&published.scoreboard, scoreboard, threshold);
+            published.full = encode(&Scoreboard::default(), scoreboard, threshold);
+            published.scoreboard.clone_from(scoreboard);
+            published.revision = Some(scoreboard.revision);
+        });
+
+        system!(
+            "send_scoreboards",
+            world,
+            &Compose($),
+            &Published($),
+            &mut Sent,
+            ?&Sidebar,
+            &ConnectionId,
+        )
+        .with_enum(PacketState::Play)
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (compose, published, sent, sidebar, stream)| {
+            let slot = match sidebar {
+                Some(_) => Some(SIDEBAR_OBJECTIVE),
+                None => published.scoreboard.displayed(DisplaySlot::Sidebar),
+            };
+
+            if sent.scoreboard
+                && published.changes.is_empty()
+                && sent.sidebar.as_ref() == sidebar
+                && sent.slot.as_deref() == slot
+            {
+                return;
+            }
+
+            let mut bundle = DataBundle::new(compose, it.system());
+
+            if sent.scoreboard {
+                bundle.add_raw(&published.changes);
+            } else {
+                bundle.add_raw(&published.full);
+            }
+
+            let mut encoder = PacketEncoder::new();
+            encoder.set_compression(compose.global().shared.compression_threshold);
+
+            if sent.sidebar.as_ref() != sidebar {
+                let old = sent
+                    .sidebar
+                    .as_ref()
+                    .map(Sidebar::scoreboard)
+                    .unwrap_or_default();
+                let new = sidebar.map(Sidebar::scoreboard).unwrap_or_default();
+
+                for change in diff::diff(&old, &new) {
+                    if let Err(e) = diff::write(&change, &mut encoder) {
+                        error!("failed to write a sidebar change: {e}");
+                    }
+                }
+            }
+
+            // the objective has to exist before it is shown
+            if sent.slot.as_deref() != slot {
+                let change = diff::Change::Display(DisplaySlot::Sidebar, slot.unwrap_or_default());
+
+                if let Err(e) = diff::write(&change, &mut encoder) {
+                    error!("failed to write a sidebar change: {e}");
+                }
+            }
+
+            bundle.add_raw(&encoder.take());
+
+            if let Err(e) = bundle.unicast(*stream) {
+                error!("failed to send the scoreboard: {e}");
+            }
+
+            sent.scoreboard = true;
+            sent.sidebar = sidebar.cloned();
+            sent.slot = slot.map(str::to_owned);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::CompressionThreshold;
+
+    use super::{DisplaySlot, ObjectiveRenderType, Scoreboard, Sidebar, Team, encode};
+
+    #[test]
+    fn unchanged_state_is_no_change() {
+        let mut scoreboard = Scoreboard::default();
+
+        scoreboard.set_objective("kills", "Kills", ObjectiveRenderType::Integer);
+        scoreboard.set_score("kills", "alice", 3);
+        scoreboard.set_team("red", Team::new("Red"));
+        scoreboard.join_team("red", "alice");
+
+        let revision = scoreboard.revision;
+
+        scoreboard.set_objective("kills", "Kills", ObjectiveRenderType::Integer);
+        scoreboard.set_score("kills", "alice", 3);
+        scoreboard.set_team("red", Team::new("Red"));
+        scoreboard.join_team("red", "alice");
+
+        assert_eq!(scoreboard.revision, revision);
+
+        scoreboard.set_score("kills", "alice", 4);
+        assert_eq!(scoreboard.revision, revision + 1);
+
+        // there is no such team
+        scoreboard.join_team("blue", "alice");
+        assert_eq!(scoreboard.team_of("alice"), Some("red"));
+    }
+
+    #[test]
+    fn removing_objectives_and_teams_cleans_up() {
+        let mut scoreboard = Scoreboard::default();
+
+        scoreboard.set_objective("kills", "Kills", ObjectiveRenderType::Integer);
+        scoreboard.display(DisplaySlot::List, Some("kills"));
+        scoreboard.set_team("red", Team::new("Red"));
+        scoreboard.join_team("red", "alice");
+        scoreboard.join_team("red", "bob");
+
+        scoreboard.remove_objective("kills");
+        assert_eq!(scoreboard.displayed(DisplaySlot::List), None);
+
+        scoreboard.leave_team("bob");
+        assert_eq!(scoreboard.members("red").collect::<Vec<_>>(), ["alice"]);
+
+        scoreboard.remove_team("red");
+        assert_eq!(scoreboard.team_of("alice"), None);
+    }
+
+    #[test]
+    fn sidebar_lines_are_ordered_from_the_top() {
+        let sidebar = Sidebar {
+            title: "Tag".to_owned(),
+            lines: vec!["first".to_owned(), "first".to_owned(), "last".to_owned()],
+        };
+
+        let scoreboard = sidebar.scoreboard();
+        let objective = scoreboard.objective(super::SIDEBAR_OBJECTIVE).unwrap();
+
+        assert_eq!(objective.score("first§0"), Some(3));
+        assert_eq!(objective.score("first§1"), Some(2));
+        assert_eq!(objective.score("last§2"), Some(1));
+    }
+
+    #[test]
+    fn the_sidebar_slot_is_left_to_each_player() {
+        let mut shown = Scoreboard::default();
+        shown.set_objective("kills", "Kills", ObjectiveRenderType::Integer);
+
+        let hidden = shown.clone();
+        shown.display(DisplaySlot::Sidebar, Some("kills"));
+
+        let threshold = CompressionThreshold(-1);
+        assert_eq!(
+            encode(&Scoreboard::default(), &shown, threshold),
+            encode(&Scoreboard::default(), &hidden, threshold)
+        );
+        assert!(encode(&hidden, &shown, threshold).is_empty());
+    }
+}
@@ -32,6 +32,17 @@ impl<'a> From<&'a str> for Text<'a> {
     }
 }
 
+impl From<String> for Text<'_> {
+    fn from(s: String) -> Self {
+        Text {
+            content: TextContent::Text {
+                text: Cow::Owned(s),
+            },
+            ..Text::new("")
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
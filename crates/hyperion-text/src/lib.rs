@@ -3,12 +3,11 @@ use std::{borrow::Cow, io::Write};
 use serde::{Deserialize, Serialize};
 use valence_protocol::{Bounded, Encode, anyhow, anyhow::Context};
 
-use crate::{
-    color::Color,
+pub use crate::{
+    color::{Color, NamedColor, RgbColor},
     event::{ClickEvent, HoverEvent},
-    font::Font,
-    scoreboard::ScoreboardValueContent,
 };
+use crate::{font::Font, scoreboard::ScoreboardValueContent};
 
 mod color;
 mod event;
@@ -0,0 +1,548 @@
+//! Configuration for the server.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Debug, Display},
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, bail};
+use flecs_ecs::macros::Component;
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use crate::{simulation::anticheat::AntiCheatConfig, util::mojang::ApiProvider};
+
+pub mod reload;
+
+/// The settings that can be changed while the server is running, including everything nested in
+/// them. Changing anything else needs a restart.
+const HOT_RELOADABLE: &[&str] = &[
+    "anticheat",
+    "border_diameter",
+    "favicon",
+    "max_players",
+    "motds",
+    "sample_players",
+    "server_desc",
+    "simulation_distance",
+    "spawn",
+    "view_distance",
+    "whitelist",
+];
+
+/// The configuration for the server representing a `toml` file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Component)]
+pub struct Config {
+    pub border_diameter: Option<f64>,
+    pub max_players: i32,
+    pub view_distance: i16,
+    pub simulation_distance: i32,
+    /// The MOTD shown in the server list.
+    pub server_desc: String,
+    /// The MOTD by the hostname players connect with, e.g. `"play.example.com"`. Other hostnames
+    /// get [`Self::server_desc`].
+    #[serde(default)]
+    pub motds: BTreeMap<String, String>,
+    /// A 64x64 PNG shown next to the server in the server list. The Hyperion logo is shown if this
+    /// is not set.
+    pub favicon: Option<PathBuf>,
+    /// How many online players are listed when hovering over the player count in the server list.
+    #[serde(default = "default_sample_players")]
+    pub sample_players: usize,
+    pub spawn: Spawn,
+    /// Whether players have to authenticate with the session server before joining.
+    #[serde(default)]
+    pub online_mode: bool,
+    /// Whether only players on the whitelist can join.
+    #[serde(default)]
+    pub whitelist: bool,
+    /// The base URL of the session server used to verify players in online mode.
+    #[serde(default = "default_session_server")]
+    pub session_server: String,
+    /// How often changed chunks are written to the world's region files, in seconds. `0`
+    /// disables autosaving; the world is still saved on shutdown.
+    #[serde(default = "default_autosave_interval_secs")]
+    pub autosave_interval_secs: u64,
+    /// A datapack, or the extracted vanilla server jar, to load recipes from. Only the built-in
+    /// recipes can be crafted if this is not set.
+    pub datapack: Option<PathBuf>,
+    #[serde(default)]
+    pub database: Database,
+    /// Which cheats are checked for and what happens to players who use them.
+    #[serde(default)]
+    pub anticheat: AntiCheatConfig,
+}
+
+fn default_session_server() -> String {
+    ApiProvider::MOJANG_SESSION_SERVER.to_owned()
+}
+
+const fn default_autosave_interval_secs() -> u64 {
+    5 * 60
+}
+
+const fn default_sample_players() -> usize {
+    12
+}
+
+/// Where the [`LocalDb`](crate::storage::LocalDb) is stored and how large it may grow.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Database {
+    /// The directory the database files are in.
+    pub path: PathBuf,
+    /// The size of the memory map when the server starts, in MiB. It doubles whenever the
+    /// database is full.
+    pub map_size_mib: usize,
+    /// The size in MiB the memory map does not grow beyond.
+    pub max_map_size_mib: usize,
+    /// How many named databases, like `uuid-to-skins`, can be created.
+    pub max_dbs: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Component)]
+pub struct Spawn {
+    pub kind: Radius,
+    pub radius: i32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radius {
+    Chebyshev,
+    Euclidean,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            border_diameter: Some(100.0),
+            max_players: 10_000,
+            view_distance: 32,
+            simulation_distance: 10,
+            server_desc: "Hyperion Test Server".to_owned(),
+            motds: BTreeMap::new(),
+            favicon: None,
+            sample_players: default_sample_players(),
+            spawn: Spawn::default(),
+            online_mode: false,
+            whitelist: false,
+            session_server: default_session_server(),
+            autosave_interval_secs: default_autosave_interval_secs(),
+            datapack: None,
+            database: Database::default(),
+            anticheat: AntiCheatConfig::default(),
+        }
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("db").join("heed.mdb"),
+            map_size_mib: 64,
+            max_map_size_mib: 4096,
+            max_dbs: 16,
+        }
+    }
+}
+
+impl Default for Spawn {
+    fn default() -> Self {
+        Self {
+            radius: 1000,
+            kind: Radius::Chebyshev,
+            x: 0,
+            y: 64,
+            z: 0,
+        }
+    }
+}
+
+impl Spawn {
+    /// The center of the spawn area.
+    #[must_use]
+    pub const fn position(&self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+}
+
+impl Config {
+    /// The interval between autosaves, or `None` if autosaving is disabled.
+    #[must_use]
+    pub const fn autosave_interval(&self) -> Option<Duration> {
+        if self.autosave_interval_secs == 0 {
+            None
+        } else {
+            Some(Duration::from_secs(self.autosave_interval_secs))
+        }
+    }
+
+    /// The MOTD for players connecting with the hostname `host`.
+    #[must_use]
+    pub fn motd(&self, host: &str) -> &str {
+        self.motds
+            .iter()
+            .find(|(motd_host, _)| motd_host.eq_ignore_ascii_case(host))
+            .map_or(&self.server_desc, |(_, motd)| motd)
+    }
+
+    #[instrument]
+    pub fn load<P>(path: P) -> anyhow::Result<Self>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        info!("loading configuration file");
+
+        if path.as_ref().exists() {
+            let mut file = File::open(path)?;
+            let mut contents = String::default();
+            file.read_to_string(&mut contents)?;
+            return Self::parse(&contents);
+        }
+
+        info!("configuration file not found, using defaults");
+
+        // make required folders
+        if let Some(parent) = path.as_ref().parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                // this might happen on a read-only filesystem (i.e.,
+                // when running on a CI, profiling in Instruments, etc.)
+                warn!(
+                    "failed to create parent directories for {:?}: {}, using defaults",
+                    path.as_ref(),
+                    e
+                );
+                return Ok(Self::default());
+            }
+        };
+
+        // write default config to file
+        let default_config = Self::default();
+        std::fs::write(&path, toml::to_string(&default_config)?.as_bytes())?;
+
+        info!("wrote default configuration to {:?}", path.as_ref());
+
+        Ok(Self::default())
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let config = toml::from_str::<Self>(contents)?;
+        Ok(config)
+    }
+
+    /// Writes the configuration to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let contents = toml::to_string(self)?;
+        std::fs::write(path, contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the value `key` has in this configuration to the file at `path`. Unlike
+    /// [`Self::save`], the rest of the file stays as it is, including its comments.
+    pub fn save_setting(&self, path: impl AsRef<Path>, key: &str) -> anyhow::Result<()> {
+        let path = path.as_ref();
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+
+        let contents = set_in_document(&contents, key, self.get(key)?.as_ref())?;
+        std::fs::write(path, contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// The value of `key` as TOML, e.g. `spawn.x`. Returns `None` if the setting is not set.
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<toml::Value>> {
+        let settings = self.settings()?;
+        Self::check_key(key, &settings)?;
+        Ok(settings.get(key).cloned())
+    }
+
+    /// All settings as TOML by their key, e.g. `spawn.x`.
+    pub fn settings(&self) -> anyhow::Result<BTreeMap<String, toml::Value>> {
+        let table = self.table()?;
+
+        let mut settings = BTreeMap::new();
+        flatten("", table, &mut settings);
+
+        Ok(settings)
+    }
+
+    /// A copy of the configuration with `key` set to `value`, e.g. `view_distance` to `16`.
+    ///
+    /// `value` is parsed as TOML, falling back to a plain string.
+    pub fn with(&self, key: &str, value: &str) -> anyhow::Result<Self> {
+        Self::check_key(key, &self.settings()?)?;
+
+        let value = parse_value(value);
+
+        let mut root = self.table()?;
+
+        let mut table = &mut root;
+        let mut parts = key.split('.').peekable();
+
+        while let Some(part) = parts.next() {
+            if parts.peek().is_none() {
+                table.insert(part.to_owned(), value);
+                break;
+            }
+
+            let Some(toml::Value::Table(inner)) = table.get_mut(part) else {
+                bail!("{part} is not a section");
+            };
+
+            table = inner;
+        }
+
+        toml::Value::Table(root)
+            .try_into()
+            .with_context(|| format!("invalid value for {key}"))
+    }
+
+    /// The settings that differ between `self` and `new`.
+    pub fn changes(&self, new: &Self) -> anyhow::Result<Vec<ConfigChange>> {
+        let mut old = self.settings()?;
+        let new = new.settings()?;
+
+        let mut changes = Vec::new();
+
+        for (key, new) in new {
+            let old = old.remove(&key);
+
+            if old.as_ref() != Some(&new) {
+                changes.push(ConfigChange {
+                    key,
+                    old,
+                    new: Some(new),
+                });
+            }
+        }
+
+        changes.extend(old.into_iter().map(|(key, old)| ConfigChange {
+            key,
+            old: Some(old),
+            new: None,
+        }));
+
+        changes.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Ok(changes)
+    }
+
+    fn table(&self) -> anyhow::Result<toml::Table> {
+        match toml::Value::try_from(self)? {
+            toml::Value::Table(table) => Ok(table),
+            _ => bail!("the configuration is not a table"),
+        }
+    }
+
+    fn check_key(key: &str, settings: &BTreeMap<String, toml::Value>) -> anyhow::Result<()> {
+        if settings.contains_key(key) || Self::default().settings()?.contains_key(key) {
+            Ok(())
+        } else {
+            bail!("unknown setting {key}")
+        }
+    }
+}
+
+/// A setting that differs between two configurations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigChange {
+    /// The key of the setting, e.g. `spawn.x`.
+    pub key: String,
+    /// `None` if the setting was not set.
+    pub old: Option<toml::Value>,
+    /// `None` if the setting is no longer set.
+    pub new: Option<toml::Value>,
+}
+
+impl ConfigChange {
+    /// Whether the setting can be changed without restarting the server.
+    #[must_use]
+    pub fn is_hot_reloadable(&self) -> bool {
+        HOT_RELOADABLE.iter().any(|setting| {
+            self.key == *setting
+                || self
+                    .key
+                    .strip_prefix(setting)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+}
+
+impl Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<toml::Value>| {
+            value
+                .as_ref()
+                .map_or_else(|| "(unset)".to_owned(), ToString::to_string)
+        };
+
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.key,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+fn flatten(prefix: &str, table: toml::Table, settings: &mut BTreeMap<String, toml::Value>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{prefix}.{key}")
+        };
+
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, settings),
+            value => {
+                settings.insert(key, value);
+            }
+        }
+    }
+}
+
+/// `contents` with `key` set to `value`, or removed if `value` is `None`. Everything else in
+/// `contents` is kept as it is.
+fn set_in_document(
+    contents: &str,
+    key: &str,
+    value: Option<&toml::Value>,
+) -> anyhow::Result<String> {
+    let mut document = contents
+        .parse::<toml_edit::DocumentMut>()
+        .context("the configuration file is not valid TOML")?;
+
+    let mut table = document.as_table_mut();
+    let mut parts = key.split('.').peekable();
+
+    while let Some(part) = parts.next() {
+        if parts.peek().is_some() {
+            table = table
+                .entry(part)
+                .or_insert_with(toml_edit::table)
+                .as_table_mut()
+                .with_context(|| format!("{part} is not a section"))?;
+            continue;
+        }
+
+        match value {
+            Some(value) => {
+                let value = value
+                    .to_string()
+                    .parse::<toml_edit::Value>()
+                    .with_context(|| format!("failed to write {key}"))?;
+
+                // keeps the comments around the setting
+                match table.get_mut(part).and_then(toml_edit::Item::as_value_mut) {
+                    Some(old) => {
+                        let decor = old.decor().clone();
+                        *old = value;
+                        *old.decor_mut() = decor;
+                    }
+                    None => {
+                        table.insert(part, toml_edit::Item::Value(value));
+                    }
+                }
+            }
+            None => {
+                table.remove(part);
+            }
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+fn parse_value(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, set_in_document};
+
+    #[test]
+    fn with_changes_nested_settings() {
+        let config = Config::default();
+
+        let changed = config.with("spawn.x", "12").unwrap();
+        assert_eq!(changed.spawn.x, 12);
+
+        let changed = changed.with("server_desc", "A Minecraft Server").unwrap();
+        assert_eq!(changed.server_desc, "A Minecraft Server");
+
+        assert!(config.with("spawn.x", "twelve").is_err());
+        assert!(config.with("no_such_setting", "1").is_err());
+    }
+
+    #[test]
+    fn changes_are_hot_reloadable_or_not() {
+        let config = Config::default();
+        let changed = config
+            .with("view_distance", "16")
+            .unwrap()
+            .with("online_mode", "true")
+            .unwrap();
+
+        let changes = config.changes(&changed).unwrap();
+        let keys: Vec<_> = changes.iter().map(|change| change.key.as_str()).collect();
+        assert_eq!(keys, ["online_mode", "view_distance"]);
+
+        assert!(!changes[0].is_hot_reloadable());
+        assert!(changes[1].is_hot_reloadable());
+        assert_eq!(changes[1].to_string(), "view_distance: 32 -> 16");
+
+        assert!(config.changes(&config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn saving_a_setting_keeps_comments() {
+        let contents = "# how far players see\nview_distance = 8 # chunks\n\n[spawn]\nx = 0\n";
+
+        let value = toml::Value::Integer(16);
+        let saved = set_in_document(contents, "view_distance", Some(&value)).unwrap();
+        assert_eq!(
+            saved,
+            "# how far players see\nview_distance = 16 # chunks\n\n[spawn]\nx = 0\n"
+        );
+
+        let value = toml::Value::Integer(12);
+        let saved = set_in_document(&saved, "spawn.z", Some(&value)).unwrap();
+        assert!(saved.ends_with("[spawn]\nx = 0\nz = 12\n"));
+
+        let saved = set_in_document(&saved, "view_distance", None).unwrap();
+        assert!(!saved.contains("view_distance"));
+    }
+
+    #[test]
+    fn motd_depends_on_host() {
+        let mut config = Config::default();
+        config
+            .motds
+            .insert("Play.Example.com".to_owned(), "Welcome!".to_owned());
+
+        assert_eq!(config.motd("play.example.com"), "Welcome!");
+        assert_eq!(config.motd("localhost"), config.server_desc);
+    }
+}
@@ -0,0 +1,234 @@
+//! Applies changes to the configuration file while the server is running.
+//!
+//! The file is checked for changes every second. Settings that are not hot reloadable (see
+//! [`ConfigChange::is_hot_reloadable`]) only take effect after a restart, so a changed file that
+//! touches one of them is rejected as a whole.
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::bail;
+use flecs_ecs::prelude::*;
+use tracing::{error, info, info_span, warn};
+use valence_protocol::{VarInt, packets::play};
+
+use super::{Config, ConfigChange};
+use crate::{
+    net::Compose,
+    simulation::worlds::{SpawnPoint, WorldId, Worlds},
+};
+
+/// How often the configuration file is checked for changes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The diameter of the world border in vanilla, used when no border is configured.
+const DEFAULT_BORDER_DIAMETER: f64 = 59_999_968.0;
+
+/// The file the [`Config`] was loaded from.
+#[derive(Component, Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    /// When the file was last changed, as of the last check.
+    modified: Option<SystemTime>,
+}
+
+impl ConfigFile {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Parses the current contents of the file.
+    pub fn read(&self) -> anyhow::Result<Config> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        Config::parse(&contents)
+    }
+
+    /// Whether the file changed since the last call.
+    fn poll_changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        modified.is_some()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The changes from `config` to `new`, or an error listing the changes that need a restart.
+pub fn check(config: &Config, new: &Config) -> anyhow::Result<Vec<ConfigChange>> {
+    let changes = config.changes(new)?;
+
+    let rejected: Vec<_> = changes
+        .iter()
+        .filter(|change| !change.is_hot_reloadable())
+        .map(ToString::to_string)
+        .collect();
+
+    if !rejected.is_empty() {
+        bail!(
+            "these settings can only be changed with a restart:\n{}",
+            rejected.join("\n")
+        );
+    }
+
+    Ok(changes)
+}
+
+/// Replaces `config` with `new` and tells the players about the changes.
+fn apply(
+    config: &mut Config,
+    new: Config,
+    compose: &Compose,
+    world: &World,
+    system: EntityView<'_>,
+) -> anyhow::Result<Vec<ConfigChange>> {
+    let changes = check(config, &new)?;
+
+    *config = new;
+
+    for change in &changes {
+        let key = change.key.as_str();
+
+        let result = match key {
+            "view_distance" => compose
+                .broadcast(
+                    &play::ChunkLoadDistanceS2c {
+                        view_distance: VarInt(i32::from(config.view_distance)),
+                    },
+                    system,
+                )
+                .send(),
+            "simulation_distance" => compose
+                .broadcast(
+                    &play::SimulationDistanceS2c {
+                        simulation_distance: VarInt(config.simulation_distance),
+                    },
+                    system,
+                )
+                .send(),
+            "border_diameter" => compose
+                .broadcast(
+                    &play::WorldBorderSizeChangedS2c {
+                        diameter: config.border_diameter.unwrap_or(DEFAULT_BORDER_DIAMETER),
+                    },
+                    system,
+                )
+                .send(),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            error!("failed to send the new {key} to players: {e}");
+        }
+    }
+
+    if changes
+        .iter()
+        .any(|change| change.key.starts_with("spawn."))
+    {
+        set_spawn(world, config, compose, system);
+    }
+
+    Ok(changes)
+}
+
+/// Moves the [`SpawnPoint`] of the default world to the configured spawn.
+fn set_spawn(world: &World, config: &Config, compose: &Compose, system: EntityView<'_>) {
+    let Some(entity) = world.get::<&Worlds>(|worlds| worlds.get(WorldId::DEFAULT)) else {
+        return;
+    };
+
+    let position = config.spawn.position();
+
+    let angle = world
+        .entity_from_id(entity)
+        .get::<&mut SpawnPoint>(|spawn| {
+            spawn.position = position;
+            spawn.yaw
+        });
+
+    let pkt = play::PlayerSpawnPositionS2c {
+        position: position.as_dvec3().into(),
+        angle,
+    };
+
+    if let Err(e) = compose
+        .broadcast(&pkt, system)
+        .world(WorldId::DEFAULT)
+        .send()
+    {
+        error!("failed to send the new spawn to players: {e}");
+    }
+}
+
+#[derive(Component)]
+pub struct ConfigReloadModule;
+
+impl Module for ConfigReloadModule {
+    fn module(world: &World) {
+        world.component::<ConfigFile>();
+
+        let mut last_check = Instant::now();
+
+        system!(
+            "reload_config",
+            world,
+            &mut ConfigFile($),
+            &mut Config($),
+            &Compose($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(move |it, _, (file, config, compose)| {
+            if last_check.elapsed() < CHECK_INTERVAL {
+                return;
+            }
+
+            last_check = Instant::now();
+
+            if !file.poll_changed() {
+                return;
+            }
+
+            let span = info_span!("reload_config");
+            let _enter = span.enter();
+
+            let new = match file.read() {
+                Ok(new) => new,
+                Err(e) => {
+                    warn!("not reloading {:?}: {e:#}", file.path());
+                    return;
+                }
+            };
+
+            let world = it.world();
+            let system = it.system();
+
+            match apply(config, new, compose, &world, system) {
+                Ok(changes) => {
+                    for change in changes {
+                        info!("config changed: {change}");
+                    }
+                }
+                Err(e) => error!("not reloading {:?}: {e:#}", file.path()),
+            }
+        });
+    }
+}
@@ -1,8 +1,9 @@
 //! See [`MojangClient`].
 
-use std::{sync::Arc, time::Duration};
+use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use anyhow::{Context, bail};
+use base64::{Engine as _, engine::general_purpose};
 use flecs_ecs::macros::Component;
 use serde_json::Value;
 use tokio::{
@@ -15,10 +16,11 @@ use uuid::Uuid;
 use crate::runtime::AsyncRuntime;
 
 /// The API provider to use for Minecraft profile lookups
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct ApiProvider {
-    username_base_url: &'static str,
-    uuid_base_url: &'static str,
+    username_base_url: Cow<'static, str>,
+    uuid_base_url: Cow<'static, str>,
+    session_base_url: Cow<'static, str>,
     max_requests: usize,
     interval: Duration,
 }
@@ -26,18 +28,33 @@ pub struct ApiProvider {
 impl ApiProvider {
     /// The matdoes.dev API mirror provider with higher rate limits
     pub const MAT_DOES_DEV: Self = Self {
-        username_base_url: "https://mowojang.matdoes.dev/users/profiles/minecraft",
-        uuid_base_url: "https://mowojang.matdoes.dev/session/minecraft/profile",
+        username_base_url: Cow::Borrowed("https://mowojang.matdoes.dev/users/profiles/minecraft"),
+        uuid_base_url: Cow::Borrowed("https://mowojang.matdoes.dev/session/minecraft/profile"),
+        session_base_url: Cow::Borrowed(Self::MOJANG_SESSION_SERVER),
         max_requests: 10_000,
         interval: Duration::from_secs(1),
     };
     /// The official Mojang API provider
     pub const MOJANG: Self = Self {
-        username_base_url: "https://api.mojang.com/users/profiles/minecraft",
-        uuid_base_url: "https://sessionserver.mojang.com/session/minecraft/profile",
+        username_base_url: Cow::Borrowed("https://api.mojang.com/users/profiles/minecraft"),
+        uuid_base_url: Cow::Borrowed("https://sessionserver.mojang.com/session/minecraft/profile"),
+        session_base_url: Cow::Borrowed(Self::MOJANG_SESSION_SERVER),
         max_requests: 600,
         interval: Duration::from_mins(10),
     };
+    /// The keys Mojang signs things with, such as the keys of chat sessions
+    pub const MOJANG_PUBLIC_KEYS: &'static str = "https://api.minecraftservices.com/publickeys";
+    /// The official session server used to authenticate players in online mode
+    pub const MOJANG_SESSION_SERVER: &'static str =
+        "https://sessionserver.mojang.com/session/minecraft";
+
+    /// Uses a different session server to authenticate players, e.g. a self-hosted one or a local
+    /// stand-in for tests.
+    #[must_use]
+    pub fn with_session_server(mut self, session_base_url: impl Into<Cow<'static, str>>) -> Self {
+        self.session_base_url = session_base_url.into();
+        self
+    }
 
     fn username_url(&self, username: &str) -> String {
         format!("{}/{username}", self.username_base_url)
@@ -47,6 +64,10 @@ impl ApiProvider {
         format!("{}/{uuid}?unsigned=false", self.uuid_base_url)
     }
 
+    fn has_joined_url(&self) -> String {
+        format!("{}/hasJoined", self.session_base_url)
+    }
+
     const fn max_requests(&self) -> usize {
         self.max_requests
     }
@@ -139,6 +160,71 @@ impl MojangClient {
         self.response_raw(&url).await
     }
 
+    /// Asks the session server whether `username` has joined the server identified by
+    /// `server_hash`, the Minecraft-style hex digest of the login key exchange.
+    ///
+    /// Returns the authenticated profile, or `None` if the player has not authenticated with
+    /// the session server. This is not rate limited since it is not sent to the profile API.
+    pub async fn has_joined(
+        &self,
+        username: &str,
+        server_hash: &str,
+    ) -> anyhow::Result<Option<Value>> {
+        let url = self.provider.has_joined_url();
+
+        let response = self
+            .req
+            .get(url)
+            .query(&[("username", username), ("serverId", server_hash)])
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            bail!("session server responded with {}", response.status());
+        }
+
+        let body = response.text().await?;
+        let json_object = serde_json::from_str::<Value>(&body)
+            .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+
+        Ok(Some(json_object))
+    }
+
+    /// Gets the DER encoded keys Mojang signs the keys of chat sessions with. This is not rate
+    /// limited since it is not sent to the profile API.
+    pub async fn player_certificate_keys(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+        let response = self.req.get(ApiProvider::MOJANG_PUBLIC_KEYS).send().await?;
+
+        if !response.status().is_success() {
+            bail!("the public key API responded with {}", response.status());
+        }
+
+        let body = response.text().await?;
+        let json_object = serde_json::from_str::<Value>(&body)
+            .with_context(|| format!("failed to parse json from response: {body:?}"))?;
+
+        json_object
+            .get("playerCertificateKeys")
+            .and_then(Value::as_array)
+            .context("no player certificate keys in response")?
+            .iter()
+            .map(|key| {
+                let key = key
+                    .get("publicKey")
+                    .and_then(Value::as_str)
+                    .context("player certificate key is not a string")?;
+
+                general_purpose::STANDARD
+                    .decode(key)
+                    .context("player certificate key is not base64")
+            })
+            .collect()
+    }
+
     async fn response_raw(&self, url: &str) -> anyhow::Result<Value> {
         self.rate_limit
             .acquire()
@@ -207,6 +293,60 @@ mod tests {
         assert_eq!(username, "Emerald_Explorer");
     }
 
+    #[test]
+    fn test_has_joined_local_session_server() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        const PROFILE: &str = r#"{"id":"86271406118844a584967af10c906204","name":"Emerald_Explorer","properties":[]}"#;
+
+        let (tx, _rx) = kanal::bounded(1);
+        let tasks = AsyncRuntime::new(tx);
+
+        let listener = tasks
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // stand-in session server which only knows about one authenticated session
+        tasks.spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 1024];
+                let len = socket.read(&mut request).await.unwrap();
+                let request = String::from_utf8_lossy(&request[..len]);
+
+                let response = if request.starts_with(
+                    "GET /session/minecraft/hasJoined?username=Emerald_Explorer&serverId=-7c9d5b",
+                ) {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: \
+                         {}\r\nConnection: close\r\n\r\n{PROFILE}",
+                        PROFILE.len()
+                    )
+                } else {
+                    "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_owned()
+                };
+
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let provider =
+            ApiProvider::MOJANG.with_session_server(format!("http://{addr}/session/minecraft"));
+        let mojang = MojangClient::new(&tasks, provider);
+
+        let profile = tasks
+            .block_on(mojang.has_joined("Emerald_Explorer", "-7c9d5b"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(profile["name"], "Emerald_Explorer");
+
+        let profile = tasks
+            .block_on(mojang.has_joined("Emerald_Explorer", "1234"))
+            .unwrap();
+        assert!(profile.is_none());
+    }
+
     #[test]
     fn test_retrieve_username() {
         let (tx, _rx) = kanal::bounded(1);
@@ -20,7 +20,11 @@ use sync_entity_state::EntityStateSyncModule;
 
 use crate::{
     net::ConnectionId,
-    simulation::{ChunkPosition, blocks::Blocks},
+    simulation::{
+        ChunkPosition,
+        blocks::Blocks,
+        worlds::{Dimension, WorldId},
+    },
 };
 
 #[derive(Component)]
@@ -58,43 +62,49 @@ impl Module for EgressModule {
             "broadcast_chunk_deltas",
             world,
             &Compose($),
-            &mut Blocks($),
+            &mut Blocks,
+            &Dimension,
         )
         .multi_threaded()
         .kind::<flecs::pipeline::OnUpdate>()
-        .each_iter(move |it: TableIter<'_, false>, _, (compose, mc)| {
-            let span = info_span!("broadcast_chunk_deltas");
-            let _enter = span.enter();
-            let system = it.system();
+        .each_iter(
+            move |it: TableIter<'_, false>, _, (compose, mc, dimension)| {
+                let span = info_span!("broadcast_chunk_deltas");
+                let _enter = span.enter();
+                let system = it.system();
 
-            let world = it.world();
+                let world = it.world();
 
-            mc.for_each_to_update_mut(|chunk| {
-                for packet in chunk.delta_drain_packets() {
-                    if let Err(e) = compose.broadcast(packet, system).send() {
-                        error!("failed to send chunk delta packet: {e}");
-                        return;
+                mc.for_each_to_update_mut(|chunk| {
+                    for packet in chunk.delta_drain_packets() {
+                        if let Err(e) = compose.broadcast(packet, system).world(dimension.id).send()
+                        {
+                            error!("failed to send chunk delta packet: {e}");
+                            return;
+                        }
                     }
-                }
-            });
-            mc.clear_should_update();
+                });
+                mc.clear_should_update();
 
-            for to_confirm in mc.to_confirm.drain(..) {
-                let entity = world.entity_from_id(to_confirm.entity);
+                for to_confirm in mc.to_confirm.drain(..) {
+                    let entity = world.entity_from_id(to_confirm.entity);
 
-                let pkt = play::PlayerActionResponseS2c {
-                    sequence: VarInt(to_confirm.sequence),
-                };
+                    let pkt = play::PlayerActionResponseS2c {
+                        sequence: VarInt(to_confirm.sequence),
+                    };
 
-                entity.get::<&ConnectionId>(|stream| {
-                    if let Err(e) = compose.unicast(&pkt, *stream, system) {
-                        error!("failed to send player action response: {e}");
-                    }
-                });
-            }
-        });
+                    // NPCs opening doors have nobody to confirm the change to
+                    entity.try_get::<&ConnectionId>(|stream| {
+                        if let Err(e) = compose.unicast(&pkt, *stream, system) {
+                            error!("failed to send player action response: {e}");
+                        }
+                    });
+                }
+            },
+        );
 
-        let player_location_query = world.new_query::<(&ConnectionId, &ChunkPosition)>();
+        let player_location_query =
+            world.new_query::<(&ConnectionId, &ChunkPosition, Option<&WorldId>)>();
 
         system!(
             "egress",
@@ -114,10 +124,11 @@ impl Module for EgressModule {
                 let mut stream = Vec::new();
                 let mut positions = Vec::new();
 
-                player_location_query.each(|(io, pos)| {
+                player_location_query.each(|(io, pos, world_id)| {
                     stream.push(io.inner());
 
                     let position = hyperion_proto::ChunkPosition {
+                        world: world_id.copied().unwrap_or_default().0,
                         x: pos.position.x,
                         z: pos.position.y,
                     };
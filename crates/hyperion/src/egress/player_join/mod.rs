@@ -1,8 +1,12 @@
-use std::{borrow::Cow, collections::BTreeSet, ops::Index};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    ops::Index,
+};
 
 use anyhow::Context;
 use flecs_ecs::prelude::*;
-use hyperion_crafting::{Action, CraftingRegistry, RecipeBookState};
+use hyperion_crafting::{Action, CraftingRegistry, RecipeBookState, datapack::ItemTags};
 use hyperion_utils::EntityExt;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tracing::{info, instrument};
@@ -10,11 +14,7 @@ use valence_protocol::{
     ByteAngle, GameMode, Ident, PacketEncoder, RawBytes, VarInt, Velocity,
     game_mode::OptGameMode,
     ident,
-    packets::play::{
-        self, GameJoinS2c,
-        player_position_look_s2c::PlayerPositionLookFlags,
-        team_s2c::{CollisionRule, Mode, NameTagVisibility, TeamColor, TeamFlags},
-    },
+    packets::play::{self, GameJoinS2c, player_position_look_s2c::PlayerPositionLookFlags},
 };
 use valence_registry::{BiomeRegistry, RegistryCodec};
 use valence_server::entity::EntityKind;
@@ -28,15 +28,20 @@ pub use list::*;
 use crate::{
     config::Config,
     egress::metadata::show_all,
-    ingress::PendingRemove,
-    net::{Compose, ConnectionId, DataBundle},
+    ingress::{PendingRemove, transfer::Transferred},
+    net::{Compose, ConnectionId, ConnectionInfo, DataBundle},
     simulation::{
         Comms, Name, Position, Uuid, Yaw,
+        anticheat::AntiCheat,
+        chat::{ChatSession, SecureChat},
         command::{Command, ROOT_COMMAND, get_command_packet},
+        event,
         metadata::{MetadataChanges, entity::EntityFlags},
         skin::PlayerSkin,
         util::registry_codec_raw,
+        worlds::{Dimension, DimensionType, WorldId, Worlds},
     },
+    storage::EventBus,
     util::{SendableQuery, SendableRef},
 };
 
@@ -54,6 +59,7 @@ pub fn player_join_world(
     position: &Position,
     yaw: &Yaw,
     pitch: &Pitch,
+    world_id: WorldId,
     world: &WorldRef<'_>,
     skin: &PlayerSkin,
     system: EntityView<'_>,
@@ -66,9 +72,11 @@ pub fn player_join_world(
         &Pitch,
         &PlayerSkin,
         &EntityFlags,
+        Option<&WorldId>,
     )>,
     crafting_registry: &CraftingRegistry,
     config: &Config,
+    host: &str,
 ) -> anyhow::Result<()> {
     static CACHED_DATA: once_cell::sync::OnceCell<bytes::Bytes> = once_cell::sync::OnceCell::new();
 
@@ -79,14 +87,30 @@ pub fn player_join_world(
     let registry_codec = registry_codec_raw();
     let codec = RegistryCodec::default();
 
-    let dimension_names: BTreeSet<Ident<Cow<'_, str>>> = codec
+    let mut dimension_names: BTreeSet<Ident<Cow<'_, str>>> = codec
         .registry(BiomeRegistry::KEY)
         .iter()
         .map(|value| value.name.as_str_ident().into())
         .collect();
 
-    let dimension_name = ident!("overworld");
-    // let dimension_name: Ident<Cow<str>> = chunk_layer.dimension_type_name().into();
+    let mut dimension = None;
+
+    world.get::<&Worlds>(|worlds| {
+        for (id, world_entity) in worlds.iter() {
+            world
+                .entity_from_id(world_entity)
+                .get::<&Dimension>(|world_dimension| {
+                    dimension_names.insert(world_dimension.name.clone().into());
+
+                    if id == world_id {
+                        dimension = Some(world_dimension.clone());
+                    }
+                });
+        }
+    });
+
+    let dimension =
+        dimension.with_context(|| format!("player is in unknown world {world_id:?}"))?;
 
     let pkt = GameJoinS2c {
         entity_id: id,
@@ -98,14 +122,14 @@ pub fn player_join_world(
         simulation_distance: config.simulation_distance.into(),
         reduced_debug_info: false,
         enable_respawn_screen: false,
-        dimension_name: dimension_name.into(),
+        dimension_name: dimension.name.as_str_ident().into(),
         hashed_seed: 0,
         game_mode: GameMode::Survival,
         is_flat: false,
         last_death_location: None,
         portal_cooldown: 60.into(),
         previous_game_mode: OptGameMode(Some(GameMode::Survival)),
-        dimension_type_name: ident!("minecraft:overworld").into(),
+        dimension_type_name: dimension.kind.ident().into(),
         is_debug: false,
     };
 
@@ -113,6 +137,28 @@ pub fn player_join_world(
         .add_packet(&pkt)
         .context("failed to send player spawn packet")?;
 
+    bundle.add_packet(&play::ServerMetadataS2c {
+        motd: config.motd(host).into_cow_text(),
+        icon: None,
+        enforces_secure_chat: world.get::<&SecureChat>(SecureChat::enforced),
+    })?;
+
+    // a client transferred from another server only reloads its world after a dimension change,
+    // so respawn it somewhere else first
+    if entity.has::<Transferred>() {
+        let elsewhere = play::PlayerRespawnS2c {
+            dimension_type_name: DimensionType::End.ident().into(),
+            dimension_name: ident!("hyperion:transfer").into(),
+            ..dimension.respawn_packet()
+        };
+
+        for pkt in [elsewhere, dimension.respawn_packet()] {
+            bundle
+                .add_packet(&pkt)
+                .context("failed to send player respawn packet")?;
+        }
+    }
+
     let center_chunk = position.to_chunk();
 
     let pkt = play::ChunkRenderDistanceCenterS2c {
@@ -172,7 +218,6 @@ pub fn player_join_world(
     })?;
 
     let mut entries = Vec::new();
-    let mut all_player_names = Vec::new();
 
     let count = query.iter_stage(world).count();
 
@@ -183,7 +228,7 @@ pub fn player_join_world(
         let _enter = scope.enter();
         query
             .iter_stage(world)
-            .each(|(uuid, name, _, _, _, _skin, _)| {
+            .each(|(uuid, name, _, _, _, _skin, ..)| {
                 // todo: in future, do not clone
 
                 let entry = PlayerListEntry {
@@ -199,12 +244,9 @@ pub fn player_join_world(
                 };
 
                 entries.push(entry);
-                all_player_names.push(name.to_string());
             });
     }
 
-    let all_player_names = all_player_names.iter().map(String::as_str).collect();
-
     let actions = PlayerListActions::default()
         .with_add_player(true)
         .with_update_listed(true)
@@ -219,6 +261,23 @@ pub fn player_join_world(
         })?;
     }
 
+    // the keys the other players sign their chat messages with, see `simulation::chat`
+    let mut session_errors = Vec::new();
+
+    query.iter_stage(world).each_entity(|other, (uuid, ..)| {
+        other.try_get::<&ChatSession>(|session| {
+            if let Err(e) = bundle.add_packet(&session.player_list(uuid.0)) {
+                session_errors.push(e);
+            }
+        });
+    });
+
+    if !session_errors.is_empty() {
+        return Err(anyhow::anyhow!(
+            "failed to send chat sessions: {session_errors:?}"
+        ));
+    }
+
     {
         let scope = tracing::info_span!("sending_player_spawns");
         let _enter = scope.enter();
@@ -229,9 +288,8 @@ pub fn player_join_world(
 
         let mut metadata = MetadataChanges::default();
 
-        query
-            .iter_stage(world)
-            .each_iter(|it, idx, (uuid, _, position, yaw, pitch, _, flags)| {
+        query.iter_stage(world).each_iter(
+            |it, idx, (uuid, _, position, yaw, pitch, _, flags, other_world)| {
                 let mut result = || {
                     let query_entity = it.entity(idx);
 
@@ -239,6 +297,10 @@ pub fn player_join_world(
                         return anyhow::Ok(());
                     }
 
+                    if other_world.copied().unwrap_or_default() != world_id {
+                        return anyhow::Ok(());
+                    }
+
                     let pkt = play::PlayerSpawnS2c {
                         entity_id: VarInt(query_entity.minecraft_id()),
                         player_uuid: uuid.0,
@@ -264,7 +326,8 @@ pub fn player_join_world(
                 if let Err(e) = result() {
                     query_errors.push(e);
                 }
-            });
+            },
+        );
 
         if !query_errors.is_empty() {
             return Err(anyhow::anyhow!(
@@ -312,22 +375,6 @@ pub fn player_join_world(
         .add_packet(&pkt)
         .context("failed to send player list packet")?;
 
-    let player_name = vec![name];
-
-    compose
-        .broadcast(
-            &play::TeamS2c {
-                team_name: "no_tag",
-                mode: Mode::AddEntities {
-                    entities: player_name,
-                },
-            },
-            system,
-        )
-        .exclude(io)
-        .send()
-        .context("failed to send team packet")?;
-
     let current_entity_id = VarInt(entity.minecraft_id());
 
     let spawn_player = play::PlayerSpawnS2c {
@@ -339,6 +386,7 @@ pub fn player_join_world(
     };
     compose
         .broadcast(&spawn_player, system)
+        .world(world_id)
         .exclude(io)
         .send()
         .context("failed to send player spawn packet")?;
@@ -346,18 +394,10 @@ pub fn player_join_world(
     let show_all = show_all(entity.minecraft_id());
     compose
         .broadcast(show_all.borrow_packet(), system)
+        .world(world_id)
         .send()
         .context("failed to send show all packet")?;
 
-    bundle
-        .add_packet(&play::TeamS2c {
-            team_name: "no_tag",
-            mode: Mode::AddEntities {
-                entities: all_player_names,
-            },
-        })
-        .context("failed to send team packet")?;
-
     let command_packet = get_command_packet(world, root_command, Some(**entity));
 
     bundle.add_packet(&command_packet)?;
@@ -369,10 +409,22 @@ pub fn player_join_world(
     Ok(())
 }
 
-fn send_sync_tags(encoder: &mut PacketEncoder) -> anyhow::Result<()> {
-    let bytes = include_bytes!("data/tags.json");
+/// The vanilla tags of every registry, with the raw IDs of their entries.
+const TAGS: &[u8] = include_bytes!("data/tags.json");
+
+/// The vanilla item tags, which are the same tags the client gets.
+pub fn item_tags() -> anyhow::Result<ItemTags> {
+    let mut groups: HashMap<String, HashMap<String, Vec<u16>>> = serde_json::from_slice(TAGS)?;
 
-    let groups = serde_json::from_slice(bytes)?;
+    let items = groups
+        .remove("minecraft:item")
+        .context("the tags have no item tags")?;
+
+    Ok(ItemTags::from_raw_ids(items))
+}
+
+fn send_sync_tags(encoder: &mut PacketEncoder) -> anyhow::Result<()> {
+    let groups = serde_json::from_slice(TAGS)?;
 
     let pkt = play::SynchronizeTagsS2c { groups };
 
@@ -407,20 +459,6 @@ fn generate_cached_packet_bytes(
 
     encoder.append_packet(&brand)?;
 
-    encoder.append_packet(&play::TeamS2c {
-        team_name: "no_tag",
-        mode: Mode::CreateTeam {
-            team_display_name: Cow::default(),
-            friendly_flags: TeamFlags::default(),
-            name_tag_visibility: NameTagVisibility::Never,
-            collision_rule: CollisionRule::Always,
-            team_color: TeamColor::Black,
-            team_prefix: Cow::default(),
-            team_suffix: Cow::default(),
-            entities: vec![],
-        },
-    })?;
-
     if let Some(pkt) = crafting_registry.packet() {
         encoder.append_packet(&pkt)?;
     }
@@ -432,8 +470,8 @@ fn generate_cached_packet_bytes(
         smelting_recipe_book: RecipeBookState::FALSE,
         blast_furnace_recipe_book: RecipeBookState::FALSE,
         smoker_recipe_book: RecipeBookState::FALSE,
-        recipe_ids_1: vec!["hyperion:what".to_string()],
-        recipe_ids_2: vec!["hyperion:what".to_string()],
+        recipe_ids_1: crafting_registry.recipe_ids().map(str::to_owned).collect(),
+        recipe_ids_2: Vec::new(),
     };
 
     encoder.append_packet(&pkt)?;
@@ -493,6 +531,7 @@ impl Module for PlayerJoinModule {
             &Pitch,
             &PlayerSkin,
             &EntityFlags,
+            Option<&WorldId>,
         )>();
 
         let query = SendableQuery(query);
@@ -563,8 +602,38 @@ impl Module for PlayerJoinModule {
 
                     let entity = world.entity_from_id(entity);
 
-                    entity.get::<(&Uuid, &Name, &Position, &Yaw, &Pitch, &ConnectionId)>(
-                        |(uuid, name, position, yaw, pitch, &stream_id)| {
+                    let mut join = event::PlayerJoin {
+                        player: entity.id(),
+                        kick_reason: "You may not join this server".to_string(),
+                    };
+
+                    if !EventBus::dispatch(world, &mut join) {
+                        entity.set(PendingRemove::new(join.kick_reason));
+                        return;
+                    }
+
+                    entity.get::<(
+                        &Uuid,
+                        &Name,
+                        &Position,
+                        &Yaw,
+                        &Pitch,
+                        &WorldId,
+                        &ConnectionId,
+                        &ConnectionInfo,
+                        &mut AntiCheat,
+                    )>(
+                        |(
+                            uuid,
+                            name,
+                            position,
+                            yaw,
+                            pitch,
+                            &world_id,
+                            &stream_id,
+                            info,
+                            anticheat,
+                        )| {
                             let query = &query;
                             let query = &query.0;
 
@@ -578,6 +647,7 @@ impl Module for PlayerJoinModule {
                                 position,
                                 yaw,
                                 pitch,
+                                world_id,
                                 world,
                                 &skin,
                                 system,
@@ -585,9 +655,14 @@ impl Module for PlayerJoinModule {
                                 query,
                                 crafting_registry,
                                 config,
+                                &info.server_address,
                             ) {
                                 entity.set(PendingRemove::new(e.to_string()));
-                            };
+                                return;
+                            }
+
+                            // transferred players join here as well, wherever they were before
+                            anticheat.teleported(**position, compose.global().tick);
                         },
                     );
 
@@ -15,13 +15,18 @@ use crate::{
     net::{Compose, ConnectionId, DataBundle},
     simulation::{
         ChunkPosition, PacketState, Position,
-        blocks::{Blocks, GetChunk},
+        blocks::GetChunk,
+        worlds::{WorldId, with_blocks},
     },
 };
 
 #[derive(Component, Deref, DerefMut, Default)]
 pub struct ChunkSendQueue {
+    #[deref]
+    #[deref_mut]
     changes: Vec<I16Vec2>,
+    /// The view distance the chunks around the last sent [`ChunkPosition`] were sent with.
+    view_distance: i16,
 }
 
 #[derive(Component)]
@@ -31,13 +36,11 @@ impl Module for SyncChunksModule {
     fn module(world: &World) {
         world.component::<ChunkSendQueue>();
 
-        let radius = world.get::<&Config>(|config| config.view_distance);
-        let liberal_radius = radius + 2;
-
         system!(
             "generate_chunk_changes",
             world,
             &Compose($),
+            &Config($),
             &mut ChunkPosition,
             &Position,
             &ConnectionId,
@@ -47,14 +50,19 @@ impl Module for SyncChunksModule {
         .kind::<flecs::pipeline::OnUpdate>()
         .multi_threaded()
         .each_iter(
-            move |it, _, (compose, last_sent, pose, &stream_id, chunk_changes)| {
+            move |it, _, (compose, config, last_sent, pose, &stream_id, chunk_changes)| {
                 let system = it.system();
 
                 let last_sent_chunk = last_sent.position;
+                let last_radius = chunk_changes.view_distance;
 
                 let current_chunk = pose.to_chunk();
 
-                if last_sent_chunk == current_chunk {
+                // the view distance can change while the server is running
+                let radius = config.view_distance;
+                let liberal_radius = radius + 2;
+
+                if last_sent_chunk == current_chunk && last_radius == radius {
                     return;
                 }
 
@@ -73,9 +81,12 @@ impl Module for SyncChunksModule {
                 }
 
                 last_sent.position = current_chunk;
+                chunk_changes.view_distance = radius;
 
-                let last_sent_range_x = (last_sent_chunk.x - radius)..(last_sent_chunk.x + radius);
-                let last_sent_range_z = (last_sent_chunk.y - radius)..(last_sent_chunk.y + radius);
+                let last_sent_range_x =
+                    (last_sent_chunk.x - last_radius)..(last_sent_chunk.x + last_radius);
+                let last_sent_range_z =
+                    (last_sent_chunk.y - last_radius)..(last_sent_chunk.y + last_radius);
 
                 let current_range_x = (current_chunk.x - radius)..(current_chunk.x + radius);
                 let current_range_z = (current_chunk.y - radius)..(current_chunk.y + radius);
@@ -158,72 +169,76 @@ impl Module for SyncChunksModule {
             },
         );
 
-        system!("send_full_loaded_chunks", world, &Blocks($), &Compose($), &ConnectionId, &mut ChunkSendQueue)
+        system!("send_full_loaded_chunks", world, ?&WorldId, &Compose($), &ConnectionId, &mut ChunkSendQueue)
             .with_enum(PacketState::Play)
             .kind::<flecs::pipeline::OnUpdate>()
             .multi_threaded()
             .each_iter(
-                move |it, _, (chunks, compose, &stream_id, queue)| {
+                move |it, _, (world_id, compose, &stream_id, queue)| {
                     const MAX_CHUNKS_PER_TICK: usize = 16;
 
                     let system = it.system();
+                    let world = it.world();
+                    let world_id = world_id.copied().unwrap_or_default();
 
-                    let last = None;
-
-                    let mut iter_count = 0;
+                    with_blocks(world, world_id, |chunks| {
+                        let last = None;
 
-                    let mut bundle = DataBundle::new(compose, system);
+                        let mut iter_count = 0;
 
-                    #[expect(
-                        clippy::cast_possible_wrap,
-                        reason = "realistically queue.changes.len() will never be large enough to wrap"
-                    )]
-                    let mut idx = (queue.changes.len() as isize) - 1;
+                        let mut bundle = DataBundle::new(compose, system);
 
-                    while idx >= 0 {
-                        #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
-                        let Some(elem) = queue.changes.get(idx as usize).copied() else {
-                            // should never happen but we do not want to panic if wrong
-                            // logic/assumptions are made
-                            error!("failed to get element from queue.changes");
-                            continue;
-                        };
+                        #[expect(
+                            clippy::cast_possible_wrap,
+                            reason = "realistically queue.changes.len() will never be large enough to wrap"
+                        )]
+                        let mut idx = (queue.changes.len() as isize) - 1;
 
-                        // de-duplicate. todo: there are cases where duplicate will not be removed properly
-                        // since sort is unstable
-                        if last == Some(elem) {
+                        while idx >= 0 {
                             #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
-                            queue.changes.swap_remove(idx as usize);
-                            idx -= 1;
-                            continue;
-                        }
+                            let Some(elem) = queue.changes.get(idx as usize).copied() else {
+                                // should never happen but we do not want to panic if wrong
+                                // logic/assumptions are made
+                                error!("failed to get element from queue.changes");
+                                continue;
+                            };
+
+                            // de-duplicate. todo: there are cases where duplicate will not be removed properly
+                            // since sort is unstable
+                            if last == Some(elem) {
+                                #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
+                                queue.changes.swap_remove(idx as usize);
+                                idx -= 1;
+                                continue;
+                            }
 
-                        if iter_count >= MAX_CHUNKS_PER_TICK {
-                            break;
-                        }
+                            if iter_count >= MAX_CHUNKS_PER_TICK {
+                                break;
+                            }
 
-                        match chunks.get_cached_or_load(elem) {
-                            GetChunk::Loaded(chunk) => {
-                                bundle.add_raw(&chunk.base_packet_bytes);
+                            match chunks.get_cached_or_load(elem) {
+                                GetChunk::Loaded(chunk) => {
+                                    bundle.add_raw(&chunk.base_packet_bytes);
 
-                                for packet in chunk.original_delta_packets() {
-                                    if let Err(e) = bundle.add_packet(packet) {
-                                        error!("failed to send chunk delta packet: {e}");
-                                        return;
+                                    for packet in chunk.original_delta_packets() {
+                                        if let Err(e) = bundle.add_packet(packet) {
+                                            error!("failed to send chunk delta packet: {e}");
+                                            return;
+                                        }
                                     }
-                                }
 
-                                iter_count += 1;
-                                #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
-                                queue.changes.swap_remove(idx as usize);
+                                    iter_count += 1;
+                                    #[expect(clippy::cast_sign_loss, reason = "we are checking if < 0")]
+                                    queue.changes.swap_remove(idx as usize);
+                                }
+                                GetChunk::Loading => {}
                             }
-                            GetChunk::Loading => {}
-                        }
 
-                        idx -= 1;
-                    }
+                            idx -= 1;
+                        }
 
-                    bundle.unicast(stream_id).unwrap();
+                        bundle.unicast(stream_id).unwrap();
+                    });
                 },
             );
     }
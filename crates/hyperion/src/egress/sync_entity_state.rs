@@ -3,14 +3,13 @@ use std::{borrow::Cow, fmt::Debug};
 use anyhow::Context;
 use flecs_ecs::prelude::*;
 use glam::Vec3;
-use hyperion_inventory::PlayerInventory;
+use hyperion_inventory::{PlayerInventory, action::Cursor};
 use hyperion_utils::EntityExt;
-use tracing::{debug, error};
+use tracing::error;
 use valence_protocol::{
     ByteAngle, RawBytes, VarInt,
     packets::play::{self, entity_equipment_update_s2c::EquipmentEntry},
 };
-use valence_server::BlockState;
 
 use crate::{
     Prev,
@@ -18,10 +17,9 @@ use crate::{
     simulation::{
         Pitch, Position, Velocity, Xp, Yaw,
         animation::ActiveAnimation,
-        blocks::Blocks,
-        entity_kind::EntityKind,
         handlers::is_grounded,
         metadata::{MetadataChanges, get_and_clear_metadata},
+        worlds::{WorldId, with_blocks},
     },
 };
 
@@ -128,10 +126,10 @@ impl Module for EntityStateSyncModule {
                 }
             });
 
-        system!("entity_metadata_sync", world, &Compose($), &mut MetadataChanges)
+        system!("entity_metadata_sync", world, &Compose($), &mut MetadataChanges, ?&WorldId)
             .multi_threaded()
             .kind::<flecs::pipeline::OnStore>()
-            .each_iter(move |it, row, (compose, metadata_changes)| {
+            .each_iter(move |it, row, (compose, metadata_changes, world_id)| {
                 let system = it.system();
                 let entity = it.entity(row);
                 let entity_id = VarInt(entity.minecraft_id());
@@ -145,7 +143,11 @@ impl Module for EntityStateSyncModule {
                     };
 
                     // todo(perf): do so locally
-                    compose.broadcast(&pkt, system).send().unwrap();
+                    compose
+                        .broadcast(&pkt, system)
+                        .world(world_id.copied().unwrap_or_default())
+                        .send()
+                        .unwrap();
                 }
             });
 
@@ -155,12 +157,13 @@ impl Module for EntityStateSyncModule {
         &Position,
         &Compose($),
         ?&ConnectionId,
+        ?&WorldId,
         &mut ActiveAnimation,
         )
         .multi_threaded()
         .kind::<flecs::pipeline::OnStore>()
         .each_iter(
-            move |it, row, (position, compose, connection_id, animation)| {
+            move |it, row, (position, compose, connection_id, world_id, animation)| {
                 let io = connection_id.copied();
 
                 let entity = it.entity(row);
@@ -168,11 +171,12 @@ impl Module for EntityStateSyncModule {
 
                 let entity_id = VarInt(entity.minecraft_id());
 
+                let world_id = world_id.copied().unwrap_or_default();
                 let chunk_pos = position.to_chunk();
 
                 for pkt in animation.packets(entity_id) {
                     compose
-                        .broadcast_local(&pkt, chunk_pos, system)
+                        .broadcast_local(&pkt, world_id, chunk_pos, system)
                         .exclude(io)
                         .send()
                         .unwrap();
@@ -188,10 +192,11 @@ impl Module for EntityStateSyncModule {
             &Compose($),
             &mut PlayerInventory,
             &ConnectionId,
+            &Cursor,
         )
         .multi_threaded()
         .kind::<flecs::pipeline::OnStore>()
-        .each_iter(move |it, _, (compose, inventory, io)| {
+        .each_iter(move |it, _, (compose, inventory, io, cursor)| {
             let mut run = || {
                 let io = *io;
                 let system = it.system();
@@ -210,7 +215,7 @@ impl Module for EntityStateSyncModule {
                     };
                     let pkt = play::ScreenHandlerSlotUpdateS2c {
                         window_id: 0,
-                        state_id: VarInt::default(),
+                        state_id: VarInt(cursor.state_id),
                         slot_idx: slot,
                         slot_data: Cow::Borrowed(item),
                     };
@@ -235,11 +240,12 @@ impl Module for EntityStateSyncModule {
             world,
             &Compose($),
             &Position,
+            ?&WorldId,
             &PlayerInventory,
         )
         .multi_threaded()
         .kind::<flecs::pipeline::OnStore>()
-        .each_iter(move |it, row, (compose, position, inventory)| {
+        .each_iter(move |it, row, (compose, position, world_id, inventory)| {
             // let entity = it.entity(row);
             let system = it.system();
             // get armor and hand
@@ -273,8 +279,10 @@ impl Module for EntityStateSyncModule {
                 equipment: vec![hand, helmet, chestplate, leggings, boots, off_hand],
             };
 
+            let world_id = world_id.copied().unwrap_or_default();
+
             compose
-                .broadcast_local(&packet, position.to_chunk(), system)
+                .broadcast_local(&packet, world_id, position.to_chunk(), system)
                 .send()
                 .unwrap();
         });
@@ -292,6 +300,7 @@ impl Module for EntityStateSyncModule {
             &mut Velocity,
             &Yaw,
             &Pitch,
+            ?&WorldId,
         )
         .multi_threaded()
         .kind::<flecs::pipeline::PreStore>()
@@ -307,6 +316,7 @@ impl Module for EntityStateSyncModule {
                 velocity,
                 yaw,
                 pitch,
+                world_id,
             )| {
                 // if io.is_none() {
                 // return;
@@ -317,17 +327,19 @@ impl Module for EntityStateSyncModule {
                 let entity = it.entity(row);
                 let entity_id = VarInt(entity.minecraft_id());
 
+                let world_id = world_id.copied().unwrap_or_default();
                 let chunk_pos = position.to_chunk();
 
                 let position_delta = **position - **prev_position;
                 let needs_teleport = position_delta.abs().max_element() >= 8.0;
                 let changed_position = **position != **prev_position;
 
-                let look_changed = (**yaw - **prev_yaw).abs() >= 0.01 || (**pitch - **prev_pitch).abs() >= 0.01;
+                let look_changed =
+                    (**yaw - **prev_yaw).abs() >= 0.01 || (**pitch - **prev_pitch).abs() >= 0.01;
 
                 let mut bundle = DataBundle::new(compose, system);
 
-                world.get::<&mut Blocks>(|blocks| {
+                with_blocks(world, world_id, |blocks| {
                     let grounded = is_grounded(position, blocks);
 
                     if changed_position && !needs_teleport && look_changed {
@@ -385,7 +397,6 @@ impl Module for EntityStateSyncModule {
                 });
 
                 if velocity.0 != Vec3::ZERO {
-
                     let packet = play::EntityVelocityUpdateS2c {
                         entity_id,
                         velocity: velocity.to_packet_units(),
@@ -394,67 +405,10 @@ impl Module for EntityStateSyncModule {
                     bundle.add_packet(&packet).unwrap();
                 }
 
-                bundle.broadcast_local(chunk_pos).unwrap();
+                bundle.broadcast_local(world_id, chunk_pos).unwrap();
             },
         );
 
-        system!(
-            "update_projectile_positions",
-            world,
-            &mut Position,
-            &mut Velocity,
-            ?&ConnectionId
-        )
-        .multi_threaded()
-        .kind::<flecs::pipeline::OnUpdate>()
-        .with_enum_wildcard::<EntityKind>()
-        .each_iter(|it, row, (position, velocity, connection_id)| {
-            if let Some(_connection_id) = connection_id {
-                return;
-            }
-
-            let world = it.system().world();
-            let _entity = it.entity(row);
-
-            if velocity.0 != Vec3::ZERO {
-                position.x += velocity.0.x;
-                position.y += velocity.0.y;
-                position.z += velocity.0.z;
-                // let (new_yaw, new_pitch) = get_rotation_from_velocity(velocity.0);
-
-                let center = **position;
-
-                let ray = geometry::ray::Ray::new(center, velocity.0);
-
-                #[allow(clippy::excessive_nesting)]
-                world.get::<&mut Blocks>(|blocks| {
-                    let Some(collision) = blocks.first_collision(ray) else {
-                        // Drag (0.99 / 20.0)
-                        // 1.0 - (0.99 / 20.0) * 0.05
-                        velocity.0 *= 0.997_525;
-
-                        // Gravity (20 MPSS)
-                        velocity.0.y -= 0.05;
-
-                        // Terminal Velocity (100.0)
-                        velocity.0 = velocity.0.clamp_length(0.0, 100.0);
-                        return;
-                    };
-
-                    debug!("collision = {collision:?}");
-
-                    velocity.0 = Vec3::ZERO;
-
-                    // Set arrow position to the collision location
-                    **position = collision.normal;
-
-                    blocks
-                        .set_block(collision.location, BlockState::DIRT)
-                        .unwrap();
-                });
-            }
-        });
-
         track_previous::<Position>(world);
         track_previous::<Yaw>(world);
         track_previous::<Pitch>(world);
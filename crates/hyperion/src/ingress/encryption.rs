@@ -0,0 +1,208 @@
+//! The online-mode key exchange. See [`ServerKeys`].
+
+use std::{fmt::Write, sync::Arc};
+
+use anyhow::{Context, ensure};
+use flecs_ecs::macros::Component;
+use rsa::{Pkcs1v15Encrypt, RsaPrivateKey, RsaPublicKey, pkcs8::EncodePublicKey};
+use sha1::{Digest, Sha1};
+use tracing::error;
+
+use crate::{simulation::skin::PlayerSkin, storage::SkinHandler, util::mojang::MojangClient};
+
+/// The RSA key pair used to exchange the shared secret with clients in online mode.
+///
+/// Only exists as a singleton if [`crate::config::Config::online_mode`] is enabled.
+#[derive(Component)]
+pub struct ServerKeys {
+    private_key: RsaPrivateKey,
+    /// The public key in the DER format the client expects.
+    public_key_der: Box<[u8]>,
+}
+
+impl ServerKeys {
+    /// Generates a new key pair. Vanilla uses 1024 bit keys, which is what clients expect.
+    pub fn generate() -> anyhow::Result<Self> {
+        let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024)
+            .context("failed to generate rsa key")?;
+
+        let public_key_der = RsaPublicKey::from(&private_key)
+            .to_public_key_der()
+            .context("failed to encode rsa public key")?
+            .into_vec()
+            .into_boxed_slice();
+
+        Ok(Self {
+            private_key,
+            public_key_der,
+        })
+    }
+
+    #[must_use]
+    pub fn public_key_der(&self) -> &[u8] {
+        &self.public_key_der
+    }
+
+    fn decrypt(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.private_key
+            .decrypt(Pkcs1v15Encrypt, data)
+            .context("failed to decrypt with server key")
+    }
+
+    /// Decrypts the client's encryption response, checking the verify token and returning the
+    /// shared secret.
+    pub fn decrypt_response(
+        &self,
+        challenge: &EncryptionChallenge,
+        shared_secret: &[u8],
+        verify_token: &[u8],
+    ) -> anyhow::Result<[u8; 16]> {
+        let verify_token = self.decrypt(verify_token)?;
+        ensure!(
+            verify_token == challenge.verify_token,
+            "verify token does not match"
+        );
+
+        let shared_secret = self.decrypt(shared_secret)?;
+        let shared_secret = <[u8; 16]>::try_from(shared_secret.as_slice())
+            .context("shared secret must be 16 bytes")?;
+
+        Ok(shared_secret)
+    }
+}
+
+/// Attached to a player in the login state after the server sent an encryption request.
+#[derive(Component, Debug)]
+pub struct EncryptionChallenge {
+    pub username: Arc<str>,
+    pub verify_token: [u8; 4],
+}
+
+impl EncryptionChallenge {
+    #[must_use]
+    pub fn new(username: Arc<str>) -> Self {
+        Self {
+            username,
+            verify_token: rand::random(),
+        }
+    }
+}
+
+/// A player verified by the session server.
+#[derive(Debug)]
+pub struct AuthenticatedProfile {
+    pub uuid: uuid::Uuid,
+    pub username: Arc<str>,
+    pub skin: PlayerSkin,
+}
+
+/// The outcome of checking a player with the session server, sent back to the world through
+/// [`crate::simulation::Comms`] to finish the login.
+#[derive(Debug)]
+pub struct Authentication {
+    /// The shared secret. Encryption is enabled regardless of the result, since the client
+    /// expects an encrypted disconnect if verification fails.
+    pub key: [u8; 16],
+    pub result: anyhow::Result<AuthenticatedProfile>,
+}
+
+/// Verifies with the session server that `username` is who they claim to be.
+pub async fn authenticate(
+    mojang: &MojangClient,
+    skins: &SkinHandler,
+    username: Arc<str>,
+    server_hash: &str,
+) -> anyhow::Result<AuthenticatedProfile> {
+    let profile = mojang
+        .has_joined(&username, server_hash)
+        .await?
+        .with_context(|| format!("{username} has not joined through the session server"))?;
+
+    let uuid = profile["id"]
+        .as_str()
+        .with_context(|| format!("no id on {profile:?}"))?;
+    let uuid = uuid::Uuid::parse_str(uuid)?;
+
+    // the session server has the correct capitalization of the name
+    let username = profile["name"].as_str().map_or(username, Arc::from);
+
+    let skin = match PlayerSkin::from_profile(&profile) {
+        Ok(Some(skin)) => {
+            skins.insert(uuid, &skin)?;
+            skin
+        }
+        Ok(None) => PlayerSkin::EMPTY,
+        Err(e) => {
+            error!("failed to get skin {e}. Using empty skin");
+            PlayerSkin::EMPTY
+        }
+    };
+
+    Ok(AuthenticatedProfile {
+        uuid,
+        username,
+        skin,
+    })
+}
+
+/// The id the client and the session server both hash the key exchange into.
+///
+/// This is a SHA-1 digest of the (empty) server id, the shared secret and the public key,
+/// formatted the way Java's `BigInteger::toString(16)` does.
+#[must_use]
+pub fn server_hash(shared_secret: &[u8], public_key_der: &[u8]) -> String {
+    let digest = Sha1::new()
+        .chain_update(b"")
+        .chain_update(shared_secret)
+        .chain_update(public_key_der)
+        .finalize();
+
+    minecraft_hex_digest(digest.into())
+}
+
+/// Formats a digest as a signed two's complement number in hex without leading zeros.
+fn minecraft_hex_digest(mut digest: [u8; 20]) -> String {
+    let negative = digest[0] & 0x80 != 0;
+
+    if negative {
+        let mut carry = true;
+        for byte in digest.iter_mut().rev() {
+            *byte = !*byte;
+            if carry {
+                (*byte, carry) = byte.overflowing_add(1);
+            }
+        }
+    }
+
+    let mut hex = String::with_capacity(41);
+
+    if negative {
+        hex.push('-');
+    }
+
+    let mut digits = String::with_capacity(40);
+    for byte in digest {
+        write!(digits, "{byte:02x}").expect("writing to a String cannot fail");
+    }
+
+    hex.push_str(digits.trim_start_matches('0'));
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use sha1::{Digest, Sha1};
+
+    use super::minecraft_hex_digest;
+
+    fn hash(name: &str) -> String {
+        minecraft_hex_digest(Sha1::digest(name).into())
+    }
+
+    #[test]
+    fn hex_digest_matches_vanilla() {
+        assert_eq!(hash("Notch"), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
+        assert_eq!(hash("jeb_"), "-7c9d5b0044c130109a5d7b5fb5c317c02b4e28c1");
+        assert_eq!(hash("simon"), "88e16a1019277b15d58faf0541e11910eb756f6");
+    }
+}
@@ -1,10 +1,9 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, net::SocketAddr, sync::Arc};
 
 use anyhow::Context;
 use colored::Colorize;
 use flecs_ecs::prelude::*;
 use hyperion_utils::EntityExt;
-use serde_json::json;
 use sha2::Digest;
 use tracing::{error, info, info_span, trace, warn};
 use valence_protocol::{
@@ -17,10 +16,17 @@ use valence_text::IntoText;
 
 use crate::{
     Prev, Shutdown,
+    config::Config,
     egress::sync_chunks::ChunkSendQueue,
+    ingress::{
+        encryption::{Authentication, EncryptionChallenge, ServerKeys},
+        moderation::{Moderation, ModerationModule},
+        status::{ServerStatus, StatusModule, legacy_ping_host, legacy_response},
+        transfer::{IncomingTransfer, TransferModule},
+    },
     net::{
-        Compose, ConnectionId, MINECRAFT_VERSION, PROTOCOL_VERSION, PacketDecoder,
-        decoder::BorrowedPacketFrame, proxy::ReceiveState,
+        Compose, ConnectionId, ConnectionInfo, PacketDecoder, decoder::BorrowedPacketFrame,
+        proxy::ReceiveState,
     },
     runtime::AsyncRuntime,
     simulation::{
@@ -28,16 +34,25 @@ use crate::{
         ImmuneStatus, Name, PacketState, Pitch, Player, Position, StreamLookup, Uuid, Velocity, Xp,
         Yaw,
         animation::ActiveAnimation,
+        anticheat::AntiCheat,
         blocks::Blocks,
+        chat::{LastSeen, SecureChat},
+        event,
         handlers::PacketSwitchQuery,
         metadata::{MetadataPrefabs, entity::Pose},
         packet::HandlerRegistry,
         skin::PlayerSkin,
+        worlds::{WorldId, with_blocks},
     },
-    storage::{Events, PlayerJoinServer, SkinHandler},
+    storage::{EventBus, Events, SkinHandler},
     util::{SendableRef, TracingExt, mojang::MojangClient},
 };
 
+pub mod encryption;
+pub mod moderation;
+pub mod status;
+pub mod transfer;
+
 #[derive(Component, Debug)]
 pub struct PendingRemove {
     pub reason: String,
@@ -54,6 +69,7 @@ impl PendingRemove {
 
 fn process_handshake(
     login_state: &mut PacketState,
+    connection_info: &mut ConnectionInfo,
     packet: &BorrowedPacketFrame<'_>,
 ) -> anyhow::Result<()> {
     debug_assert!(
@@ -63,6 +79,18 @@ fn process_handshake(
 
     let handshake: packets::handshaking::HandshakeC2s<'_> = packet.decode()?;
 
+    // modded clients append data such as `\0FML\0`, and some add a trailing dot
+    let server_address = handshake.server_address.0;
+    let server_address = server_address
+        .split('\0')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.');
+
+    connection_info.protocol_version = handshake.protocol_version.0;
+    connection_info.server_address = server_address.to_owned();
+    connection_info.server_port = handshake.server_port;
+
     // todo: check version is correct
     match handshake.next_state {
         HandshakeNextState::Status => {
@@ -87,6 +115,7 @@ fn process_login(
     mojang: MojangClient,
     packet: &BorrowedPacketFrame<'_>,
     stream_id: ConnectionId,
+    connection_info: &ConnectionInfo,
     compose: &Compose,
     entity: &EntityView<'_>,
     system: EntityView<'_>,
@@ -97,19 +126,140 @@ fn process_login(
         "process_login called with invalid state: {login_state:?}"
     );
 
+    if packet.id == login::LoginKeyC2s::ID {
+        return process_encryption_response(
+            world,
+            tasks,
+            comms,
+            skins_collection,
+            mojang,
+            packet,
+            entity,
+        );
+    }
+
     let login::LoginHelloC2s {
         username,
         profile_id,
     } = packet.decode()?;
 
-    let username = username.0;
+    let username: Arc<str> = Arc::from(username.0);
 
-    let player_join = PlayerJoinServer {
-        username: username.to_string(),
-        entity: entity.id(),
-    };
+    if world.get::<&Config>(|config| config.online_mode) {
+        // the player is logged in once the session server has verified them, see
+        // `finish_authentication`
+        let challenge = EncryptionChallenge::new(username);
+
+        world.get::<&ServerKeys>(|keys| {
+            let pkt = login::LoginHelloS2c {
+                server_id: Bounded(""),
+                public_key: keys.public_key_der(),
+                verify_token: &challenge.verify_token,
+            };
+
+            compose.unicast_no_compression(&pkt, stream_id, system)
+        })?;
+
+        entity.set(challenge);
+
+        return Ok(());
+    }
+
+    let uuid = profile_id.unwrap_or_else(|| offline_uuid(&username));
+
+    spawn_skin_fetch(tasks, comms, mojang, skins_collection, entity.id(), uuid);
+
+    finish_login(
+        world,
+        login_state,
+        decoder,
+        stream_id,
+        connection_info,
+        compose,
+        entity,
+        system,
+        ign_map,
+        username,
+        uuid,
+    )
+}
+
+/// Checks the encryption response and asks the session server to verify the player.
+fn process_encryption_response(
+    world: &WorldRef<'_>,
+    tasks: &AsyncRuntime,
+    comms: &Comms,
+    skins_collection: SkinHandler,
+    mojang: MojangClient,
+    packet: &BorrowedPacketFrame<'_>,
+    entity: &EntityView<'_>,
+) -> anyhow::Result<()> {
+    let login::LoginKeyC2s {
+        shared_secret,
+        verify_token,
+    } = packet.decode()?;
+
+    let (username, key, server_hash) = entity
+        .try_get::<&EncryptionChallenge>(|challenge| {
+            world.get::<&ServerKeys>(|keys| {
+                let key = keys.decrypt_response(challenge, shared_secret, verify_token)?;
+                let server_hash = encryption::server_hash(&key, keys.public_key_der());
+                anyhow::Ok((challenge.username.clone(), key, server_hash))
+            })
+        })
+        .context("received encryption response without an encryption request")??;
 
-    let username = player_join.username.as_str();
+    entity.remove::<EncryptionChallenge>();
+
+    let authentications = comms.authentications_tx.clone();
+    let id = entity.id();
+
+    tasks.spawn(async move {
+        let result =
+            encryption::authenticate(&mojang, &skins_collection, username, &server_hash).await;
+
+        authentications
+            .send((id, Authentication { key, result }))
+            .unwrap();
+    });
+
+    Ok(())
+}
+
+/// Switches the player to the play state once we know who they are.
+#[expect(clippy::too_many_arguments, reason = "todo; refactor")]
+fn finish_login(
+    world: &WorldRef<'_>,
+    login_state: &mut PacketState,
+    decoder: &PacketDecoder,
+    stream_id: ConnectionId,
+    connection_info: &ConnectionInfo,
+    compose: &Compose,
+    entity: &EntityView<'_>,
+    system: EntityView<'_>,
+    ign_map: &IgnMap,
+    username: Arc<str>,
+    uuid: uuid::Uuid,
+) -> anyhow::Result<()> {
+    let denial = world.get::<(&Moderation, &Config)>(|(moderation, config)| {
+        let ip = connection_info.address.map(|address| address.ip());
+        moderation.login_denial(uuid, ip, config.whitelist)
+    })?;
+
+    if let Some(reason) = denial {
+        info!("{username} may not join: {reason}");
+
+        let pkt = login::LoginDisconnectS2c {
+            reason: reason.into_cow_text(),
+        };
+
+        compose.unicast_no_compression(&pkt, stream_id, system)?;
+
+        *login_state = PacketState::Terminate;
+        entity.set(PendingRemove::new(""));
+
+        return Ok(());
+    }
 
     let global = compose.global();
 
@@ -121,14 +271,45 @@ fn process_login(
 
     decoder.set_compression(global.shared.compression_threshold);
 
-    let username = Arc::from(username);
-
-    let uuid = profile_id.unwrap_or_else(|| offline_uuid(&username));
     let uuid_s = format!("{uuid:?}").dimmed();
     info!("Starting login: {username} {uuid_s}");
 
+    let pkt = login::LoginSuccessS2c {
+        uuid,
+        username: Bounded(&username),
+        properties: Cow::default(),
+    };
+
+    compose
+        .unicast(&pkt, stream_id, system)
+        .context("failed to send login success packet")?;
+
+    *login_state = PacketState::Play;
+
+    init_player(
+        world,
+        stream_id,
+        connection_info,
+        compose,
+        entity,
+        ign_map,
+        username,
+        uuid,
+    );
+
+    Ok(())
+}
+
+/// Fetches the skin of `uuid` in the background. The player joins the world once it arrives.
+fn spawn_skin_fetch(
+    tasks: &AsyncRuntime,
+    comms: &Comms,
+    mojang: MojangClient,
+    skins_collection: SkinHandler,
+    id: Entity,
+    uuid: uuid::Uuid,
+) {
     let skins = comms.skins_tx.clone();
-    let id = entity.id();
 
     tasks.spawn(async move {
         let skin = match PlayerSkin::from_uuid(uuid, &mojang, &skins_collection).await {
@@ -145,27 +326,37 @@ fn process_login(
 
         skins.send((id, skin)).unwrap();
     });
+}
 
-    let pkt = login::LoginSuccessS2c {
-        uuid,
-        username: Bounded(&username),
-        properties: Cow::default(),
-    };
-
-    compose
-        .unicast(&pkt, stream_id, system)
-        .context("failed to send login success packet")?;
-
-    *login_state = PacketState::Play;
-
+/// Sets up the components of a logged in player, whether they logged in here or were
+/// transferred from another server.
+#[expect(clippy::too_many_arguments, reason = "todo; refactor")]
+fn init_player(
+    world: &WorldRef<'_>,
+    stream_id: ConnectionId,
+    connection_info: &ConnectionInfo,
+    compose: &Compose,
+    entity: &EntityView<'_>,
+    ign_map: &IgnMap,
+    username: Arc<str>,
+    uuid: uuid::Uuid,
+) {
     ign_map.insert(username.clone(), entity.id(), world);
 
+    let info = connection_info.player_info(
+        uuid,
+        &username,
+        compose.global().shared.compression_threshold.0,
+    );
+
     world.get::<&MetadataPrefabs>(|prefabs| {
         entity
             .is_a_id(prefabs.player_base)
             .set(Name::from(username))
             .add::<AiTargetable>()
             .set(ImmuneStatus::default())
+            // before the UUID, which restores the world the player was in
+            .set(WorldId::DEFAULT)
             .set(Uuid::from(uuid))
             .add::<Xp>()
             .set_pair::<Prev, _>(Xp::default())
@@ -175,12 +366,12 @@ fn process_login(
     });
 
     compose.io_buf().set_receive_broadcasts(stream_id, world);
-
-    Ok(())
+    compose.io_buf().set_player_info(stream_id, info, world);
 }
 
 /// Get a [`uuid::Uuid`] based on the given user's name.
-fn offline_uuid(username: &str) -> uuid::Uuid {
+#[must_use]
+pub fn offline_uuid(username: &str) -> uuid::Uuid {
     let digest = sha2::Sha256::digest(username);
     let digest: [u8; 32] = digest.into();
     let (&digest, ..) = digest.split_array_ref::<16>();
@@ -190,12 +381,16 @@ fn offline_uuid(username: &str) -> uuid::Uuid {
     uuid::Uuid::from_u128(digest)
 }
 
+#[expect(clippy::too_many_arguments, reason = "todo; refactor")]
 fn process_status(
     login_state: &mut PacketState,
     system: EntityView<'_>,
     packet: &BorrowedPacketFrame<'_>,
     packets: ConnectionId,
+    connection_info: &ConnectionInfo,
     compose: &Compose,
+    config: &Config,
+    status: &ServerStatus,
 ) -> anyhow::Result<()> {
     debug_assert!(
         *login_state == PacketState::Status,
@@ -206,30 +401,15 @@ fn process_status(
         packets::status::QueryRequestC2s::ID => {
             let query_request: packets::status::QueryRequestC2s = packet.decode()?;
 
-            // let img_bytes = include_bytes!("data/hyperion.png");
-
-            // let favicon = general_purpose::STANDARD.encode(img_bytes);
-            // let favicon = format!("data:image/png;base64,{favicon}");
-
             let online = compose
                 .global()
                 .player_count
                 .load(std::sync::atomic::Ordering::Relaxed);
 
-            // https://wiki.vg/Server_List_Ping#Response
-            let json = json!({
-                "version": {
-                    "name": MINECRAFT_VERSION,
-                    "protocol": PROTOCOL_VERSION,
-                },
-                "players": {
-                    "online": online,
-                    "max": 12_000,
-                    "sample": [],
-                },
-                "description": "Getting 10k Players to PvP at Once on a Minecraft Server to Break the Guinness World Record",
-                // "favicon": favicon,
-            });
+            let secure_chat = system.world().get::<&SecureChat>(SecureChat::enforced);
+
+            let json =
+                status.response(config, &connection_info.server_address, online, secure_chat);
 
             let json = serde_json::to_string_pretty(&json)?;
 
@@ -266,16 +446,26 @@ pub struct IngressModule;
 impl Module for IngressModule {
     #[expect(clippy::too_many_lines)]
     fn module(world: &World) {
+        let all_blocks = world.new_query::<&mut Blocks>();
+
         system!(
             "shutdown",
             world,
             &Shutdown($),
+            &AsyncRuntime($),
         )
         .kind::<flecs::pipeline::OnLoad>()
-        .each_iter(|it, _, shutdown| {
+        .each_iter(move |it, _, (shutdown, runtime)| {
             let world = it.world();
             if shutdown.value.load(std::sync::atomic::Ordering::Relaxed) {
                 info!("shutting down");
+
+                all_blocks.each(|blocks| {
+                    if let Err(e) = blocks.save_and_wait(runtime) {
+                        error!("failed to save world: {e:?}");
+                    }
+                });
+
                 world.quit();
             }
         });
@@ -314,18 +504,37 @@ impl Module for IngressModule {
             let mut recv = receive.0.lock();
 
             for connect in recv.player_connect.drain(..) {
-                info!("player_connect");
-                let view = world
-                    .entity()
-                    .set(ConnectionId::new(connect))
+                let address = connect.address.map(SocketAddr::from);
+                info!("player_connect from {address:?}");
+
+                let mut connection_info = ConnectionInfo::new(address);
+
+                let view = world.entity();
+
+                if let Some(transfer) = connect.transfer {
+                    // the handshake happened on the previous server
+                    connection_info.protocol_version = transfer.protocol_version;
+                    connection_info
+                        .server_address
+                        .clone_from(&transfer.server_address);
+                    connection_info.server_port = transfer.server_port;
+
+                    view.set(IncomingTransfer(transfer));
+                }
+
+                view.set(ConnectionId::new(connect.stream))
+                    .set(connection_info)
                     .set(hyperion_inventory::PlayerInventory::default())
+                    .set(hyperion_inventory::action::Cursor::default())
                     .set(ConfirmBlockSequences::default())
                     .set(PacketState::Handshake)
                     .set(ActiveAnimation::NONE)
+                    .set(AntiCheat::default())
+                    .set(LastSeen::default())
                     .set(PacketDecoder::default())
                     .add::<Player>();
 
-                lookup.insert(connect, view.id());
+                lookup.insert(connect.stream, view.id());
             }
 
             for disconnect in recv.player_disconnect.drain(..) {
@@ -339,6 +548,16 @@ impl Module for IngressModule {
                     .entity_from_id(*id)
                     .set(PendingRemove::new("disconnected"));
             }
+
+            for transferred in recv.player_transferred.drain(..) {
+                // the client is now connected to another server, so there is no one to send a
+                // disconnect packet to
+                let Some(id) = lookup.get(&transferred).copied() else {
+                    error!("failed to get id for transferred stream {transferred:?}");
+                    continue;
+                };
+                world.entity_from_id(*id).set(PendingRemove::new(""));
+            }
         });
 
         #[expect(
@@ -449,14 +668,99 @@ impl Module for IngressModule {
             .kind::<flecs::pipeline::PostLoad>()
             .with::<&PendingRemove>()
             .tracing_each_entity(info_span!("remove_player"), |entity, ()| {
+                let world = entity.world();
+                let reason = entity.get::<&PendingRemove>(|pending| pending.reason.clone());
+
+                let mut quit = event::PlayerQuit {
+                    player: entity.id(),
+                    reason,
+                };
+
+                EventBus::dispatch(&world, &mut quit);
+
                 entity.destruct();
             });
 
+        system!(
+            "finish_authentication",
+            world,
+            &Comms($),
+            &Compose($),
+            &IgnMap($),
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(|it, _, (comms, compose, ign_map)| {
+            let span = info_span!("finish_authentication");
+            let _enter = span.enter();
+
+            let world = it.world();
+            let system = it.system();
+
+            while let Ok(Some((id, authentication))) = comms.authentications_rx.try_recv() {
+                if !world.is_alive(id) {
+                    continue;
+                }
+
+                let entity = world.entity_from_id(id);
+                let Authentication { key, result } = authentication;
+
+                let failed = entity.get::<(
+                    &ConnectionId,
+                    &ConnectionInfo,
+                    &PacketDecoder,
+                    &mut PacketState,
+                )>(
+                    |(&stream_id, connection_info, decoder, login_state)| {
+                        // the client already encrypts, even if we are about to disconnect it
+                        compose.io_buf().set_encryption(stream_id, key, &world);
+
+                        let result = result.and_then(|profile| {
+                            finish_login(
+                                &world,
+                                login_state,
+                                decoder,
+                                stream_id,
+                                connection_info,
+                                compose,
+                                &entity,
+                                system,
+                                ign_map,
+                                profile.username,
+                                profile.uuid,
+                            )?;
+
+                            comms.skins_tx.send((id, profile.skin))?;
+                            Ok(())
+                        });
+
+                        let Err(e) = result else {
+                            return false;
+                        };
+
+                        warn!("failed to authenticate player: {e}");
+
+                        let pkt = login::LoginDisconnectS2c {
+                            reason: "§cFailed to verify username!".into_cow_text(),
+                        };
+
+                        if let Err(e) = compose.unicast_no_compression(&pkt, stream_id, system) {
+                            error!("failed to send login disconnect packet: {e}");
+                        }
+
+                        true
+                    },
+                );
+
+                if failed {
+                    entity.destruct();
+                }
+            }
+        });
+
         system!(
             "recv_data",
             world,
             &Compose($),
-            &Blocks($),
             &AsyncRuntime($),
             &Comms($),
             &SkinHandler($),
@@ -465,17 +769,22 @@ impl Module for IngressModule {
             &mut PacketDecoder,
             &mut PacketState,
             &ConnectionId,
+            &mut ConnectionInfo,
             ?&mut Pose,
             &Events($),
             &mut EntitySize,
             ?&mut Position,
+            ?&WorldId,
             &mut Yaw,
             &mut Pitch,
             &mut ConfirmBlockSequences,
             &mut hyperion_inventory::PlayerInventory,
             &mut ActiveAnimation,
+            &mut AntiCheat,
             &hyperion_crafting::CraftingRegistry($),
             &IgnMap($),
+            &Config($),
+            &ServerStatus($),
         )
         .kind::<flecs::pipeline::OnUpdate>()
         .multi_threaded()
@@ -484,7 +793,6 @@ impl Module for IngressModule {
                   row,
                   (
                 compose,
-                blocks,
                 tasks,
                 comms,
                 skins_collection,
@@ -493,17 +801,22 @@ impl Module for IngressModule {
                 decoder,
                 login_state,
                 &io_ref,
+                connection_info,
                 mut pose,
                 event_queue,
                 size,
                 mut position,
+                world_id,
                 yaw,
                 pitch,
                 confirm_block_sequences,
                 inventory,
                 animation,
+                anticheat,
                 crafting_registry,
                 ign_map,
+                config,
+                status,
             )| {
                 let system = it.system();
                 let world = it.world();
@@ -512,6 +825,26 @@ impl Module for IngressModule {
                 let bump = compose.bump.get(&world);
 
                 loop {
+                    if *login_state == PacketState::Handshake
+                        && let Some(ping) = decoder.legacy_ping()
+                    {
+                        let online = compose
+                            .global()
+                            .player_count
+                            .load(std::sync::atomic::Ordering::Relaxed);
+
+                        let null_separated = ping.get(1) == Some(&0x01);
+                        let host = legacy_ping_host(ping).unwrap_or_default();
+                        let response = legacy_response(config, &host, online, null_separated);
+
+                        decoder.clear();
+                        compose.io_buf().unicast_raw(&response, io_ref, system);
+
+                        trace!("answered legacy server list ping");
+                        *login_state = PacketState::Terminate;
+                        break;
+                    }
+
                     let frame = match decoder.try_next_packet(bump) {
                         Ok(frame) => frame,
                         Err(e) => {
@@ -527,7 +860,7 @@ impl Module for IngressModule {
 
                     match *login_state {
                         PacketState::Handshake => {
-                            if process_handshake(login_state, &frame).is_err() {
+                            if process_handshake(login_state, connection_info, &frame).is_err() {
                                 error!("failed to process handshake");
 
                                 entity.destruct();
@@ -536,9 +869,16 @@ impl Module for IngressModule {
                             }
                         }
                         PacketState::Status => {
-                            if let Err(e) =
-                                process_status(login_state, system, &frame, io_ref, compose)
-                            {
+                            if let Err(e) = process_status(
+                                login_state,
+                                system,
+                                &frame,
+                                io_ref,
+                                connection_info,
+                                compose,
+                                config,
+                                status,
+                            ) {
                                 error!("failed to process status packet: {e}");
                                 entity.destruct();
                                 break;
@@ -555,6 +895,7 @@ impl Module for IngressModule {
                                 mojang.clone(),
                                 &frame,
                                 io_ref,
+                                connection_info,
                                 compose,
                                 &entity,
                                 system,
@@ -591,36 +932,43 @@ impl Module for IngressModule {
                             // todo: better way?
                             if let Some((position, pose)) = position.as_mut().zip(pose.as_mut()) {
                                 let world = &world;
-
-                                let mut query = PacketSwitchQuery {
-                                    id: entity.id(),
-                                    view: entity,
-                                    compose,
-                                    io_ref,
-                                    position,
-                                    yaw,
-                                    pitch,
-                                    size,
-                                    pose,
-                                    events: event_queue,
-                                    world,
-                                    blocks,
-                                    system,
-                                    confirm_block_sequences,
-                                    inventory,
-                                    animation,
-                                    crafting_registry,
-                                    handler_registry,
-                                };
-
-                                // info_span!("ingress", ign = name).in_scope(|| {
-                                // SAFETY: The packet bytes are allocated in the compose bump
-                                if let Err(err) = unsafe {
-                                    crate::simulation::handlers::packet_switch(frame, &mut query)
-                                } {
-                                    error!("failed to process packet {frame:?}: {err}");
-                                }
-                                // });
+                                let world_id = world_id.copied().unwrap_or_default();
+
+                                with_blocks(*world, world_id, |blocks| {
+                                    let mut query = PacketSwitchQuery {
+                                        id: entity.id(),
+                                        view: entity,
+                                        compose,
+                                        io_ref,
+                                        position,
+                                        yaw,
+                                        pitch,
+                                        size,
+                                        pose,
+                                        events: event_queue,
+                                        world,
+                                        blocks,
+                                        system,
+                                        confirm_block_sequences,
+                                        inventory,
+                                        animation,
+                                        crafting_registry,
+                                        handler_registry,
+                                        anticheat,
+                                        config,
+                                    };
+
+                                    // info_span!("ingress", ign = name).in_scope(|| {
+                                    // SAFETY: The packet bytes are allocated in the compose bump
+                                    if let Err(err) = unsafe {
+                                        crate::simulation::handlers::packet_switch(
+                                            frame, &mut query,
+                                        )
+                                    } {
+                                        error!("failed to process packet {frame:?}: {err}");
+                                    }
+                                    // });
+                                });
                             }
                         }
                         PacketState::Terminate => {
@@ -630,5 +978,9 @@ impl Module for IngressModule {
                 }
             },
         );
+
+        world.import::<TransferModule>();
+        world.import::<StatusModule>();
+        world.import::<ModerationModule>();
     }
 }
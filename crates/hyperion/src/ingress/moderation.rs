@@ -0,0 +1,287 @@
+//! Bans, mutes and the whitelist, stored in the [`LocalDb`].
+//!
+//! Bans and the whitelist are checked when a player logs in, see [`Moderation::login_denial`].
+//! Mutes only have an effect if the chat handling of the game mode checks [`Moderation::mute_of`].
+
+use std::{
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flecs_ecs::prelude::*;
+use heed::{Database, types};
+use rkyv::{Archive, Deserialize, Serialize, rancor};
+
+use crate::storage::{LocalDb, Record};
+
+/// A ban or a mute.
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Sanction {
+    pub reason: String,
+    /// The name of whoever issued it.
+    pub by: String,
+    /// When it ends, in seconds since the Unix epoch. `None` if it is permanent.
+    pub expires_at: Option<u64>,
+}
+
+impl Sanction {
+    /// A sanction that ends after `duration`, or never if `duration` is `None`.
+    #[must_use]
+    pub fn new(
+        reason: impl Into<String>,
+        by: impl Into<String>,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            reason: reason.into(),
+            by: by.into(),
+            expires_at: duration.map(|duration| now().saturating_add(duration.as_secs())),
+        }
+    }
+
+    /// Whether the sanction has not ended yet.
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now())
+    }
+
+    /// How long until the sanction ends, `None` if it is permanent.
+    #[must_use]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| Duration::from_secs(expires_at.saturating_sub(now())))
+    }
+
+    /// A message for the sanctioned player, e.g. `You are banned` followed by the reason and how
+    /// long it lasts.
+    #[must_use]
+    pub fn message(&self, headline: &str) -> String {
+        let mut message = format!("§c{headline}");
+
+        if !self.reason.is_empty() {
+            message.push_str(&format!(": §f{}", self.reason));
+        }
+
+        match self.remaining() {
+            Some(remaining) => message.push_str(&format!(
+                "\n§7Ends in {}",
+                humantime::format_duration(remaining)
+            )),
+            None => message.push_str("\n§7This is permanent"),
+        }
+
+        message
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+/// The bans, mutes and whitelist of the server.
+#[derive(Component, Debug, Clone)]
+pub struct Moderation {
+    db: LocalDb,
+    /// The [`Sanction`]s of banned players by UUID.
+    bans: Database<types::Bytes, types::Bytes>,
+    /// The [`Sanction`]s of banned IP addresses by the address as text.
+    ip_bans: Database<types::Bytes, types::Bytes>,
+    /// The [`Sanction`]s of muted players by UUID.
+    mutes: Database<types::Bytes, types::Bytes>,
+    /// The names of whitelisted players by UUID.
+    whitelist: Database<types::Bytes, types::Str>,
+}
+
+impl Moderation {
+    /// Creates a new [`Moderation`] from a given [`LocalDb`].
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let (bans, ip_bans, mutes, whitelist) = db.write(|wtxn| {
+            Ok((
+                db.create_database(wtxn, Some("uuid-to-bans"))?,
+                db.create_database(wtxn, Some("ip-to-bans"))?,
+                db.create_database(wtxn, Some("uuid-to-mutes"))?,
+                db.create_database(wtxn, Some("uuid-to-whitelist"))?,
+            ))
+        })?;
+
+        Ok(Self {
+            db: db.clone(),
+            bans,
+            ip_bans,
+            mutes,
+            whitelist,
+        })
+    }
+
+    pub fn ban(&self, uuid: uuid::Uuid, sanction: &Sanction) -> anyhow::Result<()> {
+        self.put(self.bans, uuid.as_bytes(), sanction)
+    }
+
+    /// Lifts the ban of a player. Returns whether they were banned.
+    pub fn unban(&self, uuid: uuid::Uuid) -> anyhow::Result<bool> {
+        self.delete(self.bans, uuid.as_bytes())
+    }
+
+    /// The active ban of a player.
+    pub fn ban_of(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<Sanction>> {
+        self.get(self.bans, uuid.as_bytes())
+    }
+
+    pub fn ban_ip(&self, ip: IpAddr, sanction: &Sanction) -> anyhow::Result<()> {
+        self.put(self.ip_bans, ip.to_string().as_bytes(), sanction)
+    }
+
+    /// Lifts the ban of an IP address. Returns whether it was banned.
+    pub fn unban_ip(&self, ip: IpAddr) -> anyhow::Result<bool> {
+        self.delete(self.ip_bans, ip.to_string().as_bytes())
+    }
+
+    /// The active ban of an IP address.
+    pub fn ip_ban_of(&self, ip: IpAddr) -> anyhow::Result<Option<Sanction>> {
+        self.get(self.ip_bans, ip.to_string().as_bytes())
+    }
+
+    pub fn mute(&self, uuid: uuid::Uuid, sanction: &Sanction) -> anyhow::Result<()> {
+        self.put(self.mutes, uuid.as_bytes(), sanction)
+    }
+
+    /// Lifts the mute of a player. Returns whether they were muted.
+    pub fn unmute(&self, uuid: uuid::Uuid) -> anyhow::Result<bool> {
+        self.delete(self.mutes, uuid.as_bytes())
+    }
+
+    /// The active mute of a player.
+    pub fn mute_of(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<Sanction>> {
+        self.get(self.mutes, uuid.as_bytes())
+    }
+
+    pub fn whitelist_add(&self, uuid: uuid::Uuid, name: &str) -> anyhow::Result<()> {
+        self.db
+            .write(|wtxn| Ok(self.whitelist.put(wtxn, uuid.as_bytes(), name)?))
+    }
+
+    /// Removes a player from the whitelist. Returns whether they were on it.
+    pub fn whitelist_remove(&self, uuid: uuid::Uuid) -> anyhow::Result<bool> {
+        self.db
+            .write(|wtxn| Ok(self.whitelist.delete(wtxn, uuid.as_bytes())?))
+    }
+
+    pub fn is_whitelisted(&self, uuid: uuid::Uuid) -> anyhow::Result<bool> {
+        self.db
+            .read(|rtxn| Ok(self.whitelist.get(rtxn, uuid.as_bytes())?.is_some()))
+    }
+
+    /// The UUIDs and names of the whitelisted players.
+    pub fn whitelisted(&self) -> anyhow::Result<Vec<(uuid::Uuid, String)>> {
+        self.db.read(|rtxn| {
+            let mut players = Vec::new();
+
+            for entry in self.whitelist.iter(rtxn)? {
+                let (uuid, name) = entry?;
+                players.push((uuid::Uuid::from_slice(uuid)?, name.to_owned()));
+            }
+
+            Ok(players)
+        })
+    }
+
+    /// Why a player may not join, or `None` if they may.
+    pub fn login_denial(
+        &self,
+        uuid: uuid::Uuid,
+        ip: Option<IpAddr>,
+        whitelist: bool,
+    ) -> anyhow::Result<Option<String>> {
+        if let Some(ban) = self.ban_of(uuid)? {
+            return Ok(Some(ban.message("You are banned from this server")));
+        }
+
+        if let Some(ip) = ip
+            && let Some(ban) = self.ip_ban_of(ip)?
+        {
+            return Ok(Some(
+                ban.message("Your IP address is banned from this server"),
+            ));
+        }
+
+        if whitelist && !self.is_whitelisted(uuid)? {
+            return Ok(Some("§cYou are not whitelisted on this server".to_owned()));
+        }
+
+        Ok(None)
+    }
+
+    fn get(
+        &self,
+        sanctions: Database<types::Bytes, types::Bytes>,
+        key: &[u8],
+    ) -> anyhow::Result<Option<Sanction>> {
+        let sanction = self.db.read(|rtxn| {
+            sanctions
+                .get(rtxn, key)?
+                .map(Sanction::from_bytes)
+                .transpose()
+        })?;
+
+        // expired sanctions are left in place until they are lifted or replaced
+        Ok(sanction.filter(Sanction::is_active))
+    }
+
+    fn put(
+        &self,
+        sanctions: Database<types::Bytes, types::Bytes>,
+        key: &[u8],
+        sanction: &Sanction,
+    ) -> anyhow::Result<()> {
+        let sanction = rkyv::to_bytes::<rancor::Error>(sanction)?;
+        self.db
+            .write(|wtxn| Ok(sanctions.put(wtxn, key, &sanction)?))
+    }
+
+    fn delete(
+        &self,
+        sanctions: Database<types::Bytes, types::Bytes>,
+        key: &[u8],
+    ) -> anyhow::Result<bool> {
+        self.db.write(|wtxn| Ok(sanctions.delete(wtxn, key)?))
+    }
+}
+
+#[derive(Component)]
+pub struct ModerationModule;
+
+impl Module for ModerationModule {
+    fn module(world: &World) {
+        world.component::<Moderation>();
+
+        let moderation = world.get::<&LocalDb>(Moderation::new).unwrap();
+        world.set(moderation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Sanction;
+
+    #[test]
+    fn sanctions_expire() {
+        let permanent = Sanction::new("griefing", "admin", None);
+        assert!(permanent.is_active());
+        assert_eq!(permanent.remaining(), None);
+
+        let temporary = Sanction::new("spam", "admin", Some(Duration::from_secs(60)));
+        assert!(temporary.is_active());
+        assert!(temporary.remaining().unwrap() <= Duration::from_secs(60));
+
+        let expired = Sanction {
+            expires_at: Some(1),
+            ..temporary
+        };
+        assert!(!expired.is_active());
+        assert_eq!(expired.remaining(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,315 @@
+//! The reply to server list pings.
+//!
+//! Besides the settings in [`Config`], the reply has a favicon and a sample of the online players,
+//! which changes every few seconds so that everyone gets listed eventually. Clients from before
+//! 1.7 ping with a single unframed packet, see [`legacy_response`].
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, ensure};
+use base64::{Engine as _, engine::general_purpose};
+use flecs_ecs::prelude::*;
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    config::Config,
+    net::{MINECRAFT_VERSION, PROTOCOL_VERSION},
+    simulation::{Name, PacketState, Uuid},
+};
+
+const DEFAULT_FAVICON: &[u8] = include_bytes!("data/hyperion.png");
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// How often the sample of online players changes.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The first byte of a legacy server list ping.
+pub const LEGACY_PING: u8 = 0xFE;
+
+/// The ID of the legacy kick packet, which legacy pings are answered with.
+const LEGACY_KICK: u8 = 0xFF;
+
+/// What the server list shows besides the settings in [`Config`].
+#[derive(Component, Debug, Default)]
+pub struct ServerStatus {
+    /// The file [`Self::favicon`] was loaded from, `None` for the default favicon.
+    favicon_path: Option<PathBuf>,
+    /// The favicon as a data URL.
+    favicon: Option<String>,
+    /// The names and UUIDs of the listed players.
+    sample: Vec<(String, uuid::Uuid)>,
+    /// Where the next sample starts in the online players.
+    sample_offset: usize,
+}
+
+impl ServerStatus {
+    #[must_use]
+    pub fn new(config: &Config) -> Self {
+        let mut status = Self::default();
+        status.load_favicon(config.favicon.clone());
+        status
+    }
+
+    fn load_favicon(&mut self, path: Option<PathBuf>) {
+        self.favicon = favicon(path.as_deref())
+            .inspect_err(|e| warn!("not showing a favicon: {e:#}"))
+            .ok();
+        self.favicon_path = path;
+    }
+
+    /// The JSON reply to a status request from a client that connected with the hostname `host`.
+    /// `secure_chat` is whether chat messages have to be signed, see [`crate::simulation::chat`].
+    ///
+    /// See <https://wiki.vg/Server_List_Ping#Status_Response>.
+    #[must_use]
+    pub fn response(
+        &self,
+        config: &Config,
+        host: &str,
+        online: usize,
+        secure_chat: bool,
+    ) -> serde_json::Value {
+        let sample: Vec<_> = self
+            .sample
+            .iter()
+            .map(|(name, id)| json!({ "name": name, "id": id.to_string() }))
+            .collect();
+
+        let mut json = json!({
+            "version": {
+                "name": MINECRAFT_VERSION,
+                "protocol": PROTOCOL_VERSION,
+            },
+            "players": {
+                "online": online,
+                "max": config.max_players,
+                "sample": sample,
+            },
+            "description": config.motd(host),
+            "enforcesSecureChat": secure_chat,
+        });
+
+        if let Some(favicon) = &self.favicon {
+            json["favicon"] = favicon.as_str().into();
+        }
+
+        json
+    }
+}
+
+fn favicon(path: Option<&Path>) -> anyhow::Result<String> {
+    let bytes = match path {
+        Some(path) => Cow::Owned(
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?,
+        ),
+        None => Cow::Borrowed(DEFAULT_FAVICON),
+    };
+
+    ensure!(bytes.starts_with(PNG_SIGNATURE), "the favicon is not a PNG");
+
+    let favicon = general_purpose::STANDARD.encode(bytes);
+    Ok(format!("data:image/png;base64,{favicon}"))
+}
+
+/// The hostname a legacy ping was sent to. Only clients since 1.6 send it, in a `MC|PingHost`
+/// plugin message after `0xFE 0x01`.
+#[must_use]
+pub fn legacy_ping_host(ping: &[u8]) -> Option<String> {
+    const CHANNEL: &str = "MC|PingHost";
+
+    let mut rest = ping.strip_prefix(&[LEGACY_PING, 0x01, 0xFA])?;
+
+    let read_string = |rest: &mut &[u8]| -> Option<String> {
+        let (len, tail) = rest.split_first_chunk::<2>()?;
+        let len = usize::from(u16::from_be_bytes(*len));
+        let (string, tail) = tail.split_at_checked(2 * len)?;
+
+        let units: Vec<u16> = string
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+
+        *rest = tail;
+        String::from_utf16(&units).ok()
+    };
+
+    if read_string(&mut rest)? != CHANNEL {
+        return None;
+    }
+
+    // the length of the data and the protocol version
+    let mut rest = rest.get(3..)?;
+
+    read_string(&mut rest)
+}
+
+/// The reply to a legacy ping sent to the hostname `host`: a kick packet with the status as the
+/// reason.
+///
+/// Clients since 1.4 send `0xFE 0x01` and read the fields separated by `\0`. Older clients only
+/// send `0xFE` and read the MOTD and player counts separated by `§`.
+#[must_use]
+pub fn legacy_response(
+    config: &Config,
+    host: &str,
+    online: usize,
+    null_separated: bool,
+) -> Vec<u8> {
+    let motd = config.motd(host);
+
+    let reason = if null_separated {
+        format!(
+            "§1\0{PROTOCOL_VERSION}\0{MINECRAFT_VERSION}\0{motd}\0{online}\0{}",
+            config.max_players
+        )
+    } else {
+        // `§` separates the fields, so it can't be used for formatting
+        let motd = motd.replace('§', "");
+        format!("{motd}§{online}§{}", config.max_players)
+    };
+
+    let reason: Vec<u16> = reason.encode_utf16().take(usize::from(u16::MAX)).collect();
+    let len = u16::try_from(reason.len()).unwrap_or(u16::MAX);
+
+    let mut response = Vec::with_capacity(3 + 2 * reason.len());
+    response.push(LEGACY_KICK);
+    response.extend_from_slice(&len.to_be_bytes());
+
+    for unit in reason {
+        response.extend_from_slice(&unit.to_be_bytes());
+    }
+
+    response
+}
+
+#[derive(Component)]
+pub struct StatusModule;
+
+impl Module for StatusModule {
+    fn module(world: &World) {
+        world.component::<ServerStatus>();
+
+        let status = world.get::<&Config>(ServerStatus::new);
+        world.set(status);
+
+        let players = world
+            .query::<(&Name, &Uuid)>()
+            .with_enum(PacketState::Play)
+            .build();
+
+        let mut last_sample = Instant::now();
+
+        system!("update_server_status", world, &mut ServerStatus($), &Config($))
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each(move |(status, config)| {
+                if status.favicon_path != config.favicon {
+                    status.load_favicon(config.favicon.clone());
+                }
+
+                if last_sample.elapsed() < SAMPLE_INTERVAL {
+                    return;
+                }
+
+                last_sample = Instant::now();
+
+                let mut online = Vec::new();
+                players.each(|(name, uuid)| online.push((name.to_string(), uuid.0)));
+
+                status.sample.clear();
+
+                if online.is_empty() {
+                    return;
+                }
+
+                let start = status.sample_offset % online.len();
+                let count = config.sample_players.min(online.len());
+
+                status
+                    .sample
+                    .extend(online.into_iter().cycle().skip(start).take(count));
+                status.sample_offset = start + count;
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LEGACY_PING, legacy_ping_host, legacy_response};
+    use crate::config::Config;
+
+    fn utf16(string: &str) -> Vec<u8> {
+        let units: Vec<u16> = string.encode_utf16().collect();
+
+        let mut bytes = u16::try_from(units.len()).unwrap().to_be_bytes().to_vec();
+        bytes.extend(units.into_iter().flat_map(u16::to_be_bytes));
+        bytes
+    }
+
+    #[test]
+    fn legacy_response_is_utf16_kick_packet() {
+        let config = Config {
+            server_desc: "§aHi".to_owned(),
+            max_players: 20,
+            ..Config::default()
+        };
+
+        let response = legacy_response(&config, "", 3, false);
+        let expected = "aHi§3§20";
+
+        assert_eq!(response[0], 0xFF);
+        assert_eq!(
+            u16::from_be_bytes([response[1], response[2]]),
+            u16::try_from(expected.encode_utf16().count()).unwrap()
+        );
+
+        let reason: Vec<u16> = response[3..]
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        assert_eq!(String::from_utf16(&reason).unwrap(), expected);
+
+        let response = legacy_response(&config, "", 3, true);
+        let reason: Vec<u16> = response[3..]
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        let reason = String::from_utf16(&reason).unwrap();
+        let fields: Vec<_> = reason.split('\0').collect();
+        assert_eq!(fields[0], "§1");
+        assert_eq!(&fields[3..], ["§aHi", "3", "20"]);
+    }
+
+    #[test]
+    fn legacy_pings_since_1_6_have_a_host() {
+        let host = utf16("play.example.com");
+
+        let mut ping = vec![LEGACY_PING, 0x01, 0xFA];
+        ping.extend(utf16("MC|PingHost"));
+        ping.extend(u16::try_from(host.len() + 5).unwrap().to_be_bytes());
+        ping.push(78);
+        ping.extend(host);
+        ping.extend(25565_i32.to_be_bytes());
+
+        assert_eq!(legacy_ping_host(&ping).as_deref(), Some("play.example.com"));
+        assert_eq!(legacy_ping_host(&ping[..20]), None);
+        assert_eq!(legacy_ping_host(&[LEGACY_PING, 0x01]), None);
+
+        let config = Config {
+            motds: [("play.example.com".to_owned(), "Welcome!".to_owned())].into(),
+            ..Config::default()
+        };
+
+        let response = legacy_response(&config, "play.example.com", 0, false);
+        let reason: Vec<u16> = response[3..]
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        assert!(String::from_utf16(&reason).unwrap().starts_with("Welcome!"));
+    }
+}
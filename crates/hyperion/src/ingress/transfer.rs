@@ -0,0 +1,201 @@
+//! Moving players between the servers behind one proxy. See [`Transfer`].
+
+use std::{borrow::Cow, sync::Arc};
+
+use flecs_ecs::prelude::*;
+use hyperion_proto::TransferredPlayer;
+use tracing::{error, info, info_span, warn};
+use valence_protocol::packets::play;
+use valence_text::IntoText;
+
+use crate::{
+    config::Config,
+    ingress::{PendingRemove, init_player, moderation::Moderation, spawn_skin_fetch},
+    net::{Compose, ConnectionId, ConnectionInfo, PacketDecoder},
+    runtime::AsyncRuntime,
+    simulation::{Comms, IgnMap, Name, PacketState, Player, Uuid},
+    storage::SkinHandler,
+    util::mojang::MojangClient,
+};
+
+/// Set on a player to move them to another server behind the same proxy.
+///
+/// `backend` is the name the other server is configured with in the proxy. The client stays
+/// connected; the player is removed from this server once the proxy has moved them, and the other
+/// server makes the client load its world.
+#[derive(Component, Debug)]
+pub struct Transfer {
+    pub backend: String,
+}
+
+impl Transfer {
+    #[must_use]
+    pub fn new(backend: impl Into<String>) -> Self {
+        Self {
+            backend: backend.into(),
+        }
+    }
+}
+
+/// Attached to a player the proxy moved here from another server until they are set up.
+#[derive(Component, Debug)]
+pub struct IncomingTransfer(pub TransferredPlayer);
+
+/// Marks a player that joined through a transfer. Their client still has the world of the
+/// previous server loaded.
+#[derive(Component, Debug, Default)]
+pub struct Transferred;
+
+#[derive(Component)]
+pub struct TransferModule;
+
+impl Module for TransferModule {
+    fn module(world: &World) {
+        let players = world.query::<&Uuid>().with::<Player>().build();
+
+        system!(
+            "transfer_players",
+            world,
+            &Compose($),
+            &Transfer,
+            &PacketState,
+            &ConnectionId,
+            &Name,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(move |it, row, (compose, transfer, state, &stream, name)| {
+            let span = info_span!("transfer_players");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+            let entity = it.entity(row);
+
+            entity.remove::<Transfer>();
+
+            if *state != PacketState::Play {
+                warn!("cannot transfer {name} before they joined the world");
+                return;
+            }
+
+            // the next server sends its own player list
+            let mut uuids = Vec::new();
+            players.each(|uuid| uuids.push(uuid.0));
+
+            let pkt = play::PlayerRemoveS2c {
+                uuids: Cow::Owned(uuids),
+            };
+
+            if let Err(e) = compose.unicast(&pkt, stream, system) {
+                error!("failed to send player remove packet: {e}");
+            }
+
+            info!("transferring {name} to {}", transfer.backend);
+
+            compose.io_buf().transfer(stream, &transfer.backend, &world);
+        });
+
+        system!(
+            "accept_transfers",
+            world,
+            &Compose($),
+            &AsyncRuntime($),
+            &Comms($),
+            &SkinHandler($),
+            &MojangClient($),
+            &IgnMap($),
+            &Moderation($),
+            &Config($),
+            &IncomingTransfer,
+            &ConnectionId,
+            &ConnectionInfo,
+            &PacketDecoder,
+            &mut PacketState,
+        )
+        .kind::<flecs::pipeline::OnLoad>()
+        .each_iter(
+            |it,
+             row,
+             (
+                compose,
+                tasks,
+                comms,
+                skins_collection,
+                mojang,
+                ign_map,
+                moderation,
+                config,
+                transfer,
+                &stream,
+                connection_info,
+                decoder,
+                state,
+            )| {
+                let span = info_span!("accept_transfers");
+                let _enter = span.enter();
+
+                let system = it.system();
+                let world = it.world();
+                let entity = it.entity(row);
+
+                let IncomingTransfer(player) = transfer;
+                let uuid = uuid::Uuid::from_u128(player.uuid);
+                let username = Arc::<str>::from(player.username.as_str());
+
+                info!("{username} was transferred from another server");
+
+                // the client negotiated compression with the previous server during login
+                decoder.set_compression(compose.global().shared.compression_threshold);
+                *state = PacketState::Play;
+
+                // players who are banned or not whitelisted here must not get in through another
+                // server either
+                let ip = connection_info.address.map(|address| address.ip());
+                let denial = moderation
+                    .login_denial(uuid, ip, config.whitelist)
+                    .unwrap_or_else(|e| {
+                        error!("failed to check if {username} may join: {e:#}");
+                        Some("Could not check if you may join this server".to_owned())
+                    });
+
+                if let Some(reason) = denial {
+                    info!("{username} may not join: {reason}");
+
+                    let pkt = play::DisconnectS2c {
+                        reason: reason.into_cow_text(),
+                    };
+
+                    if let Err(e) = compose.unicast(&pkt, stream, system) {
+                        error!("failed to send disconnect packet: {e}");
+                    }
+
+                    entity.remove::<IncomingTransfer>();
+                    entity.set(PendingRemove::new(""));
+                    return;
+                }
+
+                init_player(
+                    &world,
+                    stream,
+                    connection_info,
+                    compose,
+                    &entity,
+                    ign_map,
+                    username,
+                    uuid,
+                );
+
+                spawn_skin_fetch(
+                    tasks,
+                    comms,
+                    mojang.clone(),
+                    skins_collection.clone(),
+                    entity.id(),
+                    uuid,
+                );
+
+                entity.remove::<IncomingTransfer>().add::<Transferred>();
+            },
+        );
+    }
+}
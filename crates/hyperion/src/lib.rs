@@ -25,6 +25,9 @@
 #![feature(pointer_is_aligned_to)]
 
 pub const NUM_THREADS: usize = 8;
+/// Where the [`config::Config`] is read from. Changes to it are applied while the server is
+/// running; see [`config::reload`].
+pub const CONFIG_PATH: &str = "run/config.toml";
 pub const CHUNK_HEIGHT_SPAN: u32 = 384; // 512; // usually 384
 
 use std::{
@@ -34,6 +37,7 @@ use std::{
     io::Write,
     net::SocketAddr,
     sync::{Arc, atomic::AtomicBool},
+    time::Instant,
 };
 
 use anyhow::Context;
@@ -47,9 +51,20 @@ use ingress::IngressModule;
 #[cfg(unix)]
 use libc::{RLIMIT_NOFILE, getrlimit, setrlimit};
 use libdeflater::CompressionLvl;
-use simulation::{Comms, SimModule, StreamLookup, blocks::Blocks};
-use storage::{Events, LocalDb, SkinHandler, ThreadLocal};
-use tracing::{info, info_span, warn};
+use simulation::{
+    Comms, SimModule, StreamLookup,
+    anticheat::AntiCheatModule,
+    blocks::Blocks,
+    chat::SecureChat,
+    container::ContainerModule,
+    physics::PhysicsModule,
+    window::WindowModule,
+    worlds::{Worlds, WorldsModule},
+};
+use storage::{
+    EventBus, Events, LocalDb, PlayerDataModule, PlayerDataStore, SkinHandler, ThreadLocal,
+};
+use tracing::{error, info, info_span, warn};
 use util::mojang::MojangClient;
 pub use uuid;
 pub use valence_protocol as protocol;
@@ -76,8 +91,12 @@ use system_order::SystemOrderModule;
 pub use valence_ident;
 
 use crate::{
-    ingress::PendingRemove,
-    net::{ConnectionId, PacketDecoder, proxy::ReceiveState},
+    ingress::{
+        PendingRemove,
+        encryption::{EncryptionChallenge, ServerKeys},
+        transfer::{IncomingTransfer, Transfer, Transferred},
+    },
+    net::{ConnectionId, ConnectionInfo, PacketDecoder, proxy::ReceiveState},
     runtime::Tasks,
     simulation::{EgressComm, EntitySize, IgnMap, PacketState, Player, packet::HandlerRegistry},
     util::mojang::ApiProvider,
@@ -256,6 +275,7 @@ impl HyperionCore {
         world.component::<PacketState>();
 
         world.component::<ConnectionId>();
+        world.component::<ConnectionInfo>();
         world.component::<ReceiveState>();
         world.component::<Compose>();
         world.component::<CraftingRegistry>();
@@ -283,6 +303,47 @@ impl HyperionCore {
                 }
             });
 
+        let mut last_autosave = Instant::now();
+        let all_blocks = world.new_query::<&mut Blocks>();
+
+        system!(
+            "autosave",
+            world,
+            &AsyncRuntime($),
+            &config::Config($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each(move |(runtime, config)| {
+            let Some(interval) = config.autosave_interval() else {
+                return;
+            };
+
+            if last_autosave.elapsed() < interval {
+                return;
+            }
+
+            last_autosave = Instant::now();
+
+            let span = info_span!("autosave");
+            let _enter = span.enter();
+
+            all_blocks.each(|blocks| {
+                blocks.retry_failed_saves();
+
+                if !blocks.has_unsaved_changes() {
+                    return;
+                }
+
+                let save = blocks.save();
+
+                runtime.spawn(async move {
+                    if let Err(e) = save.await {
+                        error!("failed to autosave world: {e:?}");
+                    }
+                });
+            });
+        });
+
         world.component::<StreamLookup>();
         world.component::<EntitySize>();
         world.component::<IgnMap>();
@@ -290,7 +351,10 @@ impl HyperionCore {
         world.component::<config::Config>();
 
         info!("starting hyperion");
-        let config = config::Config::load("run/config.toml")?;
+        let config = config::Config::load(CONFIG_PATH)?;
+        let online_mode = config.online_mode;
+        let session_server = config.session_server.clone();
+        let database = config.database.clone();
         world.set(config);
 
         let (task_tx, task_rx) = kanal::bounded(32);
@@ -326,15 +390,51 @@ impl HyperionCore {
         world.component::<HandlerRegistry>();
         world.set(HandlerRegistry::default());
 
+        world.component::<EventBus>();
+        world.set(EventBus::default());
+
         info!("initializing database");
-        let db = LocalDb::new()?;
+        let db = LocalDb::new(&database)?;
         let skins = SkinHandler::new(&db)?;
+        let player_data = PlayerDataStore::new(&db)?;
         info!("database initialized");
 
         world.set(db);
         world.set(skins);
+        world.set(player_data);
+
+        world.component::<ServerKeys>();
+        world.component::<EncryptionChallenge>();
+
+        world.component::<Transfer>();
+        world.component::<IncomingTransfer>();
+        world.component::<Transferred>();
+
+        if online_mode {
+            info!("online mode enabled, generating server keys");
+            world.set(ServerKeys::generate()?);
+        }
+
+        let provider = ApiProvider::MAT_DOES_DEV.with_session_server(session_server);
+        let mojang = MojangClient::new(&runtime, provider);
 
-        world.set(MojangClient::new(&runtime, ApiProvider::MAT_DOES_DEV));
+        world.component::<SecureChat>();
+
+        // chat sessions can only be verified against the keys of Mojang
+        let secure_chat = if online_mode {
+            runtime
+                .block_on(mojang.player_certificate_keys())
+                .and_then(|keys| SecureChat::from_der(&keys))
+                .unwrap_or_else(|e| {
+                    warn!("not enforcing secure chat, failed to get the keys of Mojang: {e:#}");
+                    SecureChat::default()
+                })
+        } else {
+            SecureChat::default()
+        };
+
+        world.set(secure_chat);
+        world.set(mojang);
 
         #[rustfmt::skip]
         world
@@ -358,7 +458,15 @@ impl HyperionCore {
             IoBuf::default(),
         ));
 
-        world.set(CraftingRegistry::default());
+        let mut crafting_registry = CraftingRegistry::default();
+
+        if let Some(datapack) = world.get::<&config::Config>(|config| config.datapack.clone()) {
+            let mut tags = egress::player_join::item_tags()?;
+            tags.load_datapack(&datapack)?;
+            crafting_registry.load_datapack(&datapack, &tags)?;
+        }
+
+        world.set(crafting_registry);
 
         world.set(Comms::default());
 
@@ -372,8 +480,17 @@ impl HyperionCore {
         world.import::<SimModule>();
         world.import::<EgressModule>();
         world.import::<IngressModule>();
+        world.import::<WorldsModule>();
+        world.import::<AntiCheatModule>();
+        world.import::<ContainerModule>();
+        world.import::<PhysicsModule>();
+        world.import::<WindowModule>();
+        world.import::<config::reload::ConfigReloadModule>();
+        world.import::<PlayerDataModule>();
         world.import::<SystemOrderModule>();
 
+        world.set(config::reload::ConfigFile::new(CONFIG_PATH));
+
         world
             .component::<Player>()
             .add_trait::<(flecs::With, EntitySize)>();
@@ -397,6 +514,7 @@ impl HyperionCore {
 
         world.set(IgnMap::default());
         world.set(Blocks::empty(world));
+        Worlds::init_default(world)?;
 
         Ok(())
     }
@@ -10,6 +10,8 @@ use valence_protocol::{
     CompressionThreshold, Decode, MAX_PACKET_SIZE, Packet, VarInt, var_int::VarIntDecodeError,
 };
 
+use crate::ingress::status::LEGACY_PING;
+
 #[derive(Default)]
 struct RefBytesMut {
     cursor: Cell<usize>,
@@ -245,6 +247,19 @@ impl PacketDecoder {
         self.threshold.set(threshold);
     }
 
+    /// The bytes of a legacy server list ping if the buffer starts with one. Legacy pings are not
+    /// framed like other packets, so they have to be checked for before [`Self::try_next_packet`].
+    #[must_use]
+    pub fn legacy_ping(&self) -> Option<&[u8]> {
+        let bytes = &self.buf[..];
+        (bytes.first() == Some(&LEGACY_PING)).then_some(bytes)
+    }
+
+    /// Drops all bytes in the buffer.
+    pub fn clear(&self) {
+        self.buf.advance(self.buf[..].len());
+    }
+
     /// Queues a slice of bytes into the buffer.
     pub fn queue_slice(&mut self, bytes: &[u8]) {
         self.buf.inner.extend_from_slice(bytes);
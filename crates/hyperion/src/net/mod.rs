@@ -3,6 +3,7 @@
 use std::{
     cell::{Cell, RefCell},
     fmt::Debug,
+    net::SocketAddr,
 };
 
 use bumpalo::Bump;
@@ -15,7 +16,9 @@ use flecs_ecs::{
     macros::Component,
 };
 use glam::I16Vec2;
-use hyperion_proto::{ChunkPosition, ServerToProxyMessage};
+use hyperion_proto::{
+    ChunkPosition, PeerAddress, PlayerInfo, ServerToProxyMessage, TransferredPlayer,
+};
 use hyperion_utils::LifetimeTracker;
 use libdeflater::CompressionLvl;
 use rkyv::util::AlignedVec;
@@ -24,6 +27,7 @@ use system_order::SystemOrder;
 use crate::{
     Global, PacketBundle, Scratch, Scratches,
     net::encoder::{PacketEncoder, append_packet_without_compression},
+    simulation::worlds::WorldId,
     storage::ThreadLocal,
 };
 
@@ -101,6 +105,54 @@ impl ConnectionId {
     }
 }
 
+/// Where a player connected from and what they sent in their handshake.
+///
+/// The address is forwarded by the proxy when the player connects. The handshake fields are filled
+/// in once the handshake packet has been received.
+#[derive(Component, Clone, Debug, Default)]
+pub struct ConnectionInfo {
+    /// The address of the client. This is `None` if the proxy accepted the client on a unix
+    /// socket.
+    pub address: Option<SocketAddr>,
+    /// The protocol version the client reported in its handshake.
+    pub protocol_version: i32,
+    /// The hostname the client used to connect, e.g. `play.example.com`.
+    pub server_address: String,
+    /// The port the client used to connect.
+    pub server_port: u16,
+}
+
+impl ConnectionInfo {
+    #[must_use]
+    pub fn new(address: Option<SocketAddr>) -> Self {
+        Self {
+            address,
+            ..Self::default()
+        }
+    }
+
+    /// What the proxy needs to know about a player to connect them to another backend.
+    #[must_use]
+    pub fn player_info(
+        &self,
+        uuid: uuid::Uuid,
+        username: &str,
+        compression_threshold: i32,
+    ) -> PlayerInfo {
+        PlayerInfo {
+            address: self.address.map(PeerAddress::from),
+            compression_threshold,
+            player: TransferredPlayer {
+                uuid: uuid.as_u128(),
+                username: username.to_owned(),
+                protocol_version: self.protocol_version,
+                server_address: self.server_address.clone(),
+                server_port: self.server_port,
+            },
+        }
+    }
+}
+
 /// A singleton that can be used to compose and encode packets.
 #[derive(Component)]
 pub struct Compose {
@@ -155,14 +207,17 @@ impl<'a, 'b> DataBundle<'a, 'b> {
     }
 
     // todo: use builder pattern for excluding
-    pub fn broadcast_local(&self, center: I16Vec2) -> anyhow::Result<()> {
+    pub fn broadcast_local(&self, world: WorldId, center: I16Vec2) -> anyhow::Result<()> {
         if self.data.is_empty() {
             return Ok(());
         }
 
-        self.compose
-            .io_buf
-            .broadcast_local_raw(&self.data, center, 0, self.system);
+        self.compose.io_buf.broadcast_local_raw(
+            &self.data,
+            chunk_position(world, center),
+            0,
+            self.system,
+        );
         Ok(())
     }
 }
@@ -206,6 +261,7 @@ impl Compose {
             packet,
             compose: self,
             exclude: 0,
+            world: None,
             system,
         }
     }
@@ -221,12 +277,13 @@ impl Compose {
         &mut self.io_buf
     }
 
-    /// Broadcast a packet within a certain region.
+    /// Broadcast a packet within a certain region of a world. Only players in `world` receive it.
     ///
     /// See <https://github.com/andrewgazelka/hyperion-proto/blob/main/src/server_to_proxy.proto#L17-L22>
     pub const fn broadcast_local<'a, 'b, P>(
         &'a self,
         packet: P,
+        world: WorldId,
         center: I16Vec2,
         system: EntityView<'b>,
     ) -> BroadcastLocal<'a, 'b, P>
@@ -237,10 +294,7 @@ impl Compose {
             packet,
             compose: self,
             exclude: 0,
-            center: ChunkPosition {
-                x: center.x,
-                z: center.y,
-            },
+            center: chunk_position(world, center),
             system,
         }
     }
@@ -343,6 +397,7 @@ pub struct Broadcast<'a, 'b, P> {
     packet: P,
     compose: &'a Compose,
     exclude: u64,
+    world: Option<WorldId>,
     system: EntityView<'b>,
 }
 
@@ -386,7 +441,7 @@ impl<P> Broadcast<'_, '_, P> {
 
         self.compose
             .io_buf
-            .broadcast_raw(&bytes, self.exclude, self.system);
+            .broadcast_raw(&bytes, self.exclude, self.world, self.system);
 
         Ok(())
     }
@@ -399,9 +454,18 @@ impl<P> Broadcast<'_, '_, P> {
             packet: self.packet,
             compose: self.compose,
             exclude,
+            world: self.world,
             system: self.system,
         }
     }
+
+    /// Only send the packet to players in `world`.
+    pub fn world(self, world: WorldId) -> Self {
+        Broadcast {
+            world: Some(world),
+            ..self
+        }
+    }
 }
 
 #[must_use]
@@ -448,6 +512,14 @@ impl<P> BroadcastLocal<'_, '_, P> {
     }
 }
 
+const fn chunk_position(world: WorldId, center: I16Vec2) -> ChunkPosition {
+    ChunkPosition {
+        world: world.0,
+        x: center.x,
+        z: center.y,
+    }
+}
+
 impl IoBuf {
     /// Returns an iterator over the result of splitting the buffer into packets with [`BytesMut::split`].
     pub fn reset_and_split(&mut self) -> impl Iterator<Item = Bytes> + '_ {
@@ -527,11 +599,10 @@ impl IoBuf {
     fn broadcast_local_raw(
         &self,
         data: &[u8],
-        center: impl Into<ChunkPosition>,
+        center: ChunkPosition,
         exclude: u64,
         system: EntityView<'_>,
     ) {
-        let center = center.into();
         let world = system.world();
         let system_order = SystemOrder::of(system);
 
@@ -559,7 +630,13 @@ impl IoBuf {
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
     }
 
-    pub(crate) fn broadcast_raw(&self, data: &[u8], exclude: u64, system: EntityView<'_>) {
+    pub(crate) fn broadcast_raw(
+        &self,
+        data: &[u8],
+        exclude: u64,
+        world: Option<WorldId>,
+        system: EntityView<'_>,
+    ) {
         let world = system.world();
         let buffer = self.buffer.get(&world);
         let buffer = &mut *buffer.borrow_mut();
@@ -575,6 +652,7 @@ impl IoBuf {
             // Fortunately, `to_vec` will not require any allocation if the buffer is empty.
             exclude,
             order,
+            world: world.map(|world| world.0),
         };
 
         let to_send = ServerToProxyMessage::BroadcastGlobal(to_send);
@@ -635,4 +713,71 @@ impl IoBuf {
         let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
         buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
     }
+
+    /// Tells the proxy to encrypt and decrypt all further traffic of `stream` with `key`.
+    pub(crate) fn set_encryption(&self, stream: ConnectionId, key: [u8; 16], world: &World) {
+        let buffer = self.buffer.get(world);
+        let buffer = &mut *buffer.borrow_mut();
+
+        let to_send = hyperion_proto::SetEncryption {
+            stream: stream.stream_id,
+            key,
+        };
+
+        let to_send = ServerToProxyMessage::SetEncryption(to_send);
+
+        let len = buffer.len();
+        buffer.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut *buffer).unwrap();
+
+        let new_len = buffer.len();
+        let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
+        buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+    }
+
+    /// Tells the proxy who the player on `stream` is once they are in the play state, so it can move
+    /// them to another backend or keep them connected while this server restarts.
+    pub(crate) fn set_player_info(&self, stream: ConnectionId, info: PlayerInfo, world: &World) {
+        let buffer = self.buffer.get(world);
+        let buffer = &mut *buffer.borrow_mut();
+
+        let to_send = hyperion_proto::SetPlayerInfo {
+            stream: stream.stream_id,
+            info,
+        };
+
+        let to_send = ServerToProxyMessage::SetPlayerInfo(to_send);
+
+        let len = buffer.len();
+        buffer.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut *buffer).unwrap();
+
+        let new_len = buffer.len();
+        let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
+        buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+    }
+
+    /// Tells the proxy to move `stream` to the backend named `backend`.
+    pub(crate) fn transfer(&self, stream: ConnectionId, backend: &str, world: &World) {
+        let buffer = self.buffer.get(world);
+        let buffer = &mut *buffer.borrow_mut();
+
+        let to_send = hyperion_proto::Transfer {
+            stream: stream.stream_id,
+            backend,
+        };
+
+        let to_send = ServerToProxyMessage::Transfer(to_send);
+
+        let len = buffer.len();
+        buffer.write_u64::<byteorder::BigEndian>(0x00).unwrap();
+
+        rkyv::api::high::to_bytes_in::<_, rkyv::rancor::Error>(&to_send, &mut *buffer).unwrap();
+
+        let new_len = buffer.len();
+        let packet_len = u64::try_from(new_len - len - size_of::<u64>()).unwrap();
+        buffer[len..(len + 8)].copy_from_slice(&packet_len.to_be_bytes());
+    }
 }
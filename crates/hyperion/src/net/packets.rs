@@ -79,3 +79,64 @@ pub enum BossBarAction<'a> {
     UpdateStyle(BossBarColor, BossBarDivision),
     UpdateFlags(BossBarFlags),
 }
+
+/// A system chat message whose text can have hover and click events.
+#[derive(Clone, Debug, Encode, Packet)]
+pub struct GameMessageS2c<'a> {
+    pub chat: hyperion_text::Text<'a>,
+    /// Whether the message is shown above the hotbar instead of in the chat.
+    pub overlay: bool,
+}
+
+/// A chat message of a player with the signature of their chat session, so that clients can
+/// verify it. See [`crate::simulation::chat`].
+#[derive(Clone, Debug, Packet)]
+pub struct ChatMessageS2c<'a> {
+    pub sender: Uuid,
+    /// The index of the message in the chat session of the sender.
+    pub index: VarInt,
+    pub signature: &'a [u8; 256],
+    pub message: &'a str,
+    /// When the message was sent, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub salt: u64,
+    /// The signatures of the messages the sender acknowledged, which their signature covers.
+    pub last_seen: &'a [[u8; 256]],
+    /// The registry id of the chat type the message is shown with.
+    pub chat_type: VarInt,
+    /// The `sender` parameter of the chat type.
+    pub network_name: hyperion_text::Text<'a>,
+}
+
+impl Encode for ChatMessageS2c<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        self.sender.encode(&mut w)?;
+        self.index.encode(&mut w)?;
+
+        true.encode(&mut w)?;
+        w.write_all(self.signature)?;
+
+        self.message.encode(&mut w)?;
+        self.timestamp.encode(&mut w)?;
+        self.salt.encode(&mut w)?;
+
+        VarInt(i32::try_from(self.last_seen.len())?).encode(&mut w)?;
+        for signature in self.last_seen {
+            // an id of 0 means that the signature follows, instead of one the client cached
+            VarInt(0).encode(&mut w)?;
+            w.write_all(signature)?;
+        }
+
+        // no unsigned content, which clients would mark as modified by the server
+        false.encode(&mut w)?;
+        // not filtered
+        VarInt(0).encode(&mut w)?;
+
+        self.chat_type.encode(&mut w)?;
+        self.network_name.encode(&mut w)?;
+        // no target name
+        false.encode(&mut w)?;
+
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use std::{collections::HashMap, io::Cursor, net::SocketAddr, process::Command, s
 
 use bytes::{Buf, BytesMut};
 use flecs_ecs::macros::Component;
-use hyperion_proto::ArchivedProxyToServerMessage;
+use hyperion_proto::{ArchivedPlayerDisconnectReason, ArchivedProxyToServerMessage, PlayerConnect};
 use parking_lot::Mutex;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tracing::{error, info, warn};
@@ -15,9 +15,11 @@ use crate::{runtime::AsyncRuntime, simulation::EgressComm};
 #[derive(Default)]
 pub struct ReceiveStateInner {
     /// All players who have recently connected to the server.
-    pub player_connect: Vec<u64>,
+    pub player_connect: Vec<PlayerConnect>,
     /// All players who have recently disconnected from the server.
     pub player_disconnect: Vec<u64>,
+    /// All players who have recently been moved to another server by the proxy.
+    pub player_transferred: Vec<u64>,
     /// A map of stream ids to the corresponding [`BytesMut`] buffers. This represents data from the client to the server.
     pub packets: HashMap<u64, BytesMut>,
 }
@@ -118,13 +120,22 @@ async fn inner(
 
                         match result {
                             ArchivedProxyToServerMessage::PlayerConnect(message) => {
-                                let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
+                                let Ok(message) = rkyv::deserialize::<PlayerConnect, !>(message);
 
-                                shared.lock().player_connect.push(stream);
+                                shared.lock().player_connect.push(message);
                             }
                             ArchivedProxyToServerMessage::PlayerDisconnect(message) => {
                                 let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
-                                shared.lock().player_disconnect.push(stream);
+
+                                let mut shared = shared.lock();
+                                if matches!(
+                                    message.reason,
+                                    ArchivedPlayerDisconnectReason::Transferred
+                                ) {
+                                    shared.player_transferred.push(stream);
+                                } else {
+                                    shared.player_disconnect.push(stream);
+                                }
                             }
                             ArchivedProxyToServerMessage::PlayerPackets(message) => {
                                 let Ok(stream) = rkyv::deserialize::<u64, !>(&message.stream);
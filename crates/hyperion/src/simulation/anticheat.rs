@@ -0,0 +1,749 @@
+//! Server-side checks against clients that cheat.
+//!
+//! Every time a player fails a [`Check`], their violation level for it rises by one. The levels go
+//! down again over time. Once a level is above the threshold of its check in [`AntiCheatConfig`],
+//! the configured [`Response`] is applied and an [`event::Violation`] is sent, so plugins can tell
+//! staff about it.
+//!
+//! The built-in checks run in the packet handlers. Plugins add checks of their own by defining
+//! another [`Check`] and calling [`AntiCheat::flag`] when it fails, which returns the response to
+//! apply.
+//!
+//! [`event::Violation`]: crate::simulation::event::Violation
+
+use std::{collections::BTreeMap, fmt};
+
+use flecs_ecs::prelude::*;
+use glam::{IVec3, Vec3};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use valence_generated::{block::BlockKind, item::ItemKind};
+use valence_nbt::{List, Value};
+use valence_protocol::{GameMode, ItemStack};
+
+use crate::simulation::{
+    EntitySize, aabb, blocks::Blocks, handlers::has_block_collision, physics::in_fluid,
+};
+
+/// How far the player may walk, sprint and jump in one tick, with some leeway for lag.
+const MAX_HORIZONTAL_SPEED: f32 = 0.7;
+
+/// The vertical velocity of a jump.
+const JUMP_VELOCITY: f32 = 0.42;
+
+/// The gravity pulling players down every tick.
+const GRAVITY: f32 = 0.08;
+
+/// How much higher than the apex of a jump a player may get, for step-ups and lag.
+const JUMP_LEEWAY: f32 = 0.35;
+
+/// How long a player may stay in the air without falling, which covers a full jump.
+const HOVER_TICKS: i64 = 20;
+
+/// How far below a player a block may be for the player to stand on it.
+const GROUND_LEEWAY: f32 = 0.05;
+
+/// How long the movement checks are skipped after the player was sent somewhere else, until
+/// the client has caught up.
+const SETBACK_GRACE_TICKS: i64 = 5;
+
+/// How far from their eyes players reach entities, with some leeway for lag.
+const MAX_REACH: f32 = 3.0 + 0.6;
+
+const EYE_HEIGHT: f32 = 1.62;
+
+/// How many ticks faster than the cooldown of the held item a player may attack.
+const ATTACK_LEEWAY_TICKS: i64 = 1;
+
+/// The share of the time it takes to break a block that may be skipped because of lag.
+const BREAK_LEEWAY: f32 = 0.3;
+
+/// What happens when a player fails a check often enough.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// The player is sent back to where they last stood on the ground. Anything else is
+    /// cancelled.
+    RubberBand,
+    /// Whatever the player did is undone, like a move or an attack.
+    Cancel,
+    /// The player is disconnected.
+    Kick,
+}
+
+/// How the violations of a [`Check`] are handled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CheckConfig {
+    pub response: Response,
+    /// The violation level above which the response is applied. With `0`, it is applied to
+    /// every violation.
+    pub threshold: f32,
+    /// How much the violation level goes down every tick.
+    pub decay: f32,
+}
+
+impl CheckConfig {
+    #[must_use]
+    pub const fn new(response: Response, threshold: f32, decay: f32) -> Self {
+        Self {
+            response,
+            threshold,
+            decay,
+        }
+    }
+}
+
+/// Something a cheating client does that the server can detect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Check {
+    /// The name the check is configured by in [`AntiCheatConfig::checks`].
+    pub name: &'static str,
+    /// How violations are handled unless configured otherwise.
+    pub default: CheckConfig,
+}
+
+impl Check {
+    /// Attacking faster than the cooldown of the held item allows.
+    pub const ATTACK_COOLDOWN: Self = Self::new(
+        "attack_cooldown",
+        CheckConfig::new(Response::Cancel, 0.0, 0.01),
+    );
+    /// The checks the server runs on its own.
+    pub const BUILT_IN: [Self; 6] = [
+        Self::REACH,
+        Self::FLY,
+        Self::SPEED,
+        Self::NO_FALL,
+        Self::FAST_BREAK,
+        Self::ATTACK_COOLDOWN,
+    ];
+    /// Breaking blocks faster than the held tool can.
+    pub const FAST_BREAK: Self =
+        Self::new("fast_break", CheckConfig::new(Response::Cancel, 0.0, 0.01));
+    /// Staying in the air or rising higher than a jump gets you.
+    pub const FLY: Self = Self::new("fly", CheckConfig::new(Response::RubberBand, 2.0, 0.05));
+    /// Claiming to be on the ground in mid-air to avoid fall damage.
+    pub const NO_FALL: Self =
+        Self::new("no_fall", CheckConfig::new(Response::RubberBand, 1.0, 0.05));
+    /// Attacking entities further away than a player can reach.
+    pub const REACH: Self = Self::new("reach", CheckConfig::new(Response::Cancel, 0.0, 0.01));
+    /// Moving faster than sprint-jumping, unless the server pushed the player.
+    pub const SPEED: Self = Self::new("speed", CheckConfig::new(Response::RubberBand, 2.0, 0.05));
+
+    #[must_use]
+    pub const fn new(name: &'static str, default: CheckConfig) -> Self {
+        Self { name, default }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// The anti-cheat settings in the [`Config`](crate::config::Config).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct AntiCheatConfig {
+    pub enabled: bool,
+    /// How each check is handled by its name. Checks not listed here use their defaults.
+    pub checks: BTreeMap<String, CheckConfig>,
+}
+
+impl Default for AntiCheatConfig {
+    fn default() -> Self {
+        let checks = Check::BUILT_IN
+            .iter()
+            .map(|check| (check.name.to_owned(), check.default))
+            .collect();
+
+        Self {
+            enabled: true,
+            checks,
+        }
+    }
+}
+
+impl AntiCheatConfig {
+    /// How violations of `check` are handled.
+    #[must_use]
+    pub fn check(&self, check: Check) -> CheckConfig {
+        self.checks
+            .get(check.name)
+            .copied()
+            .unwrap_or(check.default)
+    }
+}
+
+/// A move of a player, as the client claims it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Move {
+    pub from: Vec3,
+    pub to: Vec3,
+    /// Whether the client claims to be on the ground afterwards.
+    pub on_ground: bool,
+}
+
+/// How often a player failed a check recently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Violations {
+    level: f32,
+    /// How much the level goes down every tick.
+    decay: f32,
+}
+
+/// What the anti-cheat knows about a player.
+#[derive(Component, Debug, Default)]
+pub struct AntiCheat {
+    /// The violations of every check the player failed by the name of the check.
+    violations: FxHashMap<&'static str, Violations>,
+    /// Where the player last stood on the ground, which is where rubber-banding sends them.
+    last_ground: Option<Vec3>,
+    /// The tick the player left the ground.
+    left_ground: i64,
+    /// How far the player may move horizontally on top of walking because the server pushed
+    /// them. It fades out every tick.
+    knockback: Vec3,
+    /// The upwards velocity the server gave the player since they left the ground.
+    launch: f32,
+    /// Movement is not checked before this tick.
+    grace_until: i64,
+    last_attack: Option<i64>,
+    /// The block the player is breaking and the tick they started.
+    digging: Option<(IVec3, i64)>,
+    /// Whether the server lets the player fly, like with `/fly`.
+    flight: bool,
+    game_mode: GameMode,
+}
+
+impl AntiCheat {
+    /// The violation level of `check`.
+    #[must_use]
+    pub fn level(&self, check: Check) -> f32 {
+        self.violations
+            .get(check.name)
+            .map_or(0.0, |violations| violations.level)
+    }
+
+    /// Records a violation of `check`. Returns the response to apply, if the violation level is
+    /// above the threshold of the check.
+    pub fn flag(&mut self, config: &AntiCheatConfig, check: Check) -> Option<Response> {
+        let settings = config.check(check);
+
+        let violations = self.violations.entry(check.name).or_insert(Violations {
+            level: 0.0,
+            decay: settings.decay,
+        });
+
+        violations.level += 1.0;
+        violations.decay = settings.decay;
+
+        (violations.level > settings.threshold).then_some(settings.response)
+    }
+
+    /// Where to send the player when rubber-banding them.
+    #[must_use]
+    pub const fn setback(&self) -> Option<Vec3> {
+        self.last_ground
+    }
+
+    /// Lets the client catch up after the server moved the player somewhere else. Call this
+    /// whenever a player is teleported.
+    pub const fn teleported(&mut self, to: Vec3, tick: i64) {
+        self.last_ground = Some(to);
+        self.left_ground = tick;
+        self.grace_until = tick + SETBACK_GRACE_TICKS;
+    }
+
+    /// Tells the movement checks whether the server lets the player fly. Call this whenever the
+    /// abilities sent to a player allow or forbid flying.
+    pub const fn allow_flight(&mut self, allow: bool) {
+        self.flight = allow;
+    }
+
+    /// Tells the checks which game mode the player is in. Call this whenever the game mode of a
+    /// player changes.
+    pub const fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.game_mode = game_mode;
+    }
+
+    /// Whether the player may move freely, so the fly, speed and no-fall checks do not apply.
+    #[must_use]
+    pub const fn moves_freely(&self) -> bool {
+        self.flight || matches!(self.game_mode, GameMode::Creative | GameMode::Spectator)
+    }
+
+    /// Tells the speed and fly checks that the server pushed the player with `velocity`, in blocks
+    /// per tick. Call this whenever a velocity update is sent to a player.
+    pub fn expect_velocity(&mut self, velocity: Vec3) {
+        self.knockback += velocity * Vec3::new(1.0, 0.0, 1.0);
+        self.launch += velocity.y.max(0.0);
+    }
+
+    /// Lowers the violation levels and fades out knockback. Runs every tick.
+    fn tick(&mut self) {
+        self.violations.retain(|_, violations| {
+            violations.level -= violations.decay;
+            violations.level > 0.0
+        });
+
+        self.knockback *= 0.91;
+
+        if self.knockback.length_squared() < 1e-4 {
+            self.knockback = Vec3::ZERO;
+        }
+    }
+
+    /// Runs the movement checks. Returns the check that failed with its response, if any.
+    pub fn check_move(
+        &mut self,
+        config: &AntiCheatConfig,
+        tick: i64,
+        movement: Move,
+        size: EntitySize,
+        blocks: &Blocks,
+    ) -> Option<(Check, Response)> {
+        if !config.enabled || tick < self.grace_until {
+            return None;
+        }
+
+        if self.moves_freely() {
+            // once flight is taken away mid-air, the player falls from here
+            self.last_ground = Some(movement.to);
+            self.left_ground = tick;
+            return None;
+        }
+
+        let Move {
+            from,
+            to,
+            on_ground,
+        } = movement;
+        let delta = to - from;
+
+        let below = to - Vec3::Y * GROUND_LEEWAY;
+        let grounded = has_block_collision(&below, size, blocks);
+        let supported = grounded || is_supported(to, size, blocks);
+
+        let mut failed = Vec::new();
+
+        let horizontal = Vec3::new(delta.x, 0.0, delta.z).length();
+        if horizontal > MAX_HORIZONTAL_SPEED + self.knockback.length() {
+            failed.push(Check::SPEED);
+        }
+
+        if on_ground && !grounded && !supported {
+            failed.push(Check::NO_FALL);
+        }
+
+        if supported {
+            self.last_ground = Some(to);
+            self.left_ground = tick;
+
+            // slime blocks bounce players back up as fast as they fell
+            self.launch = if bounces(to, blocks) {
+                (-delta.y).max(0.0)
+            } else {
+                0.0
+            };
+        } else {
+            let ground_y = self.last_ground.map_or(from.y, |ground| ground.y);
+            let rise = to.y - ground_y;
+
+            let velocity = JUMP_VELOCITY + self.launch;
+            let apex = velocity * velocity / (2.0 * GRAVITY) + JUMP_LEEWAY;
+
+            let hovering =
+                self.launch <= 0.0 && tick - self.left_ground > HOVER_TICKS && delta.y >= 0.0;
+
+            if rise > apex || hovering {
+                failed.push(Check::FLY);
+            }
+        }
+
+        // the strictest response wins
+        failed
+            .into_iter()
+            .filter_map(|check| Some((check, self.flag(config, check)?)))
+            .max_by_key(|(_, response)| severity(*response))
+    }
+
+    /// Runs the reach and attack cooldown checks when the player at `position` attacks an entity
+    /// at `target` while holding `held`.
+    pub fn check_attack(
+        &mut self,
+        config: &AntiCheatConfig,
+        tick: i64,
+        position: Vec3,
+        target: (Vec3, EntitySize),
+        held: &ItemStack,
+    ) -> Option<(Check, Response)> {
+        if !config.enabled {
+            return None;
+        }
+
+        let mut failed = Vec::new();
+
+        let eyes = position + Vec3::Y * EYE_HEIGHT;
+        let (target, size) = target;
+        let target = aabb(target, size);
+        let closest = eyes.clamp(target.min, target.max);
+
+        if eyes.distance(closest) > MAX_REACH {
+            failed.push(Check::REACH);
+        }
+
+        if let Some(last_attack) = self.last_attack
+            && tick - last_attack < attack_cooldown_ticks(held.item) - ATTACK_LEEWAY_TICKS
+        {
+            failed.push(Check::ATTACK_COOLDOWN);
+        }
+
+        self.last_attack = Some(tick);
+
+        failed
+            .into_iter()
+            .filter_map(|check| Some((check, self.flag(config, check)?)))
+            .max_by_key(|(_, response)| severity(*response))
+    }
+
+    /// Remembers that the player started breaking the block at `position`.
+    pub const fn start_digging(&mut self, position: IVec3, tick: i64) {
+        self.digging = Some((position, tick));
+    }
+
+    /// Runs the fast-break check when the player finishes breaking `block` at `position` while
+    /// holding `tool`.
+    pub fn check_break(
+        &mut self,
+        config: &AntiCheatConfig,
+        tick: i64,
+        position: IVec3,
+        block: BlockKind,
+        tool: &ItemStack,
+    ) -> Option<Response> {
+        let started = self.digging.take();
+
+        if !config.enabled || self.game_mode == GameMode::Creative {
+            return None;
+        }
+
+        let needed = break_ticks(block, tool)?;
+
+        let elapsed = match started {
+            Some((digging, started)) if digging == position => tick - started,
+            _ => 0,
+        };
+
+        #[expect(clippy::cast_possible_truncation, reason = "break times are short")]
+        let needed = (needed as f32 * (1.0 - BREAK_LEEWAY)).floor() as i64;
+
+        if elapsed < needed {
+            self.flag(config, Check::FAST_BREAK)
+        } else {
+            None
+        }
+    }
+}
+
+const fn severity(response: Response) -> u8 {
+    match response {
+        Response::Cancel => 0,
+        Response::RubberBand => 1,
+        Response::Kick => 2,
+    }
+}
+
+/// Whether something other than solid ground holds the player up, like water or a ladder.
+fn is_supported(position: Vec3, size: EntitySize, blocks: &Blocks) -> bool {
+    if in_fluid(aabb(position, size), blocks) {
+        return true;
+    }
+
+    let feet = position.floor().as_ivec3();
+
+    [feet, feet - IVec3::Y].into_iter().any(|position| {
+        blocks.get_block(position).is_some_and(|block| {
+            matches!(
+                block.to_kind(),
+                BlockKind::Ladder
+                    | BlockKind::Vine
+                    | BlockKind::Scaffolding
+                    | BlockKind::Cobweb
+                    | BlockKind::TwistingVines
+                    | BlockKind::TwistingVinesPlant
+                    | BlockKind::WeepingVines
+                    | BlockKind::WeepingVinesPlant
+                    | BlockKind::HoneyBlock
+            )
+        })
+    })
+}
+
+/// Whether the block below `position` bounces players up.
+fn bounces(position: Vec3, blocks: &Blocks) -> bool {
+    let below = (position - Vec3::Y * GROUND_LEEWAY).floor().as_ivec3();
+
+    blocks
+        .get_block(below)
+        .is_some_and(|block| block.to_kind() == BlockKind::SlimeBlock)
+}
+
+/// The ticks between two full-strength attacks with `item`, from its attack speed.
+#[expect(clippy::cast_possible_truncation, reason = "cooldowns are short")]
+fn attack_cooldown_ticks(item: ItemKind) -> i64 {
+    let name = item.to_str();
+
+    let attack_speed = if name.ends_with("_sword") {
+        1.6
+    } else if name.ends_with("_axe") {
+        match name {
+            "wooden_axe" | "stone_axe" => 0.8,
+            "iron_axe" => 0.9,
+            _ => 1.0,
+        }
+    } else if name.ends_with("_pickaxe") {
+        1.2
+    } else if name.ends_with("_hoe") {
+        match name {
+            "stone_hoe" => 2.0,
+            "iron_hoe" => 3.0,
+            "diamond_hoe" | "netherite_hoe" => 4.0,
+            _ => 1.0,
+        }
+    } else if name.ends_with("_shovel") {
+        1.0
+    } else if item == ItemKind::Trident {
+        1.1
+    } else {
+        4.0
+    };
+
+    (20.0 / attack_speed).floor() as i64
+}
+
+/// How long it takes to break `block` with `tool` in the best case. Returns `None` for blocks
+/// whose hardness is not known, which are not checked.
+fn break_ticks(block: BlockKind, tool: &ItemStack) -> Option<i64> {
+    let hardness = hardness(block)?;
+
+    if hardness < 0.0 {
+        return Some(i64::MAX);
+    }
+
+    if hardness <= 0.0 {
+        return Some(0);
+    }
+
+    // assume the tool is the right one for the block, so players are never flagged for it
+    let speed = tool_speed(tool);
+    let damage = speed / hardness / 30.0;
+
+    if damage >= 1.0 {
+        return Some(0);
+    }
+
+    #[expect(clippy::cast_possible_truncation, reason = "break times are short")]
+    let ticks = (1.0 / damage).ceil() as i64;
+
+    Some(ticks)
+}
+
+/// How fast `tool` breaks the blocks it is made for, including efficiency.
+fn tool_speed(tool: &ItemStack) -> f32 {
+    let name = tool.item.to_str();
+
+    let is_tool = ["_pickaxe", "_axe", "_shovel", "_hoe"]
+        .iter()
+        .any(|kind| name.ends_with(kind));
+
+    let speed = if tool.item == ItemKind::Shears {
+        15.0
+    } else if !is_tool {
+        1.0
+    } else if name.starts_with("wooden_") {
+        2.0
+    } else if name.starts_with("stone_") {
+        4.0
+    } else if name.starts_with("iron_") {
+        6.0
+    } else if name.starts_with("diamond_") {
+        8.0
+    } else if name.starts_with("netherite_") {
+        9.0
+    } else if name.starts_with("golden_") {
+        12.0
+    } else {
+        1.0
+    };
+
+    let efficiency = efficiency(tool);
+
+    if efficiency > 0 && speed > 1.0 {
+        let bonus = efficiency.saturating_mul(efficiency).saturating_add(1) as f32;
+        speed + bonus
+    } else {
+        speed
+    }
+}
+
+/// The efficiency level of `tool`.
+fn efficiency(tool: &ItemStack) -> i32 {
+    let Some(Value::List(List::Compound(enchantments))) =
+        tool.nbt.as_ref().and_then(|nbt| nbt.get("Enchantments"))
+    else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .filter(|enchantment| {
+            matches!(enchantment.get("id"), Some(Value::String(id)) if id == "minecraft:efficiency")
+        })
+        .find_map(|enchantment| match enchantment.get("lvl") {
+            Some(Value::Short(level)) => Some(i32::from(*level)),
+            Some(Value::Int(level)) => Some(*level),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// The hardness of the most common blocks. Blocks that cannot be broken have a negative hardness.
+const fn hardness(block: BlockKind) -> Option<f32> {
+    let hardness = match block {
+        BlockKind::Grass
+        | BlockKind::TallGrass
+        | BlockKind::Fern
+        | BlockKind::Dandelion
+        | BlockKind::Poppy
+        | BlockKind::Torch
+        | BlockKind::RedstoneWire
+        | BlockKind::Wheat
+        | BlockKind::SugarCane => 0.0,
+        BlockKind::OakLeaves
+        | BlockKind::SpruceLeaves
+        | BlockKind::BirchLeaves
+        | BlockKind::JungleLeaves
+        | BlockKind::AcaciaLeaves
+        | BlockKind::DarkOakLeaves => 0.2,
+        BlockKind::Glass | BlockKind::Glowstone => 0.3,
+        BlockKind::Netherrack => 0.4,
+        BlockKind::Dirt | BlockKind::Sand | BlockKind::RedSand | BlockKind::SoulSand => 0.5,
+        BlockKind::GrassBlock | BlockKind::Gravel | BlockKind::Clay | BlockKind::Mycelium => 0.6,
+        BlockKind::WhiteWool | BlockKind::Sandstone => 0.8,
+        BlockKind::Stone
+        | BlockKind::Granite
+        | BlockKind::Diorite
+        | BlockKind::Andesite
+        | BlockKind::StoneBricks => 1.5,
+        BlockKind::Cobblestone
+        | BlockKind::MossyCobblestone
+        | BlockKind::OakPlanks
+        | BlockKind::SprucePlanks
+        | BlockKind::BirchPlanks
+        | BlockKind::OakLog
+        | BlockKind::SpruceLog
+        | BlockKind::BirchLog
+        | BlockKind::Bricks => 2.0,
+        BlockKind::Deepslate
+        | BlockKind::CoalOre
+        | BlockKind::CopperOre
+        | BlockKind::IronOre
+        | BlockKind::GoldOre
+        | BlockKind::RedstoneOre
+        | BlockKind::LapisOre
+        | BlockKind::DiamondOre
+        | BlockKind::EmeraldOre
+        | BlockKind::EndStone => 3.0,
+        BlockKind::DeepslateCoalOre
+        | BlockKind::DeepslateCopperOre
+        | BlockKind::DeepslateIronOre
+        | BlockKind::DeepslateGoldOre
+        | BlockKind::DeepslateRedstoneOre
+        | BlockKind::DeepslateLapisOre
+        | BlockKind::DeepslateDiamondOre
+        | BlockKind::DeepslateEmeraldOre => 4.5,
+        BlockKind::IronBlock | BlockKind::DiamondBlock => 5.0,
+        BlockKind::Obsidian => 50.0,
+        BlockKind::Bedrock | BlockKind::Barrier => -1.0,
+        _ => return None,
+    };
+
+    Some(hardness)
+}
+
+#[derive(Component)]
+pub struct AntiCheatModule;
+
+impl Module for AntiCheatModule {
+    fn module(world: &World) {
+        world.component::<AntiCheat>();
+
+        system!("anticheat_tick", world, &mut AntiCheat)
+            .multi_threaded()
+            .each(AntiCheat::tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::IVec3;
+    use valence_generated::{block::BlockKind, item::ItemKind};
+    use valence_protocol::{GameMode, ItemStack};
+
+    use super::{AntiCheat, AntiCheatConfig, Check, CheckConfig, Response, break_ticks};
+
+    #[test]
+    fn responses_apply_above_the_threshold() {
+        let mut config = AntiCheatConfig::default();
+        config.checks.insert(
+            Check::FLY.name.to_owned(),
+            CheckConfig::new(Response::Kick, 1.0, 0.5),
+        );
+
+        let mut anticheat = AntiCheat::default();
+
+        assert_eq!(anticheat.flag(&config, Check::FLY), None);
+        assert_eq!(anticheat.flag(&config, Check::FLY), Some(Response::Kick));
+
+        anticheat.tick();
+        assert!((anticheat.level(Check::FLY) - 1.5).abs() < f32::EPSILON);
+
+        for _ in 0..3 {
+            anticheat.tick();
+        }
+
+        assert!(anticheat.level(Check::FLY).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn better_tools_break_blocks_faster() {
+        let hand = ItemStack::EMPTY;
+        let pickaxe = ItemStack::new(ItemKind::DiamondPickaxe, 1, None);
+
+        assert_eq!(break_ticks(BlockKind::Stone, &hand), Some(45));
+        assert_eq!(break_ticks(BlockKind::Stone, &pickaxe), Some(6));
+        assert_eq!(break_ticks(BlockKind::Grass, &hand), Some(0));
+        assert_eq!(break_ticks(BlockKind::Beacon, &hand), None);
+    }
+
+    #[test]
+    fn creative_players_break_blocks_instantly() {
+        let config = AntiCheatConfig::default();
+        let mut anticheat = AntiCheat::default();
+
+        anticheat.start_digging(IVec3::ZERO, 0);
+        assert_eq!(
+            anticheat.check_break(&config, 0, IVec3::ZERO, BlockKind::Stone, &ItemStack::EMPTY),
+            Some(Response::Cancel)
+        );
+
+        anticheat.set_game_mode(GameMode::Creative);
+        anticheat.start_digging(IVec3::ZERO, 0);
+        assert_eq!(
+            anticheat.check_break(&config, 0, IVec3::ZERO, BlockKind::Stone, &ItemStack::EMPTY),
+            None
+        );
+        assert!(anticheat.moves_freely());
+    }
+}
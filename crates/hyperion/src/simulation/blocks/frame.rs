@@ -18,6 +18,7 @@ impl Blocks {
         };
 
         self.should_update.insert(index as u32);
+        self.should_save.insert(index as u32);
     }
 
     pub fn paste(&mut self, offset: IVec3, frame: ArrayView3<'_, BlockState>) {
@@ -48,6 +49,7 @@ impl Blocks {
                 };
 
                 self.should_update.insert(idx as u32);
+                self.should_save.insert(idx as u32);
 
                 let chunk = &mut loaded_chunk.data;
 
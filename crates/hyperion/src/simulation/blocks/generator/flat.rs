@@ -0,0 +1,126 @@
+use glam::IVec2;
+use valence_generated::block::BlockState;
+use valence_server::layer::chunk::Chunk;
+
+use super::{BiomeLookup, ChunkGenerator};
+use crate::{
+    CHUNK_HEIGHT_SPAN,
+    simulation::blocks::{
+        chunk::START_Y,
+        loader::parse::{ColumnData, section::Section},
+    },
+};
+
+/// Generates a superflat world: the same layers of blocks everywhere, starting at the bottom of
+/// the world.
+#[derive(Clone, Debug)]
+pub struct FlatGenerator {
+    /// One block per layer, from the bottom up.
+    layers: Vec<BlockState>,
+    biome: String,
+}
+
+impl FlatGenerator {
+    /// Creates a generator from `(block, thickness)` layers, from the bottom up.
+    #[must_use]
+    pub fn new(layers: impl IntoIterator<Item = (BlockState, u32)>) -> Self {
+        let layers = layers
+            .into_iter()
+            .flat_map(|(block, thickness)| std::iter::repeat_n(block, thickness as usize))
+            .take(CHUNK_HEIGHT_SPAN as usize)
+            .collect();
+
+        Self {
+            layers,
+            biome: "minecraft:plains".to_owned(),
+        }
+    }
+
+    /// Sets the biome of the whole world.
+    #[must_use]
+    pub fn with_biome(mut self, biome: impl Into<String>) -> Self {
+        self.biome = biome.into();
+        self
+    }
+
+    /// The y coordinate of the lowest air block, where players can stand.
+    #[must_use]
+    pub fn surface_y(&self) -> i32 {
+        i32::from(START_Y) + i32::try_from(self.layers.len()).unwrap()
+    }
+}
+
+impl Default for FlatGenerator {
+    /// The vanilla superflat preset: bedrock, two layers of dirt and grass.
+    fn default() -> Self {
+        Self::new([
+            (BlockState::BEDROCK, 1),
+            (BlockState::DIRT, 2),
+            (BlockState::GRASS_BLOCK, 1),
+        ])
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _position: IVec2, biomes: &BiomeLookup<'_>) -> ColumnData {
+        let mut chunk = ColumnData::new_with(CHUNK_HEIGHT_SPAN, Section::empty_sky);
+
+        for (y, &block) in (0_u32..).zip(&self.layers) {
+            for x in 0..16 {
+                for z in 0..16 {
+                    chunk.set_block_state(x, y, z, block);
+                }
+            }
+        }
+
+        let biome = biomes.get(&self.biome);
+        for section in 0..CHUNK_HEIGHT_SPAN / 16 {
+            chunk.fill_biome_section(section, biome);
+        }
+
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::IVec2;
+    use valence_generated::block::BlockState;
+    use valence_registry::{RegistryIdx, biome::BiomeId};
+    use valence_server::layer::chunk::Chunk;
+
+    use super::FlatGenerator;
+    use crate::simulation::blocks::generator::{BiomeLookup, ChunkGenerator, tests};
+
+    #[test]
+    fn default_layers() {
+        let biomes = tests::biome_registry();
+        let generator = FlatGenerator::default();
+        let chunk = generator.generate(IVec2::new(12, -7), &BiomeLookup::new(&biomes));
+
+        for (x, z) in [(0, 0), (15, 15), (4, 9)] {
+            assert_eq!(chunk.block_state(x, 0, z), BlockState::BEDROCK);
+            assert_eq!(chunk.block_state(x, 1, z), BlockState::DIRT);
+            assert_eq!(chunk.block_state(x, 2, z), BlockState::DIRT);
+            assert_eq!(chunk.block_state(x, 3, z), BlockState::GRASS_BLOCK);
+            assert_eq!(chunk.block_state(x, 4, z), BlockState::AIR);
+        }
+
+        assert_eq!(generator.surface_y(), -60);
+        assert_eq!(chunk.biome(1, 50, 2), BiomeId::from_index(0));
+    }
+
+    #[test]
+    fn custom_layers_and_biome() {
+        let biomes = tests::biome_registry();
+        let generator = FlatGenerator::new([(BlockState::STONE, 64), (BlockState::SAND, 3)])
+            .with_biome("minecraft:desert");
+        let chunk = generator.generate(IVec2::ZERO, &BiomeLookup::new(&biomes));
+
+        assert_eq!(chunk.block_state(3, 63, 3), BlockState::STONE);
+        assert_eq!(chunk.block_state(3, 66, 3), BlockState::SAND);
+        assert_eq!(chunk.block_state(3, 67, 3), BlockState::AIR);
+        assert_eq!(generator.surface_y(), 3);
+        assert_eq!(chunk.biome(0, 0, 0), BiomeId::from_index(2));
+    }
+}
@@ -0,0 +1,78 @@
+//! Generates chunks that are not stored in the world's region files.
+//!
+//! A [`ChunkGenerator`] is consulted by the chunk loader whenever the region files have no chunk
+//! at a position. Generated chunks are only written to disk once they are changed, like any other
+//! chunk.
+
+use std::collections::BTreeMap;
+
+use glam::IVec2;
+use valence_protocol::Ident;
+use valence_registry::biome::BiomeId;
+
+use super::loader::parse::ColumnData;
+
+mod flat;
+mod noise;
+
+pub use flat::FlatGenerator;
+pub use noise::{Biome, NoiseGenerator};
+
+/// A source of chunks for positions the world has no saved data for.
+///
+/// Generators must be deterministic: generating the same position twice has to give the same
+/// chunk, since a chunk that was never changed is generated again every time it is loaded.
+pub trait ChunkGenerator: Send + Sync + 'static {
+    /// Generates the column at chunk position `position`. The column must be
+    /// [`CHUNK_HEIGHT_SPAN`](crate::CHUNK_HEIGHT_SPAN) blocks high.
+    fn generate(&self, position: IVec2, biomes: &BiomeLookup<'_>) -> ColumnData;
+}
+
+/// Resolves biome names to the ids of the server's biome registry.
+#[derive(Copy, Clone)]
+pub struct BiomeLookup<'a> {
+    biome_to_id: &'a BTreeMap<Ident<String>, BiomeId>,
+}
+
+impl<'a> BiomeLookup<'a> {
+    #[must_use]
+    pub const fn new(biome_to_id: &'a BTreeMap<Ident<String>, BiomeId>) -> Self {
+        Self { biome_to_id }
+    }
+
+    /// The id of the biome called `name`, e.g. `minecraft:plains`. Unknown biomes resolve to the
+    /// default biome.
+    #[must_use]
+    pub fn get(&self, name: &str) -> BiomeId {
+        self.biome_to_id.get(name).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::BTreeMap;
+
+    use valence_protocol::Ident;
+    use valence_registry::{RegistryIdx, biome::BiomeId};
+
+    /// A biome registry with the biomes the generators use, in a fixed order.
+    pub fn biome_registry() -> BTreeMap<Ident<String>, BiomeId> {
+        [
+            "minecraft:plains",
+            "minecraft:ocean",
+            "minecraft:desert",
+            "minecraft:forest",
+            "minecraft:snowy_plains",
+            "minecraft:windswept_hills",
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            (
+                Ident::new(name.to_owned()).unwrap(),
+                BiomeId::from_index(idx),
+            )
+        })
+        .collect()
+    }
+}
@@ -0,0 +1,389 @@
+use glam::IVec2;
+use valence_generated::block::BlockState;
+use valence_server::layer::chunk::Chunk;
+
+use super::{BiomeLookup, ChunkGenerator};
+use crate::{
+    CHUNK_HEIGHT_SPAN,
+    simulation::blocks::{
+        chunk::START_Y,
+        loader::parse::{ColumnData, section::Section},
+    },
+};
+
+/// Salts that make the noise of the different maps independent of each other.
+const HEIGHT_SALT: u64 = 0x6865_6967_6874;
+const TEMPERATURE_SALT: u64 = 0x7465_6d70;
+const HUMIDITY_SALT: u64 = 0x6875_6d69_6469_7479;
+
+/// The gradients of the noise lattice, picked by hashing the lattice point.
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+    (
+        -std::f64::consts::FRAC_1_SQRT_2,
+        -std::f64::consts::FRAC_1_SQRT_2,
+    ),
+];
+
+/// The biomes [`NoiseGenerator`] places.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Plains,
+    Forest,
+    Desert,
+    SnowyPlains,
+    WindsweptHills,
+}
+
+impl Biome {
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Ocean => "minecraft:ocean",
+            Self::Plains => "minecraft:plains",
+            Self::Forest => "minecraft:forest",
+            Self::Desert => "minecraft:desert",
+            Self::SnowyPlains => "minecraft:snowy_plains",
+            Self::WindsweptHills => "minecraft:windswept_hills",
+        }
+    }
+
+    /// The top block of the terrain.
+    #[must_use]
+    pub const fn surface(self) -> BlockState {
+        match self {
+            Self::Ocean | Self::Desert => BlockState::SAND,
+            Self::Plains | Self::Forest => BlockState::GRASS_BLOCK,
+            Self::SnowyPlains => BlockState::SNOW_BLOCK,
+            Self::WindsweptHills => BlockState::STONE,
+        }
+    }
+
+    /// The blocks between the surface and the stone.
+    #[must_use]
+    pub const fn filler(self) -> BlockState {
+        match self {
+            Self::Ocean => BlockState::SAND,
+            Self::Desert => BlockState::SANDSTONE,
+            Self::Plains | Self::Forest | Self::SnowyPlains => BlockState::DIRT,
+            Self::WindsweptHills => BlockState::STONE,
+        }
+    }
+}
+
+/// Generates rolling terrain with oceans, hills and a few biomes from a seed. The same seed
+/// always generates the same world.
+#[derive(Copy, Clone, Debug)]
+pub struct NoiseGenerator {
+    seed: u64,
+}
+
+impl NoiseGenerator {
+    /// Terrain above this height is windswept hills.
+    const HILLS_LEVEL: i32 = 90;
+    /// Blocks below this height and above the terrain are water.
+    pub const SEA_LEVEL: i32 = 62;
+    /// The depth of the surface and filler blocks.
+    const SOIL_DEPTH: i32 = 4;
+
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    #[must_use]
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The y coordinate of the surface block at block position `x`, `z`.
+    #[must_use]
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "the noise is bounded, so the height is well within i32"
+    )]
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let continents = fbm(self.seed ^ HEIGHT_SALT, x, z, 512.0, 2);
+        let hills = fbm(self.seed.wrapping_add(HEIGHT_SALT), x, z, 96.0, 4);
+
+        let height = hills.mul_add(24.0, continents.mul_add(48.0, 64.0));
+        height.round() as i32
+    }
+
+    /// The biome at block position `x`, `z`.
+    #[must_use]
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let height = self.height_at(x, z);
+
+        if height < Self::SEA_LEVEL {
+            return Biome::Ocean;
+        }
+
+        if height > Self::HILLS_LEVEL {
+            return Biome::WindsweptHills;
+        }
+
+        let temperature = fbm(self.seed ^ TEMPERATURE_SALT, x, z, 384.0, 2);
+        let humidity = fbm(self.seed ^ HUMIDITY_SALT, x, z, 256.0, 2);
+
+        if temperature > 0.2 {
+            Biome::Desert
+        } else if temperature < -0.2 {
+            Biome::SnowyPlains
+        } else if humidity > 0.1 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    fn block_at(y: i32, height: i32, biome: Biome) -> BlockState {
+        if y == i32::from(START_Y) {
+            BlockState::BEDROCK
+        } else if y <= height - Self::SOIL_DEPTH {
+            BlockState::STONE
+        } else if y < height {
+            biome.filler()
+        } else if y == height {
+            biome.surface()
+        } else if y <= Self::SEA_LEVEL {
+            BlockState::WATER
+        } else {
+            BlockState::AIR
+        }
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, position: IVec2, biomes: &BiomeLookup<'_>) -> ColumnData {
+        let mut chunk = ColumnData::new_with(CHUNK_HEIGHT_SPAN, Section::empty_sky);
+
+        let origin = position * 16;
+        let max_y = i32::from(START_Y) + i32::try_from(CHUNK_HEIGHT_SPAN).unwrap() - 1;
+
+        // vanilla stores biomes in 4x4x4 cells, so the biome of a cell is the one at its center
+        let cells: [[Biome; 4]; 4] = std::array::from_fn(|cell_x| {
+            std::array::from_fn(|cell_z| {
+                let x = origin.x + i32::try_from(cell_x).unwrap() * 4 + 2;
+                let z = origin.y + i32::try_from(cell_z).unwrap() * 4 + 2;
+                self.biome_at(x, z)
+            })
+        });
+
+        for (cell_x, row) in (0_u32..).zip(&cells) {
+            for (cell_z, biome) in (0_u32..).zip(row) {
+                let id = biomes.get(biome.name());
+                for cell_y in 0..CHUNK_HEIGHT_SPAN / 4 {
+                    chunk.set_biome(cell_x, cell_y, cell_z, id);
+                }
+            }
+        }
+
+        for x in 0..16_u32 {
+            for z in 0..16_u32 {
+                let biome = cells[x as usize / 4][z as usize / 4];
+                let world_x = origin.x + i32::try_from(x).unwrap();
+                let world_z = origin.y + i32::try_from(z).unwrap();
+                let height = self
+                    .height_at(world_x, world_z)
+                    .clamp(i32::from(START_Y) + 1, max_y);
+
+                for y in i32::from(START_Y)..=height.max(Self::SEA_LEVEL) {
+                    let block = Self::block_at(y, height, biome);
+                    let y = u32::try_from(y - i32::from(START_Y)).unwrap();
+                    chunk.set_block_state(x, y, z, block);
+                }
+            }
+        }
+
+        chunk
+    }
+}
+
+/// Fractal noise in about `-1.0..=1.0`: `octaves` layers of gradient noise, each with twice the
+/// frequency and half the amplitude of the previous one. `scale` is the size of the largest
+/// features in blocks.
+fn fbm(seed: u64, x: i32, z: i32, scale: f64, octaves: u32) -> f64 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut max = 0.0;
+    let mut frequency = 1.0 / scale;
+
+    for octave in 0..octaves {
+        let seed = seed.wrapping_add(u64::from(octave).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let noise = gradient_noise(seed, f64::from(x) * frequency, f64::from(z) * frequency);
+        total = amplitude.mul_add(noise, total);
+        max += amplitude;
+        amplitude /= 2.0;
+        frequency *= 2.0;
+    }
+
+    total / max
+}
+
+/// Perlin-style gradient noise in about `-1.0..=1.0`.
+#[expect(
+    clippy::cast_possible_truncation,
+    reason = "the coordinates are block coordinates divided by the scale, so they fit in i32"
+)]
+fn gradient_noise(seed: u64, x: f64, z: f64) -> f64 {
+    let x0 = x.floor();
+    let z0 = z.floor();
+    let fx = x - x0;
+    let fz = z - z0;
+    let x0 = x0 as i32;
+    let z0 = z0 as i32;
+
+    let dot = |dx: i32, dz: i32| {
+        let hash = lattice_hash(seed, x0 + dx, z0 + dz);
+        let (gx, gz) = GRADIENTS[usize::try_from(hash % 8).unwrap()];
+        gx.mul_add(fx - f64::from(dx), gz * (fz - f64::from(dz)))
+    };
+
+    let u = fade(fx);
+    let v = fade(fz);
+
+    let bottom = lerp(dot(0, 0), dot(1, 0), u);
+    let top = lerp(dot(0, 1), dot(1, 1), u);
+
+    lerp(bottom, top, v) * std::f64::consts::SQRT_2
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * t.mul_add(t.mul_add(6.0, -15.0), 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    (b - a).mul_add(t, a)
+}
+
+/// Hashes a lattice point. This is the finalizer of splitmix64, which is enough to make
+/// neighbouring points unrelated.
+#[expect(
+    clippy::cast_sign_loss,
+    reason = "only the bits of the coordinates matter"
+)]
+fn lattice_hash(seed: u64, x: i32, z: i32) -> u64 {
+    let mut hash = seed
+        ^ u64::from(x as u32).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ u64::from(z as u32).wrapping_mul(0xc2b2_ae3d_27d4_eb4f);
+
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::IVec2;
+    use valence_generated::block::BlockState;
+    use valence_server::layer::chunk::Chunk;
+
+    use super::{Biome, NoiseGenerator};
+    use crate::simulation::blocks::{
+        chunk::START_Y,
+        generator::{BiomeLookup, ChunkGenerator, tests},
+    };
+
+    fn y(world_y: i32) -> u32 {
+        u32::try_from(world_y - i32::from(START_Y)).unwrap()
+    }
+
+    #[test]
+    fn same_seed_same_chunk() {
+        let biomes = tests::biome_registry();
+        let biomes = BiomeLookup::new(&biomes);
+        let position = IVec2::new(-4, 9);
+
+        let a = NoiseGenerator::new(42).generate(position, &biomes);
+        let b = NoiseGenerator::new(42).generate(position, &biomes);
+
+        for section in 0..a.sections.len() {
+            assert!(
+                a.sections[section]
+                    .block_states
+                    .iter()
+                    .eq(b.sections[section].block_states.iter())
+            );
+        }
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let a = NoiseGenerator::new(1);
+        let b = NoiseGenerator::new(2);
+
+        let differs = (0..64).any(|i| a.height_at(i * 37, i * 11) != b.height_at(i * 37, i * 11));
+        assert!(differs);
+    }
+
+    #[test]
+    fn columns_follow_height_and_biome() {
+        let biomes = tests::biome_registry();
+        let lookup = BiomeLookup::new(&biomes);
+        let generator = NoiseGenerator::new(0x00c0_ffee);
+        let position = IVec2::new(3, -2);
+
+        let chunk = generator.generate(position, &lookup);
+
+        for x in 0..16_u32 {
+            for z in 0..16_u32 {
+                let world_x = position.x * 16 + i32::try_from(x).unwrap();
+                let world_z = position.y * 16 + i32::try_from(z).unwrap();
+
+                // the biome of the 4x4 cell the column is in
+                let height = generator.height_at(world_x, world_z);
+                let biome = generator.biome_at(
+                    world_x - world_x.rem_euclid(4) + 2,
+                    world_z - world_z.rem_euclid(4) + 2,
+                );
+
+                assert_eq!(chunk.block_state(x, 0, z), BlockState::BEDROCK);
+                assert_eq!(chunk.block_state(x, y(height), z), biome.surface());
+                assert_eq!(chunk.block_state(x, y(height - 1), z), biome.filler());
+                assert_eq!(chunk.block_state(x, y(height - 10), z), BlockState::STONE);
+
+                let above = if height < NoiseGenerator::SEA_LEVEL {
+                    BlockState::WATER
+                } else {
+                    BlockState::AIR
+                };
+                assert_eq!(chunk.block_state(x, y(height + 1), z), above);
+            }
+        }
+    }
+
+    #[test]
+    fn biomes_follow_terrain() {
+        let generator = NoiseGenerator::new(7);
+
+        for i in -50..50 {
+            let (x, z) = (i * 113, i * -71);
+            let height = generator.height_at(x, z);
+
+            match generator.biome_at(x, z) {
+                Biome::Ocean => assert!(height < NoiseGenerator::SEA_LEVEL),
+                Biome::WindsweptHills => assert!(height > NoiseGenerator::HILLS_LEVEL),
+                _ => assert!(
+                    (NoiseGenerator::SEA_LEVEL..=NoiseGenerator::HILLS_LEVEL).contains(&height)
+                ),
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::{borrow::Cow, cell::RefCell, io::Write, sync::Arc};
 
-use anyhow::{Context, bail};
+use anyhow::bail;
 use bytes::BytesMut;
 use derive_more::Constructor;
 use glam::{I16Vec2, IVec2};
@@ -17,8 +17,13 @@ use valence_registry::RegistryIdx;
 use valence_server::layer::chunk::{BiomeContainer, Chunk, bit_width};
 
 pub mod parse;
+pub mod serialize;
 
-use super::{chunk::Column, shared::WorldShared};
+use super::{
+    chunk::Column,
+    generator::{BiomeLookup, ChunkGenerator},
+    shared::WorldShared,
+};
 use crate::{
     CHUNK_HEIGHT_SPAN, Scratch,
     net::encoder::PacketEncoder,
@@ -190,6 +195,10 @@ async fn load_chunk(position: I16Vec2, shared: &WorldShared) -> anyhow::Result<C
 
     // https://rust-lang.github.io/rust-clippy/master/index.html#/large_futures
     let Ok(region) = shared.regions.get_region_from_chunk(x, y).await else {
+        if let Some(generator) = &shared.generator {
+            return generate_chunk(position, generator.as_ref(), shared);
+        }
+
         // most likely the file representing the region does not exist so we will just return en empty chunk
         warn!("region file for {position} does not exist; returning empty chunk");
         return Ok(empty_column(position));
@@ -199,9 +208,15 @@ async fn load_chunk(position: I16Vec2, shared: &WorldShared) -> anyhow::Result<C
         // todo: note that this is likely blocking to tokio
         let x = i32::from(x);
         let y = i32::from(y);
-        region
-            .get_chunk(x, y, &mut decompress_buf, shared.regions.root())?
-            .context("no chunk found")?
+        region.get_chunk(x, y, &mut decompress_buf, shared.regions.root())?
+    };
+
+    let Some(raw_chunk) = raw_chunk else {
+        if let Some(generator) = &shared.generator {
+            return generate_chunk(position, generator.as_ref(), shared);
+        }
+
+        bail!("no chunk found");
     };
 
     let chunk = match parse::parse_chunk(raw_chunk.data, &shared.biome_to_id) {
@@ -211,6 +226,20 @@ async fn load_chunk(position: I16Vec2, shared: &WorldShared) -> anyhow::Result<C
         }
     };
 
+    encode_column(chunk, position)
+}
+
+fn generate_chunk(
+    position: I16Vec2,
+    generator: &dyn ChunkGenerator,
+    shared: &WorldShared,
+) -> anyhow::Result<Column> {
+    let biomes = BiomeLookup::new(&shared.biome_to_id);
+    let chunk = generator.generate(position.as_ivec2(), &biomes);
+    encode_column(chunk, position)
+}
+
+fn encode_column(chunk: ColumnData, position: I16Vec2) -> anyhow::Result<Column> {
     STATE.with_borrow_mut(|state| {
         let position = position.as_ivec2();
         let Ok(Some(bytes)) = encode_chunk_packet(&chunk, position, state) else {
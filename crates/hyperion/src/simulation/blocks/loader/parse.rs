@@ -459,11 +459,12 @@ pub fn parse_chunk(
 
     if let List::Compound(block_entities) = block_entities {
         for mut comp in block_entities {
-            let Some(Value::String(ident)) = comp.remove("id") else {
+            // the id is kept so the block entity can be saved again
+            let Some(Value::String(ident)) = comp.get("id") else {
                 return Err(ParseChunkError::MissingBlockEntityIdent);
             };
 
-            if let Err(e) = Ident::new(ident) {
+            if let Err(e) = Ident::new(ident.clone()) {
                 return Err(ParseChunkError::InvalidBlockEntityName(e.0));
             }
 
@@ -0,0 +1,246 @@
+//! The inverse of [`super::parse::parse_chunk`]: turns a [`ColumnData`] back into the NBT vanilla
+//! stores in region files.
+
+use glam::IVec2;
+use rustc_hash::FxHashMap;
+use valence_generated::block::BlockState;
+use valence_nbt::{Compound, List, Value, compound};
+use valence_protocol::Ident;
+use valence_registry::RegistryIdx;
+use valence_server::layer::chunk::bit_width;
+
+use super::parse::{ColumnData, section::Section};
+use crate::simulation::blocks::chunk::START_Y;
+
+/// The data version of 1.20.1.
+const DATA_VERSION: i32 = 3465;
+
+/// The biome written for biome ids that are not in the registry.
+const FALLBACK_BIOME: &str = "minecraft:plains";
+
+/// Serializes `chunk` at chunk position `position`. `biome_names` maps biome ids (by index) to
+/// their names.
+pub fn serialize_chunk(
+    chunk: &ColumnData,
+    position: IVec2,
+    biome_names: &[Ident<String>],
+) -> Compound {
+    let min_section_y = i32::from(START_Y) / 16;
+
+    let sections = chunk
+        .sections
+        .iter()
+        .enumerate()
+        .map(|(idx, section)| {
+            let y = min_section_y + i32::try_from(idx).unwrap();
+            serialize_section(section, i8::try_from(y).unwrap(), biome_names)
+        })
+        .collect();
+
+    let block_entities = chunk
+        .block_entities
+        .iter()
+        .map(|(&idx, block_entity)| {
+            let idx = i32::try_from(idx).unwrap();
+
+            let mut block_entity = block_entity.clone();
+            block_entity.insert("x", position.x * 16 + idx % 16);
+            block_entity.insert("y", idx / (16 * 16) + i32::from(START_Y));
+            block_entity.insert("z", position.y * 16 + idx / 16 % 16);
+            block_entity
+        })
+        .collect();
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => position.x,
+        "yPos" => min_section_y,
+        "zPos" => position.y,
+        "Status" => "minecraft:full",
+        "sections" => List::Compound(sections),
+        "block_entities" => List::Compound(block_entities),
+    }
+}
+
+fn serialize_section(section: &Section, y: i8, biome_names: &[Ident<String>]) -> Compound {
+    let mut compound = compound! {
+        "Y" => y,
+        "block_states" => serialize_block_states(section),
+        "biomes" => serialize_biomes(section, biome_names),
+    };
+
+    if let Some(block_light) = &section.block_light {
+        let block_light: &[i8] = bytemuck::cast_slice(block_light.as_slice());
+        compound.insert("BlockLight", Value::ByteArray(block_light.to_vec()));
+    }
+
+    if let Some(sky_light) = &section.sky_light {
+        let sky_light: &[i8] = bytemuck::cast_slice(sky_light.as_slice());
+        compound.insert("SkyLight", Value::ByteArray(sky_light.to_vec()));
+    }
+
+    compound
+}
+
+fn serialize_block_states(section: &Section) -> Compound {
+    let mut palette = Vec::new();
+    let mut palette_indices = FxHashMap::default();
+
+    let indices: Vec<_> = section
+        .block_states
+        .iter()
+        .map(|raw| {
+            *palette_indices.entry(raw).or_insert_with(|| {
+                palette.push(BlockState::from_raw(raw).unwrap_or(BlockState::AIR));
+                palette.len() - 1
+            })
+        })
+        .collect();
+
+    let palette = palette.into_iter().map(block_state_nbt).collect();
+
+    let mut compound = compound! {
+        "palette" => List::Compound(palette),
+    };
+
+    if palette_indices.len() > 1 {
+        let bits = bit_width(palette_indices.len() - 1).max(4);
+        compound.insert("data", Value::LongArray(pack(&indices, bits)));
+    }
+
+    compound
+}
+
+fn block_state_nbt(state: BlockState) -> Compound {
+    let kind = state.to_kind();
+
+    let mut compound = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    let properties: Compound = kind
+        .props()
+        .iter()
+        .filter_map(|&name| {
+            let value = state.get(name)?;
+            Some((name.to_str().to_owned(), Value::from(value.to_str())))
+        })
+        .collect();
+
+    if !properties.is_empty() {
+        compound.insert("Properties", properties);
+    }
+
+    compound
+}
+
+fn serialize_biomes(section: &Section, biome_names: &[Ident<String>]) -> Compound {
+    let mut palette = Vec::new();
+    let mut palette_indices = FxHashMap::default();
+
+    let indices: Vec<_> = (0..4 * 4 * 4)
+        .map(|idx| {
+            let biome = section.biomes.get(idx);
+
+            *palette_indices.entry(biome).or_insert_with(|| {
+                let name = biome_names
+                    .get(biome.to_index())
+                    .map_or(FALLBACK_BIOME, |name| name.as_str());
+
+                palette.push(name.to_owned());
+                palette.len() - 1
+            })
+        })
+        .collect();
+
+    let mut compound = compound! {
+        "palette" => List::String(palette),
+    };
+
+    if palette_indices.len() > 1 {
+        let bits = bit_width(palette_indices.len() - 1);
+        compound.insert("data", Value::LongArray(pack(&indices, bits)));
+    }
+
+    compound
+}
+
+/// Packs palette indices into longs the way vanilla does since 1.16: indices do not span
+/// multiple longs.
+#[expect(
+    clippy::cast_possible_wrap,
+    reason = "vanilla stores the bits as signed longs"
+)]
+fn pack(indices: &[usize], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+
+    indices
+        .chunks(per_long)
+        .map(|indices| {
+            let long = indices
+                .iter()
+                .enumerate()
+                .fold(0_u64, |long, (i, &idx)| long | ((idx as u64) << (i * bits)));
+
+            long as i64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use glam::IVec2;
+    use valence_generated::block::{BlockState, PropName, PropValue};
+    use valence_nbt::compound;
+    use valence_protocol::Ident;
+    use valence_registry::{RegistryIdx, biome::BiomeId};
+    use valence_server::layer::chunk::Chunk;
+
+    use super::serialize_chunk;
+    use crate::{
+        CHUNK_HEIGHT_SPAN,
+        simulation::blocks::loader::parse::{ColumnData, parse_chunk, section::Section},
+    };
+
+    #[test]
+    fn roundtrip() {
+        let biome_names: Vec<_> = ["minecraft:plains", "minecraft:desert"]
+            .into_iter()
+            .map(|name| Ident::new(name.to_owned()).unwrap())
+            .collect();
+        let biome_map: BTreeMap<_, _> = biome_names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| (name.clone(), BiomeId::from_index(idx)))
+            .collect();
+
+        let mut chunk = ColumnData::new_with(CHUNK_HEIGHT_SPAN, Section::empty_sky);
+
+        let stairs = BlockState::OAK_STAIRS.set(PropName::Facing, PropValue::East);
+
+        chunk.set_block_state(0, 0, 0, BlockState::BEDROCK);
+        chunk.set_block_state(1, 70, 15, stairs);
+        chunk.set_block_state(15, 383, 3, BlockState::STONE);
+        chunk.set_biome(2, 40, 1, BiomeId::from_index(1));
+        chunk.set_block_entity(3, 65, 4, Some(compound! { "id" => "minecraft:chest" }));
+
+        let nbt = serialize_chunk(&chunk, IVec2::new(-3, 5), &biome_names);
+        let parsed = parse_chunk(nbt, &biome_map).unwrap();
+
+        assert_eq!(parsed.height(), chunk.height());
+
+        for (x, y, z) in [(0, 0, 0), (1, 70, 15), (15, 383, 3), (7, 100, 7)] {
+            assert_eq!(parsed.block_state(x, y, z), chunk.block_state(x, y, z));
+        }
+
+        assert_eq!(parsed.biome(2, 40, 1), BiomeId::from_index(1));
+        assert_eq!(parsed.biome(0, 0, 0), BiomeId::from_index(0));
+        assert_eq!(
+            parsed.block_entity(3, 65, 4),
+            Some(&compound! { "id" => "minecraft:chest" })
+        );
+        assert_eq!(parsed.sections[0].sky_light, Some([0xff; 2048]));
+    }
+}
@@ -2,6 +2,7 @@ use std::{
     collections::HashMap,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::ensure;
@@ -12,14 +13,20 @@ use tokio::{
     sync::{mpsc, oneshot},
 };
 use tracing::info;
+use valence_nbt::Compound;
 
-use super::region::Region;
+use super::region::{Region, RegionWriter};
 
 enum RegionRequest {
     Get {
         coord: IVec2,
         response: oneshot::Sender<std::io::Result<Arc<Region>>>,
     },
+    Save {
+        coord: IVec2,
+        chunks: Vec<(IVec2, Compound)>,
+        response: oneshot::Sender<std::io::Result<()>>,
+    },
 }
 
 pub struct RegionManager {
@@ -70,6 +77,28 @@ impl RegionManager {
             .await
             .expect("RegionManagerTask has been dropped")
     }
+
+    /// Writes chunks into the region at `coord`, creating the region file if needed. The chunks
+    /// are given by chunk position and must all be inside the region.
+    pub async fn save_region(
+        &self,
+        coord: IVec2,
+        chunks: Vec<(IVec2, Compound)>,
+    ) -> std::io::Result<()> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.sender
+            .send(RegionRequest::Save {
+                coord,
+                chunks,
+                response: response_tx,
+            })
+            .await
+            .expect("RegionManagerTask has been dropped");
+
+        response_rx
+            .await
+            .expect("RegionManagerTask has been dropped")
+    }
 }
 
 struct RegionManagerTask {
@@ -108,9 +137,49 @@ impl RegionManagerTask {
                 // todo: what should we  do here
                 drop(response.send(region));
             }
+            RegionRequest::Save {
+                coord,
+                chunks,
+                response,
+            } => {
+                let result = self.save_region(coord, chunks).await;
+                drop(response.send(result));
+            }
         }
     }
 
+    async fn save_region(
+        &mut self,
+        coord: IVec2,
+        chunks: Vec<(IVec2, Compound)>,
+    ) -> std::io::Result<()> {
+        // regions that are still being read keep their own copy of the header; new reads have to
+        // see the new chunk locations
+        self.regions.remove(&coord);
+
+        let path = self.region_path(coord.x, coord.y);
+        let root = self.root.clone();
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                u32::try_from(elapsed.as_secs()).unwrap_or(u32::MAX)
+            });
+
+        tokio::task::spawn_blocking(move || {
+            let mut writer = RegionWriter::open(&path, &root)?;
+
+            for (position, chunk) in &chunks {
+                writer.write_chunk(position.x, position.y, chunk, timestamp)?;
+            }
+
+            writer.sync()
+        })
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    }
+
     async fn get_or_create_region(&mut self, coord: IVec2) -> std::io::Result<Arc<Region>> {
         if let Some(region) = self.regions.get(&coord) {
             if let Some(region) = region.upgrade() {
@@ -10,6 +10,7 @@ use flecs_ecs::{
     core::{Entity, World, WorldGet},
     macros::Component,
 };
+use generator::ChunkGenerator;
 use geometry::ray::Ray;
 use glam::{I16Vec2, IVec2, IVec3, Vec3};
 use indexmap::IndexMap;
@@ -18,17 +19,15 @@ use rayon::iter::ParallelIterator;
 use roaring::RoaringBitmap;
 use rustc_hash::FxBuildHasher;
 use shared::WorldShared;
-use tracing::error;
+use tracing::{error, info};
 use valence_generated::block::BlockState;
+use valence_nbt::Compound;
 use valence_server::layer::chunk::Chunk;
 
 use crate::{
     CHUNK_HEIGHT_SPAN,
     runtime::AsyncRuntime,
-    simulation::{
-        blocks::loader::{launch_empty_loader, parse::section::Section},
-        util::generate_biome_registry,
-    },
+    simulation::{blocks::loader::launch_empty_loader, util::generate_biome_registry},
 };
 
 pub mod chunk;
@@ -37,9 +36,12 @@ mod loader;
 mod manager;
 
 pub mod frame;
+pub mod generator;
 mod region;
 mod shared;
 
+pub use loader::parse::{ColumnData, section::Section};
+
 pub enum GetChunk<'a> {
     Loaded(&'a Column),
     Loading,
@@ -71,40 +73,82 @@ pub struct Blocks {
     /// Map to a Chunk by Entity ID
     chunk_cache: IndexMap<I16Vec2, Column, FxBuildHasher>,
     should_update: RoaringBitmap,
+    /// Indices into [`Self::chunk_cache`] of columns changed since they were last saved.
+    should_save: RoaringBitmap,
 
     loader_handle: ChunkLoaderHandle,
+    /// The world the chunks are loaded from and saved to. `None` for [`Self::empty`].
+    shared: Option<Arc<WorldShared>>,
 
     tx_loaded_chunks: tokio::sync::mpsc::UnboundedSender<Column>,
     rx_loaded_chunks: tokio::sync::mpsc::UnboundedReceiver<Column>,
+    /// The columns of saves that failed, which are saved again with the next save.
+    tx_failed_saves: tokio::sync::mpsc::UnboundedSender<Vec<I16Vec2>>,
+    rx_failed_saves: tokio::sync::mpsc::UnboundedReceiver<Vec<I16Vec2>>,
     pub to_confirm: Vec<EntityAndSequence>,
+    /// The block entities of blocks that were broken or replaced since this was last drained,
+    /// e.g. the items of a broken chest.
+    pub removed_block_entities: Vec<(IVec3, Compound)>,
+    /// The number of blocks changed so far, see [`Self::changes`].
+    changes: u64,
 }
 
 impl From<ChunkLoaderHandle> for Blocks {
     fn from(loader_handle: ChunkLoaderHandle) -> Self {
         let (tx_loaded_chunks, rx_loaded_chunks) = tokio::sync::mpsc::unbounded_channel();
+        let (tx_failed_saves, rx_failed_saves) = tokio::sync::mpsc::unbounded_channel();
         Self {
             chunk_cache: IndexMap::default(),
             should_update: RoaringBitmap::default(),
+            should_save: RoaringBitmap::default(),
             loader_handle,
+            shared: None,
             tx_loaded_chunks,
             rx_loaded_chunks,
+            tx_failed_saves,
+            rx_failed_saves,
             to_confirm: vec![],
+            removed_block_entities: vec![],
+            changes: 0,
         }
     }
 }
 
 impl Blocks {
     pub fn new(world: &World, path: &Path) -> anyhow::Result<Self> {
+        Self::load(world, path, None)
+    }
+
+    /// Like [`Self::new`], but chunks that are not in the world at `path` are generated by
+    /// `generator`. The world is created if it does not exist yet.
+    pub fn generated(
+        world: &World,
+        path: &Path,
+        generator: impl ChunkGenerator,
+    ) -> anyhow::Result<Self> {
+        let region_path = path.join("region");
+        std::fs::create_dir_all(&region_path)
+            .with_context(|| format!("failed to create {}", region_path.display()))?;
+
+        Self::load(world, path, Some(Arc::new(generator)))
+    }
+
+    fn load(
+        world: &World,
+        path: &Path,
+        generator: Option<Arc<dyn ChunkGenerator>>,
+    ) -> anyhow::Result<Self> {
         world.get::<&AsyncRuntime>(|runtime| {
             let biome_registry =
                 generate_biome_registry().context("failed to generate biome registry")?;
 
-            let shared = WorldShared::new(&biome_registry, runtime, path)?;
+            let shared = WorldShared::new(&biome_registry, runtime, path, generator)?;
             let shared = Arc::new(shared);
 
-            let loader_handle = launch_loader(shared, runtime);
+            let loader_handle = launch_loader(shared.clone(), runtime);
 
-            let result = Self::from(loader_handle);
+            let mut result = Self::from(loader_handle);
+            result.shared = Some(shared);
 
             Ok(result)
         })
@@ -222,6 +266,88 @@ impl Blocks {
         self.should_update.clear();
     }
 
+    /// Counts every changed block, so systems can tell whether any block changed since they last
+    /// looked.
+    #[must_use]
+    pub const fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    /// Whether there are changed columns that have not been saved yet.
+    #[must_use]
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.should_save.is_empty()
+    }
+
+    /// Marks the columns of failed saves as changed again, so the next save retries them.
+    pub fn retry_failed_saves(&mut self) {
+        while let Ok(positions) = self.rx_failed_saves.try_recv() {
+            for position in positions {
+                if let Some(idx) = self.chunk_cache.get_index_of(&position) {
+                    self.should_save.insert(u32::try_from(idx).unwrap());
+                }
+            }
+        }
+    }
+
+    /// Writes all columns changed since the last save to the region files of the world.
+    ///
+    /// The columns are copied before this returns, so changes made while the returned future runs
+    /// are part of the next save. If the save fails, its columns are saved again by the next one.
+    /// For worlds created with [`Self::empty`] there is nowhere to save to, and the changes are
+    /// discarded.
+    #[must_use]
+    pub fn save(&mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> {
+        let Some(shared) = self.shared.clone() else {
+            self.should_save.clear();
+            return Box::pin(core::future::ready(Ok(())));
+        };
+
+        self.retry_failed_saves();
+
+        let (positions, columns): (Vec<_>, Vec<_>) = self
+            .should_save
+            .iter()
+            .filter_map(|idx| self.chunk_cache.get_index(idx as usize))
+            .map(|(position, column)| (*position, (column.position, column.data.clone())))
+            .unzip();
+
+        self.should_save.clear();
+
+        if columns.is_empty() {
+            return Box::pin(core::future::ready(Ok(())));
+        }
+
+        let failed_saves = self.tx_failed_saves.clone();
+        let pending = shared.start_save();
+
+        Box::pin(async move {
+            let _pending = pending;
+            let count = columns.len();
+
+            if let Err(e) = shared.save(columns).await {
+                // the receiver only goes away with the world, which then has nothing to retry
+                let _ = failed_saves.send(positions);
+                return Err(e);
+            }
+
+            info!("saved {count} chunks");
+            Ok(())
+        })
+    }
+
+    /// Saves all changed columns and blocks until they are written. Saves that were started
+    /// before, such as autosaves that are spawned but not running yet, are waited for first, so
+    /// they cannot overwrite this save with older columns, and the columns of those that fail are
+    /// saved too.
+    pub fn save_and_wait(&mut self, runtime: &AsyncRuntime) -> anyhow::Result<()> {
+        if let Some(shared) = &self.shared {
+            runtime.block_on(shared.wait_for_saves());
+        }
+
+        runtime.block_on(self.save())
+    }
+
     pub fn cache_mut(&mut self) -> &mut IndexMap<I16Vec2, Column, FxBuildHasher> {
         &mut self.chunk_cache
     }
@@ -435,12 +561,53 @@ impl Blocks {
         let old_state = chunk.data.set_delta(x, y, z, state);
 
         if old_state != state {
-            self.should_update.insert(u32::try_from(chunk_idx).unwrap());
+            if old_state.to_kind() != state.to_kind() {
+                // the block entity belonged to the old block, e.g. the items of a broken chest
+                if let Some(removed) = chunk.data.set_block_entity(x, y, z, None) {
+                    self.removed_block_entities.push((position, removed));
+                }
+            }
+
+            let chunk_idx = u32::try_from(chunk_idx).unwrap();
+            self.should_update.insert(chunk_idx);
+            self.should_save.insert(chunk_idx);
+            self.changes += 1;
         }
 
         Ok(old_state)
     }
 
+    /// The block entity at `position`, e.g. the items of a chest. `None` if there is none or its
+    /// chunk is not loaded.
+    #[must_use]
+    pub fn block_entity(&self, position: IVec3) -> Option<&Compound> {
+        let (chunk_pos, x, y, z) = chunk_local(position)?;
+        let chunk = self.get_loaded_chunk(chunk_pos)?;
+
+        chunk.data.block_entity(x, y, z)
+    }
+
+    /// Replaces the block entity at `position`, which is saved with its chunk. Returns the old
+    /// block entity.
+    pub fn set_block_entity(
+        &mut self,
+        position: IVec3,
+        block_entity: Option<Compound>,
+    ) -> Result<Option<Compound>, TrySetBlockDeltaError> {
+        let (chunk_pos, x, y, z) =
+            chunk_local(position).ok_or(TrySetBlockDeltaError::OutOfBounds)?;
+
+        let Some((chunk_idx, _, chunk)) = self.chunk_cache.get_full_mut(&chunk_pos) else {
+            return Err(TrySetBlockDeltaError::ChunkNotLoaded);
+        };
+
+        let old = chunk.data.set_block_entity(x, y, z, block_entity);
+
+        self.should_save.insert(u32::try_from(chunk_idx).unwrap());
+
+        Ok(old)
+    }
+
     // todo: allow modifying the chunk. we will need to implement resending
     // So,
     // for instance, if a player modifies a chunk, we're going to need to rebroadcast it to all the players in that region.
@@ -470,3 +637,68 @@ impl Blocks {
         GetChunk::Loading
     }
 }
+
+/// The chunk of `position` and the position within it, or `None` if it is outside of the world.
+fn chunk_local(position: IVec3) -> Option<(I16Vec2, u32, u32, u32)> {
+    let chunk_pos: IVec2 = IVec2::new(position.x, position.z) >> 4;
+    let chunk_start_block: IVec2 = chunk_pos << 4;
+
+    let x = u32::try_from(position.x - chunk_start_block.x).unwrap();
+    let y = u32::try_from(position.y - i32::from(chunk::START_Y)).ok()?;
+    let z = u32::try_from(position.z - chunk_start_block.y).unwrap();
+
+    if y >= CHUNK_HEIGHT_SPAN {
+        return None;
+    }
+
+    Some((chunk_pos.as_i16vec2(), x, y, z))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use flecs_ecs::core::{World, WorldGet};
+    use glam::{I16Vec2, IVec3};
+    use valence_generated::block::BlockState;
+
+    use super::{Blocks, generator::FlatGenerator};
+    use crate::runtime::AsyncRuntime;
+
+    #[test]
+    fn shutting_down_waits_for_autosaves_that_have_not_started() {
+        let path = std::env::temp_dir().join(format!("hyperion-blocks-{}", std::process::id()));
+        drop(std::fs::remove_dir_all(&path));
+
+        let world = World::new();
+        let (tx, _rx) = kanal::bounded(1);
+        world.set(AsyncRuntime::new(tx));
+
+        let position = IVec3::new(0, -60, 0);
+        let mut blocks = Blocks::generated(&world, &path, FlatGenerator::default()).unwrap();
+
+        world.get::<&AsyncRuntime>(|runtime| {
+            blocks.block_and_load(I16Vec2::ZERO, runtime);
+
+            blocks.set_block(position, BlockState::STONE).unwrap();
+            let autosave = blocks.save();
+            blocks.set_block(position, BlockState::GOLD_BLOCK).unwrap();
+
+            // the autosave only starts running once the server is already shutting down
+            let handle = runtime.handle().clone();
+            let autosave = std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(100));
+                handle.block_on(autosave)
+            });
+
+            blocks.save_and_wait(runtime).unwrap();
+            autosave.join().unwrap().unwrap();
+        });
+
+        let mut saved = Blocks::new(&world, &path).unwrap();
+        world.get::<&AsyncRuntime>(|runtime| saved.block_and_load(I16Vec2::ZERO, runtime));
+        assert_eq!(saved.get_block(position), Some(BlockState::GOLD_BLOCK));
+
+        drop(std::fs::remove_dir_all(&path));
+    }
+}
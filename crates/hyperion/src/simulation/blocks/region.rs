@@ -1,14 +1,18 @@
 use std::{
     hash::Hash,
-    io::Read,
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
 };
 
 use bitfield_struct::bitfield;
-use flate2::bufread::{GzDecoder, ZlibDecoder};
+use bitvec::vec::BitVec;
+use flate2::{
+    bufread::{GzDecoder, ZlibDecoder},
+    write::ZlibEncoder,
+};
 use tokio::fs::File;
 use valence_anvil::{Compression, RawChunk, RegionError};
-use valence_nbt::binary::FromModifiedUtf8;
+use valence_nbt::{Compound, binary::FromModifiedUtf8};
 
 #[bitfield(u32)]
 struct Location {
@@ -38,6 +42,60 @@ pub struct Region {
 
 const SECTOR_SIZE: usize = 4096;
 
+/// The largest chunk that fits into the region file. Larger chunks are stored in a separate
+/// `.mcc` file.
+const MAX_SECTOR_COUNT: usize = u8::MAX as usize;
+
+/// The compression id of zlib, which is what vanilla writes.
+const ZLIB_COMPRESSION: u8 = 2;
+
+type Header = ([Location; 1024], [u32; 1024]);
+
+fn parse_header(header: &[u8]) -> Header {
+    let locations = std::array::from_fn(|i| {
+        Location(u32::from_be_bytes(
+            header[i * 4..i * 4 + 4].try_into().unwrap(),
+        ))
+    });
+    let timestamps = std::array::from_fn(|i| {
+        u32::from_be_bytes(
+            header[i * 4 + SECTOR_SIZE..i * 4 + SECTOR_SIZE + 4]
+                .try_into()
+                .unwrap(),
+        )
+    });
+
+    (locations, timestamps)
+}
+
+/// The sectors of a region file that are used by the header or a chunk.
+fn used_sectors(locations: &[Location; 1024], file_len: u64) -> BitVec {
+    let mut used_sectors = BitVec::repeat(true, 2);
+    for location in locations {
+        if location.is_none() {
+            // No chunk exists at this position.
+            continue;
+        }
+
+        let (sector_offset, sector_count) = location.offset_and_count();
+        if sector_offset < 2 {
+            // skip locations pointing inside the header
+            continue;
+        }
+        if sector_count == 0 {
+            continue;
+        }
+        if sector_offset * SECTOR_SIZE as u64 > file_len {
+            // this would go past the end of the file, which is impossible
+            continue;
+        }
+
+        Region::reserve_sectors(&mut used_sectors, sector_offset, sector_count);
+    }
+
+    used_sectors
+}
+
 impl Region {
     pub fn open(file: &File) -> Result<Self, RegionError> {
         let mmap = unsafe { MmapOptions::new().map(file)? };
@@ -49,47 +107,12 @@ impl Region {
             )));
         };
 
-        let locations = std::array::from_fn(|i| {
-            Location(u32::from_be_bytes(
-                header[i * 4..i * 4 + 4].try_into().unwrap(),
-            ))
-        });
-        let timestamps = std::array::from_fn(|i| {
-            u32::from_be_bytes(
-                header[i * 4 + SECTOR_SIZE..i * 4 + SECTOR_SIZE + 4]
-                    .try_into()
-                    .unwrap(),
-            )
-        });
-
-        let mut used_sectors = bitvec::vec::BitVec::repeat(true, 2);
-        for location in locations {
-            if location.is_none() {
-                // No chunk exists at this position.
-                continue;
-            }
-
-            let (sector_offset, sector_count) = location.offset_and_count();
-            if sector_offset < 2 {
-                // skip locations pointing inside the header
-                continue;
-            }
-            if sector_count == 0 {
-                continue;
-            }
-            if sector_offset * SECTOR_SIZE as u64 > mmap.len() as u64 {
-                // this would go past the end of the file, which is impossible
-                continue;
-            }
-
-            Self::reserve_sectors(&mut used_sectors, sector_offset, sector_count);
-        }
+        let (locations, timestamps) = parse_header(header);
 
         Ok(Self {
             mmap,
             locations,
             timestamps,
-            // used_sectors,
         })
     }
 
@@ -136,8 +159,9 @@ impl Region {
             return Err(RegionError::MissingChunkStream);
         }
 
-        // size of this chunk in sectors must always be >= the exact size.
-        if sector_count * SECTOR_SIZE < exact_chunk_size {
+        // size of this chunk in sectors must always be >= the exact size. The exact size does not
+        // include the four bytes of the size itself.
+        if sector_count * SECTOR_SIZE < exact_chunk_size + 4 {
             return Err(RegionError::InvalidChunkSize);
         }
 
@@ -149,7 +173,9 @@ impl Region {
             let external_mmap = unsafe { MmapOptions::new().map(&external_file)? };
             external_mmap.to_vec().into_boxed_slice()
         } else {
-            chunk_data[5..exact_chunk_size].to_vec().into_boxed_slice()
+            chunk_data[5..exact_chunk_size + 4]
+                .to_vec()
+                .into_boxed_slice()
         };
 
         let r: &[u8] = data_buf.as_ref();
@@ -214,23 +240,19 @@ impl Region {
             .join(format!("c.{pos_x}.{pos_z}.mcc"))
     }
 
-    // fn delete_external_chunk_file(
-    //     pos_x: i32,
-    //     pos_z: i32,
-    //     region_root: &Path,
-    // ) -> Result<(), RegionError> {
-    //     match std::fs::remove_file(Self::external_chunk_file(pos_x, pos_z, region_root)) {
-    //         Ok(()) => Ok(()),
-    //         Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
-    //         Err(err) => Err(err.into()),
-    //     }
-    // }
+    fn delete_external_chunk_file(
+        pos_x: i32,
+        pos_z: i32,
+        region_root: &Path,
+    ) -> Result<(), RegionError> {
+        match std::fs::remove_file(Self::external_chunk_file(pos_x, pos_z, region_root)) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
 
-    fn reserve_sectors(
-        used_sectors: &mut bitvec::vec::BitVec,
-        sector_offset: u64,
-        sector_count: usize,
-    ) {
+    fn reserve_sectors(used_sectors: &mut BitVec, sector_offset: u64, sector_count: usize) {
         let start_index = usize::try_from(sector_offset).unwrap();
         let end_index = usize::try_from(sector_offset).unwrap() + sector_count;
         if used_sectors.len() < end_index {
@@ -264,3 +286,237 @@ const fn compression_from_u8(compression: u8) -> Option<Compression> {
         _ => None,
     }
 }
+
+/// Writes chunks into a region file, creating it if it does not exist.
+///
+/// Unlike [`Region`], which maps the file into memory for reading, this writes through a regular
+/// file handle. A chunk is written to free sectors before its location in the header is updated,
+/// so a crash while writing never corrupts other chunks.
+#[derive(Debug)]
+pub struct RegionWriter {
+    file: std::fs::File,
+    region_root: PathBuf,
+    locations: [Location; 1024],
+    used_sectors: BitVec,
+}
+
+impl RegionWriter {
+    pub fn open(path: &Path, region_root: &Path) -> Result<Self, RegionError> {
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let file_len = file.metadata()?.len();
+
+        if file_len < (SECTOR_SIZE * 2) as u64 {
+            // a new region file, or one with a broken header which we cannot read anyway
+            file.set_len((SECTOR_SIZE * 2) as u64)?;
+        }
+
+        let mut header = vec![0; SECTOR_SIZE * 2];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        let (locations, _) = parse_header(&header);
+        let used_sectors = used_sectors(&locations, file_len);
+
+        Ok(Self {
+            file,
+            region_root: region_root.to_path_buf(),
+            locations,
+            used_sectors,
+        })
+    }
+
+    /// Writes `chunk` at the given chunk position, replacing the chunk that was there before.
+    pub fn write_chunk(
+        &mut self,
+        pos_x: i32,
+        pos_z: i32,
+        chunk: &Compound,
+        timestamp: u32,
+    ) -> Result<(), RegionError> {
+        let chunk_idx = Region::chunk_idx(pos_x, pos_z);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        valence_nbt::to_binary(chunk, &mut encoder, "")?;
+        let compressed = encoder.finish()?;
+
+        // [exact size: u32] [compression: u8] [data]
+        let mut payload = Vec::with_capacity(compressed.len() + 5);
+
+        if compressed.len() + 5 > MAX_SECTOR_COUNT * SECTOR_SIZE {
+            std::fs::write(
+                Region::external_chunk_file(pos_x, pos_z, &self.region_root),
+                &compressed,
+            )?;
+
+            payload.extend_from_slice(&1_u32.to_be_bytes());
+            payload.push(ZLIB_COMPRESSION | 0x80);
+        } else {
+            Region::delete_external_chunk_file(pos_x, pos_z, &self.region_root)?;
+
+            let exact_size = u32::try_from(compressed.len() + 1).unwrap();
+            payload.extend_from_slice(&exact_size.to_be_bytes());
+            payload.push(ZLIB_COMPRESSION);
+            payload.extend_from_slice(&compressed);
+        }
+
+        let sector_count = payload.len().div_ceil(SECTOR_SIZE);
+        payload.resize(sector_count * SECTOR_SIZE, 0);
+
+        // the old sectors are still marked as used, so the header keeps pointing to a valid chunk
+        // until the new one is written
+        let old_location = self.locations[chunk_idx];
+        let sector_offset = self.allocate(sector_count);
+
+        self.file
+            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+        self.file.write_all(&payload)?;
+
+        let location = Location::new()
+            .with_offset(u32::try_from(sector_offset).unwrap())
+            .with_count(u8::try_from(sector_count).unwrap());
+
+        self.file.seek(SeekFrom::Start(chunk_idx as u64 * 4))?;
+        self.file.write_all(&location.0.to_be_bytes())?;
+
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + chunk_idx * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+
+        self.locations[chunk_idx] = location;
+
+        // only now that the header points to the new sectors can the old ones be reused
+        if !old_location.is_none() {
+            let (old_offset, old_count) = old_location.offset_and_count();
+            if old_offset >= 2 {
+                let start = usize::try_from(old_offset).unwrap();
+                let end = (start + old_count).min(self.used_sectors.len());
+                if start < end {
+                    self.used_sectors[start..end].fill(false);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes sure everything written so far is on disk.
+    pub fn sync(&self) -> Result<(), RegionError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+
+    /// Finds `count` consecutive free sectors, growing the file if there are none.
+    fn allocate(&mut self, count: usize) -> u64 {
+        let mut run_start = 2;
+        let mut run_len = 0;
+
+        for (sector, used) in self.used_sectors.iter().by_vals().enumerate().skip(2) {
+            if used {
+                run_start = sector + 1;
+                run_len = 0;
+                continue;
+            }
+
+            run_len += 1;
+
+            if run_len == count {
+                break;
+            }
+        }
+
+        // if no run was long enough, `run_start` is the start of the free sectors at the end of
+        // the file, which can be extended
+        Region::reserve_sectors(&mut self.used_sectors, run_start as u64, count);
+        run_start as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use valence_nbt::{Compound, List, Value, compound};
+
+    use super::{Region, RegionWriter};
+
+    fn region_root(name: &str) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("hyperion-region-{name}-{}", std::process::id()));
+        drop(std::fs::remove_dir_all(&root));
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn read(root: &std::path::Path, pos_x: i32, pos_z: i32) -> Option<Compound> {
+        let file = std::fs::File::open(root.join("r.0.0.mca")).unwrap();
+        let region = Region::open(&tokio::fs::File::from_std(file)).unwrap();
+
+        let mut buf = Vec::new();
+        region
+            .get_chunk::<String>(pos_x, pos_z, &mut buf, root)
+            .unwrap()
+            .map(|chunk| chunk.data)
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let root = region_root("roundtrip");
+        let path = root.join("r.0.0.mca");
+
+        let small = compound! { "value" => 1 };
+        let large = compound! {
+            "values" => List::Long((0..10_000).collect()),
+        };
+
+        let mut writer = RegionWriter::open(&path, &root).unwrap();
+        writer.write_chunk(0, 0, &small, 1).unwrap();
+        writer.write_chunk(1, 0, &small, 1).unwrap();
+        // grows, so it has to move behind the other chunk
+        writer.write_chunk(0, 0, &large, 2).unwrap();
+        drop(writer);
+
+        assert_eq!(read(&root, 0, 0), Some(large));
+        assert_eq!(read(&root, 1, 0), Some(small.clone()));
+        assert_eq!(read(&root, 2, 0), None);
+
+        // reopening picks up the existing chunks
+        let mut writer = RegionWriter::open(&path, &root).unwrap();
+        writer.write_chunk(0, 0, &small, 3).unwrap();
+        drop(writer);
+
+        assert_eq!(read(&root, 0, 0), Some(small.clone()));
+        assert_eq!(read(&root, 1, 0), Some(small));
+    }
+
+    #[test]
+    fn oversized_chunk_is_stored_externally() {
+        let root = region_root("external");
+        let path = root.join("r.0.0.mca");
+
+        // random data does not compress below the 255 sectors that fit into a region file
+        let mut rng = fastrand::Rng::with_seed(7);
+        let noise: Vec<i8> = (0..2 * 1024 * 1024).map(|_| rng.i8(..)).collect();
+        let oversized = compound! { "noise" => Value::ByteArray(noise) };
+        let small = compound! { "value" => 1 };
+
+        let mut writer = RegionWriter::open(&path, &root).unwrap();
+        writer.write_chunk(3, 4, &oversized, 1).unwrap();
+        drop(writer);
+
+        assert!(root.join("c.3.4.mcc").exists());
+        assert_eq!(read(&root, 3, 4), Some(oversized));
+
+        let mut writer = RegionWriter::open(&path, &root).unwrap();
+        writer.write_chunk(3, 4, &small, 2).unwrap();
+        drop(writer);
+
+        assert!(!root.join("c.3.4.mcc").exists());
+        assert_eq!(read(&root, 3, 4), Some(small));
+    }
+}
@@ -1,16 +1,52 @@
-use std::{collections::BTreeMap, path::Path};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use anyhow::Context;
+use glam::IVec2;
+use rustc_hash::FxHashMap;
 use tokio::runtime::Runtime;
+use valence_nbt::Compound;
 use valence_protocol::Ident;
-use valence_registry::{BiomeRegistry, biome::BiomeId};
+use valence_registry::{BiomeRegistry, RegistryIdx, biome::BiomeId};
 
-use super::manager::RegionManager;
+use super::{
+    generator::ChunkGenerator,
+    loader::{parse::ColumnData, serialize::serialize_chunk},
+    manager::RegionManager,
+};
 
 /// Inner state of the [`MinecraftWorld`] component.
 pub struct WorldShared {
     pub regions: RegionManager,
     pub biome_to_id: BTreeMap<Ident<String>, BiomeId>,
+    /// The names of the biomes, indexed by [`BiomeId`].
+    pub biome_names: Vec<Ident<String>>,
+    /// Generates the chunks the region files do not have. Without a generator such chunks are
+    /// empty.
+    pub generator: Option<Arc<dyn ChunkGenerator>>,
+    /// Held while columns are written, so saves of the same region do not interleave.
+    pub saving: tokio::sync::Mutex<()>,
+    /// The saves that were started and are not done yet, including those still waiting for
+    /// [`Self::saving`].
+    pending_saves: AtomicUsize,
+    saves_done: tokio::sync::Notify,
+}
+
+/// Counts a save as running until it is dropped.
+pub struct PendingSave(Arc<WorldShared>);
+
+impl Drop for PendingSave {
+    fn drop(&mut self) {
+        if self.0.pending_saves.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.saves_done.notify_waiters();
+        }
+    }
 }
 
 impl WorldShared {
@@ -18,17 +54,74 @@ impl WorldShared {
         biomes: &BiomeRegistry,
         runtime: &Runtime,
         path: &Path,
+        generator: Option<Arc<dyn ChunkGenerator>>,
     ) -> anyhow::Result<Self> {
         let regions = RegionManager::new(runtime, path).context("failed to get anvil data")?;
 
-        let biome_to_id = biomes
+        let biome_to_id: BTreeMap<_, _> = biomes
             .iter()
             .map(|(id, name, _)| (name.to_string_ident(), id))
             .collect();
 
+        // registry ids are contiguous, so sorting by id puts every name at its index
+        let mut biome_names: Vec<_> = biome_to_id.iter().collect();
+        biome_names.sort_unstable_by_key(|(_, id)| id.to_index());
+        let biome_names = biome_names
+            .into_iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+
         Ok(Self {
             regions,
             biome_to_id,
+            biome_names,
+            generator,
+            saving: tokio::sync::Mutex::new(()),
+            pending_saves: AtomicUsize::new(0),
+            saves_done: tokio::sync::Notify::new(),
         })
     }
+
+    /// Counts a save as running from now on, before it is even spawned, until the returned guard
+    /// is dropped.
+    pub fn start_save(self: &Arc<Self>) -> PendingSave {
+        self.pending_saves.fetch_add(1, Ordering::AcqRel);
+        PendingSave(self.clone())
+    }
+
+    /// Waits until every save started with [`Self::start_save`] is done.
+    pub async fn wait_for_saves(&self) {
+        loop {
+            // registered before checking, so a save finishing in between still wakes it up
+            let done = self.saves_done.notified();
+
+            if self.pending_saves.load(Ordering::Acquire) == 0 {
+                return;
+            }
+
+            done.await;
+        }
+    }
+
+    /// Writes the given columns to their region files.
+    pub async fn save(&self, columns: Vec<(IVec2, ColumnData)>) -> anyhow::Result<()> {
+        let _saving = self.saving.lock().await;
+
+        let mut regions: FxHashMap<IVec2, Vec<(IVec2, Compound)>> = FxHashMap::default();
+
+        for (position, data) in columns {
+            let nbt = serialize_chunk(&data, position, &self.biome_names);
+            let region = IVec2::new(position.x.div_euclid(32), position.y.div_euclid(32));
+            regions.entry(region).or_default().push((position, nbt));
+        }
+
+        for (region, chunks) in regions {
+            self.regions
+                .save_region(region, chunks)
+                .await
+                .with_context(|| format!("failed to save region {region}"))?;
+        }
+
+        Ok(())
+    }
 }
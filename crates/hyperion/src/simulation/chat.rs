@@ -0,0 +1,562 @@
+//! Secure chat: the chat sessions 1.19.3+ clients sign their chat messages with.
+//!
+//! In online mode, the server fetches the keys Mojang signs chat sessions with (see
+//! [`SecureChat`]) and tells clients that it enforces secure chat. A [`ChatSession`] is only
+//! accepted if Mojang signed its key, and every chat message has to be signed with it, arrive in
+//! order and acknowledge the messages its sender was shown (see [`LastSeen`]). Verified messages
+//! are relayed as the signed player chat messages they are, so the clients of other players verify
+//! them too, and the sessions are announced in the player list so they can.
+//!
+//! In offline mode, or if the keys could not be fetched, nothing can be verified. Sessions are then
+//! ignored, chat is relayed as system messages and clients are told that secure chat is not
+//! enforced, so they warn that messages can't be verified, like on any offline mode server.
+//!
+//! Private messages and commands are not signed either way. They are sent as system messages,
+//! which clients show without a warning.
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, bail, ensure};
+use flecs_ecs::macros::Component;
+use rsa::{Pkcs1v15Sign, RsaPublicKey, pkcs8::DecodePublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use valence_protocol::{VarInt, packets::play};
+
+use crate::{
+    egress::player_join::{ChatData, PlayerListActions, PlayerListEntry, PlayerListS2c},
+    net::packets::ChatMessageS2c,
+};
+
+/// A signature made with the key of a chat session.
+pub type Signature = [u8; 256];
+
+/// How many of the last messages a player was sent their chat messages acknowledge.
+pub const LAST_SEEN: usize = 20;
+
+/// How many messages a player may not have acknowledged yet before they are disconnected, like in
+/// vanilla.
+const MAX_PENDING: usize = 4096;
+
+/// The keys Mojang signs the keys of chat sessions with.
+///
+/// Always exists as a singleton. It has no keys unless the server is in online mode and could fetch
+/// them, in which case secure chat is [enforced](Self::enforced).
+#[derive(Component, Debug, Default)]
+pub struct SecureChat {
+    keys: Vec<RsaPublicKey>,
+}
+
+impl SecureChat {
+    /// Uses the DER encoded `keys`, as returned by the Mojang API.
+    pub fn from_der<K: AsRef<[u8]>>(keys: &[K]) -> anyhow::Result<Self> {
+        let keys = keys
+            .iter()
+            .map(|key| {
+                RsaPublicKey::from_public_key_der(key.as_ref())
+                    .context("failed to decode a Mojang public key")
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { keys })
+    }
+
+    /// Whether chat messages have to be signed, which they only can be if there are keys to check
+    /// the chat sessions against.
+    #[must_use]
+    pub const fn enforced(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Whether Mojang signed the key of a chat session of the player `uuid`, which expires at
+    /// `expires_at`.
+    #[must_use]
+    pub fn verify_key(
+        &self,
+        uuid: Uuid,
+        expires_at: i64,
+        public_key: &[u8],
+        signature: &[u8],
+    ) -> bool {
+        let mut payload = Vec::with_capacity(24 + public_key.len());
+        payload.extend_from_slice(uuid.as_bytes());
+        payload.extend_from_slice(&expires_at.to_be_bytes());
+        payload.extend_from_slice(public_key);
+
+        let hashed = Sha1::digest(&payload);
+
+        self.keys.iter().any(|key| {
+            key.verify(Pkcs1v15Sign::new::<Sha1>(), &hashed, signature)
+                .is_ok()
+        })
+    }
+}
+
+/// The chat session of a player, which they send after joining and whenever their key changes.
+#[derive(Component, Clone, Debug)]
+pub struct ChatSession {
+    pub id: Uuid,
+    /// When the public key of the session expires, in milliseconds since the Unix epoch.
+    pub expires_at: i64,
+    /// The DER encoded public key the messages of the player are signed with.
+    pub public_key: Box<[u8]>,
+    /// The signature of Mojang for the public key.
+    pub key_signature: Box<[u8]>,
+    key: RsaPublicKey,
+    /// The index of the next message in the session.
+    index: i32,
+    /// The timestamp of the last message, in milliseconds since the Unix epoch.
+    last_timestamp: u64,
+}
+
+impl ChatSession {
+    pub fn new(
+        id: Uuid,
+        expires_at: i64,
+        public_key: impl Into<Box<[u8]>>,
+        key_signature: impl Into<Box<[u8]>>,
+    ) -> anyhow::Result<Self> {
+        let public_key = public_key.into();
+        let key = RsaPublicKey::from_public_key_der(&public_key)
+            .context("failed to decode the public key of a chat session")?;
+
+        Ok(Self {
+            id,
+            expires_at,
+            public_key,
+            key_signature: key_signature.into(),
+            key,
+            index: 0,
+            last_timestamp: 0,
+        })
+    }
+
+    /// Whether the key of the session expired at `now`, in milliseconds since the Unix epoch.
+    #[must_use]
+    pub const fn expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Records a message sent at `timestamp`. Returns `false` if the message is older than the
+    /// last one, which vanilla disconnects the player for.
+    pub fn accept(&mut self, timestamp: u64) -> bool {
+        if timestamp < self.last_timestamp {
+            return false;
+        }
+
+        self.last_timestamp = timestamp;
+        true
+    }
+
+    /// Checks a chat message of `sender`, whose client was sent the messages in `last_seen`.
+    ///
+    /// Returns the message with the signatures it acknowledges, or why it has to be rejected, for
+    /// which vanilla disconnects the player.
+    pub fn verify(
+        &mut self,
+        sender: Uuid,
+        last_seen: &mut LastSeen,
+        pkt: &play::ChatMessageC2s<'_>,
+    ) -> anyhow::Result<SignedMessage> {
+        ensure!(
+            self.accept(pkt.timestamp),
+            "chat messages arrived out of order"
+        );
+
+        let signature = pkt.signature.context("the chat message is not signed")?;
+        let seen = last_seen.acknowledge(pkt.message_count.0, &pkt.acknowledgement)?;
+
+        let message = SignedMessage {
+            sender,
+            index: self.index,
+            timestamp: pkt.timestamp,
+            salt: pkt.salt,
+            signature: *signature,
+            last_seen: seen,
+        };
+
+        self.check(&message, pkt.message.0, signature)?;
+
+        Ok(message)
+    }
+
+    /// Checks that `signature` is what the key of the session signed for `message`, whose content
+    /// is `content`, and moves on to the next message of the session.
+    fn check(
+        &mut self,
+        message: &SignedMessage,
+        content: &str,
+        signature: &[u8],
+    ) -> anyhow::Result<()> {
+        let hashed = message.hash(self.id, content);
+
+        if self
+            .key
+            .verify(Pkcs1v15Sign::new::<Sha256>(), &hashed, signature)
+            .is_err()
+        {
+            bail!("the signature of the chat message is invalid");
+        }
+
+        self.index += 1;
+
+        Ok(())
+    }
+
+    /// The player list update that tells clients the key `player` signs their messages with, so
+    /// they can verify them.
+    #[must_use]
+    pub fn player_list(&self, player: Uuid) -> PlayerListS2c<'_> {
+        PlayerListS2c {
+            actions: PlayerListActions::default().with_initialize_chat(true),
+            entries: Cow::Owned(vec![PlayerListEntry {
+                player_uuid: player,
+                chat_data: Some(ChatData {
+                    session_id: self.id,
+                    key_expiry_time: self.expires_at,
+                    public_key: &self.public_key,
+                    public_key_signature: &self.key_signature,
+                }),
+                ..PlayerListEntry::default()
+            }]),
+        }
+    }
+}
+
+/// A chat message whose signature was verified.
+#[derive(Clone, Debug)]
+pub struct SignedMessage {
+    pub sender: Uuid,
+    /// The index of the message in the chat session of the sender.
+    pub index: i32,
+    /// When the message was sent, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub salt: u64,
+    pub signature: Signature,
+    /// The signatures of the messages the sender acknowledged, which the signature covers.
+    pub last_seen: Vec<Signature>,
+}
+
+impl SignedMessage {
+    /// The SHA-256 hash of what the sender signed when they sent `content` in the session `session`.
+    fn hash(&self, session: Uuid, content: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+
+        // the version of the signature format
+        hasher.update(1_i32.to_be_bytes());
+
+        hasher.update(self.sender.as_bytes());
+        hasher.update(session.as_bytes());
+        hasher.update(self.index.to_be_bytes());
+
+        hasher.update(self.salt.to_be_bytes());
+        // the timestamp is signed in seconds
+        hasher.update((self.timestamp / 1000).to_be_bytes());
+
+        let content = content.as_bytes();
+        hasher.update(
+            u32::try_from(content.len())
+                .unwrap_or(u32::MAX)
+                .to_be_bytes(),
+        );
+        hasher.update(content);
+
+        hasher.update(
+            u32::try_from(self.last_seen.len())
+                .unwrap_or(u32::MAX)
+                .to_be_bytes(),
+        );
+        for signature in &self.last_seen {
+            hasher.update(signature);
+        }
+
+        hasher.finalize().into()
+    }
+
+    /// The packet relaying `content`, this message, to other players. Clients show it as the
+    /// `sender` parameter of `chat_type`, see [`crate::simulation::util::chat_type_id`].
+    #[must_use]
+    pub fn packet<'a>(
+        &'a self,
+        content: &'a str,
+        chat_type: i32,
+        network_name: hyperion_text::Text<'a>,
+    ) -> ChatMessageS2c<'a> {
+        ChatMessageS2c {
+            sender: self.sender,
+            index: VarInt(self.index),
+            signature: &self.signature,
+            message: content,
+            timestamp: self.timestamp,
+            salt: self.salt,
+            last_seen: &self.last_seen,
+            chat_type: VarInt(chat_type),
+            network_name,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Tracked {
+    signature: Signature,
+    /// Whether the player did not acknowledge the message yet.
+    pending: bool,
+}
+
+/// The signed messages a player was sent, which their chat messages acknowledge so that their
+/// signature covers them.
+///
+/// Every message the player is sent is [tracked](Self::track). When they chat, they tell the
+/// server how many messages arrived since they last did and which of the last [`LAST_SEEN`] they
+/// saw, like vanilla's last seen messages validator.
+#[derive(Component, Clone, Debug)]
+pub struct LastSeen {
+    /// The window of the last [`LAST_SEEN`] messages, followed by the messages the player did not
+    /// acknowledge yet.
+    tracked: VecDeque<Option<Tracked>>,
+}
+
+impl Default for LastSeen {
+    fn default() -> Self {
+        Self {
+            tracked: std::iter::repeat_n(None, LAST_SEEN).collect(),
+        }
+    }
+}
+
+impl LastSeen {
+    /// Records that the player was sent a message signed with `signature`.
+    pub fn track(&mut self, signature: Signature) -> anyhow::Result<()> {
+        ensure!(
+            self.tracked.len() < LAST_SEEN + MAX_PENDING,
+            "too many chat messages were not acknowledged"
+        );
+
+        self.tracked.push_back(Some(Tracked {
+            signature,
+            pending: true,
+        }));
+
+        Ok(())
+    }
+
+    /// Moves the window past the `offset` messages that arrived since the player last chatted or
+    /// acknowledged messages.
+    pub fn apply_offset(&mut self, offset: i32) -> anyhow::Result<()> {
+        let pending = self.tracked.len() - LAST_SEEN;
+
+        let offset = usize::try_from(offset)
+            .ok()
+            .filter(|&offset| offset <= pending)
+            .context("more chat messages were acknowledged than were sent")?;
+
+        self.tracked.drain(..offset);
+
+        Ok(())
+    }
+
+    /// Applies the acknowledgement of a chat message: `offset` as in [`Self::apply_offset`] and
+    /// the bit set of which messages in the window the player saw.
+    ///
+    /// Returns the signatures of the messages the player saw, oldest first.
+    pub fn acknowledge(
+        &mut self,
+        offset: i32,
+        acknowledged: &[u8],
+    ) -> anyhow::Result<Vec<Signature>> {
+        self.apply_offset(offset)?;
+
+        let seen = |i: usize| {
+            acknowledged
+                .get(i / 8)
+                .is_some_and(|byte| byte & (1 << (i % 8)) != 0)
+        };
+
+        ensure!(
+            (LAST_SEEN..acknowledged.len() * 8).all(|i| !seen(i)),
+            "more chat messages were acknowledged than are remembered"
+        );
+
+        let mut signatures = Vec::new();
+
+        for (i, entry) in self.tracked.iter_mut().take(LAST_SEEN).enumerate() {
+            if seen(i) {
+                let tracked = entry
+                    .as_mut()
+                    .context("an unknown chat message was acknowledged")?;
+
+                tracked.pending = false;
+                signatures.push(tracked.signature);
+            } else {
+                ensure!(
+                    entry.is_none_or(|tracked| tracked.pending),
+                    "a chat message that was acknowledged before was ignored"
+                );
+
+                *entry = None;
+            }
+        }
+
+        Ok(signatures)
+    }
+}
+
+/// The current time in milliseconds since the Unix epoch, which chat timestamps are in.
+#[must_use]
+pub fn now_millis() -> i64 {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+
+    i64::try_from(millis).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::{Pkcs1v15Sign, RsaPrivateKey, pkcs8::EncodePublicKey};
+    use sha1::Sha1;
+    use sha2::{Digest, Sha256};
+    use uuid::Uuid;
+
+    use super::{ChatSession, LastSeen, SecureChat, SignedMessage};
+
+    fn key() -> RsaPrivateKey {
+        RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap()
+    }
+
+    fn der(key: &RsaPrivateKey) -> Vec<u8> {
+        key.to_public_key().to_public_key_der().unwrap().into_vec()
+    }
+
+    #[test]
+    fn messages_stay_in_order() {
+        let mut session = ChatSession::new(Uuid::nil(), 1_000, der(&key()), &b""[..]).unwrap();
+
+        assert!(session.accept(10));
+        assert!(session.accept(10));
+        assert!(!session.accept(9));
+        assert!(session.accept(11));
+
+        assert!(!session.expired(999));
+        assert!(session.expired(1_000));
+    }
+
+    #[test]
+    fn only_keys_signed_by_mojang_are_accepted() {
+        let mojang = key();
+        let player = Uuid::from_u128(7);
+        let public_key = der(&key());
+
+        let mut payload = player.as_bytes().to_vec();
+        payload.extend_from_slice(&1_000_i64.to_be_bytes());
+        payload.extend_from_slice(&public_key);
+        let signature = mojang
+            .sign(Pkcs1v15Sign::new::<Sha1>(), &Sha1::digest(&payload))
+            .unwrap();
+
+        let secure = SecureChat::from_der(&[der(&mojang)]).unwrap();
+        assert!(secure.enforced());
+        assert!(secure.verify_key(player, 1_000, &public_key, &signature));
+
+        assert!(!secure.verify_key(Uuid::from_u128(8), 1_000, &public_key, &signature));
+        assert!(!secure.verify_key(player, 2_000, &public_key, &signature));
+        assert!(!SecureChat::default().verify_key(player, 1_000, &public_key, &signature));
+    }
+
+    #[test]
+    fn last_seen_messages_are_acknowledged_in_order() {
+        let mut last_seen = LastSeen::default();
+
+        for i in 1..=3 {
+            last_seen.track([i; 256]).unwrap();
+        }
+
+        // the client saw all three messages, which are at the end of its window
+        let seen = last_seen.acknowledge(3, &[0, 0, 0b1110]).unwrap();
+        assert_eq!(seen, [[1; 256], [2; 256], [3; 256]]);
+
+        last_seen.track([4; 256]).unwrap();
+        let seen = last_seen.acknowledge(1, &[0, 0, 0b1111]).unwrap();
+        assert_eq!(seen, [[1; 256], [2; 256], [3; 256], [4; 256]]);
+
+        // a message that was acknowledged can't be ignored afterwards
+        assert!(last_seen.clone().acknowledge(0, &[0, 0, 0b1110]).is_err());
+
+        let mut last_seen = LastSeen::default();
+        last_seen.track([1; 256]).unwrap();
+
+        // more messages than were sent
+        assert!(last_seen.clone().acknowledge(2, &[0, 0, 0]).is_err());
+        // a message that is not in the window
+        assert!(last_seen.clone().acknowledge(0, &[1, 0, 0]).is_err());
+        // a message past the window
+        assert!(last_seen.clone().acknowledge(1, &[0, 0, 1 << 4]).is_err());
+
+        assert_eq!(last_seen.acknowledge(1, &[0, 0, 1 << 3]).unwrap(), [
+            [1; 256]
+        ]);
+    }
+
+    #[test]
+    fn signed_messages_are_verified() {
+        let player = key();
+        let sender = Uuid::from_u128(7);
+        let session_id = Uuid::from_u128(8);
+        let mut session = ChatSession::new(session_id, i64::MAX, der(&player), &b""[..]).unwrap();
+
+        // what vanilla signs: the version, the link of the message in the session and its body
+        let sign = |index: i32, content: &str| {
+            let mut payload = Vec::new();
+            payload.extend_from_slice(&1_i32.to_be_bytes());
+            payload.extend_from_slice(sender.as_bytes());
+            payload.extend_from_slice(session_id.as_bytes());
+            payload.extend_from_slice(&index.to_be_bytes());
+            payload.extend_from_slice(&42_u64.to_be_bytes());
+            payload.extend_from_slice(&1_700_000_000_u64.to_be_bytes());
+            payload.extend_from_slice(&u32::try_from(content.len()).unwrap().to_be_bytes());
+            payload.extend_from_slice(content.as_bytes());
+            payload.extend_from_slice(&1_u32.to_be_bytes());
+            payload.extend_from_slice(&[9; 256]);
+
+            player
+                .sign(Pkcs1v15Sign::new::<Sha256>(), &Sha256::digest(&payload))
+                .unwrap()
+        };
+
+        let message = |index| SignedMessage {
+            sender,
+            index,
+            timestamp: 1_700_000_000_123,
+            salt: 42,
+            signature: [0; 256],
+            last_seen: vec![[9; 256]],
+        };
+
+        session
+            .check(&message(0), "hello", &sign(0, "hello"))
+            .unwrap();
+
+        // the signature covers the content and the index of the message
+        assert!(
+            session
+                .check(&message(1), "hello!", &sign(1, "hello"))
+                .is_err()
+        );
+        assert!(
+            session
+                .check(&message(1), "hello", &sign(0, "hello"))
+                .is_err()
+        );
+
+        session
+            .check(&message(1), "hello", &sign(1, "hello"))
+            .unwrap();
+        assert_eq!(session.index, 2);
+    }
+}
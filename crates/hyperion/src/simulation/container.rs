@@ -0,0 +1,600 @@
+//! Blocks that hold items, like chests, barrels, furnaces and hoppers.
+//!
+//! While at least one player looks into a container, its items are kept in [`Containers`]. They
+//! are read from the block entity of the block when the first player opens it, and every change
+//! is written back to the block entity, which is saved with its chunk. Each half of a double
+//! chest is opened on its own.
+
+use std::{borrow::Cow, collections::HashMap, ops::Range};
+
+use flecs_ecs::prelude::*;
+use glam::IVec3;
+use hyperion_inventory::{
+    Inventory, InventoryAccessError, PlayerInventory, action::Cursor, window::ContainerWindow,
+};
+use roaring::RoaringBitmap;
+use tracing::{info_span, warn};
+use valence_generated::{block::BlockKind, item::ItemKind};
+use valence_nbt::{Compound, List, Value, compound};
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::play::{self, open_screen_s2c::WindowType},
+};
+use valence_text::IntoText;
+
+use crate::{
+    net::{Compose, ConnectionId},
+    simulation::{
+        blocks::Blocks,
+        event, window,
+        worlds::{Dimension, WorldId, with_blocks, with_blocks_mut},
+    },
+    storage::{EventQueue, Events},
+};
+
+/// The kinds of blocks players can put items into.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ContainerKind {
+    /// Chests and trapped chests.
+    Chest,
+    Barrel,
+    Furnace,
+    BlastFurnace,
+    Smoker,
+    Hopper,
+}
+
+impl ContainerKind {
+    /// The kind of container `block` is, or `None` if it is not a container.
+    #[must_use]
+    pub const fn of(block: BlockKind) -> Option<Self> {
+        let kind = match block {
+            BlockKind::Chest | BlockKind::TrappedChest => Self::Chest,
+            BlockKind::Barrel => Self::Barrel,
+            BlockKind::Furnace => Self::Furnace,
+            BlockKind::BlastFurnace => Self::BlastFurnace,
+            BlockKind::Smoker => Self::Smoker,
+            BlockKind::Hopper => Self::Hopper,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    #[must_use]
+    pub const fn window_type(self) -> WindowType {
+        match self {
+            Self::Chest | Self::Barrel => WindowType::Generic9x3,
+            Self::Furnace => WindowType::Furnace,
+            Self::BlastFurnace => WindowType::BlastFurnace,
+            Self::Smoker => WindowType::Smoker,
+            Self::Hopper => WindowType::Hopper,
+        }
+    }
+
+    #[must_use]
+    pub const fn title(self) -> &'static str {
+        match self {
+            Self::Chest => "Chest",
+            Self::Barrel => "Barrel",
+            Self::Furnace => "Furnace",
+            Self::BlastFurnace => "Blast Furnace",
+            Self::Smoker => "Smoker",
+            Self::Hopper => "Hopper",
+        }
+    }
+
+    /// The slots players may put items into. Furnaces only take them out of their result slot.
+    #[must_use]
+    pub const fn inputs(self) -> Range<u16> {
+        match self {
+            Self::Chest | Self::Barrel => 0..27,
+            Self::Furnace | Self::BlastFurnace | Self::Smoker => 0..2,
+            Self::Hopper => 0..5,
+        }
+    }
+
+    /// An empty inventory of the right size.
+    #[must_use]
+    pub fn inventory(self) -> ContainerInventory {
+        match self {
+            Self::Chest | Self::Barrel => ContainerInventory::Generic9x3(Inventory::default()),
+            Self::Furnace | Self::BlastFurnace | Self::Smoker => {
+                ContainerInventory::Furnace(Inventory::default())
+            }
+            Self::Hopper => ContainerInventory::Hopper(Inventory::default()),
+        }
+    }
+}
+
+/// The items of a container.
+#[derive(Debug)]
+pub enum ContainerInventory {
+    /// A chest or barrel.
+    Generic9x3(Inventory<27>),
+    /// The input, fuel and result of a furnace, blast furnace or smoker.
+    Furnace(Inventory<3>),
+    Hopper(Inventory<5>),
+}
+
+impl ContainerInventory {
+    #[must_use]
+    pub fn slots(&self) -> &[ItemStack] {
+        match self {
+            Self::Generic9x3(inventory) => inventory.slots(),
+            Self::Furnace(inventory) => inventory.slots(),
+            Self::Hopper(inventory) => inventory.slots(),
+        }
+    }
+
+    pub fn set(&mut self, index: u16, stack: ItemStack) -> Result<(), InventoryAccessError> {
+        match self {
+            Self::Generic9x3(inventory) => inventory.set(index, stack),
+            Self::Furnace(inventory) => inventory.set(index, stack),
+            Self::Hopper(inventory) => inventory.set(index, stack),
+        }
+    }
+
+    /// The slots changed since this was last called.
+    fn take_updated(&mut self) -> RoaringBitmap {
+        let updated = match self {
+            Self::Generic9x3(inventory) => &mut inventory.updated_since_last_tick,
+            Self::Furnace(inventory) => &mut inventory.updated_since_last_tick,
+            Self::Hopper(inventory) => &mut inventory.updated_since_last_tick,
+        };
+
+        core::mem::take(updated)
+    }
+
+    /// Applies a click of a player in the window showing this container above `player`, see
+    /// [`window::click`].
+    pub(crate) fn click(
+        &mut self,
+        player: &mut PlayerInventory,
+        cursor: &mut Cursor,
+        inputs: Range<u16>,
+        event: &event::ClickWindow,
+    ) -> window::Click {
+        match self {
+            Self::Generic9x3(inventory) => {
+                let window = ContainerWindow::new(inventory, player).with_inputs(inputs);
+                window::click(window, cursor, event)
+            }
+            Self::Furnace(inventory) => {
+                let window = ContainerWindow::new(inventory, player).with_inputs(inputs);
+                window::click(window, cursor, event)
+            }
+            Self::Hopper(inventory) => {
+                let window = ContainerWindow::new(inventory, player).with_inputs(inputs);
+                window::click(window, cursor, event)
+            }
+        }
+    }
+}
+
+/// A container block at least one player looks into.
+#[derive(Debug)]
+pub struct Container {
+    pub kind: ContainerKind,
+    pub inventory: ContainerInventory,
+    /// The players looking into the container.
+    pub viewers: Vec<Entity>,
+    /// The id of the block entity, e.g. `minecraft:trapped_chest`.
+    id: String,
+}
+
+impl Container {
+    /// Writes the items into the block entity at `position`, keeping everything else in it.
+    fn save(&self, blocks: &mut Blocks, position: IVec3) {
+        let mut block_entity = blocks
+            .block_entity(position)
+            .cloned()
+            .unwrap_or_else(|| compound! { "id" => self.id.clone() });
+
+        block_entity.insert("Items", write_items(self.inventory.slots()));
+
+        if let Err(e) = blocks.set_block_entity(position, Some(block_entity)) {
+            warn!("failed to save the container at {position}: {e:?}");
+        }
+    }
+}
+
+/// The `Items` of a block entity.
+fn write_items(slots: &[ItemStack]) -> List {
+    let items = slots
+        .iter()
+        .enumerate()
+        .filter(|(_, stack)| !stack.is_empty())
+        .map(|(slot, stack)| {
+            let mut item = compound! {
+                "Slot" => i8::try_from(slot).unwrap(),
+                "id" => format!("minecraft:{}", stack.item.to_str()),
+                "Count" => stack.count,
+            };
+
+            if let Some(tag) = &stack.nbt {
+                item.insert("tag", tag.clone());
+            }
+
+            item
+        })
+        .collect();
+
+    List::Compound(items)
+}
+
+/// The `Items` of a block entity with their slots.
+fn stored_items(block_entity: &Compound) -> Vec<(u16, ItemStack)> {
+    let Some(Value::List(List::Compound(items))) = block_entity.get("Items") else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .filter_map(|item| {
+            let (Some(Value::Byte(slot)), Some(Value::String(id)), Some(Value::Byte(count))) =
+                (item.get("Slot"), item.get("id"), item.get("Count"))
+            else {
+                warn!("skipping invalid item {item:?}");
+                return None;
+            };
+
+            let Some(kind) = id.strip_prefix("minecraft:").and_then(ItemKind::from_str) else {
+                warn!("skipping unknown item {id}");
+                return None;
+            };
+
+            let tag = match item.get("tag") {
+                Some(Value::Compound(tag)) => Some(tag.clone()),
+                _ => None,
+            };
+
+            let slot = u16::try_from(*slot).ok()?;
+
+            Some((slot, ItemStack::new(kind, *count, tag)))
+        })
+        .collect()
+}
+
+/// Reads the `Items` of a block entity into `inventory`.
+fn read_items(block_entity: &Compound, inventory: &mut ContainerInventory) {
+    for (slot, item) in stored_items(block_entity) {
+        if let Err(e) = inventory.set(slot, item) {
+            warn!("skipping item in slot {slot}: {e}");
+        }
+    }
+}
+
+/// The containers players look into, by world and position.
+#[derive(Component, Debug, Default)]
+pub struct Containers {
+    open: HashMap<(WorldId, IVec3), Container>,
+    last_window_id: u8,
+}
+
+impl Containers {
+    #[must_use]
+    pub fn get(&self, world: WorldId, position: IVec3) -> Option<&Container> {
+        self.open.get(&(world, position))
+    }
+
+    /// The container at `position`. Changes to its inventory are sent to the players looking into
+    /// it and saved.
+    pub fn get_mut(&mut self, world: WorldId, position: IVec3) -> Option<&mut Container> {
+        self.open.get_mut(&(world, position))
+    }
+
+    /// Window ids go from 1 to 100 like in vanilla; 0 is the inventory of the player.
+    pub(crate) const fn next_window_id(&mut self) -> u8 {
+        self.last_window_id = self.last_window_id % 100 + 1;
+        self.last_window_id
+    }
+
+    /// Stops `player` from looking into the container at `key`. The container is saved and
+    /// forgotten once nobody looks into it.
+    pub(crate) fn leave(&mut self, world: &World, key: (WorldId, IVec3), player: Entity) {
+        let Some(container) = self.open.get_mut(&key) else {
+            return;
+        };
+
+        container.viewers.retain(|viewer| *viewer != player);
+
+        if !container.viewers.is_empty() {
+            return;
+        }
+
+        let (world_id, position) = key;
+        let container = self.open.remove(&key).unwrap();
+
+        with_blocks_mut(world, world_id, |blocks| container.save(blocks, position));
+    }
+}
+
+/// The container a player looks into.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OpenContainer {
+    pub window_id: u8,
+    pub world: WorldId,
+    pub position: IVec3,
+}
+
+/// Sends the container and the inventory of the player below it.
+pub(crate) fn send_window(
+    compose: &Compose,
+    stream: ConnectionId,
+    system: EntityView<'_>,
+    window_id: u8,
+    container: &[ItemStack],
+    player: &PlayerInventory,
+    cursor: &Cursor,
+) {
+    // the main inventory and hotbar
+    let slots: Vec<_> = container
+        .iter()
+        .chain(&player.slots()[9..45])
+        .cloned()
+        .collect();
+
+    window::send(compose, stream, system, window_id, slots, cursor);
+}
+
+#[derive(Component)]
+pub struct ContainerModule;
+
+impl Module for ContainerModule {
+    fn module(world: &World) {
+        world.component::<Containers>();
+        world.component::<OpenContainer>();
+
+        world.set(Containers::default());
+
+        // players who leave while looking into a container
+        observer!(world, flecs::OnRemove, &OpenContainer, &mut Containers($)).each_iter(
+            |it, row, (open, containers)| {
+                let world = it.world();
+                let entity = it.entity(row);
+                containers.leave(&world, (open.world, open.position), entity.id());
+            },
+        );
+
+        system!(
+            "open_containers",
+            world,
+            &Compose($),
+            &mut Containers($),
+            &mut EventQueue<event::OpenContainer>($),
+        )
+        .each_iter(|it, _, (compose, containers, event_queue)| {
+            let span = info_span!("open_containers");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+
+            for event::OpenContainer { position, from } in event_queue.drain() {
+                let player = world.entity_from_id(from);
+
+                if !player.is_alive() {
+                    continue;
+                }
+
+                let world_id = player.try_get::<&WorldId>(|id| *id).unwrap_or_default();
+                let key = (world_id, position);
+
+                if let Some(open) = player.try_get::<&OpenContainer>(|open| *open) {
+                    containers.leave(&world, (open.world, open.position), from);
+                }
+
+                if !containers.open.contains_key(&key) {
+                    let container = with_blocks(world, world_id, |blocks| {
+                        let block = blocks.get_block(position)?.to_kind();
+                        let kind = ContainerKind::of(block)?;
+
+                        let mut inventory = kind.inventory();
+                        if let Some(block_entity) = blocks.block_entity(position) {
+                            read_items(block_entity, &mut inventory);
+                        }
+
+                        // the items were not changed by anyone
+                        inventory.take_updated();
+
+                        Some(Container {
+                            kind,
+                            inventory,
+                            viewers: Vec::new(),
+                            id: format!("minecraft:{}", block.to_str()),
+                        })
+                    });
+
+                    let Some(Some(container)) = container else {
+                        continue;
+                    };
+
+                    containers.open.insert(key, container);
+                }
+
+                let window_id = containers.next_window_id();
+                let container = containers.open.get_mut(&key).unwrap();
+                container.viewers.push(from);
+
+                player.set(OpenContainer {
+                    window_id,
+                    world: world_id,
+                    position,
+                });
+
+                player.get::<(&ConnectionId, &PlayerInventory, &Cursor)>(
+                    |(&stream, inventory, cursor)| {
+                        let pkt = play::OpenScreenS2c {
+                            window_id: VarInt(i32::from(window_id)),
+                            window_type: container.kind.window_type(),
+                            window_title: container.kind.title().into_cow_text(),
+                        };
+
+                        if let Err(e) = compose.unicast(&pkt, stream, system) {
+                            warn!("failed to open container: {e}");
+                        }
+
+                        send_window(
+                            compose,
+                            stream,
+                            system,
+                            window_id,
+                            container.inventory.slots(),
+                            inventory,
+                            cursor,
+                        );
+                    },
+                );
+            }
+        });
+
+        // broken containers nobody looks into drop the items saved in their block entity
+        system!(
+            "drop_container_items",
+            world,
+            &mut Blocks,
+            &Dimension,
+            &Containers($),
+            &Events($),
+        )
+        .kind::<flecs::pipeline::OnStore>()
+        .each_iter(|it, _, (blocks, dimension, containers, events)| {
+            let world = it.world();
+
+            for (position, block_entity) in blocks.removed_block_entities.drain(..) {
+                // open containers drop what their viewers see in them in `sync_containers`
+                if containers.get(dimension.id, position).is_some() {
+                    continue;
+                }
+
+                for (_, item) in stored_items(&block_entity) {
+                    let event = event::ItemDropEvent::from_block(item, position, dimension.id);
+                    events.push(event, &world);
+                }
+            }
+        });
+
+        system!("sync_containers", world, &Compose($), &mut Containers($), &Events($))
+            .kind::<flecs::pipeline::OnStore>()
+            .each_iter(|it, _, (compose, containers, events)| {
+                let span = info_span!("sync_containers");
+                let _enter = span.enter();
+
+                let system = it.system();
+                let world = it.world();
+
+                // containers whose block was broken or replaced
+                let removed: Vec<_> = containers
+                    .open
+                    .iter()
+                    .filter(|((world_id, position), container)| {
+                        let block =
+                            with_blocks(world, *world_id, |blocks| blocks.get_block(*position));
+
+                        let kind = block.flatten().map(|block| block.to_kind());
+                        kind.and_then(ContainerKind::of) != Some(container.kind)
+                    })
+                    .map(|(key, _)| *key)
+                    .collect();
+
+                for key in removed {
+                    let container = containers.open.remove(&key).unwrap();
+
+                    let (world_id, position) = key;
+                    for item in container.inventory.slots() {
+                        if item.is_empty() {
+                            continue;
+                        }
+
+                        let event =
+                            event::ItemDropEvent::from_block(item.clone(), position, world_id);
+                        events.push(event, &world);
+                    }
+
+                    for viewer in container.viewers {
+                        let viewer = world.entity_from_id(viewer);
+
+                        viewer.try_get::<(&OpenContainer, &ConnectionId)>(|(open, &stream)| {
+                            let pkt = play::CloseScreenS2c {
+                                window_id: open.window_id,
+                            };
+
+                            if let Err(e) = compose.unicast(&pkt, stream, system) {
+                                warn!("failed to close container: {e}");
+                            }
+                        });
+
+                        viewer.remove::<OpenContainer>();
+                    }
+                }
+
+                for ((world_id, position), container) in &mut containers.open {
+                    let updated = container.inventory.take_updated();
+
+                    if updated.is_empty() {
+                        continue;
+                    }
+
+                    for &viewer in &container.viewers {
+                        world
+                            .entity_from_id(viewer)
+                            .try_get::<(&OpenContainer, &ConnectionId, &Cursor)>(
+                                |(open, &stream, cursor)| {
+                                    for slot in &updated {
+                                        let slot = u16::try_from(slot).unwrap();
+                                        let slots = container.inventory.slots();
+                                        let Some(slot_data) = slots.get(usize::from(slot)) else {
+                                            continue;
+                                        };
+
+                                        let pkt = play::ScreenHandlerSlotUpdateS2c {
+                                            window_id: i8::try_from(open.window_id).unwrap(),
+                                            state_id: VarInt(cursor.state_id),
+                                            slot_idx: i16::try_from(slot).unwrap(),
+                                            slot_data: Cow::Borrowed(slot_data),
+                                        };
+
+                                        if let Err(e) = compose.unicast(&pkt, stream, system) {
+                                            warn!("failed to update container: {e}");
+                                        }
+                                    }
+                                },
+                            );
+                    }
+
+                    with_blocks_mut(world, *world_id, |blocks| container.save(blocks, *position));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_generated::item::ItemKind;
+    use valence_nbt::{Value, compound};
+    use valence_protocol::ItemStack;
+
+    use super::{ContainerKind, read_items, write_items};
+
+    #[test]
+    fn items_round_trip_through_block_entities() {
+        let mut chest = ContainerKind::Chest.inventory();
+        chest
+            .set(0, ItemStack::new(ItemKind::Diamond, 3, None))
+            .unwrap();
+        chest
+            .set(26, ItemStack::new(ItemKind::OakLog, 64, None))
+            .unwrap();
+
+        let block_entity = compound! {
+            "id" => "minecraft:chest",
+            "Items" => Value::List(write_items(chest.slots())),
+        };
+
+        let mut read = ContainerKind::Barrel.inventory();
+        read_items(&block_entity, &mut read);
+
+        assert_eq!(read.slots(), chest.slots());
+    }
+}
@@ -3,17 +3,73 @@
 use derive_more::Constructor;
 use flecs_ecs::{core::Entity, macros::Component};
 use glam::{IVec3, Vec3};
+use hyperion_inventory::action::InventoryAction;
 use hyperion_utils::{Lifetime, RuntimeLifetime};
 use valence_generated::block::BlockState;
 use valence_protocol::Hand;
 use valence_server::{ItemKind, entity::item_frame::ItemStack};
 
-use crate::simulation::skin::PlayerSkin;
+use crate::simulation::{
+    anticheat::{Check, Response},
+    chat::SignedMessage,
+    get_direction_from_rotation,
+    skin::PlayerSkin,
+    worlds::WorldId,
+};
 
+/// An item that falls into the world as an item entity which can be picked up.
 #[derive(Component, Default, Debug)]
 pub struct ItemDropEvent {
     pub item: ItemStack,
     pub location: Vec3,
+    pub velocity: Vec3,
+    pub world: WorldId,
+    /// The ticks until the item can be picked up.
+    pub pickup_delay: u16,
+}
+
+impl ItemDropEvent {
+    /// An item thrown by a player whose feet are at `position` in the direction they look.
+    #[must_use]
+    pub fn thrown(item: ItemStack, position: Vec3, yaw: f32, pitch: f32, world: WorldId) -> Self {
+        // like vanilla, the item leaves slightly below the eyes with a bit of spread
+        let angle = fastrand::f32() * core::f32::consts::TAU;
+        let spread = fastrand::f32() * 0.02;
+        let spread = Vec3::new(
+            angle.cos() * spread,
+            (fastrand::f32() - fastrand::f32()) * 0.1,
+            angle.sin() * spread,
+        );
+
+        let velocity = get_direction_from_rotation(yaw, pitch) * 0.3 + Vec3::Y * 0.1 + spread;
+
+        Self {
+            item,
+            location: position + Vec3::Y * (1.62 - 0.3),
+            velocity,
+            world,
+            pickup_delay: 40,
+        }
+    }
+
+    /// An item that pops out of the block at `position`, e.g. out of a broken chest.
+    #[must_use]
+    pub fn from_block(item: ItemStack, position: IVec3, world: WorldId) -> Self {
+        let offset = Vec3::new(fastrand::f32(), fastrand::f32(), fastrand::f32()) * 0.5 - 0.25;
+        let velocity = Vec3::new(
+            fastrand::f32().mul_add(0.2, -0.1),
+            0.2,
+            fastrand::f32().mul_add(0.2, -0.1),
+        );
+
+        Self {
+            item,
+            location: position.as_vec3() + Vec3::splat(0.5) + offset,
+            velocity,
+            world,
+            pickup_delay: 10,
+        }
+    }
 }
 
 #[derive(Component, Default, Debug)]
@@ -26,6 +82,9 @@ pub struct ItemInteract {
 pub struct ChatMessage {
     pub msg: RuntimeLifetime<&'static str>,
     pub by: Entity,
+    /// The verified signature of the message if secure chat is enforced, see
+    /// [`crate::simulation::chat`].
+    pub signed: Option<SignedMessage>,
 }
 
 #[derive(Debug)]
@@ -50,6 +109,54 @@ pub struct HealthUpdate {
     pub to: f32,
 }
 
+/// A player failed an anti-cheat check often enough that `response` was applied.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub player: Entity,
+    pub check: Check,
+    /// The violation level of the check for the player.
+    pub level: f32,
+    pub response: Response,
+}
+
+/// A player is about to join the game. Cancelling it kicks them with `kick_reason`.
+///
+/// Fired on the [`EventBus`](crate::storage::EventBus) only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerJoin {
+    pub player: Entity,
+    pub kick_reason: String,
+}
+
+/// A player left the game or was kicked with `reason`. It cannot be cancelled.
+///
+/// Fired on the [`EventBus`](crate::storage::EventBus) only.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlayerQuit {
+    pub player: Entity,
+    pub reason: String,
+}
+
+/// An entity is about to take `amount` damage. Cancelling it spares the entity.
+///
+/// Fired on the [`EventBus`](crate::storage::EventBus) only, by whatever deals the damage.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Damage {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    /// The damage after armor. This corresponds to the same unit as [`crate::simulation::metadata::living_entity::Health`].
+    pub amount: f32,
+}
+
+/// An entity is about to die. Cancelling it keeps the entity alive with half a heart.
+///
+/// Fired on the [`EventBus`](crate::storage::EventBus) only, by whatever deals the damage.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Death {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct StartDestroyBlock {
     pub position: IVec3,
@@ -79,6 +186,41 @@ pub struct ToggleDoor {
     pub sequence: i32,
 }
 
+/// A player right-clicked a container block like a chest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OpenContainer {
+    pub position: IVec3,
+    pub from: Entity,
+}
+
+/// A player right-clicked a crafting table.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OpenCraftingTable {
+    pub from: Entity,
+}
+
+/// A player clicked a slot of a window, which is their own inventory if `window_id` is 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClickWindow {
+    pub from: Entity,
+    pub window_id: u8,
+    /// The state id of the window the client clicked in.
+    pub state_id: i32,
+    /// `None` if the click could not be understood, in which case the window is sent again.
+    pub action: Option<InventoryAction>,
+    /// The slots the client predicts the click to change.
+    pub slot_changes: Vec<(u16, ItemStack)>,
+    /// The item the client predicts the player to hold with their mouse after the click.
+    pub carried_item: ItemStack,
+}
+
+/// A player closed a window, which is their own inventory if `window_id` is 0.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CloseWindow {
+    pub from: Entity,
+    pub window_id: u8,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct SwingArm {
     pub hand: Hand,
@@ -10,6 +10,7 @@ use anyhow::{Context, bail};
 use flecs_ecs::core::{Entity, EntityView, EntityViewGet, World};
 use geometry::aabb::Aabb;
 use glam::{IVec3, Vec3};
+use hyperion_inventory::parser::create_inventory_action;
 use hyperion_utils::{EntityExt, LifetimeHandle, RuntimeLifetime};
 use tracing::{info, instrument, warn};
 use valence_generated::{
@@ -17,7 +18,7 @@ use valence_generated::{
     item::ItemKind,
 };
 use valence_protocol::{
-    Hand, ItemStack, VarInt,
+    BlockPos, Hand, VarInt,
     packets::play::{
         self, client_command_c2s::ClientCommand, player_action_c2s::PlayerAction,
         player_interact_entity_c2s::EntityInteraction,
@@ -29,18 +30,24 @@ use valence_text::IntoText;
 use super::{
     ConfirmBlockSequences, EntitySize, Position,
     animation::{self, ActiveAnimation},
+    anticheat::{AntiCheat, Check, Move, Response},
     block_bounds,
     blocks::Blocks,
     bow::BowCharging,
+    container::ContainerKind,
     event::ClientStatusEvent,
 };
 use crate::{
+    config::Config,
+    ingress::PendingRemove,
     net::{Compose, ConnectionId, decoder::BorrowedPacketFrame},
     simulation::{
-        Pitch, Yaw, aabb,
+        Pitch, Uuid, Yaw, aabb,
+        chat::{self, ChatSession, LastSeen, SecureChat},
         event::{self, PluginMessage},
         metadata::entity::Pose,
         packet::HandlerRegistry,
+        worlds::WorldId,
     },
     storage::{CommandCompletionRequest, Events, InteractEvent},
 };
@@ -50,7 +57,7 @@ fn full(
         position,
         yaw,
         pitch,
-        ..
+        on_ground,
     }: &play::FullC2s,
     _: &dyn LifetimeHandle<'_>,
     query: &mut PacketSwitchQuery<'_>,
@@ -59,7 +66,7 @@ fn full(
     // if they are, ignore the packet
 
     let position = position.as_vec3();
-    change_position_or_correct_client(query, position);
+    change_position_or_correct_client(query, position, on_ground);
 
     query.yaw.yaw = yaw;
     query.pitch.pitch = pitch;
@@ -68,10 +75,14 @@ fn full(
 }
 
 // #[instrument(skip_all)]
-fn change_position_or_correct_client(query: &mut PacketSwitchQuery<'_>, proposed: Vec3) {
-    let pose = &mut *query.position;
+fn change_position_or_correct_client(
+    query: &mut PacketSwitchQuery<'_>,
+    proposed: Vec3,
+    on_ground: bool,
+) {
+    let from = **query.position;
 
-    if let Err(e) = try_change_position(proposed, pose, *query.size, query.blocks) {
+    if let Err(e) = try_change_position(proposed, query.position, *query.size, query.blocks) {
         // Send error message to player
         let msg = format!("§c{e}");
         let pkt = play::GameMessageS2c {
@@ -83,17 +94,106 @@ fn change_position_or_correct_client(query: &mut PacketSwitchQuery<'_>, proposed
             warn!("Failed to send error message to player: {e}");
         }
 
-        // Correct client position
-        let pkt = play::PlayerPositionLookS2c {
-            position: pose.position.as_dvec3(),
-            yaw: query.yaw.yaw,
-            pitch: query.pitch.pitch,
-            flags: PlayerPositionLookFlags::default(),
-            teleport_id: VarInt(fastrand::i32(..)),
+        correct_position(query);
+        return;
+    }
+
+    let movement = Move {
+        from,
+        to: proposed,
+        on_ground,
+    };
+
+    let tick = query.compose.global().tick;
+    let violation = query.anticheat.check_move(
+        &query.config.anticheat,
+        tick,
+        movement,
+        *query.size,
+        query.blocks,
+    );
+
+    if let Some((check, response)) = violation {
+        // whatever the response, the move does not happen
+        **query.position = from;
+
+        respond(query, check, response);
+
+        if response != Response::RubberBand {
+            correct_position(query);
+        }
+    }
+}
+
+/// Sends the client back to where the server has the player.
+fn correct_position(query: &mut PacketSwitchQuery<'_>) {
+    let pkt = play::PlayerPositionLookS2c {
+        position: query.position.as_dvec3(),
+        yaw: query.yaw.yaw,
+        pitch: query.pitch.pitch,
+        flags: PlayerPositionLookFlags::default(),
+        teleport_id: VarInt(fastrand::i32(..)),
+    };
+
+    if let Err(e) = query.compose.unicast(&pkt, query.io_ref, query.system) {
+        warn!("Failed to correct client position: {e}");
+    }
+
+    let tick = query.compose.global().tick;
+    query.anticheat.teleported(**query.position, tick);
+}
+
+/// Shows the client the blocks the server has at `positions` and acknowledges `sequence`, undoing
+/// block changes the client predicted.
+fn resync_blocks(
+    query: &mut PacketSwitchQuery<'_>,
+    positions: &[IVec3],
+    sequence: i32,
+) -> anyhow::Result<()> {
+    for &position in positions {
+        let Some(block) = query.blocks.get_block(position) else {
+            continue;
         };
 
-        if let Err(e) = query.compose.unicast(&pkt, query.io_ref, query.system) {
-            warn!("Failed to correct client position: {e}");
+        let pkt = play::BlockUpdateS2c {
+            position: BlockPos::new(position.x, position.y, position.z),
+            block_id: block,
+        };
+
+        query.compose.unicast(&pkt, query.io_ref, query.system)?;
+    }
+
+    query.confirm_block_sequences.push(sequence);
+
+    Ok(())
+}
+
+/// Applies the `response` to the player failing `check` and tells plugins about it. Whatever the
+/// player did has to be undone by the caller.
+fn respond(query: &mut PacketSwitchQuery<'_>, check: Check, response: Response) {
+    let violation = event::Violation {
+        player: query.id,
+        check,
+        level: query.anticheat.level(check),
+        response,
+    };
+
+    query.events.push(violation, query.world);
+
+    match response {
+        Response::RubberBand => {
+            if let Some(setback) = query.anticheat.setback() {
+                **query.position = setback;
+            }
+
+            correct_position(query);
+        }
+        Response::Cancel => {}
+        Response::Kick => {
+            info!("kicking player for failing the {check} check");
+            query.view.set(PendingRemove::new(format!(
+                "§cKicked for cheating ({check})"
+            )));
         }
     }
 }
@@ -158,7 +258,9 @@ fn is_within_speed_limits(current: Vec3, proposed: Vec3) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn has_block_collision(position: &Vec3, size: EntitySize, blocks: &Blocks) -> bool {
+/// Whether an entity of `size` at `position` is inside a block it cannot pass through.
+#[must_use]
+pub fn has_block_collision(position: &Vec3, size: EntitySize, blocks: &Blocks) -> bool {
     use std::ops::ControlFlow;
 
     let (min, max) = block_bounds(*position, size);
@@ -194,11 +296,14 @@ fn look_and_on_ground(
 }
 
 fn position_and_on_ground(
-    &play::PositionAndOnGroundC2s { position, .. }: &play::PositionAndOnGroundC2s,
+    &play::PositionAndOnGroundC2s {
+        position,
+        on_ground,
+    }: &play::PositionAndOnGroundC2s,
     _: &dyn LifetimeHandle<'_>,
     query: &mut PacketSwitchQuery<'_>,
 ) -> anyhow::Result<()> {
-    change_position_or_correct_client(query, position.as_vec3());
+    change_position_or_correct_client(query, position.as_vec3(), on_ground);
 
     Ok(())
 }
@@ -208,6 +313,13 @@ fn chat_command<'a>(
     handle: &dyn LifetimeHandle<'a>,
     query: &mut PacketSwitchQuery<'_>,
 ) -> anyhow::Result<()> {
+    // commands are not signed, but acknowledge messages like chat messages do
+    acknowledge(query, |last_seen| {
+        last_seen
+            .acknowledge(pkt.message_count.0, &pkt.acknowledgement)
+            .map(drop)
+    });
+
     let command = RuntimeLifetime::new(pkt.command.0, handle);
 
     query.events.push(
@@ -252,6 +364,27 @@ fn player_interact_entity(
     let target = packet.entity_id.0;
     let target = Entity::from_minecraft_id(target);
 
+    let target_box = query
+        .world
+        .entity_from_id(target)
+        .try_get::<(&Position, &EntitySize)>(|(position, size)| (**position, *size));
+
+    if let Some(target_box) = target_box {
+        let tick = query.compose.global().tick;
+        let violation = query.anticheat.check_attack(
+            &query.config.anticheat,
+            tick,
+            **query.position,
+            target_box,
+            query.inventory.get_cursor(),
+        );
+
+        if let Some((check, response)) = violation {
+            respond(query, check, response);
+            return Ok(());
+        }
+    }
+
     query.events.push(
         event::AttackEntity {
             origin: query.id,
@@ -283,6 +416,8 @@ pub struct PacketSwitchQuery<'a> {
     pub inventory: &'a mut hyperion_inventory::PlayerInventory,
     pub animation: &'a mut ActiveAnimation,
     pub crafting_registry: &'a hyperion_crafting::CraftingRegistry,
+    pub anticheat: &'a mut AntiCheat,
+    pub config: &'a Config,
 }
 
 // i.e., shooting a bow, digging a block, etc
@@ -296,6 +431,9 @@ fn player_action(
 
     match packet.action {
         PlayerAction::StartDestroyBlock => {
+            let tick = query.compose.global().tick;
+            query.anticheat.start_digging(position, tick);
+
             let event = event::StartDestroyBlock {
                 position,
                 from: query.id,
@@ -304,13 +442,33 @@ fn player_action(
             query.events.push(event, query.world);
         }
         PlayerAction::StopDestroyBlock => {
+            if let Some(block) = query.blocks.get_block(position) {
+                let tick = query.compose.global().tick;
+                let response = query.anticheat.check_break(
+                    &query.config.anticheat,
+                    tick,
+                    position,
+                    block.to_kind(),
+                    query.inventory.get_cursor(),
+                );
+
+                if let Some(response) = response {
+                    respond(query, Check::FAST_BREAK, response);
+
+                    // the client already removed the block
+                    return resync_blocks(query, &[position], sequence);
+                }
+            }
+
             let event = event::DestroyBlock {
                 position,
                 from: query.id,
                 sequence,
             };
 
-            query.events.push(event, query.world);
+            if !query.events.try_push(event, query.world) {
+                resync_blocks(query, &[position], sequence)?;
+            }
         }
         PlayerAction::ReleaseUseItem => {
             let event = event::ReleaseUseItem {
@@ -320,6 +478,29 @@ fn player_action(
 
             query.events.push(event, query.world);
         }
+        PlayerAction::DropItem | PlayerAction::DropAllItems => {
+            let item = if matches!(packet.action, PlayerAction::DropItem) {
+                query.inventory.take_one_held()
+            } else {
+                core::mem::take(query.inventory.get_cursor_mut())
+            };
+
+            if item.is_empty() {
+                return Ok(());
+            }
+
+            let world = query.view.try_get::<&WorldId>(|id| *id).unwrap_or_default();
+
+            let event = event::ItemDropEvent::thrown(
+                item,
+                **query.position,
+                **query.yaw,
+                **query.pitch,
+                world,
+            );
+
+            query.events.push(event, query.world);
+        }
         action => bail!("unimplemented {action:?}"),
     }
 
@@ -421,12 +602,28 @@ pub fn player_interact_block(
         return Ok(());
     };
 
-    if interacted_block.get(PropName::Open).is_some() {
+    let sneaking = *query.pose == Pose::Sneaking;
+    let holding_item = !query.inventory.get_cursor().is_empty();
+    let opens = !(sneaking && holding_item);
+
+    if opens && ContainerKind::of(interacted_block.to_kind()).is_some() {
+        query.events.push(
+            event::OpenContainer {
+                position: interacted_block_pos_vec,
+                from: query.id,
+            },
+            query.world,
+        );
+    } else if opens && interacted_block.to_kind() == BlockKind::CraftingTable {
+        query
+            .events
+            .push(event::OpenCraftingTable { from: query.id }, query.world);
+    } else if interacted_block.get(PropName::Open).is_some() {
         // Toggle the open state of a door
         // todo: place block instead of toggling door if the player is crouching and holding a
         // block
 
-        query.events.push(
+        let toggled = query.events.try_push(
             event::ToggleDoor {
                 position: interacted_block_pos_vec,
                 from: query.id,
@@ -434,6 +631,16 @@ pub fn player_interact_block(
             },
             query.world,
         );
+
+        if !toggled {
+            // both halves of a door may have been predicted
+            let halves = [
+                interacted_block_pos_vec,
+                interacted_block_pos_vec + IVec3::Y,
+                interacted_block_pos_vec - IVec3::Y,
+            ];
+            resync_blocks(query, &halves, packet.sequence.0)?;
+        }
     } else {
         // Attempt to place a block
 
@@ -471,7 +678,7 @@ pub fn player_interact_block(
             return Ok(());
         }
 
-        query.events.push(
+        let placed = query.events.try_push(
             event::PlaceBlock {
                 position,
                 from: query.id,
@@ -480,6 +687,10 @@ pub fn player_interact_block(
             },
             query.world,
         );
+
+        if !placed {
+            resync_blocks(query, &[position], packet.sequence.0)?;
+        }
     }
 
     Ok(())
@@ -539,68 +750,201 @@ fn click_slot(
     _: &dyn LifetimeHandle<'_>,
     query: &mut PacketSwitchQuery<'_>,
 ) -> anyhow::Result<()> {
-    let to_send_pkt = play::ScreenHandlerSlotUpdateS2c {
-        window_id: -1,
-        state_id: VarInt::default(),
-        slot_idx: 0, // crafting result
-        slot_data: Cow::Borrowed(&ItemStack::EMPTY),
-    };
+    let button = u8::try_from(pkt.button).context("button is negative")?;
+    let action = create_inventory_action(pkt.mode as u8, button, pkt.slot_idx);
 
-    // negate click
-    query
-        .compose
-        .unicast(&to_send_pkt, query.io_ref, query.system)?;
+    if let Err(e) = &action {
+        warn!("invalid click in window {}: {e}", pkt.window_id);
+    }
 
-    let slot_idx = u16::try_from(pkt.slot_idx).context("slot index is negative")?;
+    // slots the client does not have are left out; the window is sent again in that case
+    let slot_changes = pkt
+        .slot_changes
+        .iter()
+        .filter_map(|change| Some((u16::try_from(change.idx).ok()?, change.stack.clone())))
+        .collect();
 
-    let item_in_slot = query.inventory.get(slot_idx)?;
+    query.events.push(
+        event::ClickWindow {
+            from: query.id,
+            window_id: pkt.window_id,
+            state_id: pkt.state_id.0,
+            action: action.ok(),
+            slot_changes,
+            carried_item: pkt.carried_item.clone(),
+        },
+        query.world,
+    );
+
+    Ok(())
+}
 
-    let to_send_pkt = play::ScreenHandlerSlotUpdateS2c {
-        window_id: 0,
-        state_id: VarInt::default(),
-        slot_idx: pkt.slot_idx,
-        slot_data: Cow::Borrowed(item_in_slot),
+fn close_handled_screen(
+    pkt: &play::CloseHandledScreenC2s,
+    _: &dyn LifetimeHandle<'_>,
+    query: &mut PacketSwitchQuery<'_>,
+) -> anyhow::Result<()> {
+    let Ok(window_id) = u8::try_from(pkt.window_id) else {
+        return Ok(());
     };
 
-    query
-        .compose
-        .unicast(&to_send_pkt, query.io_ref, query.system)?;
+    query.events.push(
+        event::CloseWindow {
+            from: query.id,
+            window_id,
+        },
+        query.world,
+    );
 
-    // info!("click slot\n{pkt:#?}");
+    Ok(())
+}
 
-    // // todo(security): validate the player can do this. This is a MAJOR security issue.
-    // // as players will be able to spawn items in their inventory wit current logic.
-    // for SlotChange { idx, stack } in pkt.slot_changes.iter() {
-    //     let idx = u16::try_from(*idx).context("slot index is negative")?;
-    //     query.inventory.set(idx, stack.clone())?;
-    // }
+fn chat_message<'a>(
+    pkt: &play::ChatMessageC2s<'a>,
+    handle: &dyn LifetimeHandle<'a>,
+    query: &mut PacketSwitchQuery<'_>,
+) -> anyhow::Result<()> {
+    let msg = RuntimeLifetime::new(pkt.message.0, handle);
 
-    let item = query.inventory.crafting_result(query.crafting_registry);
+    if !query.world.get::<&SecureChat>(SecureChat::enforced) {
+        query.events.push(
+            event::ChatMessage {
+                msg,
+                by: query.id,
+                signed: None,
+            },
+            query.world,
+        );
 
-    let set_item_pkt = play::ScreenHandlerSlotUpdateS2c {
-        window_id: 0,
-        state_id: VarInt(0),
-        slot_idx: 0, // crafting result
-        slot_data: Cow::Owned(item),
+        return Ok(());
+    }
+
+    let verified = query
+        .view
+        .try_get::<(&Uuid, &mut ChatSession, &mut LastSeen)>(|(uuid, session, last_seen)| {
+            if session.expired(chat::now_millis()) {
+                return Ok(None);
+            }
+
+            session.verify(uuid.0, last_seen, pkt).map(Some)
+        });
+
+    let notice = match verified {
+        Some(Ok(Some(signed))) => {
+            query.events.push(
+                event::ChatMessage {
+                    msg,
+                    by: query.id,
+                    signed: Some(signed),
+                },
+                query.world,
+            );
+
+            return Ok(());
+        }
+        Some(Ok(None)) => "§cYour chat session expired, rejoin to chat again",
+        None => "§cChat is disabled until your client sends its chat session",
+        Some(Err(e)) => {
+            warn!("rejected a chat message: {e:#}");
+
+            query
+                .view
+                .set(PendingRemove::new("§cChat message validation failure"));
+            return Ok(());
+        }
     };
 
-    query
-        .compose
-        .unicast(&set_item_pkt, query.io_ref, query.system)?;
+    let pkt = play::GameMessageS2c {
+        chat: notice.into_cow_text(),
+        overlay: false,
+    };
+
+    query.compose.unicast(&pkt, query.io_ref, query.system)?;
 
     Ok(())
 }
 
-fn chat_message<'a>(
-    pkt: &play::ChatMessageC2s<'a>,
-    handle: &dyn LifetimeHandle<'a>,
+fn message_acknowledgment(
+    pkt: &play::MessageAcknowledgmentC2s,
+    _: &dyn LifetimeHandle<'_>,
     query: &mut PacketSwitchQuery<'_>,
 ) -> anyhow::Result<()> {
-    let msg = RuntimeLifetime::new(pkt.message.0, handle);
+    acknowledge(query, |last_seen| {
+        last_seen.apply_offset(pkt.message_index.0)
+    });
+
+    Ok(())
+}
+
+/// Applies what a player acknowledged with `apply` to the messages they were sent, disconnecting
+/// them if that fails like vanilla does.
+fn acknowledge(
+    query: &PacketSwitchQuery<'_>,
+    apply: impl FnOnce(&mut LastSeen) -> anyhow::Result<()>,
+) {
+    if !query.world.get::<&SecureChat>(SecureChat::enforced) {
+        return;
+    }
+
+    let result = query.view.try_get::<&mut LastSeen>(apply);
+
+    if let Some(Err(e)) = result {
+        warn!("rejected acknowledged chat messages: {e:#}");
+
+        query
+            .view
+            .set(PendingRemove::new("§cChat message validation failure"));
+    }
+}
+
+fn player_session<'a>(
+    pkt: &play::PlayerSessionC2s<'a>,
+    _: &dyn LifetimeHandle<'a>,
+    query: &mut PacketSwitchQuery<'_>,
+) -> anyhow::Result<()> {
+    // without the keys of Mojang the session can't be trusted, so chat stays unsigned
+    if !query.world.get::<&SecureChat>(SecureChat::enforced) {
+        return Ok(());
+    }
+
+    let uuid = query.view.get::<&Uuid>(|uuid| uuid.0);
 
+    if chat::now_millis() >= pkt.expires_at {
+        query
+            .view
+            .set(PendingRemove::new("§cYour profile public key has expired"));
+        return Ok(());
+    }
+
+    let signed = query.world.get::<&SecureChat>(|secure| {
+        secure.verify_key(uuid, pkt.expires_at, pkt.public_key_data, pkt.key_signature)
+    });
+
+    let session = signed
+        .then(|| {
+            ChatSession::new(
+                pkt.session_id,
+                pkt.expires_at,
+                pkt.public_key_data,
+                pkt.key_signature,
+            )
+        })
+        .and_then(Result::ok);
+
+    let Some(session) = session else {
+        query.view.set(PendingRemove::new(
+            "§cInvalid signature for profile public key",
+        ));
+        return Ok(());
+    };
+
+    // so every client can verify the messages of the player
     query
-        .events
-        .push(event::ChatMessage { msg, by: query.id }, query.world);
+        .compose
+        .broadcast(&session.player_list(uuid), query.system)
+        .send()?;
+
+    query.view.set(session);
 
     Ok(())
 }
@@ -649,16 +993,19 @@ pub fn add_builtin_handlers(registry: &mut HandlerRegistry) {
     registry.add_handler(Box::new(click_slot));
     registry.add_handler(Box::new(client_command));
     registry.add_handler(Box::new(client_status));
+    registry.add_handler(Box::new(close_handled_screen));
     registry.add_handler(Box::new(chat_command));
     registry.add_handler(Box::new(creative_inventory_action));
     registry.add_handler(Box::new(custom_payload));
     registry.add_handler(Box::new(full));
     registry.add_handler(Box::new(hand_swing));
     registry.add_handler(Box::new(look_and_on_ground));
+    registry.add_handler(Box::new(message_acknowledgment));
     registry.add_handler(Box::new(player_action));
     registry.add_handler(Box::new(player_interact_block));
     registry.add_handler(Box::new(player_interact_entity));
     registry.add_handler(Box::new(player_interact_item));
+    registry.add_handler(Box::new(player_session));
     registry.add_handler(Box::new(position_and_on_ground));
     registry.add_handler(Box::new(request_command_completions));
     registry.add_handler(Box::new(update_selected_slot));
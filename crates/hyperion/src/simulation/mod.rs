@@ -17,6 +17,7 @@ use valence_protocol::{ByteAngle, VarInt, packets::play};
 
 use crate::{
     Global,
+    ingress::encryption::Authentication,
     net::{Compose, DataBundle},
     simulation::{
         command::Command,
@@ -27,16 +28,22 @@ use crate::{
 };
 
 pub mod animation;
+pub mod anticheat;
 pub mod blocks;
 pub mod bow;
+pub mod chat;
 pub mod command;
+pub mod container;
 pub mod entity_kind;
 pub mod event;
 pub mod handlers;
 pub mod metadata;
 pub mod packet;
+pub mod physics;
 pub mod skin;
 pub mod util;
+pub mod window;
+pub mod worlds;
 
 #[derive(Component, Default, Debug, Deref, DerefMut)]
 pub struct StreamLookup {
@@ -319,13 +326,23 @@ pub struct Comms {
     pub skins_rx: kanal::Receiver<(Entity, PlayerSkin)>,
     /// Skin tx channel.
     pub skins_tx: kanal::Sender<(Entity, PlayerSkin)>,
+    /// Session server verification rx channel.
+    pub authentications_rx: kanal::Receiver<(Entity, Authentication)>,
+    /// Session server verification tx channel.
+    pub authentications_tx: kanal::Sender<(Entity, Authentication)>,
 }
 
 impl Default for Comms {
     fn default() -> Self {
         let (skins_tx, skins_rx) = kanal::unbounded();
+        let (authentications_tx, authentications_rx) = kanal::unbounded();
 
-        Self { skins_rx, skins_tx }
+        Self {
+            skins_rx,
+            skins_tx,
+            authentications_rx,
+            authentications_tx,
+        }
     }
 }
 
@@ -624,10 +641,18 @@ impl Module for SimModule {
         component!(world, Uuid).opaque_func(meta_ser_stringify_type_display::<Uuid>);
 
         world.component::<ChunkPosition>().meta();
+        world.component::<worlds::WorldId>();
+        world.component::<worlds::Dimension>();
+        world.component::<worlds::SpawnPoint>();
+        world.component::<worlds::Worlds>();
         world.component::<ConfirmBlockSequences>();
         world.component::<animation::ActiveAnimation>();
 
         world.component::<hyperion_inventory::PlayerInventory>();
+        world.component::<hyperion_inventory::action::Cursor>();
+
+        world.component::<chat::ChatSession>();
+        world.component::<chat::LastSeen>();
 
         world.component::<BowCharging>();
         component!(world, BowCharging).opaque_func(meta_ser_stringify_type_display::<BowCharging>);
@@ -641,56 +666,62 @@ impl Module for SimModule {
             [filter] & Pitch,
             [filter] & Yaw,
             [filter] & Velocity,
+            ?&worlds::WorldId,
         )
         .with::<flecs::Any>()
         .with_enum_wildcard::<EntityKind>()
-        .each_iter(|it, row, (compose, uuid, position, pitch, yaw, velocity)| {
-            let system = it.system();
+        .each_iter(
+            |it, row, (compose, uuid, position, pitch, yaw, velocity, world_id)| {
+                let system = it.system();
 
-            let entity = it.entity(row);
-            let minecraft_id = entity.minecraft_id();
+                let entity = it.entity(row);
+                let minecraft_id = entity.minecraft_id();
 
-            let mut bundle = DataBundle::new(compose, system);
+                let mut bundle = DataBundle::new(compose, system);
 
-            let mut spawn_entity = move |kind: EntityKind| -> anyhow::Result<()> {
-                let kind = kind as i32;
+                let mut spawn_entity = move |kind: EntityKind| -> anyhow::Result<()> {
+                    let kind = kind as i32;
 
-                let velocity = velocity.to_packet_units();
+                    let velocity = velocity.to_packet_units();
 
-                let packet = play::EntitySpawnS2c {
-                    entity_id: VarInt(minecraft_id),
-                    object_uuid: uuid.0,
-                    kind: VarInt(kind),
-                    position: position.as_dvec3(),
-                    pitch: ByteAngle::from_degrees(**pitch),
-                    yaw: ByteAngle::from_degrees(**yaw),
-                    head_yaw: ByteAngle::from_degrees(0.0), // todo:
-                    data: VarInt::default(),                // todo:
-                    velocity,
-                };
+                    let packet = play::EntitySpawnS2c {
+                        entity_id: VarInt(minecraft_id),
+                        object_uuid: uuid.0,
+                        kind: VarInt(kind),
+                        position: position.as_dvec3(),
+                        pitch: ByteAngle::from_degrees(**pitch),
+                        yaw: ByteAngle::from_degrees(**yaw),
+                        head_yaw: ByteAngle::from_degrees(0.0), // todo:
+                        data: VarInt::default(),                // todo:
+                        velocity,
+                    };
 
-                bundle.add_packet(&packet).unwrap();
+                    bundle.add_packet(&packet).unwrap();
 
-                let packet = play::EntityVelocityUpdateS2c {
-                    entity_id: VarInt(minecraft_id),
-                    velocity,
-                };
+                    let packet = play::EntityVelocityUpdateS2c {
+                        entity_id: VarInt(minecraft_id),
+                        velocity,
+                    };
 
-                bundle.add_packet(&packet).unwrap();
+                    bundle.add_packet(&packet).unwrap();
 
-                bundle.broadcast_local(position.to_chunk()).unwrap();
+                    let world_id = world_id.copied().unwrap_or_default();
+                    bundle
+                        .broadcast_local(world_id, position.to_chunk())
+                        .unwrap();
 
-                Ok(())
-            };
+                    Ok(())
+                };
 
-            debug!("spawned entity");
+                debug!("spawned entity");
 
-            entity.get::<&EntityKind>(|kind| {
-                if let Err(e) = spawn_entity(*kind) {
-                    error!("failed to spawn entity: {e}");
-                }
-            });
-        });
+                entity.get::<&EntityKind>(|kind| {
+                    if let Err(e) = spawn_entity(*kind) {
+                        error!("failed to spawn entity: {e}");
+                    }
+                });
+            },
+        );
 
         // for every new entity without a UUID, give it one
         world
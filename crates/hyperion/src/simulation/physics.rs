@@ -0,0 +1,450 @@
+//! Movement of entities which are not players, like items, projectiles and NPCs.
+//!
+//! Every entity with [`Physics`] is moved by its [`Velocity`] each tick. It is stopped by the
+//! blocks in its way one axis after the other like in vanilla, slowed down by drag and pulled
+//! down by gravity. What the entity ran into is kept in its [`PhysicsState`].
+//!
+//! Entities at rest on the ground or stuck in a block are not moved. Once blocks change, they
+//! check whether they are still held up and start falling if not.
+
+use std::ops::ControlFlow;
+
+use flecs_ecs::prelude::*;
+use geometry::aabb::Aabb;
+use glam::{BVec3, IVec3, Vec3};
+use valence_generated::block::BlockKind;
+
+use crate::simulation::{
+    EntitySize, Position, Velocity, aabb,
+    blocks::Blocks,
+    entity_kind::EntityKind,
+    worlds::{WorldId, with_blocks},
+};
+
+/// Distances below this are treated as touching.
+const EPSILON: f32 = 1e-5;
+
+/// How far below an entity a block may be for the entity to stand on it.
+const GROUND_PROBE: f32 = 1e-3;
+
+/// Velocities below this are rounded to zero, so entities come to rest.
+const REST_SPEED: f32 = 1e-3;
+
+/// The size of entities without an [`EntitySize`], which is the size of an arrow.
+const DEFAULT_SIZE: EntitySize = EntitySize {
+    half_width: 0.25,
+    height: 0.5,
+};
+
+/// How an entity moves on its own.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct Physics {
+    /// Subtracted from the vertical velocity every tick the entity is in the air.
+    pub gravity: f32,
+    /// The velocity is multiplied by this every tick.
+    pub drag: f32,
+    /// The velocity is multiplied by this instead of `drag` while in water or lava.
+    pub fluid_drag: f32,
+    /// The horizontal velocity is also multiplied by this while on the ground.
+    pub ground_friction: f32,
+    /// Whether the entity stops where it hits a block, like an arrow, instead of sliding along it.
+    pub sticks: bool,
+    /// Whether overlapping entities push this one away.
+    pub pushable: bool,
+}
+
+impl Physics {
+    /// Dropped items.
+    pub const ITEM: Self = Self {
+        gravity: 0.04,
+        drag: 0.98,
+        fluid_drag: 0.8,
+        ground_friction: 0.6,
+        sticks: false,
+        pushable: false,
+    };
+    /// Mobs and NPCs.
+    pub const LIVING: Self = Self {
+        gravity: 0.08,
+        drag: 0.98,
+        fluid_drag: 0.8,
+        ground_friction: 0.6,
+        sticks: false,
+        pushable: true,
+    };
+    /// Arrows and tridents.
+    pub const PROJECTILE: Self = Self {
+        gravity: 0.05,
+        drag: 0.997_525,
+        fluid_drag: 0.6,
+        ground_friction: 1.0,
+        sticks: true,
+        pushable: false,
+    };
+    /// Snowballs, eggs, ender pearls and other thrown items.
+    pub const THROWN: Self = Self {
+        gravity: 0.03,
+        drag: 0.99,
+        fluid_drag: 0.8,
+        ground_friction: 1.0,
+        sticks: true,
+        pushable: false,
+    };
+
+    /// The physics entities of `kind` get when they are created without any. Players move on
+    /// their own, and entities like NPCs are given their physics when they are spawned.
+    #[must_use]
+    pub const fn of(kind: EntityKind) -> Option<Self> {
+        let physics = match kind {
+            EntityKind::Item => Self::ITEM,
+            EntityKind::Arrow | EntityKind::SpectralArrow | EntityKind::Trident => Self::PROJECTILE,
+            EntityKind::Snowball
+            | EntityKind::Egg
+            | EntityKind::EnderPearl
+            | EntityKind::ExperienceBottle
+            | EntityKind::Potion => Self::THROWN,
+            _ => return None,
+        };
+
+        Some(physics)
+    }
+}
+
+/// What an entity with [`Physics`] ran into in the last tick.
+#[derive(Component, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhysicsState {
+    pub on_ground: bool,
+    /// Whether the entity touches water or lava.
+    pub in_fluid: bool,
+    /// Whether the entity ran into a block sideways.
+    pub horizontal_collision: bool,
+    /// Whether the entity is stuck in a block it hit. It no longer moves.
+    pub stuck: bool,
+    /// The block a stuck entity hit. Taking it leaves the entity stuck.
+    pub hit_block: Option<IVec3>,
+    /// The [`Blocks::changes`] the entity last checked the blocks around it at.
+    seen_changes: u64,
+}
+
+/// The collision boxes of all blocks that touch `aabb`.
+fn collision_shapes(aabb: Aabb, blocks: &Blocks) -> Vec<Aabb> {
+    // fences and walls reach into the block above them
+    let min = aabb.min.floor().as_ivec3() - IVec3::Y;
+    let max = aabb.max.floor().as_ivec3();
+
+    let mut shapes = Vec::new();
+
+    let _: ControlFlow<()> = blocks.get_blocks(min, max, |position, block| {
+        for shape in block.collision_shapes() {
+            let shape = Aabb::new(shape.min().as_vec3(), shape.max().as_vec3());
+            shapes.push(shape.move_by(position.as_vec3()));
+        }
+
+        ControlFlow::Continue(())
+    });
+
+    shapes
+}
+
+/// How far `aabb` can move `distance` along `axis` before it runs into `shape`.
+fn clip(aabb: &Aabb, shape: &Aabb, axis: usize, distance: f32) -> f32 {
+    // shapes beside the path are not in the way
+    for other in 0..3 {
+        if other == axis {
+            continue;
+        }
+
+        if aabb.max[other] <= shape.min[other] + EPSILON
+            || aabb.min[other] >= shape.max[other] - EPSILON
+        {
+            return distance;
+        }
+    }
+
+    if distance > 0.0 && aabb.max[axis] <= shape.min[axis] + EPSILON {
+        distance.min(shape.min[axis] - aabb.max[axis])
+    } else if distance < 0.0 && aabb.min[axis] >= shape.max[axis] - EPSILON {
+        distance.max(shape.max[axis] - aabb.min[axis])
+    } else {
+        distance
+    }
+}
+
+/// Moves `aabb` by `motion` until it runs into blocks, first vertically and then along x and z
+/// like vanilla. Returns how far it could move.
+#[must_use]
+pub fn sweep(aabb: Aabb, motion: Vec3, blocks: &Blocks) -> Vec3 {
+    let reach = Aabb::new(
+        aabb.min.min(aabb.min + motion),
+        aabb.max.max(aabb.max + motion),
+    );
+    let shapes = collision_shapes(reach, blocks);
+
+    let mut aabb = aabb;
+    let mut moved = Vec3::ZERO;
+
+    for axis in [1, 0, 2] {
+        let distance = shapes.iter().fold(motion[axis], |distance, shape| {
+            clip(&aabb, shape, axis, distance)
+        });
+
+        let mut offset = Vec3::ZERO;
+        offset[axis] = distance;
+
+        aabb = aabb.move_by(offset);
+        moved[axis] = distance;
+    }
+
+    moved
+}
+
+/// Whether `aabb` touches water or lava.
+#[must_use]
+pub fn in_fluid(aabb: Aabb, blocks: &Blocks) -> bool {
+    let min = aabb.min.floor().as_ivec3();
+    let max = aabb.max.floor().as_ivec3();
+
+    let found = blocks.get_blocks(min, max, |_, block| {
+        if matches!(block.to_kind(), BlockKind::Water | BlockKind::Lava) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+
+    found.is_break()
+}
+
+/// Whether the blocks that stopped an entity at `aabb` are still there.
+fn is_held(state: &PhysicsState, aabb: Aabb, blocks: &Blocks) -> bool {
+    if state.stuck {
+        let reach = aabb.expand(GROUND_PROBE);
+        return collision_shapes(reach, blocks)
+            .iter()
+            .any(|shape| shape.collides(&reach));
+    }
+
+    let probe = Vec3::NEG_Y * GROUND_PROBE;
+    sweep(aabb, probe, blocks).y > -GROUND_PROBE
+}
+
+/// The block an entity at `aabb` ran into while moving by `motion`.
+fn hit_block(aabb: Aabb, motion: Vec3, blocked: BVec3) -> IVec3 {
+    let mut point = aabb.mid();
+
+    // the axes are swept in this order
+    for axis in [1, 0, 2] {
+        if !blocked.test(axis) {
+            continue;
+        }
+
+        point[axis] = if motion[axis] > 0.0 {
+            aabb.max[axis] + GROUND_PROBE
+        } else {
+            aabb.min[axis] - GROUND_PROBE
+        };
+
+        break;
+    }
+
+    point.floor().as_ivec3()
+}
+
+/// Moves an entity by one tick. Without `blocks`, it moves as if nothing is in its way.
+fn step(
+    physics: &Physics,
+    state: &mut PhysicsState,
+    position: &mut Position,
+    velocity: &mut Velocity,
+    size: EntitySize,
+    blocks: Option<&Blocks>,
+) {
+    let motion = velocity.0;
+    let moved = blocks.map_or(motion, |blocks| {
+        sweep(aabb(**position, size), motion, blocks)
+    });
+
+    **position += moved;
+
+    let blocked = (motion - moved).abs().cmpgt(Vec3::splat(EPSILON));
+
+    state.horizontal_collision = blocked.x || blocked.z;
+    state.on_ground = blocks.is_some_and(|blocks| {
+        let probe = Vec3::NEG_Y * GROUND_PROBE;
+        sweep(aabb(**position, size), probe, blocks).y > -GROUND_PROBE
+    });
+    state.in_fluid = blocks.is_some_and(|blocks| in_fluid(aabb(**position, size), blocks));
+
+    if physics.sticks && blocked.any() {
+        velocity.0 = Vec3::ZERO;
+        state.stuck = true;
+        state.hit_block = Some(hit_block(aabb(**position, size), motion, blocked));
+        return;
+    }
+
+    // running into a block stops the movement along that axis
+    velocity.0 = Vec3::select(blocked, Vec3::ZERO, velocity.0);
+
+    let drag = if state.in_fluid {
+        physics.fluid_drag
+    } else {
+        physics.drag
+    };
+
+    velocity.0 *= drag;
+
+    if state.on_ground {
+        velocity.0.x *= physics.ground_friction;
+        velocity.0.z *= physics.ground_friction;
+    } else {
+        velocity.0.y -= physics.gravity;
+    }
+
+    if velocity.0.length_squared() < REST_SPEED * REST_SPEED {
+        velocity.0 = Vec3::ZERO;
+    }
+}
+
+#[derive(Component)]
+pub struct PhysicsModule;
+
+impl Module for PhysicsModule {
+    fn module(world: &World) {
+        world
+            .component::<Physics>()
+            .add_trait::<(flecs::With, PhysicsState)>();
+        world.component::<PhysicsState>();
+
+        // entities get the physics of their kind unless they were given other physics
+        world
+            .observer::<flecs::OnAdd, ()>()
+            .with_enum_wildcard::<EntityKind>()
+            .without::<Physics>()
+            .each_entity(|entity, ()| {
+                let physics = entity.get::<&EntityKind>(|kind| Physics::of(*kind));
+
+                if let Some(physics) = physics {
+                    entity.set(physics);
+                }
+            });
+
+        system!(
+            "physics",
+            world,
+            &Physics,
+            &mut PhysicsState,
+            &mut Position,
+            &mut Velocity,
+            ?&EntitySize,
+            ?&WorldId,
+        )
+        .multi_threaded()
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(
+            |it, _, (physics, state, position, velocity, size, world_id)| {
+                let resting = state.stuck || (velocity.0 == Vec3::ZERO && state.on_ground);
+
+                let world = it.world();
+                let size = size.copied().unwrap_or(DEFAULT_SIZE);
+                let world_id = world_id.copied().unwrap_or_default();
+
+                let stepped = with_blocks(&world, world_id, |blocks| {
+                    let changes = blocks.changes();
+
+                    if resting {
+                        // only changed blocks can take away what holds the entity up
+                        if state.seen_changes == changes
+                            || is_held(state, aabb(**position, size), blocks)
+                        {
+                            state.seen_changes = changes;
+                            return;
+                        }
+
+                        state.stuck = false;
+                        state.on_ground = false;
+                    }
+
+                    step(physics, state, position, velocity, size, Some(blocks));
+                    state.seen_changes = changes;
+                });
+
+                if stepped.is_none() && !resting {
+                    step(physics, state, position, velocity, size, None);
+                }
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geometry::aabb::Aabb;
+    use glam::{BVec3, IVec3, Vec3};
+
+    use super::{Physics, PhysicsState, clip, hit_block, step};
+    use crate::simulation::{EntitySize, Position, Velocity};
+
+    #[test]
+    fn entities_fly_freely_without_blocks() {
+        let size = EntitySize {
+            half_width: 0.25,
+            height: 0.5,
+        };
+
+        let mut state = PhysicsState::default();
+        let mut position = Position::new(0.0, 20.0, 0.0);
+        let mut velocity = Velocity::new(0.0, 1.0, 0.0);
+
+        step(
+            &Physics::PROJECTILE,
+            &mut state,
+            &mut position,
+            &mut velocity,
+            size,
+            None,
+        );
+
+        assert_eq!(position, Position::new(0.0, 21.0, 0.0));
+        assert!((velocity.0.y - 0.947_525).abs() < 1e-6);
+        assert!(!state.on_ground);
+        assert!(!state.stuck);
+    }
+
+    #[test]
+    fn blocks_stop_entities_in_their_way() {
+        let block = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        let item = Aabb::new(Vec3::new(0.25, 1.5, 0.25), Vec3::new(0.5, 1.75, 0.5));
+
+        // falling onto the block ends on top of it
+        assert!((clip(&item, &block, 1, -1.0) + 0.5).abs() < 1e-6);
+
+        // moving up or sideways above the block is not blocked
+        assert!((clip(&item, &block, 1, 1.0) - 1.0).abs() < 1e-6);
+        assert!((clip(&item, &block, 0, 2.0) - 2.0).abs() < 1e-6);
+
+        // the block is not in the way of items beside it
+        let beside = item.move_by(Vec3::new(2.0, 0.0, 0.0));
+        assert!((clip(&beside, &block, 1, -1.0) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stuck_entities_know_the_block_they_hit() {
+        let arrow = Aabb::new(Vec3::new(0.75, 1.0, 0.25), Vec3::new(1.0, 1.5, 0.75));
+
+        // flying along x into the block next to it
+        let hit = hit_block(
+            arrow,
+            Vec3::new(1.0, -0.2, 0.0),
+            BVec3::new(true, false, false),
+        );
+        assert_eq!(hit, IVec3::new(1, 1, 0));
+
+        // falling onto the block below
+        let hit = hit_block(
+            arrow,
+            Vec3::new(1.0, -0.2, 0.0),
+            BVec3::new(false, true, false),
+        );
+        assert_eq!(hit, IVec3::new(0, 0, 0));
+    }
+}
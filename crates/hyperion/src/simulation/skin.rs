@@ -60,6 +60,18 @@ impl PlayerSkin {
         info!("player skin cache miss for {uuid}");
 
         let json_object = mojang.data_from_uuid(&uuid).await?;
+
+        let Some(res) = Self::from_profile(&json_object)? else {
+            return Ok(None);
+        };
+
+        skins.insert(uuid, &res)?;
+        Ok(Some(res))
+    }
+
+    /// Gets a skin from the `textures` property of a profile returned by the Mojang API or the
+    /// session server.
+    pub fn from_profile(json_object: &serde_json::Value) -> anyhow::Result<Option<Self>> {
         let properties_array = json_object["properties"]
             .as_array()
             .with_context(|| format!("no properties on {json_object:?}"))?;
@@ -85,12 +97,10 @@ impl PlayerSkin {
                 .decode(signature)
                 .context("invalid signature value")?;
 
-            let res = Self {
+            return Ok(Some(Self {
                 textures: textures.to_string(),
                 signature: signature.to_string(),
-            };
-            skins.insert(uuid, &res)?;
-            return Ok(Some(res));
+            }));
         }
         Ok(None)
     }
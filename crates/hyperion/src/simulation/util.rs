@@ -2,14 +2,14 @@ use std::sync::LazyLock;
 
 use anyhow::{Context, bail};
 use serde::Deserialize;
-use valence_nbt::{Compound, Value, value::ValueRef};
+use valence_nbt::{Compound, List, Value, value::ValueRef};
 use valence_registry::{
     BiomeRegistry,
     biome::{Biome, BiomeEffects},
 };
 use valence_server::Ident;
 
-use crate::storage::BitStorage;
+use crate::{CHUNK_HEIGHT_SPAN, simulation::blocks::chunk::START_Y, storage::BitStorage};
 
 #[must_use]
 pub fn registry_codec_raw() -> &'static Compound {
@@ -17,13 +17,100 @@ pub fn registry_codec_raw() -> &'static Compound {
         let bytes = include_bytes!("data/registries.nbt");
         let mut bytes = &bytes[..];
         let bytes_reader = &mut bytes;
-        let (compound, _) = valence_nbt::from_binary(bytes_reader).unwrap();
+        let (mut compound, _) = valence_nbt::from_binary(bytes_reader).unwrap();
+        set_dimension_heights(&mut compound);
+        add_chat_type(&mut compound);
         compound
     });
 
     &CACHED
 }
 
+/// Gives every dimension type the height of [`crate::simulation::blocks::Blocks`], so worlds can
+/// use the nether and end dimension types, which are only 256 blocks high in vanilla.
+fn set_dimension_heights(registry_codec: &mut Compound) {
+    let Some(Value::Compound(dimension_types)) = registry_codec.get_mut("minecraft:dimension_type")
+    else {
+        return;
+    };
+
+    let Some(Value::List(List::Compound(dimension_types))) = dimension_types.get_mut("value")
+    else {
+        return;
+    };
+
+    let height = i32::try_from(CHUNK_HEIGHT_SPAN).unwrap();
+    let min_y = i32::from(START_Y);
+
+    for dimension_type in dimension_types {
+        if let Some(Value::Compound(element)) = dimension_type.get_mut("element") {
+            element.insert("height", height);
+            element.insert("logical_height", height);
+            element.insert("min_y", min_y);
+        }
+    }
+}
+
+/// The chat type signed chat messages are shown with, see [`crate::simulation::chat`].
+pub const CHAT_TYPE: &str = "hyperion:chat";
+
+/// Adds [`CHAT_TYPE`], which shows a chat message as nothing but its `sender` parameter. The server
+/// renders the whole message into it, and clients still verify the message since its content is
+/// part of what they show.
+fn add_chat_type(registry_codec: &mut Compound) {
+    let Some(Value::Compound(chat_types)) = registry_codec.get_mut("minecraft:chat_type") else {
+        return;
+    };
+
+    let Some(Value::List(List::Compound(chat_types))) = chat_types.get_mut("value") else {
+        return;
+    };
+
+    let decoration = || {
+        let mut decoration = Compound::new();
+        decoration.insert("translation_key", "%s".to_owned());
+        decoration.insert("parameters", List::String(vec!["sender".to_owned()]));
+        decoration
+    };
+
+    let mut element = Compound::new();
+    element.insert("chat", decoration());
+    element.insert("narration", decoration());
+
+    let mut chat_type = Compound::new();
+    chat_type.insert("name", CHAT_TYPE.to_owned());
+    chat_type.insert("id", i32::try_from(chat_types.len()).unwrap());
+    chat_type.insert("element", element);
+
+    chat_types.push(chat_type);
+}
+
+/// The registry id of [`CHAT_TYPE`].
+#[must_use]
+pub fn chat_type_id() -> i32 {
+    static CACHED: LazyLock<i32> = LazyLock::new(|| {
+        let Some(Value::Compound(chat_types)) = registry_codec_raw().get("minecraft:chat_type")
+        else {
+            panic!("the registry codec has no chat types");
+        };
+
+        let Some(Value::List(List::Compound(chat_types))) = chat_types.get("value") else {
+            panic!("the registry codec has no chat types");
+        };
+
+        chat_types
+            .iter()
+            .find(|chat_type| chat_type.get("name") == Some(&Value::String(CHAT_TYPE.to_owned())))
+            .and_then(|chat_type| match chat_type.get("id") {
+                Some(&Value::Int(id)) => Some(id),
+                _ => None,
+            })
+            .expect("the chat type of hyperion is registered")
+    });
+
+    *CACHED
+}
+
 pub fn generate_biome_registry() -> anyhow::Result<BiomeRegistry> {
     let registry_codec = registry_codec_raw();
 
@@ -156,4 +243,60 @@ mod tests {
         assert_eq!(super::ceil_log2(17), 5);
         assert_eq!(super::ceil_log2(18), 5);
     }
+
+    #[test]
+    fn dimension_types_have_full_height() {
+        use valence_nbt::{List, Value};
+
+        let codec = super::registry_codec_raw();
+
+        let Some(Value::Compound(dimension_types)) = codec.get("minecraft:dimension_type") else {
+            panic!("missing dimension types");
+        };
+        let Some(Value::List(List::Compound(dimension_types))) = dimension_types.get("value")
+        else {
+            panic!("missing dimension type values");
+        };
+
+        assert!(dimension_types.len() >= 3);
+
+        for dimension_type in dimension_types {
+            let Some(Value::Compound(element)) = dimension_type.get("element") else {
+                panic!("missing dimension type element");
+            };
+
+            assert_eq!(element.get("height"), Some(&Value::Int(384)));
+            assert_eq!(element.get("min_y"), Some(&Value::Int(-64)));
+        }
+    }
+
+    #[test]
+    fn signed_chat_has_a_chat_type() {
+        use valence_nbt::{List, Value};
+
+        let codec = super::registry_codec_raw();
+
+        let Some(Value::Compound(chat_types)) = codec.get("minecraft:chat_type") else {
+            panic!("missing chat types");
+        };
+        let Some(Value::List(List::Compound(chat_types))) = chat_types.get("value") else {
+            panic!("missing chat type values");
+        };
+
+        let id = super::chat_type_id();
+        let chat_type = &chat_types[usize::try_from(id).unwrap()];
+
+        assert_eq!(
+            chat_type.get("name"),
+            Some(&Value::String(super::CHAT_TYPE.to_owned()))
+        );
+
+        // the ids of the vanilla chat types are kept
+        for (i, chat_type) in chat_types.iter().enumerate() {
+            assert_eq!(
+                chat_type.get("id"),
+                Some(&Value::Int(i32::try_from(i).unwrap()))
+            );
+        }
+    }
 }
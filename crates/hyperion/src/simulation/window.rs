@@ -0,0 +1,484 @@
+//! Clicks in the windows players have open, which are their own inventory, a container or a
+//! crafting table.
+//!
+//! The client predicts what a click does. The server applies it the way vanilla does and sends
+//! the whole window again if the client guessed wrong or clicked on outdated contents.
+
+use std::{borrow::Cow, ops::RangeInclusive};
+
+use flecs_ecs::prelude::*;
+use hyperion_crafting::CraftingRegistry;
+use hyperion_inventory::{
+    CraftingTableInventory, Inventory, PlayerInventory,
+    action::{Cursor, InventoryAndCursor},
+    window::{ContainerWindow, Window},
+};
+use tracing::{info_span, warn};
+use valence_protocol::{
+    ItemStack, VarInt,
+    packets::play::{self, open_screen_s2c::WindowType},
+};
+use valence_text::IntoText;
+
+use crate::{
+    net::{Compose, ConnectionId},
+    simulation::{
+        Pitch, Position, Yaw,
+        container::{Containers, OpenContainer, send_window},
+        event,
+        worlds::WorldId,
+    },
+    storage::{EventQueue, Events},
+};
+
+/// The slots of the crafting grid in the inventory of a player.
+const CRAFTING_GRID: RangeInclusive<u16> = 1..=4;
+
+/// The slots of the crafting grid of a crafting table.
+const CRAFTING_TABLE_GRID: RangeInclusive<u16> = 1..=9;
+
+/// The crafting table a player crafts in. Unlike containers, every player has their own grid,
+/// which is emptied into their inventory when they close it.
+#[derive(Component, Debug)]
+pub struct OpenCraftingTable {
+    pub window_id: u8,
+    pub grid: CraftingTableInventory,
+}
+
+/// What a click in a window did.
+pub(crate) struct Click {
+    /// Whether the client predicted the result of the click.
+    pub predicted: bool,
+    /// The items thrown out of the window.
+    pub dropped: Vec<ItemStack>,
+}
+
+/// Applies a click in `window`.
+pub(crate) fn click<W: Window>(
+    window: W,
+    cursor: &mut Cursor,
+    event: &event::ClickWindow,
+) -> Click {
+    apply(InventoryAndCursor::new(window, cursor), event)
+}
+
+/// Applies a click in `window`, which may take the result of a crafting grid.
+fn apply<W: Window>(mut window: InventoryAndCursor<'_, W>, event: &event::ClickWindow) -> Click {
+    let Some(action) = event.action else {
+        return Click {
+            predicted: false,
+            dropped: Vec::new(),
+        };
+    };
+
+    let up_to_date = event.state_id == window.cursor.state_id;
+
+    let before = window.inventory.items();
+
+    window.apply(action);
+
+    let predicted =
+        up_to_date && window.is_predicted(&before, &event.slot_changes, &event.carried_item);
+
+    Click {
+        predicted,
+        dropped: window.dropped,
+    }
+}
+
+/// Throws `items` out of the window of `player` in the direction they look.
+fn throw(world: &World, player: EntityView<'_>, items: Vec<ItemStack>) {
+    if items.is_empty() {
+        return;
+    }
+
+    let world_id = player.try_get::<&WorldId>(|id| *id).unwrap_or_default();
+
+    let (position, yaw, pitch) = player
+        .get::<(&Position, &Yaw, &Pitch)>(|(position, yaw, pitch)| (**position, **yaw, **pitch));
+
+    world.get::<&Events>(|events| {
+        for item in items {
+            let event = event::ItemDropEvent::thrown(item, position, yaw, pitch, world_id);
+            events.push(event, world);
+        }
+    });
+}
+
+/// Sends all slots of a window and the cursor item.
+pub(crate) fn send(
+    compose: &Compose,
+    stream: ConnectionId,
+    system: EntityView<'_>,
+    window_id: u8,
+    slots: Vec<ItemStack>,
+    cursor: &Cursor,
+) {
+    let pkt = play::InventoryS2c {
+        window_id,
+        state_id: VarInt(cursor.state_id),
+        slots: Cow::Owned(slots),
+        carried_item: Cow::Borrowed(&cursor.item),
+    };
+
+    if let Err(e) = compose.unicast(&pkt, stream, system) {
+        warn!("failed to send window: {e}");
+    }
+}
+
+/// Uses up the crafting `grid` after the result in slot 0 was taken and shows what it crafts now.
+fn craft<const N: usize>(
+    inventory: &mut Inventory<N>,
+    grid: RangeInclusive<u16>,
+    crafting_result: impl FnOnce(&Inventory<N>) -> ItemStack,
+) {
+    for slot in grid {
+        let Ok(item) = inventory.get_mut(slot) else {
+            continue;
+        };
+
+        if item.is_empty() {
+            continue;
+        }
+
+        item.count -= 1;
+
+        if item.count <= 0 {
+            *item = ItemStack::EMPTY;
+        }
+    }
+
+    show_result(inventory, crafting_result);
+}
+
+/// Shows what the crafting grid crafts in slot 0 and returns whether that changed.
+fn show_result<const N: usize>(
+    inventory: &mut Inventory<N>,
+    crafting_result: impl FnOnce(&Inventory<N>) -> ItemStack,
+) -> bool {
+    let result = crafting_result(inventory);
+
+    if inventory.slots()[0] == result {
+        return false;
+    }
+
+    inventory.set(0, result).unwrap();
+    true
+}
+
+/// Moves `item` into the inventory and returns what does not fit.
+fn return_item(inventory: &mut PlayerInventory, item: ItemStack) -> Option<ItemStack> {
+    if item.is_empty() {
+        return None;
+    }
+
+    inventory.try_add_item(item).remaining
+}
+
+/// Empties the grid of a crafting table into the inventory and returns what does not fit.
+fn return_grid(
+    inventory: &mut PlayerInventory,
+    grid: &mut CraftingTableInventory,
+) -> Vec<ItemStack> {
+    let mut dropped = Vec::new();
+
+    for slot in CRAFTING_TABLE_GRID {
+        let item = core::mem::take(grid.get_mut(slot).unwrap());
+        dropped.extend(return_item(inventory, item));
+    }
+
+    grid.clear();
+    dropped
+}
+
+#[derive(Component)]
+pub struct WindowModule;
+
+impl Module for WindowModule {
+    fn module(world: &World) {
+        world.component::<OpenCraftingTable>();
+
+        system!(
+            "open_crafting_tables",
+            world,
+            &Compose($),
+            &mut Containers($),
+            &mut EventQueue<event::OpenCraftingTable>($),
+        )
+        .each_iter(|it, _, (compose, containers, event_queue)| {
+            let span = info_span!("open_crafting_tables");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+
+            for event::OpenCraftingTable { from } in event_queue.drain() {
+                let player = world.entity_from_id(from);
+
+                if !player.is_alive() {
+                    continue;
+                }
+
+                // the observer removes the player from the viewers
+                player.remove::<OpenContainer>();
+
+                let mut dropped = Vec::new();
+
+                // an old grid goes back into the inventory
+                player.try_get::<(&mut PlayerInventory, &mut OpenCraftingTable)>(
+                    |(inventory, table)| {
+                        dropped = return_grid(inventory, &mut table.grid);
+                    },
+                );
+
+                let window_id = containers.next_window_id();
+
+                player.set(OpenCraftingTable {
+                    window_id,
+                    grid: CraftingTableInventory::default(),
+                });
+
+                player.get::<(&ConnectionId, &PlayerInventory, &Cursor)>(
+                    |(&stream, inventory, cursor)| {
+                        let pkt = play::OpenScreenS2c {
+                            window_id: VarInt(i32::from(window_id)),
+                            window_type: WindowType::Crafting,
+                            window_title: "Crafting".into_cow_text(),
+                        };
+
+                        if let Err(e) = compose.unicast(&pkt, stream, system) {
+                            warn!("failed to open crafting table: {e}");
+                        }
+
+                        let grid = CraftingTableInventory::default();
+                        send_window(
+                            compose,
+                            stream,
+                            system,
+                            window_id,
+                            grid.slots(),
+                            inventory,
+                            cursor,
+                        );
+                    },
+                );
+
+                throw(&world, player, dropped);
+            }
+        });
+
+        system!(
+            "click_windows",
+            world,
+            &Compose($),
+            &mut Containers($),
+            &CraftingRegistry($),
+            &mut EventQueue<event::ClickWindow>($),
+        )
+        .each_iter(|it, _, (compose, containers, registry, event_queue)| {
+            let span = info_span!("click_windows");
+            let _enter = span.enter();
+
+            let system = it.system();
+            let world = it.world();
+
+            for event in event_queue.drain() {
+                let player = world.entity_from_id(event.from);
+
+                if !player.is_alive() {
+                    continue;
+                }
+
+                let mut dropped = Vec::new();
+
+                if event.window_id == 0 {
+                    player.get::<(&ConnectionId, &mut PlayerInventory, &mut Cursor)>(
+                        |(&stream, inventory, cursor)| {
+                            let crafting_result =
+                                |inventory: &PlayerInventory| inventory.crafting_result(registry);
+
+                            let window = InventoryAndCursor::new(&mut *inventory, cursor)
+                                .with_crafting(|inventory| {
+                                    craft(inventory, CRAFTING_GRID, crafting_result);
+                                });
+
+                            let click = apply(window, &event);
+
+                            // the client does not predict crafting results, so they are shown
+                            // after the click was checked. The slot update is sent with the rest
+                            // of the inventory.
+                            show_result(inventory, crafting_result);
+
+                            if !click.predicted {
+                                cursor.next_state_id();
+                                let slots = inventory.slots().to_vec();
+                                send(compose, stream, system, 0, slots, cursor);
+                            }
+
+                            dropped = click.dropped;
+                        },
+                    );
+
+                    throw(&world, player, dropped);
+                    continue;
+                }
+
+                let crafting = player
+                    .try_get::<&OpenCraftingTable>(|table| table.window_id == event.window_id)
+                    .unwrap_or(false);
+
+                if crafting {
+                    player.get::<(
+                        &ConnectionId,
+                        &mut PlayerInventory,
+                        &mut Cursor,
+                        &mut OpenCraftingTable,
+                    )>(|(&stream, inventory, cursor, table)| {
+                        let crafting_result =
+                            |grid: &CraftingTableInventory| grid.crafting_result(registry);
+
+                        let mut crafted = false;
+
+                        let window = ContainerWindow::new(&mut table.grid, inventory)
+                            .with_inputs(1..10)
+                            .with_crafting_result(0);
+
+                        let window =
+                            InventoryAndCursor::new(window, cursor).with_crafting(|window| {
+                                crafted = true;
+                                craft(window.container, CRAFTING_TABLE_GRID, crafting_result);
+                            });
+
+                        let click = apply(window, &event);
+                        let updated = show_result(&mut table.grid, crafting_result);
+
+                        // the grid is not synced like containers are, so it is sent whenever
+                        // crafting changed it
+                        if !click.predicted || crafted || updated {
+                            cursor.next_state_id();
+                            send_window(
+                                compose,
+                                stream,
+                                system,
+                                table.window_id,
+                                table.grid.slots(),
+                                inventory,
+                                cursor,
+                            );
+                        }
+
+                        table.grid.updated_since_last_tick.clear();
+                        dropped = click.dropped;
+                    });
+
+                    throw(&world, player, dropped);
+                    continue;
+                }
+
+                // other windows, e.g. menus, handle their clicks themselves
+                let Some(open) = player.try_get::<&OpenContainer>(|open| *open) else {
+                    continue;
+                };
+
+                if open.window_id != event.window_id {
+                    continue;
+                }
+
+                let Some(container) = containers.get_mut(open.world, open.position) else {
+                    continue;
+                };
+
+                player.get::<(&ConnectionId, &mut PlayerInventory, &mut Cursor)>(
+                    |(&stream, inventory, cursor)| {
+                        let inputs = container.kind.inputs();
+                        let click = container.inventory.click(inventory, cursor, inputs, &event);
+
+                        if !click.predicted {
+                            cursor.next_state_id();
+                            send_window(
+                                compose,
+                                stream,
+                                system,
+                                open.window_id,
+                                container.inventory.slots(),
+                                inventory,
+                                cursor,
+                            );
+                        }
+
+                        dropped = click.dropped;
+                    },
+                );
+
+                throw(&world, player, dropped);
+            }
+        });
+
+        system!(
+            "close_windows",
+            world,
+            &mut EventQueue<event::CloseWindow>($),
+        )
+        .each_iter(|it, _, event_queue| {
+            let world = it.world();
+
+            for event in event_queue.drain() {
+                let player = world.entity_from_id(event.from);
+
+                if !player.is_alive() {
+                    continue;
+                }
+
+                let mut dropped = Vec::new();
+
+                let crafting = player
+                    .try_get::<&OpenCraftingTable>(|table| table.window_id == event.window_id)
+                    .unwrap_or(false);
+
+                if crafting {
+                    player.get::<(&mut PlayerInventory, &mut OpenCraftingTable)>(
+                        |(inventory, table)| {
+                            dropped = return_grid(inventory, &mut table.grid);
+                        },
+                    );
+
+                    player.remove::<OpenCraftingTable>();
+                } else if event.window_id == 0 {
+                    // the crafting grid is emptied into the inventory
+                    player.get::<&mut PlayerInventory>(|inventory| {
+                        for slot in CRAFTING_GRID {
+                            let item = core::mem::take(inventory.get_mut(slot).unwrap());
+                            dropped.extend(return_item(inventory, item));
+                        }
+
+                        if !inventory.slots()[0].is_empty() {
+                            inventory.set(0, ItemStack::EMPTY).unwrap();
+                        }
+                    });
+                } else {
+                    let is_open = player
+                        .try_get::<&OpenContainer>(|open| open.window_id == event.window_id)
+                        .unwrap_or(false);
+
+                    if !is_open {
+                        continue;
+                    }
+
+                    // the observer removes the player from the viewers
+                    player.remove::<OpenContainer>();
+                }
+
+                // the item held with the mouse goes back into the inventory or is thrown if it
+                // does not fit
+                player.get::<(&mut PlayerInventory, &mut Cursor)>(|(inventory, cursor)| {
+                    cursor.cancel_drag();
+
+                    let held = core::mem::take(&mut cursor.item);
+                    dropped.extend(return_item(inventory, held));
+                });
+
+                throw(&world, player, dropped);
+            }
+        });
+    }
+}
@@ -0,0 +1,463 @@
+//! Several worlds in one server, e.g. the overworld, the nether or minigame instances.
+//!
+//! Every world is an entity with a [`Dimension`], its own [`Blocks`] and a [`SpawnPoint`], and is
+//! looked up by its [`WorldId`] in [`Worlds`]. The default world is the entity of the [`Blocks`]
+//! component itself, so `&Blocks($)` in queries and `world.get::<&Blocks>` keep referring to it.
+//!
+//! Entities are in the world of their [`WorldId`]; entities without one are in the default world.
+//! Players are moved between worlds with [`ChangeWorld`].
+
+use std::borrow::Cow;
+
+use anyhow::bail;
+use flecs_ecs::prelude::*;
+use glam::Vec3;
+use hyperion_utils::EntityExt;
+use tracing::{error, info, info_span, warn};
+use valence_protocol::{
+    ByteAngle, GameMode, Ident, VarInt,
+    game_mode::OptGameMode,
+    ident,
+    packets::play::{self, player_position_look_s2c::PlayerPositionLookFlags},
+};
+
+use crate::{
+    config::Config,
+    egress::{metadata::show_all, sync_chunks::ChunkSendQueue},
+    net::{Compose, ConnectionId, DataBundle},
+    simulation::{
+        ChunkPosition, PLAYER_SPAWN_POSITION, PacketState, Pitch, Player, Position, Uuid, Yaw,
+        anticheat::AntiCheat, blocks::Blocks,
+    },
+};
+
+/// Identifies a world. The proxy gets it with every chunk position, so local broadcasts only
+/// reach players in the same world.
+#[derive(
+    Component, Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord
+)]
+pub struct WorldId(pub u16);
+
+impl WorldId {
+    /// The world players join.
+    pub const DEFAULT: Self = Self(0);
+}
+
+/// How the client renders a world: sky, fog, ambient light and so on.
+///
+/// All dimension types are sent to the client with the height of [`Blocks`], so any of them can
+/// be used for any world.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DimensionType {
+    Overworld,
+    Nether,
+    End,
+}
+
+impl DimensionType {
+    #[must_use]
+    pub const fn ident(self) -> Ident<&'static str> {
+        match self {
+            Self::Overworld => ident!("minecraft:overworld"),
+            Self::Nether => ident!("minecraft:the_nether"),
+            Self::End => ident!("minecraft:the_end"),
+        }
+    }
+}
+
+/// Marks an entity as a world.
+#[derive(Component, Clone, Debug)]
+pub struct Dimension {
+    pub id: WorldId,
+    /// The name of the world, e.g. `minecraft:overworld`. Unique among all worlds; the client
+    /// only reloads its world when it respawns into a world with another name.
+    pub name: Ident<String>,
+    pub kind: DimensionType,
+}
+
+impl Dimension {
+    /// The packet that makes the client load this world.
+    #[must_use]
+    pub fn respawn_packet(&self) -> play::PlayerRespawnS2c<'_> {
+        play::PlayerRespawnS2c {
+            dimension_type_name: self.kind.ident().into(),
+            dimension_name: self.name.as_str_ident().into(),
+            hashed_seed: 0,
+            game_mode: GameMode::Survival,
+            previous_game_mode: OptGameMode(Some(GameMode::Survival)),
+            is_debug: false,
+            is_flat: false,
+            copy_metadata: false,
+            last_death_location: None,
+            portal_cooldown: 60.into(),
+        }
+    }
+}
+
+/// Where players appear when they are moved to a world.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl SpawnPoint {
+    #[must_use]
+    pub const fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl Default for SpawnPoint {
+    fn default() -> Self {
+        Self::new(PLAYER_SPAWN_POSITION)
+    }
+}
+
+/// All worlds of the server.
+#[derive(Component, Debug, Default)]
+pub struct Worlds {
+    /// The world entities, indexed by [`WorldId`].
+    entities: Vec<Entity>,
+}
+
+impl Worlds {
+    /// The entity of world `id`.
+    #[must_use]
+    pub fn get(&self, id: WorldId) -> Option<Entity> {
+        self.entities.get(usize::from(id.0)).copied()
+    }
+
+    /// The id of the world called `name`.
+    #[must_use]
+    pub fn find<'a>(&self, world: impl WorldProvider<'a>, name: &str) -> Option<WorldId> {
+        let world = world.world();
+
+        self.iter().find_map(|(id, entity)| {
+            let matches = world
+                .entity_from_id(entity)
+                .get::<&Dimension>(|dimension| dimension.name.as_str() == name);
+            matches.then_some(id)
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WorldId, Entity)> + '_ {
+        (0..).map(WorldId).zip(self.entities.iter().copied())
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Creates a world called `name`, e.g. `hyperion:arena`.
+    pub fn create(
+        world: &World,
+        name: &str,
+        kind: DimensionType,
+        blocks: Blocks,
+        spawn: SpawnPoint,
+    ) -> anyhow::Result<WorldId> {
+        let entity = world.entity().set(blocks).set(spawn);
+        Self::register(world, entity, name, kind)
+    }
+
+    /// Turns the entity of the [`Blocks`] singleton into the default world.
+    pub(crate) fn init_default(world: &World) -> anyhow::Result<()> {
+        world.set(Self::default());
+
+        let spawn = world.get::<&Config>(|config| SpawnPoint::new(config.spawn.position()));
+
+        let entity = world.entity_from_id(world.component::<Blocks>().id());
+        entity.set(spawn);
+
+        let id = Self::register(
+            world,
+            entity,
+            "minecraft:overworld",
+            DimensionType::Overworld,
+        )?;
+        debug_assert_eq!(id, WorldId::DEFAULT);
+
+        Ok(())
+    }
+
+    fn register(
+        world: &World,
+        entity: EntityView<'_>,
+        name: &str,
+        kind: DimensionType,
+    ) -> anyhow::Result<WorldId> {
+        let Ok(name) = Ident::new(name.to_owned()) else {
+            bail!("invalid world name {name}");
+        };
+
+        let id = world.get::<&mut Self>(|worlds| {
+            if worlds.find(world, name.as_str()).is_some() {
+                bail!("there already is a world called {name}");
+            }
+
+            let Ok(id) = u16::try_from(worlds.entities.len()) else {
+                bail!("too many worlds");
+            };
+
+            worlds.entities.push(entity.id());
+
+            Ok(WorldId(id))
+        })?;
+
+        info!("created world {name} ({id:?})");
+
+        entity.set(Dimension { id, name, kind });
+
+        Ok(id)
+    }
+}
+
+/// Runs `f` with the blocks of world `id`. Returns `None` if there is no such world.
+pub fn with_blocks<'a, R>(
+    world: impl WorldProvider<'a>,
+    id: WorldId,
+    f: impl FnOnce(&Blocks) -> R,
+) -> Option<R> {
+    let world = world.world();
+    let entity = world.get::<&Worlds>(|worlds| worlds.get(id))?;
+    world.entity_from_id(entity).try_get::<&Blocks>(f)
+}
+
+/// Like [`with_blocks`], but the blocks can be changed.
+pub fn with_blocks_mut<'a, R>(
+    world: impl WorldProvider<'a>,
+    id: WorldId,
+    f: impl FnOnce(&mut Blocks) -> R,
+) -> Option<R> {
+    let world = world.world();
+    let entity = world.get::<&Worlds>(|worlds| worlds.get(id))?;
+    world.entity_from_id(entity).try_get::<&mut Blocks>(f)
+}
+
+/// Set on a player to move them to another world. They appear at `position`, or at the
+/// [`SpawnPoint`] of the world if it is `None`.
+#[derive(Component, Debug)]
+pub struct ChangeWorld {
+    pub world: WorldId,
+    pub position: Option<Vec3>,
+}
+
+impl ChangeWorld {
+    #[must_use]
+    pub const fn new(world: WorldId) -> Self {
+        Self {
+            world,
+            position: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn at(world: WorldId, position: Vec3) -> Self {
+        Self {
+            world,
+            position: Some(position),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct WorldsModule;
+
+impl Module for WorldsModule {
+    fn module(world: &World) {
+        world.component::<ChangeWorld>();
+
+        let players = world
+            .query::<(&Uuid, &Position, &Yaw, &Pitch, &WorldId, &ConnectionId)>()
+            .with::<Player>()
+            .build();
+
+        system!(
+            "change_worlds",
+            world,
+            &Compose($),
+            &Worlds($),
+            &ChangeWorld,
+            &PacketState,
+            &ConnectionId,
+            &Uuid,
+            &mut WorldId,
+            &mut Position,
+            &mut Yaw,
+            &mut Pitch,
+            &mut ChunkPosition,
+            &mut ChunkSendQueue,
+            &mut AntiCheat,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(
+            move |it,
+                  row,
+                  (
+                compose,
+                worlds,
+                change,
+                state,
+                &stream,
+                uuid,
+                world_id,
+                position,
+                yaw,
+                pitch,
+                chunk_position,
+                queue,
+                anticheat,
+            )| {
+                let span = info_span!("change_worlds");
+                let _enter = span.enter();
+
+                let system = it.system();
+                let world = it.world();
+                let entity = it.entity(row);
+
+                let target = change.world;
+                let new_position = change.position;
+                entity.remove::<ChangeWorld>();
+
+                if *state != PacketState::Play {
+                    warn!("cannot move a player to another world before they joined");
+                    return;
+                }
+
+                if target == *world_id {
+                    warn!("player is already in world {target:?}");
+                    return;
+                }
+
+                let Some(target_entity) = worlds.get(target) else {
+                    warn!("cannot move player to unknown world {target:?}");
+                    return;
+                };
+
+                let (dimension, spawn) =
+                    world
+                        .entity_from_id(target_entity)
+                        .get::<(&Dimension, &SpawnPoint)>(|(dimension, spawn)| {
+                            (dimension.clone(), *spawn)
+                        });
+
+                let entity_id = VarInt(entity.minecraft_id());
+
+                // the players of the old world no longer see this player
+                let destroy = play::EntitiesDestroyS2c {
+                    entity_ids: Cow::Borrowed(&[entity_id]),
+                };
+
+                if let Err(e) = compose
+                    .broadcast(&destroy, system)
+                    .world(*world_id)
+                    .exclude(stream)
+                    .send()
+                {
+                    error!("failed to send entities destroy packet: {e}");
+                }
+
+                *world_id = target;
+                **position = new_position.unwrap_or(spawn.position);
+                if new_position.is_none() {
+                    **yaw = spawn.yaw;
+                    **pitch = spawn.pitch;
+                }
+
+                anticheat.teleported(**position, compose.global().tick);
+
+                // makes the chunks of the new world be sent from scratch
+                *chunk_position = ChunkPosition::null();
+                queue.clear();
+
+                let mut bundle = DataBundle::new(compose, system);
+                let mut arrival = DataBundle::new(compose, system);
+
+                let result = (|| {
+                    bundle.add_packet(&dimension.respawn_packet())?;
+
+                    bundle.add_packet(&play::PlayerPositionLookS2c {
+                        position: position.as_dvec3(),
+                        yaw: **yaw,
+                        pitch: **pitch,
+                        flags: PlayerPositionLookFlags::default(),
+                        teleport_id: 1.into(),
+                    })?;
+
+                    bundle.add_packet(&play::PlayerSpawnPositionS2c {
+                        position: spawn.position.as_dvec3().into(),
+                        angle: spawn.yaw,
+                    })?;
+
+                    arrival.add_packet(&play::PlayerSpawnS2c {
+                        entity_id,
+                        player_uuid: uuid.0,
+                        position: position.as_dvec3(),
+                        yaw: ByteAngle::from_degrees(**yaw),
+                        pitch: ByteAngle::from_degrees(**pitch),
+                    })?;
+                    arrival.add_packet(show_all(entity.minecraft_id()).borrow_packet())?;
+
+                    anyhow::Ok(())
+                })();
+
+                if let Err(e) = result {
+                    error!("failed to encode world change packets: {e}");
+                    return;
+                }
+
+                players.each_entity(
+                    |other,
+                     (
+                        other_uuid,
+                        other_position,
+                        other_yaw,
+                        other_pitch,
+                        other_world,
+                        &other_stream,
+                    )| {
+                        if *other_world != target || other.id() == entity.id() {
+                            return;
+                        }
+
+                        let spawn = play::PlayerSpawnS2c {
+                            entity_id: VarInt(other.minecraft_id()),
+                            player_uuid: other_uuid.0,
+                            position: other_position.as_dvec3(),
+                            yaw: ByteAngle::from_degrees(**other_yaw),
+                            pitch: ByteAngle::from_degrees(**other_pitch),
+                        };
+
+                        if let Err(e) = bundle.add_packet(&spawn).and_then(|()| {
+                            bundle.add_packet(show_all(other.minecraft_id()).borrow_packet())
+                        }) {
+                            error!("failed to encode player spawn packet: {e}");
+                        }
+
+                        if let Err(e) = arrival.unicast(other_stream) {
+                            error!("failed to send player spawn packet: {e}");
+                        }
+                    },
+                );
+
+                if let Err(e) = bundle.unicast(stream) {
+                    error!("failed to send world change packets: {e}");
+                }
+
+                info!("moved player to world {}", dimension.name);
+            },
+        );
+    }
+}
@@ -1,59 +1,140 @@
 //! Constructs for connecting and working with a `Heed` database.
 
-use std::path::Path;
+use std::sync::Arc;
 
+use anyhow::ensure;
 use byteorder::NativeEndian;
 use derive_more::Deref;
 use flecs_ecs::macros::Component;
-use heed::{Database, Env, EnvOpenOptions, types};
+use heed::{Database, Env, EnvOpenOptions, MdbError, RoTxn, RwTxn, types};
+use parking_lot::RwLock;
+use tracing::info;
 use uuid::Uuid;
 
-use crate::simulation::skin::{ArchivedPlayerSkin, PlayerSkin};
+use crate::{
+    config,
+    simulation::skin::{ArchivedPlayerSkin, PlayerSkin},
+};
+
+const MIB: usize = 1024 * 1024;
 
 /// A wrapper around a `Heed` database
+///
+/// Transactions should go through [`Self::read`] and [`Self::write`], which grow the memory map
+/// when the database is full.
 #[derive(Component, Debug, Clone, Deref)]
 pub struct LocalDb {
+    #[deref]
     env: Env,
+    /// Held shared by every transaction and exclusively while the memory map is resized, which
+    /// must not happen while a transaction is open.
+    resize: Arc<RwLock<()>>,
+    max_map_size: usize,
 }
 
 impl LocalDb {
     /// Creates a new [`LocalDb`]
-    pub fn new() -> anyhow::Result<Self> {
-        let path = Path::new("db").join("heed.mdb");
+    pub fn new(config: &config::Database) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.path)?;
 
-        std::fs::create_dir_all(&path)?;
+        let max_map_size = config.max_map_size_mib * MIB;
 
         let env = unsafe {
             EnvOpenOptions::new()
-                .map_size(10 * 1024 * 1024) // 10MB
-                .max_dbs(8) // todo: why is this needed/configurable? ideally would be infinite...
-                .open(&path)?
+                .map_size(config.map_size_mib.min(config.max_map_size_mib) * MIB)
+                .max_dbs(config.max_dbs)
+                .open(&config.path)?
         };
 
-        Ok(Self { env })
+        Ok(Self {
+            env,
+            resize: Arc::default(),
+            max_map_size,
+        })
+    }
+
+    /// Runs `f` in a read transaction.
+    pub fn read<T>(&self, f: impl FnOnce(&RoTxn<'_>) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let _resize = self.resize.read();
+        let rtxn = self.env.read_txn()?;
+        f(&rtxn)
+    }
+
+    /// Runs `f` in a write transaction and commits it.
+    ///
+    /// If the database is full, the memory map is grown and `f` runs again in a new transaction.
+    pub fn write<T>(
+        &self,
+        mut f: impl FnMut(&mut RwTxn<'_>) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        loop {
+            let (map_size, result) = {
+                let _resize = self.resize.read();
+                let map_size = self.env.info().map_size;
+
+                let mut wtxn = self.env.write_txn()?;
+                let result = f(&mut wtxn).and_then(|value| {
+                    wtxn.commit()?;
+                    Ok(value)
+                });
+
+                (map_size, result)
+            };
+
+            match result {
+                Err(e) if is_map_full(&e) => self.grow(map_size)?,
+                result => return result,
+            }
+        }
     }
+
+    /// Doubles the memory map, which was `map_size` bytes when the database was full.
+    fn grow(&self, map_size: usize) -> anyhow::Result<()> {
+        let _resize = self.resize.write();
+
+        // another transaction might have grown it already
+        if self.env.info().map_size > map_size {
+            return Ok(());
+        }
+
+        ensure!(
+            map_size < self.max_map_size,
+            "the database is full at its maximum size of {} MiB",
+            self.max_map_size / MIB
+        );
+
+        let new_size = map_size.saturating_mul(2).min(self.max_map_size);
+
+        // SAFETY: no transaction is open, as they all hold `self.resize`
+        unsafe { self.env.resize(new_size)? };
+
+        info!("grew the database to {} MiB", new_size / MIB);
+
+        Ok(())
+    }
+}
+
+fn is_map_full(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<heed::Error>(),
+        Some(heed::Error::Mdb(MdbError::MapFull))
+    )
 }
 
 /// A handler for player skin operations
 #[derive(Component, Debug, Clone)]
 pub struct SkinHandler {
-    env: Env,
+    db: LocalDb,
     skins: Database<types::U128<NativeEndian>, types::Bytes>,
 }
 
 impl SkinHandler {
     /// Creates a new [`SkinHandler`] from a given [`LocalDb`].
     pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
-        // We open the default unnamed database
-        let skins = {
-            let mut wtxn = db.write_txn()?;
-            let db = db.create_database(&mut wtxn, Some("uuid-to-skins"))?;
-            wtxn.commit()?;
-            db
-        };
+        let skins = db.write(|wtxn| Ok(db.create_database(wtxn, Some("uuid-to-skins"))?))?;
 
         Ok(Self {
-            env: db.env.clone(),
+            db: db.clone(),
             skins,
         })
     }
@@ -64,29 +145,61 @@ impl SkinHandler {
 
         let uuid = uuid.as_u128();
 
-        let rtxn = self.env.read_txn()?;
-        let skin = self.skins.get(&rtxn, &uuid);
-
-        let Some(skin) = skin? else {
-            return Ok(None);
-        };
+        self.db.read(|rtxn| {
+            let Some(skin) = self.skins.get(rtxn, &uuid)? else {
+                return Ok(None);
+            };
 
-        let skin = unsafe { rkyv::access_unchecked::<ArchivedPlayerSkin>(skin) };
-        let skin = rkyv::deserialize::<_, rkyv::rancor::Error>(skin).unwrap();
-        Ok(Some(skin))
+            let skin = unsafe { rkyv::access_unchecked::<ArchivedPlayerSkin>(skin) };
+            let skin = rkyv::deserialize::<_, rkyv::rancor::Error>(skin).unwrap();
+            Ok(Some(skin))
+        })
     }
 
     /// Inserts a [`PlayerSkin`] into the database.
     pub fn insert(&self, uuid: Uuid, skin: &PlayerSkin) -> anyhow::Result<()> {
         let uuid = uuid.as_u128();
 
-        let mut wtxn = self.env.write_txn()?;
-
         let skin = rkyv::to_bytes::<rkyv::rancor::Error>(skin).unwrap();
 
-        self.skins.put(&mut wtxn, &uuid, &skin)?;
-        wtxn.commit()?;
+        self.db
+            .write(|wtxn| Ok(self.skins.put(wtxn, &uuid, &skin)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::BigEndian;
+    use heed::{Database, types};
 
-        Ok(())
+    use super::{LocalDb, MIB};
+    use crate::config;
+
+    #[test]
+    fn grows_when_full() {
+        let path = std::env::temp_dir().join(format!("hyperion-db-{}", std::process::id()));
+
+        let db = LocalDb::new(&config::Database {
+            path: path.clone(),
+            map_size_mib: 1,
+            max_map_size_mib: 16,
+            max_dbs: 1,
+        })
+        .unwrap();
+
+        let values: Database<types::U32<BigEndian>, types::Bytes> = db
+            .write(|wtxn| Ok(db.create_database(wtxn, Some("values"))?))
+            .unwrap();
+
+        let value = vec![7; 64 * 1024];
+        for i in 0..64 {
+            db.write(|wtxn| Ok(values.put(wtxn, &i, &value)?)).unwrap();
+        }
+
+        assert!(db.info().map_size > MIB);
+        assert_eq!(db.read(|rtxn| Ok(values.len(rtxn)?)).unwrap(), 64);
+
+        drop(db);
+        std::fs::remove_dir_all(path).unwrap();
     }
 }
@@ -0,0 +1,166 @@
+//! Listeners which see events before the game acts on them.
+//!
+//! Every event pushed to [`Events`](crate::storage::Events) and the events the server fires on
+//! its own, like [`event::PlayerJoin`](crate::simulation::event::PlayerJoin), first go through
+//! the listeners on the [`EventBus`]. Listeners run in the order of their [`Priority`], may change
+//! the event and may cancel it. A cancelled event is dropped before anything acts on it, and a
+//! listener after the one that cancelled it may let it through again.
+
+use std::any::{Any, TypeId};
+
+use flecs_ecs::{
+    core::{World, WorldGet},
+    macros::Component,
+};
+use rustc_hash::FxHashMap;
+
+/// When a listener hears about an event. Listeners with a lower priority run first, so the ones
+/// with a higher priority have the last word on the event.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Lowest,
+    Low,
+    #[default]
+    Normal,
+    High,
+    Highest,
+    /// Runs last to see what happens to the event, like for logging. Monitors should not change
+    /// or cancel the event.
+    Monitor,
+}
+
+/// What a listener knows about the event it is handed besides the event itself.
+pub struct EventContext<'a> {
+    pub world: &'a World,
+    cancelled: bool,
+}
+
+impl EventContext<'_> {
+    /// Drops the event, unless a later listener lets it through again with [`Self::uncancel`].
+    pub const fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub const fn uncancel(&mut self) {
+        self.cancelled = false;
+    }
+
+    #[must_use]
+    pub const fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+type Listener<E> = Box<dyn Fn(&mut E, &mut EventContext<'_>) + Send + Sync>;
+
+struct Listeners<E> {
+    /// Sorted by priority. Listeners of the same priority run in the order they were added.
+    listeners: Vec<(Priority, Listener<E>)>,
+}
+
+/// The listeners of all events.
+#[derive(Component, Default)]
+pub struct EventBus {
+    listeners: FxHashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl EventBus {
+    /// Calls `listener` with every event of type `E`.
+    pub fn listen<E: Send + Sync + 'static>(
+        &mut self,
+        priority: Priority,
+        listener: impl Fn(&mut E, &mut EventContext<'_>) + Send + Sync + 'static,
+    ) {
+        let listeners = self
+            .listeners
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| {
+                Box::new(Listeners::<E> {
+                    listeners: Vec::new(),
+                })
+            })
+            .downcast_mut::<Listeners<E>>()
+            .expect("listeners are stored under the type id of their event");
+
+        let listeners = &mut listeners.listeners;
+        let index = listeners.partition_point(|(other, _)| *other <= priority);
+        listeners.insert(index, (priority, Box::new(listener)));
+    }
+
+    /// Whether anything listens to events of type `E`.
+    #[must_use]
+    pub fn has_listeners<E: 'static>(&self) -> bool {
+        self.listeners.contains_key(&TypeId::of::<E>())
+    }
+
+    /// Hands `event` to its listeners. Returns whether the event went through, which is `false`
+    /// if the listeners cancelled it.
+    pub fn fire<E: 'static>(&self, event: &mut E, world: &World) -> bool {
+        let Some(listeners) = self.listeners.get(&TypeId::of::<E>()) else {
+            return true;
+        };
+
+        let Some(listeners) = listeners.downcast_ref::<Listeners<E>>() else {
+            return true;
+        };
+
+        let mut context = EventContext {
+            world,
+            cancelled: false,
+        };
+
+        for (_, listener) in &listeners.listeners {
+            listener(event, &mut context);
+        }
+
+        !context.cancelled
+    }
+
+    /// Like [`Self::fire`] with the [`EventBus`] of `world`.
+    pub fn dispatch<E: 'static>(world: &World, event: &mut E) -> bool {
+        world.get::<&Self>(|bus| bus.fire(event, world))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::core::World;
+
+    use super::{EventBus, Priority};
+
+    struct Damage(f32);
+
+    #[test]
+    fn listeners_run_by_priority() {
+        let world = World::new();
+        let mut bus = EventBus::default();
+
+        bus.listen(Priority::High, |damage: &mut Damage, _| damage.0 *= 2.0);
+        bus.listen(Priority::Low, |damage: &mut Damage, _| damage.0 += 1.0);
+        bus.listen(Priority::Normal, |damage: &mut Damage, _| damage.0 += 2.0);
+
+        let mut damage = Damage(0.0);
+        assert!(bus.fire(&mut damage, &world));
+        assert!((damage.0 - 6.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn later_listeners_decide_on_cancellation() {
+        let world = World::new();
+        let mut bus = EventBus::default();
+
+        bus.listen(Priority::Normal, |_: &mut Damage, context| context.cancel());
+        assert!(!bus.fire(&mut Damage(1.0), &world));
+
+        bus.listen(Priority::Highest, |damage: &mut Damage, context| {
+            if damage.0 > 5.0 {
+                context.uncancel();
+            }
+        });
+        assert!(!bus.fire(&mut Damage(1.0), &world));
+        assert!(bus.fire(&mut Damage(10.0), &world));
+
+        // events nobody listens to always go through
+        assert!(bus.fire(&mut 0_u8, &world));
+    }
+}
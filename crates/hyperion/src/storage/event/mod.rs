@@ -1,5 +1,7 @@
+mod bus;
 mod queue;
 mod sync;
 
+pub use bus::*;
 pub use queue::*;
 pub use sync::*;
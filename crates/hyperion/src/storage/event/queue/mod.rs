@@ -5,7 +5,7 @@ use flecs_ecs::{
     macros::Component,
 };
 
-use crate::simulation::event;
+use crate::{simulation::event, storage::EventBus};
 
 pub mod event_queue;
 pub mod raw;
@@ -14,8 +14,23 @@ pub use event_queue::EventQueue;
 use hyperion_event_macros::define_events;
 
 impl Events {
-    pub fn push<E: Event>(&self, event: E, world: &World) {
+    /// Queues `event` unless a listener on the [`EventBus`] cancels it.
+    pub fn push<E: Event>(&self, mut event: E, world: &World) {
+        if EventBus::dispatch(world, &mut event) {
+            E::input(event, self, world);
+        }
+    }
+
+    /// Like [`Self::push`], but returns whether the event was queued, e.g. to undo what the
+    /// client predicted if it was cancelled.
+    #[must_use]
+    pub fn try_push<E: Event>(&self, mut event: E, world: &World) -> bool {
+        if !EventBus::dispatch(world, &mut event) {
+            return false;
+        }
+
         E::input(event, self, world);
+        true
     }
 }
 
@@ -57,7 +72,12 @@ define_events! {
     event::PostureUpdate,
     event::SwingArm,
     event::ToggleDoor,
+    event::OpenContainer,
+    event::OpenCraftingTable,
+    event::ClickWindow,
+    event::CloseWindow,
     event::ReleaseUseItem,
     event::ClientStatusEvent,
-    event::StartDestroyBlock
+    event::StartDestroyBlock,
+    event::Violation
 }
@@ -2,10 +2,12 @@ mod bits;
 mod buf;
 mod db;
 mod event;
+mod player_data;
 mod thread_local;
 
 pub use bits::*;
 pub use buf::*;
 pub use db::*;
 pub use event::*;
+pub use player_data::*;
 pub use thread_local::*;
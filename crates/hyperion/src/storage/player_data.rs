@@ -0,0 +1,379 @@
+//! Player data that is kept across disconnects and restarts, stored in the [`LocalDb`].
+//!
+//! Components opt in by implementing [`Persistent`] and calling [`persist`]. They are saved when
+//! the player leaves and restored as soon as the [`Uuid`] of the player is known when they join.
+//! Every record is stored with the [`Persistent::VERSION`] it was written with, and records of an
+//! older version go through [`Persistent::migrate`] when they are read.
+
+use anyhow::{Context, bail};
+use flecs_ecs::{
+    core::{ComponentId, ComponentType, DataComponent, Struct},
+    prelude::*,
+};
+use heed::{Database, types};
+use hyperion_inventory::PlayerInventory;
+use rkyv::{
+    Archive, Deserialize, Serialize,
+    api::high::{HighDeserializer, HighSerializer, HighValidator},
+    bytecheck::CheckBytes,
+    rancor,
+    ser::allocator::ArenaHandle,
+    util::AlignedVec,
+};
+use tracing::{error, warn};
+use valence_protocol::{Decode, Encode, ItemStack};
+
+use crate::{
+    simulation::{
+        Player, Position, Uuid, Xp,
+        metadata::living_entity::Health,
+        worlds::{SpawnPoint, WorldId, Worlds},
+    },
+    storage::LocalDb,
+};
+
+/// What a [`Persistent`] component is stored as.
+pub trait Record:
+    Archive + for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>
+{
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self>
+    where
+        Self: Sized;
+}
+
+impl<T> Record for T
+where
+    T: Archive + for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, rancor::Error>>,
+    T::Archived: for<'a> CheckBytes<HighValidator<'a, rancor::Error>>
+        + Deserialize<T, HighDeserializer<rancor::Error>>,
+{
+    fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        // the database does not keep the alignment rkyv needs
+        let mut aligned = AlignedVec::<16>::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+
+        let record = rkyv::from_bytes::<Self, rancor::Error>(&aligned)?;
+        Ok(record)
+    }
+}
+
+/// A component of players that is saved when they leave and restored when they join again.
+pub trait Persistent: ComponentId + DataComponent + ComponentType<Struct> {
+    /// The name the records are stored under. Changing it loses all saved records.
+    const KEY: &'static str;
+
+    /// The version of [`Self::Record`]. Bump it whenever the record changes and upgrade the
+    /// records of the old version in [`Self::migrate`].
+    const VERSION: u32;
+
+    type Record: Record;
+
+    fn to_record(&self) -> Self::Record;
+
+    fn from_record(record: Self::Record) -> Self;
+
+    /// Puts a loaded value on the player `entity`.
+    fn restore(self, entity: EntityView<'_>)
+    where
+        Self: Sized,
+    {
+        entity.set(self);
+    }
+
+    /// Upgrades a record that was written with an older `version`, where `bytes` is the record as
+    /// serialized by rkyv. Records that can't be migrated are dropped.
+    fn migrate(version: u32, bytes: &[u8]) -> anyhow::Result<Self::Record> {
+        let _ = bytes;
+        bail!("no migration of {} from version {version}", Self::KEY)
+    }
+}
+
+/// The records of every [`Persistent`] component by player.
+#[derive(Component, Debug, Clone)]
+pub struct PlayerDataStore {
+    db: LocalDb,
+    /// Keyed by the UUID of the player followed by [`Persistent::KEY`]. Every value starts with
+    /// the [`Persistent::VERSION`] of the record as a little endian `u32`.
+    records: Database<types::Bytes, types::Bytes>,
+}
+
+impl PlayerDataStore {
+    /// Creates a new [`PlayerDataStore`] from a given [`LocalDb`].
+    pub fn new(db: &LocalDb) -> anyhow::Result<Self> {
+        let records = db.write(|wtxn| Ok(db.create_database(wtxn, Some("player-data"))?))?;
+
+        Ok(Self {
+            db: db.clone(),
+            records,
+        })
+    }
+
+    /// Loads the saved `T` of a player.
+    pub fn load<T: Persistent>(&self, uuid: uuid::Uuid) -> anyhow::Result<Option<T>> {
+        self.db.read(|rtxn| {
+            let Some(value) = self.records.get(rtxn, &key::<T>(uuid))? else {
+                return Ok(None);
+            };
+
+            let (version, bytes) = value
+                .split_first_chunk::<4>()
+                .context("the record has no version")?;
+            let version = u32::from_le_bytes(*version);
+
+            let record = match version.cmp(&T::VERSION) {
+                std::cmp::Ordering::Equal => T::Record::from_bytes(bytes)?,
+                std::cmp::Ordering::Less => T::migrate(version, bytes)?,
+                std::cmp::Ordering::Greater => bail!(
+                    "the record has version {version}, but only {} is known",
+                    T::VERSION
+                ),
+            };
+
+            Ok(Some(T::from_record(record)))
+        })
+    }
+
+    /// Saves `value` as the `T` of a player.
+    pub fn save<T: Persistent>(&self, uuid: uuid::Uuid, value: &T) -> anyhow::Result<()> {
+        let record = rkyv::to_bytes::<rancor::Error>(&value.to_record())?;
+
+        let mut bytes = Vec::with_capacity(4 + record.len());
+        bytes.extend_from_slice(&T::VERSION.to_le_bytes());
+        bytes.extend_from_slice(&record);
+
+        let key = key::<T>(uuid);
+        self.db
+            .write(|wtxn| Ok(self.records.put(wtxn, &key, &bytes)?))
+    }
+
+    /// Forgets the saved `T` of a player.
+    pub fn remove<T: Persistent>(&self, uuid: uuid::Uuid) -> anyhow::Result<()> {
+        let key = key::<T>(uuid);
+        self.db.write(|wtxn| {
+            self.records.delete(wtxn, &key)?;
+            Ok(())
+        })
+    }
+}
+
+fn key<T: Persistent>(uuid: uuid::Uuid) -> Vec<u8> {
+    let mut key = Vec::with_capacity(16 + T::KEY.len());
+    key.extend_from_slice(uuid.as_bytes());
+    key.extend_from_slice(T::KEY.as_bytes());
+    key
+}
+
+/// Saves `T` when a player leaves and restores it when they join.
+pub fn persist<T: Persistent>(world: &World) {
+    world.component::<T>();
+
+    observer!(world, flecs::OnSet, &Uuid, &PlayerDataStore($))
+        .with::<Player>()
+        .each_entity(|entity, (uuid, store)| match store.load::<T>(uuid.0) {
+            Ok(Some(value)) => value.restore(entity),
+            Ok(None) => {}
+            Err(e) => warn!("failed to load {} of {}: {e:#}", T::KEY, uuid.0),
+        });
+
+    observer!(world, flecs::OnRemove, &Uuid, &T, &PlayerDataStore($))
+        .with::<Player>()
+        .each(|(uuid, value, store)| {
+            if let Err(e) = store.save(uuid.0, value) {
+                error!("failed to save {} of {}: {e:#}", T::KEY, uuid.0);
+            }
+        });
+}
+
+impl Persistent for Position {
+    type Record = [f32; 3];
+
+    const KEY: &'static str = "position";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        self.to_array()
+    }
+
+    fn from_record(record: Self::Record) -> Self {
+        Self::from(glam::Vec3::from_array(record))
+    }
+}
+
+/// The world is saved along with the [`Position`], which is only meaningful in it. World ids are
+/// given out in the order worlds are created, so they stay the same across restarts as long as the
+/// worlds are created in the same order.
+impl Persistent for WorldId {
+    type Record = u16;
+
+    const KEY: &'static str = "world";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        self.0
+    }
+
+    fn from_record(record: Self::Record) -> Self {
+        Self(record)
+    }
+
+    /// Players whose world does not exist anymore are moved to the spawn of the default world.
+    fn restore(self, entity: EntityView<'_>) {
+        let world = entity.world();
+
+        if world.get::<&Worlds>(|worlds| worlds.get(self).is_some()) {
+            entity.set(self);
+            return;
+        }
+
+        warn!("the saved world {self:?} does not exist anymore");
+
+        let spawn = world
+            .get::<&Worlds>(|worlds| worlds.get(WorldId::DEFAULT))
+            .and_then(|default| {
+                world
+                    .entity_from_id(default)
+                    .try_get::<&SpawnPoint>(|spawn| *spawn)
+            })
+            .unwrap_or_default();
+
+        entity
+            .set(WorldId::DEFAULT)
+            .set(Position::from(spawn.position));
+    }
+}
+
+impl Persistent for Xp {
+    type Record = u16;
+
+    const KEY: &'static str = "xp";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        self.amount
+    }
+
+    fn from_record(amount: Self::Record) -> Self {
+        Self { amount }
+    }
+}
+
+impl Persistent for Health {
+    type Record = f32;
+
+    const KEY: &'static str = "health";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        **self
+    }
+
+    fn from_record(record: Self::Record) -> Self {
+        Self::new(record)
+    }
+}
+
+/// The non-empty slots of an inventory.
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct InventoryRecord {
+    /// The slot index and the [`ItemStack`] in it, encoded like in packets.
+    slots: Vec<(u16, Vec<u8>)>,
+}
+
+impl Persistent for PlayerInventory {
+    type Record = InventoryRecord;
+
+    const KEY: &'static str = "inventory";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        let slots = self
+            .items()
+            .filter_map(|(slot, stack)| {
+                let mut bytes = Vec::new();
+                stack.encode(&mut bytes).ok()?;
+                Some((slot, bytes))
+            })
+            .collect();
+
+        InventoryRecord { slots }
+    }
+
+    fn from_record(record: Self::Record) -> Self {
+        let mut inventory = Self::default();
+
+        for (slot, bytes) in record.slots {
+            let stack = match ItemStack::decode(&mut bytes.as_slice()) {
+                Ok(stack) => stack,
+                Err(e) => {
+                    warn!("dropping the item in slot {slot}: {e}");
+                    continue;
+                }
+            };
+
+            if let Err(e) = inventory.set(slot, stack) {
+                warn!("dropping the item in slot {slot}: {e}");
+            }
+        }
+
+        inventory
+    }
+}
+
+#[derive(Component)]
+pub struct PlayerDataModule;
+
+impl Module for PlayerDataModule {
+    fn module(world: &World) {
+        world.component::<PlayerDataStore>();
+
+        persist::<Position>(world);
+        persist::<WorldId>(world);
+        persist::<Xp>(world);
+        persist::<Health>(world);
+        persist::<PlayerInventory>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flecs_ecs::prelude::*;
+
+    use super::{Persistent, Record};
+    use crate::simulation::{
+        Position,
+        worlds::{SpawnPoint, WorldId, Worlds},
+    };
+
+    #[test]
+    fn records_round_trip() {
+        let position = Position::new(1.0, 2.5, -3.0);
+
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&position.to_record()).unwrap();
+
+        // the bytes read from the database are not necessarily aligned
+        let mut unaligned = vec![0];
+        unaligned.extend_from_slice(&bytes);
+
+        let record = <Position as Persistent>::Record::from_bytes(&unaligned[1..]).unwrap();
+        assert_eq!(Position::from_record(record), position);
+    }
+
+    #[test]
+    fn players_of_removed_worlds_go_to_the_spawn() {
+        let world = World::new();
+        world.set(Worlds::default());
+
+        let player = world
+            .entity()
+            .set(WorldId(3))
+            .set(Position::new(10.0, 64.0, 10.0));
+
+        WorldId(2).restore(player);
+
+        assert_eq!(player.get::<&WorldId>(|id| *id), WorldId::DEFAULT);
+        assert_eq!(
+            player.get::<&Position>(|position| *position),
+            Position::from(SpawnPoint::default().position)
+        );
+    }
+}
@@ -8,14 +8,14 @@
 use std::{assert_matches::assert_matches, collections::HashSet};
 
 use approx::assert_relative_eq;
-use flecs_ecs::core::{QueryBuilderImpl, SystemAPI, World, WorldGet, flecs};
+use flecs_ecs::core::{QueryBuilderImpl, SystemAPI, World, flecs};
 use geometry::{aabb::Aabb, ray::Ray};
 use glam::Vec3;
 use hyperion::{
     HyperionCore,
-    simulation::{EntitySize, Position, entity_kind::EntityKind},
+    simulation::{EntitySize, Position, entity_kind::EntityKind, worlds::WorldId},
 };
-use spatial::{Spatial, SpatialIndex, SpatialModule};
+use spatial::{Spatial, SpatialModule};
 
 #[test]
 fn spatial() {
@@ -46,7 +46,7 @@ fn spatial() {
     // progress one tick to ensure that the index is updated
     world.progress();
 
-    world.get::<&SpatialIndex>(|spatial| {
+    spatial::with_index(&world, WorldId::DEFAULT, |spatial| {
         let closest = spatial
             .closest_to(Vec3::new(1.0, 2.0, 0.0), &world)
             .expect("there to be a closest entity");
@@ -76,5 +76,6 @@ fn spatial() {
 
         let ray = Ray::from_points(Vec3::new(12.0, 0.0, 0.0), Vec3::new(13.0, 1.0, 1.0));
         assert_matches!(spatial.first_ray_collision(ray, &world), None);
-    });
+    })
+    .expect("the default world to have a spatial index");
 }
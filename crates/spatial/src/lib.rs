@@ -11,8 +11,10 @@ use hyperion::{
     egress::player_join::RayonWorldStages,
     glam::Vec3,
     simulation::{
-        EntitySize, Position, aabb,
-        blocks::{Blocks, RayCollision},
+        EntitySize, Position, Velocity, aabb,
+        blocks::RayCollision,
+        physics::Physics,
+        worlds::{Dimension, WorldId, Worlds, with_blocks},
     },
 };
 use ordered_float::NotNan;
@@ -21,20 +23,31 @@ use rayon::iter::Either;
 #[derive(Component)]
 pub struct SpatialModule;
 
+/// Every world has its own index, on the world entity.
 #[derive(Component, Debug, Default)]
 pub struct SpatialIndex {
-    /// The bounding boxes of all entities with the [`Spatial`] component
+    /// The bounding boxes of all entities with the [`Spatial`] component in the world
     query: bvh_region::Bvh<Entity>,
 }
 
+/// Runs `f` with the spatial index of world `id`. Returns `None` if there is no such world.
+pub fn with_index<R>(world: &World, id: WorldId, f: impl FnOnce(&SpatialIndex) -> R) -> Option<R> {
+    let entity = world.get::<&Worlds>(|worlds| worlds.get(id))?;
+    world.entity_from_id(entity).try_get::<&SpatialIndex>(f)
+}
+
 #[must_use]
 pub fn get_first_collision(
     ray: Ray,
+    world_id: WorldId,
     world: &World,
 ) -> Option<Either<EntityView<'_>, RayCollision>> {
     // Check for collisions with entities
-    let entity = world.get::<&SpatialIndex>(|index| index.first_ray_collision(ray, world));
-    let block = world.get::<&Blocks>(|blocks| blocks.first_collision(ray));
+    let entity = with_index(world, world_id, |index| {
+        index.first_ray_collision(ray, world)
+    })
+    .flatten();
+    let block = with_blocks(world, world_id, |blocks| blocks.first_collision(ray)).flatten();
 
     // check which one is closest to the Ray don't forget to account for entity size
     entity.map_or(block.map(Either::Right), |(entity, _)| {
@@ -76,8 +89,8 @@ fn get_aabb_func<'a>(world: &'a World) -> impl Fn(&Entity) -> Aabb + Send + Sync
 }
 
 impl SpatialIndex {
-    fn recalculate(&mut self, world: &World) {
-        let all_entities = all_indexed_entities(world);
+    fn recalculate(&mut self, world: &World, world_id: WorldId) {
+        let all_entities = all_indexed_entities(world, world_id);
         let get_aabb = get_aabb_func(world);
 
         self.query = bvh_region::Bvh::build(all_entities, &get_aabb);
@@ -117,10 +130,10 @@ impl SpatialIndex {
 #[derive(Component)]
 pub struct Spatial;
 // todo(perf): re-use allocations?
-fn all_indexed_entities(world: &World) -> Vec<Entity> {
+fn all_indexed_entities(world: &World, world_id: WorldId) -> Vec<Entity> {
     // todo(perf): can we cache this?
     let query = world
-        .query::<()>()
+        .query::<Option<&WorldId>>()
         .with::<Position>()
         .with::<EntitySize>()
         .with::<Spatial>()
@@ -130,28 +143,119 @@ fn all_indexed_entities(world: &World) -> Vec<Entity> {
     let count = usize::try_from(count).unwrap();
     let mut entities = Vec::with_capacity(count);
 
-    query.each_entity(|entity, ()| {
-        entities.push(entity.id());
+    query.each_entity(|entity, entity_world| {
+        if entity_world.copied().unwrap_or_default() == world_id {
+            entities.push(entity.id());
+        }
     });
 
     entities
 }
+
+/// How hard overlapping entities push each other apart, like in vanilla.
+const PUSH_STRENGTH: f32 = 0.05;
+
+/// The velocity `position` gets from being pushed away from `other`.
+fn push_away(position: Vec3, other: Vec3) -> Vec3 {
+    let dx = position.x - other.x;
+    let dz = position.z - other.z;
+
+    let distance = dx.abs().max(dz.abs());
+
+    // entities at the same spot do not know which way to go
+    if distance < 0.01 {
+        return Vec3::ZERO;
+    }
+
+    let distance = distance.sqrt();
+    let strength = (1.0 / distance).min(1.0) * PUSH_STRENGTH / distance;
+
+    Vec3::new(dx * strength, 0.0, dz * strength)
+}
 //
 impl Module for SpatialModule {
     fn module(world: &World) {
         world.component::<Spatial>();
         world.component::<SpatialIndex>();
-        world.add::<SpatialIndex>();
+
+        world
+            .component::<Dimension>()
+            .add_trait::<(flecs::With, SpatialIndex)>();
+
+        // worlds created before this module was imported
+        let mut existing = Vec::new();
+        world
+            .query::<()>()
+            .with::<Dimension>()
+            .build()
+            .each_entity(|entity, ()| existing.push(entity.id()));
+
+        for entity in existing {
+            world.entity_from_id(entity).add::<SpatialIndex>();
+        }
 
         system!(
             "recalculate_spatial_index",
             world,
-            &mut SpatialIndex($),
+            &mut SpatialIndex,
+            &Dimension,
         )
         .with::<flecs::pipeline::OnStore>()
-        .each_iter(|it, _, index| {
+        .each_iter(|it, _, (index, dimension)| {
+            let world = it.world();
+            index.recalculate(&world, dimension.id);
+        });
+
+        system!(
+            "push_entities",
+            world,
+            &Physics,
+            &Position,
+            &EntitySize,
+            &mut Velocity,
+            ?&WorldId,
+        )
+        .kind::<flecs::pipeline::OnUpdate>()
+        .with::<Spatial>()
+        .each_iter(|it, row, (physics, position, size, velocity, world_id)| {
+            if !physics.pushable {
+                return;
+            }
+
             let world = it.world();
-            index.recalculate(&world);
+            let entity = it.entity(row).id();
+            let world_id = world_id.copied().unwrap_or_default();
+
+            let push = with_index(&world, world_id, |index| {
+                index
+                    .get_collisions(aabb(**position, *size), &world)
+                    .filter(|other| *other != entity)
+                    .map(|other| {
+                        let other = world.entity_from_id(other);
+                        other.get::<&Position>(|other| push_away(**position, **other))
+                    })
+                    .sum::<Vec3>()
+            });
+
+            velocity.0 += push.unwrap_or_default();
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyperion::glam::Vec3;
+
+    use super::push_away;
+
+    #[test]
+    fn entities_are_pushed_apart() {
+        let push = push_away(Vec3::new(0.5, 0.0, 0.0), Vec3::ZERO);
+
+        assert!(push.x > 0.0);
+        assert!(push.y.abs() < f32::EPSILON);
+        assert!(push.z.abs() < f32::EPSILON);
+
+        assert_eq!(push_away(Vec3::ZERO, Vec3::ZERO), Vec3::ZERO);
+    }
+}
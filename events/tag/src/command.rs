@@ -2,17 +2,35 @@ use flecs_ecs::core::World;
 use hyperion_clap::{MinecraftCommand, hyperion_command::CommandRegistry};
 
 use crate::command::{
-    bow::BowCommand, class::ClassCommand, fly::FlyCommand, gui::GuiCommand,
-    raycast::RaycastCommand, replace::ReplaceCommand, shoot::ShootCommand, spawn::SpawnCommand,
-    speed::SpeedCommand, vanish::VanishCommand, xp::XpCommand,
+    bow::BowCommand,
+    chat::{ChannelCommand, MsgCommand, ReplyCommand},
+    class::ClassCommand,
+    config::ConfigCommand,
+    fly::FlyCommand,
+    gui::GuiCommand,
+    moderation::{
+        BanCommand, KickCommand, MuteCommand, UnbanCommand, UnmuteCommand, WhitelistCommand,
+    },
+    raycast::RaycastCommand,
+    replace::ReplaceCommand,
+    server::ServerCommand,
+    shoot::ShootCommand,
+    spawn::SpawnCommand,
+    speed::SpeedCommand,
+    vanish::VanishCommand,
+    xp::XpCommand,
 };
 
 mod bow;
+mod chat;
 mod class;
+mod config;
 mod fly;
 mod gui;
+mod moderation;
 mod raycast;
 mod replace;
+mod server;
 mod shoot;
 mod spawn;
 mod speed;
@@ -20,15 +38,26 @@ mod vanish;
 mod xp;
 
 pub fn register(registry: &mut CommandRegistry, world: &World) {
+    BanCommand::register(registry, world);
     BowCommand::register(registry, world);
+    ChannelCommand::register(registry, world);
     ClassCommand::register(registry, world);
+    ConfigCommand::register(registry, world);
     FlyCommand::register(registry, world);
     GuiCommand::register(registry, world);
+    KickCommand::register(registry, world);
+    MsgCommand::register(registry, world);
+    MuteCommand::register(registry, world);
     RaycastCommand::register(registry, world);
     ReplaceCommand::register(registry, world);
+    ReplyCommand::register(registry, world);
+    ServerCommand::register(registry, world);
     ShootCommand::register(registry, world);
     SpawnCommand::register(registry, world);
     SpeedCommand::register(registry, world);
+    UnbanCommand::register(registry, world);
+    UnmuteCommand::register(registry, world);
     VanishCommand::register(registry, world);
+    WhitelistCommand::register(registry, world);
     XpCommand::register(registry, world);
 }
@@ -0,0 +1,132 @@
+use anyhow::{Context, bail};
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityView, EntityViewGet, World, WorldGet, WorldProvider};
+use hyperion::{
+    net::{Compose, ConnectionId, agnostic},
+    simulation::IgnMap,
+};
+use hyperion_chat::{Channels, ChatChannel};
+use hyperion_clap::{CommandPermission, MinecraftCommand};
+
+/// Sends a private message to a player.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "msg")]
+#[command_permission(group = "Normal")]
+pub struct MsgCommand {
+    player: String,
+    message: Vec<String>,
+}
+
+impl MinecraftCommand for MsgCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let world = system.world();
+
+        let result = world.get::<&IgnMap>(|ign_map| ign_map.get(self.player.as_str()).copied());
+        let result = result
+            .with_context(|| format!("{} is not online", self.player))
+            .and_then(|to| {
+                world.get::<&Compose>(|compose| {
+                    hyperion_chat::whisper(
+                        &world,
+                        compose,
+                        system,
+                        caller,
+                        to,
+                        &message(&self.message)?,
+                    )
+                })
+            });
+
+        report(system, caller, result);
+    }
+}
+
+/// Replies to the player you last exchanged private messages with.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "r")]
+#[command_permission(group = "Normal")]
+pub struct ReplyCommand {
+    message: Vec<String>,
+}
+
+impl MinecraftCommand for ReplyCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let world = system.world();
+
+        let result = world.get::<&Compose>(|compose| {
+            hyperion_chat::reply(&world, compose, system, caller, &message(&self.message)?)
+        });
+
+        report(system, caller, result);
+    }
+}
+
+/// Switches the chat channel you talk in, or lists the channels you can talk in.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "channel")]
+#[command_permission(group = "Normal")]
+pub struct ChannelCommand {
+    channel: Option<String>,
+}
+
+impl MinecraftCommand for ChannelCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let line = self
+            .run(&system.world(), caller)
+            .unwrap_or_else(|e| format!("§c{e:#}"));
+
+        tell(system, caller, line);
+    }
+}
+
+impl ChannelCommand {
+    fn run(self, world: &World, caller: Entity) -> anyhow::Result<String> {
+        world.get::<&Channels>(|channels| {
+            let Some(name) = self.channel else {
+                return Ok(format!(
+                    "§7Channels: §f{}",
+                    channels.available(caller).join(", ")
+                ));
+            };
+
+            let Some(channel) = channels.get(&name) else {
+                bail!("there is no channel called {name}");
+            };
+
+            if !channel.can_talk(caller) {
+                bail!("you can't talk in {}", channel.display_name);
+            }
+
+            let line = format!("§aYou now talk in {}", channel.display_name);
+            caller.entity_view(world).set(ChatChannel(name));
+
+            Ok(line)
+        })
+    }
+}
+
+fn message(words: &[String]) -> anyhow::Result<String> {
+    if words.is_empty() {
+        bail!("the message is empty");
+    }
+
+    Ok(words.join(" "))
+}
+
+fn report(system: EntityView<'_>, caller: Entity, result: anyhow::Result<()>) {
+    if let Err(e) = result {
+        tell(system, caller, format!("§c{e:#}"));
+    }
+}
+
+fn tell(system: EntityView<'_>, to: Entity, line: String) {
+    let world = system.world();
+
+    world.get::<&Compose>(|compose| {
+        to.entity_view(world).get::<&ConnectionId>(|stream| {
+            compose
+                .unicast(&agnostic::chat(line), *stream, system)
+                .unwrap();
+        });
+    });
+}
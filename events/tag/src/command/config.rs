@@ -0,0 +1,95 @@
+use clap::{Parser, ValueEnum};
+use flecs_ecs::core::{Entity, EntityView, EntityViewGet, WorldGet, WorldProvider};
+use hyperion::{
+    config::{Config, reload},
+    net::{Compose, ConnectionId, agnostic},
+};
+use hyperion_clap::{CommandPermission, MinecraftCommand};
+
+/// Shows or changes the server configuration.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "config")]
+#[command_permission(group = "Admin")]
+pub struct ConfigCommand {
+    action: ConfigAction,
+    /// The setting, e.g. `view_distance` or `spawn.x`. Lists all settings if omitted.
+    key: Option<String>,
+    /// The new value, parsed as TOML
+    value: Vec<String>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum ConfigAction {
+    Get,
+    Set,
+}
+
+impl MinecraftCommand for ConfigCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let world = system.world();
+
+        let lines = world.get::<(&Config, &reload::ConfigFile)>(|(config, file)| {
+            let result = match self.action {
+                ConfigAction::Get => get(config, self.key.as_deref()),
+                ConfigAction::Set => set(config, file, self.key.as_deref(), &self.value.join(" ")),
+            };
+
+            result.unwrap_or_else(|e| vec![format!("§c{e:#}")])
+        });
+
+        world.get::<&Compose>(|compose| {
+            caller.entity_view(world).get::<&ConnectionId>(|stream| {
+                for line in lines {
+                    compose
+                        .unicast(&agnostic::chat(line), *stream, system)
+                        .unwrap();
+                }
+            });
+        });
+    }
+}
+
+fn get(config: &Config, key: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let Some(key) = key else {
+        let settings = config.settings()?;
+        return Ok(settings
+            .into_iter()
+            .map(|(key, value)| format!("{key} = {value}"))
+            .collect());
+    };
+
+    let line = match config.get(key)? {
+        Some(value) => format!("{key} = {value}"),
+        None => format!("{key} is not set"),
+    };
+
+    Ok(vec![line])
+}
+
+/// Writes the changed setting to the configuration file, which applies it once the file is
+/// reloaded. The rest of the file, including its comments, is kept. Settings that need a restart
+/// are rejected.
+fn set(
+    config: &Config,
+    file: &reload::ConfigFile,
+    key: Option<&str>,
+    value: &str,
+) -> anyhow::Result<Vec<String>> {
+    let Some(key) = key else {
+        anyhow::bail!("usage: /config set <key> <value>");
+    };
+
+    let new = config.with(key, value)?;
+    let changes = reload::check(config, &new)?;
+
+    if changes.is_empty() {
+        return Ok(vec![format!("{key} is unchanged")]);
+    }
+
+    new.save_setting(file.path(), key)?;
+
+    Ok(changes
+        .into_iter()
+        .map(|change| format!("§a{change}"))
+        .collect())
+}
@@ -5,7 +5,7 @@ use flecs_ecs::{
 };
 use hyperion::{
     net::{Compose, ConnectionId, DataBundle, agnostic},
-    simulation::Player,
+    simulation::{Player, anticheat::AntiCheat},
     valence_protocol::packets::play::{
         PlayerAbilitiesS2c, player_abilities_s2c::PlayerAbilitiesFlags,
     },
@@ -29,25 +29,28 @@ impl MinecraftCommand for FlyCommand {
         world.get::<&Compose>(|compose| {
             caller
                 .entity_view(world)
-                .get::<(&mut Flight, &ConnectionId)>(|(flight, stream)| {
-                    flight.allow = !flight.allow;
+                .get::<(&mut Flight, &ConnectionId, &mut AntiCheat)>(
+                    |(flight, stream, anticheat)| {
+                        flight.allow = !flight.allow;
 
-                    let allow_flight = flight.allow;
+                        let allow_flight = flight.allow;
+                        anticheat.allow_flight(allow_flight);
 
-                    let chat_packet = if allow_flight {
-                        agnostic::chat("§aFlying enabled")
-                    } else {
-                        agnostic::chat("§cFlying disabled")
-                    };
+                        let chat_packet = if allow_flight {
+                            agnostic::chat("§aFlying enabled")
+                        } else {
+                            agnostic::chat("§cFlying disabled")
+                        };
 
-                    let packet = fly_packet(allow_flight);
+                        let packet = fly_packet(allow_flight);
 
-                    let mut bundle = DataBundle::new(compose, system);
-                    bundle.add_packet(&packet).unwrap();
-                    bundle.add_packet(&chat_packet).unwrap();
+                        let mut bundle = DataBundle::new(compose, system);
+                        bundle.add_packet(&packet).unwrap();
+                        bundle.add_packet(&chat_packet).unwrap();
 
-                    bundle.unicast(*stream).unwrap();
-                });
+                        bundle.unicast(*stream).unwrap();
+                    },
+                );
         });
     }
 
@@ -0,0 +1,370 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, bail};
+use clap::{Parser, ValueEnum};
+use flecs_ecs::core::{Entity, EntityView, EntityViewGet, World, WorldGet, WorldProvider};
+use hyperion::{
+    config::{Config, reload},
+    ingress::{
+        PendingRemove,
+        moderation::{Moderation, Sanction},
+        offline_uuid,
+    },
+    net::{Compose, ConnectionId, ConnectionInfo, agnostic},
+    simulation::{IgnMap, Name, Uuid},
+};
+use hyperion_clap::{CommandPermission, MinecraftCommand};
+
+/// Bans a player from the server.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "ban")]
+#[command_permission(group = "Moderator")]
+pub struct BanCommand {
+    /// The name of an online player or the UUID of any player
+    player: String,
+    /// How long the ban lasts, e.g. `1d` or `2h30m`. Permanent if omitted.
+    #[arg(short, long)]
+    duration: Option<humantime::Duration>,
+    /// Also ban the IP address the player is connected from
+    #[arg(long)]
+    ip: bool,
+    reason: Vec<String>,
+}
+
+impl MinecraftCommand for BanCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(&system.world(), caller);
+        reply(system, caller, result);
+    }
+}
+
+impl BanCommand {
+    fn run(self, world: &World, caller: Entity) -> anyhow::Result<String> {
+        let target = Target::find(world, &self.player)?;
+        let sanction = Sanction::new(
+            self.reason.join(" "),
+            name_of(world, caller),
+            self.duration.map(Into::into),
+        );
+
+        // nothing is banned unless everything can be
+        let ip = self
+            .ip
+            .then(|| {
+                target
+                    .address(world)
+                    .context("only the IP address of online players can be banned")
+            })
+            .transpose()?;
+
+        world.get::<&Moderation>(|moderation| {
+            moderation.ban(target.uuid, &sanction)?;
+
+            if let Some(ip) = ip {
+                moderation.ban_ip(ip, &sanction)?;
+            }
+
+            anyhow::Ok(())
+        })?;
+
+        target.kick(world, sanction.message("You are banned from this server"));
+
+        Ok(format!("§aBanned {}", target.name))
+    }
+}
+
+/// Lifts the ban of a player or an IP address.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "unban")]
+#[command_permission(group = "Moderator")]
+pub struct UnbanCommand {
+    /// The name or UUID of the player, or an IP address
+    player: String,
+}
+
+impl MinecraftCommand for UnbanCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(&system.world());
+        reply(system, caller, result);
+    }
+}
+
+impl UnbanCommand {
+    fn run(self, world: &World) -> anyhow::Result<String> {
+        let banned = if let Ok(ip) = self.player.parse::<IpAddr>() {
+            world.get::<&Moderation>(|moderation| moderation.unban_ip(ip))?
+        } else {
+            let target = Target::find(world, &self.player)?;
+            world.get::<&Moderation>(|moderation| moderation.unban(target.uuid))?
+        };
+
+        if banned {
+            Ok(format!("§aUnbanned {}", self.player))
+        } else {
+            bail!("{} is not banned", self.player)
+        }
+    }
+}
+
+/// Disconnects a player.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "kick")]
+#[command_permission(group = "Moderator")]
+pub struct KickCommand {
+    player: String,
+    reason: Vec<String>,
+}
+
+impl MinecraftCommand for KickCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(&system.world());
+        reply(system, caller, result);
+    }
+}
+
+impl KickCommand {
+    fn run(self, world: &World) -> anyhow::Result<String> {
+        let target = Target::find(world, &self.player)?;
+
+        let reason = if self.reason.is_empty() {
+            "§cYou were kicked from the server".to_owned()
+        } else {
+            format!("§cYou were kicked: §f{}", self.reason.join(" "))
+        };
+
+        if !target.kick(world, reason) {
+            bail!("{} is not online", self.player);
+        }
+
+        Ok(format!("§aKicked {}", target.name))
+    }
+}
+
+/// Stops a player from chatting.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "mute")]
+#[command_permission(group = "Moderator")]
+pub struct MuteCommand {
+    /// The name of an online player or the UUID of any player
+    player: String,
+    /// How long the mute lasts, e.g. `10m`. Permanent if omitted.
+    #[arg(short, long)]
+    duration: Option<humantime::Duration>,
+    reason: Vec<String>,
+}
+
+impl MinecraftCommand for MuteCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(system, caller);
+        reply(system, caller, result);
+    }
+}
+
+impl MuteCommand {
+    fn run(self, system: EntityView<'_>, caller: Entity) -> anyhow::Result<String> {
+        let world = system.world();
+
+        let target = Target::find(&world, &self.player)?;
+        let sanction = Sanction::new(
+            self.reason.join(" "),
+            name_of(&world, caller),
+            self.duration.map(Into::into),
+        );
+
+        world.get::<&Moderation>(|moderation| moderation.mute(target.uuid, &sanction))?;
+
+        if let Some(entity) = target.entity {
+            reply(system, entity, Ok(sanction.message("You were muted")));
+        }
+
+        Ok(format!("§aMuted {}", target.name))
+    }
+}
+
+/// Lets a muted player chat again.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "unmute")]
+#[command_permission(group = "Moderator")]
+pub struct UnmuteCommand {
+    player: String,
+}
+
+impl MinecraftCommand for UnmuteCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(&system.world());
+        reply(system, caller, result);
+    }
+}
+
+impl UnmuteCommand {
+    fn run(self, world: &World) -> anyhow::Result<String> {
+        let target = Target::find(world, &self.player)?;
+
+        if !world.get::<&Moderation>(|moderation| moderation.unmute(target.uuid))? {
+            bail!("{} is not muted", target.name);
+        }
+
+        Ok(format!("§aUnmuted {}", target.name))
+    }
+}
+
+/// Manages who can join while the whitelist is on.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "whitelist")]
+#[command_permission(group = "Admin")]
+pub struct WhitelistCommand {
+    action: WhitelistAction,
+    /// The name or UUID of the player to add or remove
+    player: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum WhitelistAction {
+    On,
+    Off,
+    Add,
+    Remove,
+    List,
+}
+
+impl MinecraftCommand for WhitelistCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let result = self.run(&system.world());
+        reply(system, caller, result);
+    }
+}
+
+impl WhitelistCommand {
+    fn run(self, world: &World) -> anyhow::Result<String> {
+        let target = || {
+            let player = self
+                .player
+                .as_deref()
+                .context("usage: /whitelist <add|remove> <player>")?;
+            Target::find(world, player)
+        };
+
+        match self.action {
+            WhitelistAction::On | WhitelistAction::Off => {
+                let on = matches!(self.action, WhitelistAction::On);
+                set_whitelist(world, on)?;
+                Ok(format!(
+                    "§aThe whitelist is {}",
+                    if on { "on" } else { "off" }
+                ))
+            }
+            WhitelistAction::Add => {
+                let target = target()?;
+                world.get::<&Moderation>(|moderation| {
+                    moderation.whitelist_add(target.uuid, &target.name)
+                })?;
+                Ok(format!("§aAdded {} to the whitelist", target.name))
+            }
+            WhitelistAction::Remove => {
+                let target = target()?;
+                if !world
+                    .get::<&Moderation>(|moderation| moderation.whitelist_remove(target.uuid))?
+                {
+                    bail!("{} is not on the whitelist", target.name);
+                }
+                Ok(format!("§aRemoved {} from the whitelist", target.name))
+            }
+            WhitelistAction::List => {
+                let players = world.get::<&Moderation>(Moderation::whitelisted)?;
+                let names: Vec<_> = players.into_iter().map(|(_, name)| name).collect();
+                Ok(format!(
+                    "§e{} whitelisted: §f{}",
+                    names.len(),
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Turns the whitelist on or off by changing the configuration file, which applies once the file
+/// is reloaded.
+fn set_whitelist(world: &World, on: bool) -> anyhow::Result<()> {
+    world.get::<(&Config, &reload::ConfigFile)>(|(config, file)| {
+        let new = config.with("whitelist", &on.to_string())?;
+        reload::check(config, &new)?;
+        new.save_setting(file.path(), "whitelist")
+    })
+}
+
+/// A player named in a command.
+struct Target {
+    uuid: uuid::Uuid,
+    name: String,
+    /// The player if they are online.
+    entity: Option<Entity>,
+}
+
+impl Target {
+    /// Finds an online player by name, or any player by UUID. In offline mode, players who are not
+    /// online can also be found by name.
+    fn find(world: &World, player: &str) -> anyhow::Result<Self> {
+        let entity = world.get::<&IgnMap>(|ign_map| ign_map.get(player).copied());
+
+        if let Some(entity) = entity {
+            let uuid = entity.entity_view(world).get::<&Uuid>(|uuid| uuid.0);
+
+            return Ok(Self {
+                uuid,
+                name: player.to_owned(),
+                entity: Some(entity),
+            });
+        }
+
+        if let Ok(uuid) = uuid::Uuid::parse_str(player) {
+            return Ok(Self {
+                uuid,
+                name: player.to_owned(),
+                entity: None,
+            });
+        }
+
+        if world.get::<&Config>(|config| config.online_mode) {
+            bail!("{player} is not online, use their UUID instead");
+        }
+
+        Ok(Self {
+            uuid: offline_uuid(player),
+            name: player.to_owned(),
+            entity: None,
+        })
+    }
+
+    /// The IP address of the player if they are online.
+    fn address(&self, world: &World) -> Option<IpAddr> {
+        let entity = self.entity?.entity_view(world);
+        entity.get::<&ConnectionInfo>(|info| info.address.map(|address| address.ip()))
+    }
+
+    /// Disconnects the player with `reason`. Returns whether they were online.
+    fn kick(&self, world: &World, reason: String) -> bool {
+        let Some(entity) = self.entity else {
+            return false;
+        };
+
+        entity.entity_view(world).set(PendingRemove::new(reason));
+        true
+    }
+}
+
+fn name_of(world: &World, caller: Entity) -> String {
+    caller.entity_view(world).get::<&Name>(ToString::to_string)
+}
+
+fn reply(system: EntityView<'_>, to: Entity, result: anyhow::Result<String>) {
+    let world = system.world();
+    let line = result.unwrap_or_else(|e| format!("§c{e:#}"));
+
+    world.get::<&Compose>(|compose| {
+        to.entity_view(world).get::<&ConnectionId>(|stream| {
+            compose
+                .unicast(&agnostic::chat(line), *stream, system)
+                .unwrap();
+        });
+    });
+}
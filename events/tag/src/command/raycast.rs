@@ -2,7 +2,7 @@ use clap::Parser;
 use flecs_ecs::core::{Entity, EntityView, EntityViewGet, WorldProvider};
 use hyperion::{
     glam::Vec3,
-    simulation::{Pitch, Position, Yaw, entity_kind::EntityKind},
+    simulation::{Pitch, Position, Yaw, entity_kind::EntityKind, worlds::WorldId},
 };
 use hyperion_clap::{CommandPermission, MinecraftCommand};
 use rayon::iter::Either;
@@ -48,21 +48,23 @@ impl MinecraftCommand for RaycastCommand {
 
         let world = system.world();
 
-        let ray =
-            caller
-                .entity_view(world)
-                .get::<(&Position, &Yaw, &Pitch)>(|(position, yaw, pitch)| {
-                    let center = **position;
+        let (ray, world_id) = caller
+            .entity_view(world)
+            .get::<(&Position, &Yaw, &Pitch, &WorldId)>(|(position, yaw, pitch, world_id)| {
+                let center = **position;
 
-                    let eye = center + Vec3::new(0.0, EYE_HEIGHT, 0.0);
-                    let direction = get_direction_from_rotation(**yaw, **pitch);
+                let eye = center + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+                let direction = get_direction_from_rotation(**yaw, **pitch);
 
-                    geometry::ray::Ray::new(eye, direction) * DISTANCE
-                });
+                (
+                    geometry::ray::Ray::new(eye, direction) * DISTANCE,
+                    *world_id,
+                )
+            });
 
         debug!("ray = {ray:?}");
 
-        let result = get_first_collision(ray, &world);
+        let result = get_first_collision(ray, world_id, &world);
 
         match result {
             Some(Either::Left(entity)) => {
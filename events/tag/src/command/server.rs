@@ -0,0 +1,22 @@
+use clap::Parser;
+use flecs_ecs::core::{Entity, EntityView, WorldProvider};
+use hyperion::ingress::transfer::Transfer;
+use hyperion_clap::{CommandPermission, MinecraftCommand};
+
+/// Moves the caller to another server behind the proxy.
+#[derive(Parser, CommandPermission, Debug)]
+#[command(name = "server")]
+#[command_permission(group = "Normal")]
+pub struct ServerCommand {
+    /// The name of the server as configured in the proxy
+    name: String,
+}
+
+impl MinecraftCommand for ServerCommand {
+    fn execute(self, system: EntityView<'_>, caller: Entity) {
+        let Self { name } = self;
+
+        let world = system.world();
+        caller.entity_view(world).set(Transfer::new(name));
+    }
+}
@@ -6,17 +6,17 @@ use flecs_ecs::{
 use hyperion::{
     BlockState,
     simulation::{
-        Pitch, Position, Spawn, Uuid, Velocity, Yaw,
+        EntitySize, Pitch, Position, Spawn, Uuid, Velocity, Yaw,
         entity_kind::EntityKind,
         metadata::{
             block_display::DisplayedBlockState,
             display::{Height, Width},
         },
+        physics::Physics,
     },
 };
 use hyperion_clap::{CommandPermission, MinecraftCommand};
-
-use crate::FollowClosestPlayer;
+use hyperion_npc::goal::{Goal, Goals};
 
 #[derive(Parser, CommandPermission, Debug)]
 #[command(name = "spawn")]
@@ -40,7 +40,15 @@ impl MinecraftCommand for SpawnCommand {
             .set(Pitch::new(0.0))
             .set(Yaw::new(0.0))
             .set(Velocity::new(0.0, 0.0, 0.0))
-            .add::<FollowClosestPlayer>()
+            .set(EntitySize::new(0.5, 1.0))
+            .set(Physics::LIVING)
+            .set(Goals::new([
+                Goal::Follow {
+                    range: 32.0,
+                    distance: 2.0,
+                },
+                Goal::Wander { radius: 8.0 },
+            ]))
             .set(DisplayedBlockState::new(BlockState::DIRT))
             // .is_a_id(prefabs.block_display_base)
             .enqueue(Spawn);
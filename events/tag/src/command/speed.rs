@@ -2,6 +2,7 @@ use clap::Parser;
 use flecs_ecs::core::{Entity, EntityView, EntityViewGet, WorldGet, WorldProvider};
 use hyperion::{
     net::{Compose, ConnectionId, DataBundle, agnostic},
+    simulation::anticheat::AntiCheat,
     valence_protocol::packets::play::{
         PlayerAbilitiesS2c, player_abilities_s2c::PlayerAbilitiesFlags,
     },
@@ -22,15 +23,19 @@ impl MinecraftCommand for SpeedCommand {
         let chat = agnostic::chat(msg);
 
         world.get::<&Compose>(|compose| {
-            caller.entity_view(world).get::<&ConnectionId>(|stream| {
-                let packet = speed_packet(self.amount);
+            caller
+                .entity_view(world)
+                .get::<(&ConnectionId, &mut AntiCheat)>(|(stream, anticheat)| {
+                    let packet = speed_packet(self.amount);
+                    // the speed is the flying speed, so the player may fly from now on
+                    anticheat.allow_flight(true);
 
-                let mut bundle = DataBundle::new(compose, system);
-                bundle.add_packet(&packet).unwrap();
-                bundle.add_packet(&chat).unwrap();
+                    let mut bundle = DataBundle::new(compose, system);
+                    bundle.add_packet(&packet).unwrap();
+                    bundle.add_packet(&chat).unwrap();
 
-                bundle.unicast(*stream).unwrap();
-            });
+                    bundle.unicast(*stream).unwrap();
+                });
         });
     }
 }
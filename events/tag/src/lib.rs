@@ -13,10 +13,12 @@ use module::{block::BlockModule, vanish::VanishModule};
 mod module;
 
 use derive_more::{Deref, DerefMut};
-use hyperion::{glam::IVec3, simulation::Position};
+use hyperion::glam::IVec3;
 use hyperion_rank_tree::Team;
-use module::{attack::AttackModule, level::LevelModule, regeneration::RegenerationModule};
-use spatial::SpatialIndex;
+use module::{
+    attack::AttackModule, level::LevelModule, regeneration::RegenerationModule,
+    scoreboard::ScoreboardModule,
+};
 
 use crate::{
     module::{bow::BowModule, chat::ChatModule, spawn::SpawnModule, stats::StatsModule},
@@ -43,14 +45,10 @@ impl Default for MainBlockCount {
     }
 }
 
-#[derive(Component)]
-struct FollowClosestPlayer;
-
 impl Module for TagModule {
     fn module(world: &World) {
         // on entity kind set UUID
 
-        world.component::<FollowClosestPlayer>();
         world.component::<MainBlockCount>();
 
         world
@@ -75,12 +73,16 @@ impl Module for TagModule {
         world.import::<LevelModule>();
         world.import::<BowModule>();
         world.import::<RegenerationModule>();
+        world.import::<ScoreboardModule>();
         world.import::<hyperion_permission::PermissionModule>();
         world.import::<hyperion_utils::HyperionUtilsModule>();
         world.import::<hyperion_clap::ClapCommandModule>();
         world.import::<SkinModule>();
         world.import::<VanishModule>();
-        world.import::<hyperion_genmap::GenMapModule>();
+        world.import::<hyperion_genmap::GeneratedMapModule>();
+        world.import::<hyperion_npc::NpcModule>();
+        world.import::<hyperion_hud::HudModule>();
+        world.import::<hyperion_plugin::PluginModule>();
 
         world.get::<&mut CommandRegistry>(|registry| {
             command::register(registry, world);
@@ -97,34 +99,6 @@ impl Module for TagModule {
         world
             .component::<Player>()
             .add_trait::<(flecs::With, spatial::Spatial)>();
-
-        system!(
-            "follow_closest_player",
-            world,
-            &SpatialIndex($),
-            &mut Position,
-        )
-        .with::<FollowClosestPlayer>()
-        .each_entity(|entity, (index, position)| {
-            let world = entity.world();
-
-            let Some(closest) = index.closest_to(**position, &world) else {
-                return;
-            };
-
-            closest.get::<&Position>(|target_position| {
-                let delta = **target_position - **position;
-
-                if delta.length_squared() < 0.01 {
-                    // we are already at the target position
-                    return;
-                }
-
-                let delta = delta.normalize() * 0.1;
-
-                **position += delta;
-            });
-        });
     }
 }
 
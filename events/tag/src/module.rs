@@ -4,6 +4,7 @@ pub mod bow;
 pub mod chat;
 pub mod level;
 pub mod regeneration;
+pub mod scoreboard;
 pub mod spawn;
 pub mod stats;
 pub mod vanish;
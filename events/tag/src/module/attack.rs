@@ -1,42 +1,36 @@
 use std::borrow::Cow;
 
-use compact_str::format_compact;
 use flecs_ecs::{
     core::{
-        Builder, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl,
-        World, WorldGet, flecs,
+        Builder, Entity, EntityViewGet, QueryAPI, QueryBuilderImpl, SystemAPI, TableIter,
+        TermBuilderImpl, World, WorldGet, flecs,
     },
-    macros::{Component, system},
+    macros::{Component, observer, system},
     prelude::Module,
 };
 use glam::IVec3;
 use hyperion::{
-    net::{
-        Compose, ConnectionId, agnostic,
-        packets::{BossBarAction, BossBarS2c},
-    },
+    net::{Compose, ConnectionId, agnostic},
     simulation::{
         PacketState, Pitch, Player, Position, Velocity, Xp, Yaw,
+        anticheat::AntiCheat,
         blocks::Blocks,
         event::{self, ClientStatusCommand, ClientStatusEvent},
         handlers::PacketSwitchQuery,
         metadata::{entity::Pose, living_entity::Health},
         packet::HandlerRegistry,
+        worlds::WorldId,
     },
-    storage::EventQueue,
-    uuid::Uuid,
+    storage::{self, EventBus, EventQueue, Persistent},
     valence_protocol::{
         ItemKind, ItemStack, Particle, VarInt, ident,
         math::{DVec3, Vec3},
         nbt,
-        packets::play::{
-            self,
-            boss_bar_s2c::{BossBarColor, BossBarDivision, BossBarFlags},
-            entity_attributes_s2c::AttributeProperty,
-        },
+        packets::play::{self, entity_attributes_s2c::AttributeProperty},
         text::IntoText,
     },
 };
+use hyperion_hud::{BossBar, BossBarColor};
 use hyperion_inventory::PlayerInventory;
 use hyperion_rank_tree::Team;
 use hyperion_utils::{EntityExt, LifetimeHandle};
@@ -70,12 +64,31 @@ pub struct CombatStats {
     pub protection: f32,
 }
 
+/// The boss bar showing a player their kills.
+#[derive(Component, Copy, Clone, Debug)]
+struct KillBar(Entity);
+
 #[derive(Component, Default, Copy, Clone, Debug)]
 #[meta]
 pub struct KillCount {
     pub kill_count: u32,
 }
 
+impl Persistent for KillCount {
+    type Record = u32;
+
+    const KEY: &'static str = "kill_count";
+    const VERSION: u32 = 1;
+
+    fn to_record(&self) -> Self::Record {
+        self.kill_count
+    }
+
+    fn from_record(kill_count: Self::Record) -> Self {
+        Self { kill_count }
+    }
+}
+
 #[allow(clippy::cast_possible_truncation)]
 impl Module for AttackModule {
     #[allow(clippy::excessive_nesting)]
@@ -85,6 +98,8 @@ impl Module for AttackModule {
         world.component::<Armor>().meta();
         world.component::<CombatStats>().meta();
         world.component::<KillCount>().meta();
+        world.component::<KillBar>();
+        storage::persist::<KillCount>(world);
 
         world
             .component::<Player>()
@@ -93,40 +108,39 @@ impl Module for AttackModule {
             .add_trait::<(flecs::With, KillCount)>()
             .add_trait::<(flecs::With, Armor)>();
 
-        let kill_count_uuid = Uuid::new_v4();
-
-        system!(
-            "kill_counts",
-            world,
-            &Compose($),
-            &KillCount,
-            &ConnectionId,
-        )
-        .with_enum(PacketState::Play)
-        .multi_threaded()
-        .kind::<flecs::pipeline::OnUpdate>()
-        .each_iter(move |it, _, (compose, kill_count, stream)| {
-            const MAX_KILLS: usize = 10;
-
-            let system = it.system();
-
-            let kills = kill_count.kill_count;
-            let title = format_compact!("{kills} kills");
-            let title = hyperion_text::Text::new(&title);
-            let health = (kill_count.kill_count as f32 / MAX_KILLS as f32).min(1.0);
-
-            let pkt = BossBarS2c {
-                id: kill_count_uuid,
-                action: BossBarAction::Add {
-                    title,
-                    health,
-                    color: BossBarColor::Red,
-                    division: BossBarDivision::NoDivision,
-                    flags: BossBarFlags::default(),
-                },
-            };
-
-            compose.unicast(&pkt, *stream, system).unwrap();
+        system!("kill_counts", world, &KillCount, ?&KillBar)
+            .with_enum(PacketState::Play)
+            .kind::<flecs::pipeline::OnUpdate>()
+            .each_iter(|it, row, (kill_count, kill_bar)| {
+                const MAX_KILLS: usize = 10;
+
+                let world = it.world();
+                let player = it.entity(row);
+
+                let kills = kill_count.kill_count;
+                let title = format!("{kills} kills");
+                let progress = (kills as f32 / MAX_KILLS as f32).min(1.0);
+
+                let Some(kill_bar) = kill_bar else {
+                    let mut bar = BossBar::new(title);
+                    bar.progress = progress;
+                    bar.color = BossBarColor::Red;
+                    bar.add_viewer(player.id());
+
+                    let bar = world.entity().set(bar);
+                    player.set(KillBar(bar.id()));
+                    return;
+                };
+
+                world.entity_from_id(kill_bar.0).get::<&mut BossBar>(|bar| {
+                    bar.title = title;
+                    bar.progress = progress;
+                });
+            });
+
+        // the kill bar leaves with its player
+        observer!(world, flecs::OnRemove, &KillBar).each_iter(|it, _, kill_bar| {
+            it.world().entity_from_id(kill_bar.0).destruct();
         });
 
         system!("handle_attacks", world, &mut EventQueue<event::AttackEntity>($), &Compose($))
@@ -152,7 +166,9 @@ impl Module for AttackModule {
                     for event in event_queue.drain() {
                         let target = world.entity_from_id(event.target);
                         let origin = world.entity_from_id(event.origin);
-                        origin.get::<(&ConnectionId, &Position, &mut KillCount, &mut PlayerInventory, &mut Armor, &CombatStats, &PlayerInventory, &Team, &mut Xp)>(|(origin_connection, origin_pos, kill_count, inventory, origin_armor, from_stats, from_inventory, origin_team, origin_xp)| {
+                        let target_world = target.try_get::<&WorldId>(|id| *id).unwrap_or_default();
+                        // only players fight here, attacks by NPCs are up to their own modules
+                        origin.try_get::<(&ConnectionId, &Position, &mut KillCount, &mut PlayerInventory, &mut Armor, &CombatStats, &PlayerInventory, &Team, &mut Xp)>(|(origin_connection, origin_pos, kill_count, inventory, origin_armor, from_stats, from_inventory, origin_team, origin_xp)| {
                             let damage = from_stats.damage + calculate_stats(from_inventory).damage;
                             target.try_get::<(
                                 &ConnectionId,
@@ -164,9 +180,10 @@ impl Module for AttackModule {
                                 &PlayerInventory,
                                 &Team,
                                 &mut Pose,
-                                &mut Xp
+                                &mut Xp,
+                                Option<&mut AntiCheat>
                             )>(
-                                |(target_connection, immune_until, health, target_position, target_yaw, stats, target_inventory, target_team, target_pose, target_xp)| {
+                                |(target_connection, immune_until, health, target_position, target_yaw, stats, target_inventory, target_team, target_pose, target_xp, target_anticheat)| {
                                     if let Some(immune_until) = immune_until {
                                         if immune_until.tick > current_tick {
                                             return;
@@ -193,7 +210,28 @@ impl Module for AttackModule {
                                     let damage_after_armor = get_damage_left(damage, armor, toughness);
                                     let damage_after_protection = get_inflicted_damage(damage_after_armor, protection);
 
-                                    health.damage(damage_after_protection);
+                                    let mut damage_event = event::Damage {
+                                        target: target.id(),
+                                        source: Some(origin.id()),
+                                        amount: damage_after_protection,
+                                    };
+
+                                    if !EventBus::dispatch(&world, &mut damage_event) {
+                                        return;
+                                    }
+
+                                    health.damage(damage_event.amount);
+
+                                    if health.is_dead() {
+                                        let mut death = event::Death {
+                                            entity: target.id(),
+                                            killer: Some(origin.id()),
+                                        };
+
+                                        if !EventBus::dispatch(&world, &mut death) {
+                                            health.heal(1.0);
+                                        }
+                                    }
 
                                     let pkt_health = play::HealthUpdateS2c {
                                         health: health.abs(),
@@ -501,7 +539,11 @@ impl Module for AttackModule {
                                         velocity: new_vel.to_packet_units(),
                                     };
 
-                                    compose.broadcast_local(&packet, target_position.to_chunk(), system).send().unwrap();
+                                    compose.broadcast_local(&packet, target_world, target_position.to_chunk(), system).send().unwrap();
+
+                                    if let Some(anticheat) = target_anticheat {
+                                        anticheat.expect_velocity(new_vel.0);
+                                    }
                                 },
                             );
                         });
@@ -554,6 +596,9 @@ impl Module for AttackModule {
                                     .compose
                                     .unicast(&pkt_teleport, *connection, query.system)
                                     .unwrap();
+
+                                let tick = query.compose.global().tick;
+                                query.anticheat.teleported(**position, tick);
                             }
                         },
                     );
@@ -15,10 +15,11 @@ use hyperion::{
         Xp,
         blocks::{Blocks, EntityAndSequence},
         event,
+        worlds::WorldId,
     },
-    storage::EventQueue,
+    storage::{EventQueue, Events},
     valence_protocol::{
-        BlockPos, BlockState, Particle, VarInt,
+        BlockPos, BlockState, ItemStack, Particle, VarInt,
         block::{PropName, PropValue},
         ident,
         math::{DVec3, IVec3, Vec3},
@@ -153,9 +154,9 @@ impl Module for BlockModule {
                 },
             );
 
-        system!("handle_destroyed_blocks", world, &mut Blocks($), &mut EventQueue<event::DestroyBlock>($), &Compose($), &OreVeins($))
+        system!("handle_destroyed_blocks", world, &mut Blocks($), &mut EventQueue<event::DestroyBlock>($), &Compose($), &OreVeins($), &Events($))
             .multi_threaded()
-            .each_iter(move |it: TableIter<'_, false>, _, (blocks, event_queue, compose, ore_veins): (&mut Blocks, &mut EventQueue<event::DestroyBlock>, &Compose, &OreVeins)| {
+            .each_iter(move |it: TableIter<'_, false>, _, (blocks, event_queue, compose, ore_veins, events): (&mut Blocks, &mut EventQueue<event::DestroyBlock>, &Compose, &OreVeins, &Events)| {
                 let span = info_span!("handle_blocks");
                 let _enter = span.enter();
                 let system = it.system();
@@ -215,6 +216,11 @@ impl Module for BlockModule {
                         return;
                     };
 
+                    // the mined ore pops out of the block
+                    let item = ItemStack::new(current.to_kind().to_item_kind(), 1, None);
+                    let item_drop = event::ItemDropEvent::from_block(item, event.position, WorldId::DEFAULT);
+                    events.push(item_drop, &world);
+
 
                     let from = event.from;
                     let from_entity = world.entity_from_id(from);
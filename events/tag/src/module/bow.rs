@@ -6,13 +6,14 @@ use hyperion::{
     ItemKind, ItemStack,
     glam::Vec3,
     simulation::{
-        Pitch, Position, Spawn, Uuid, Velocity, Yaw, bow::BowCharging, entity_kind::EntityKind,
-        event, get_direction_from_rotation,
+        Pitch, Position, Spawn, Uuid, Velocity, Yaw, blocks::Blocks, bow::BowCharging,
+        entity_kind::EntityKind, event, get_direction_from_rotation, physics::PhysicsState,
     },
     storage::EventQueue,
+    valence_protocol::BlockState,
 };
 use hyperion_inventory::PlayerInventory;
-use tracing::debug;
+use tracing::{debug, warn};
 
 #[derive(Component)]
 pub struct BowModule;
@@ -108,5 +109,19 @@ impl Module for BowModule {
                 );
             }
         });
+
+        // blocks hit by arrows turn into dirt
+        system!("arrows_hit_blocks", world, &mut Blocks($), &mut PhysicsState)
+            .with_enum(EntityKind::Arrow)
+            .kind::<flecs::pipeline::PostUpdate>()
+            .each(|(blocks, state)| {
+                let Some(position) = state.hit_block.take() else {
+                    return;
+                };
+
+                if let Err(e) = blocks.set_block(position, BlockState::DIRT) {
+                    warn!("failed to turn the block hit by an arrow into dirt: {e:?}");
+                }
+            });
     }
 }
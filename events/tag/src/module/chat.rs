@@ -1,98 +1,151 @@
-use flecs_ecs::{
-    core::{EntityViewGet, QueryBuilderImpl, SystemAPI, TableIter, TermBuilderImpl, World, flecs},
-    macros::{Component, system},
-    prelude::Module,
-};
-use hyperion::{
-    net::ConnectionId,
-    simulation::{Name, Player, Position, event},
-    storage::EventQueue,
-    valence_protocol::{packets::play, text::IntoText},
-};
-use hyperion_rank_tree::Team;
-use tracing::info_span;
+use flecs_ecs::prelude::*;
+use hyperion::simulation::{PacketState, Player};
+use hyperion_chat::{Audience, Channel, Channels, ChatStyle, DEFAULT_CHANNEL, Template};
+use hyperion_permission::{Group, PermissionGroups, PlayerPermissions};
+use hyperion_rank_tree::{Class, Team};
+use hyperion_text::{Color, NamedColor};
+
+use crate::module::attack::KillCount;
 
 const CHAT_COOLDOWN_SECONDS: i64 = 15; // 15 seconds
 const CHAT_COOLDOWN_TICKS: i64 = CHAT_COOLDOWN_SECONDS * 20; // Convert seconds to ticks
 
-#[derive(Default, Component)]
-#[meta]
-pub struct ChatCooldown {
-    pub expires: i64,
+const TEAM_CHANNEL: &str = "team";
+const STAFF_CHANNEL: &str = "staff";
+
+/// Players with this permission read and talk in the staff channel.
+const STAFF_PERMISSION: &str = "tag.chat.staff";
+
+/// Whether `receiver` is on the team of `sender`.
+fn teammates(world: &World, sender: Entity, receiver: Entity) -> bool {
+    let team = |player: Entity| world.entity_from_id(player).try_get::<&Team>(|team| *team);
+
+    team(sender).is_some_and(|sender| team(receiver) == Some(sender))
+}
+
+const fn team_color(team: Team) -> NamedColor {
+    match team {
+        Team::Blue => NamedColor::Blue,
+        Team::Green => NamedColor::Green,
+        Team::Red => NamedColor::Red,
+        Team::Yellow => NamedColor::Yellow,
+    }
+}
+
+fn rank_prefix(group: &str) -> &'static str {
+    if group == Group::Admin.name() {
+        "§c[Admin] "
+    } else if group == Group::Moderator.name() {
+        "§9[Mod] "
+    } else {
+        ""
+    }
 }
 
+fn channel(display_name: &str, template: &str, audience: Audience) -> Channel {
+    #[expect(clippy::unwrap_used, reason = "the templates of tag are valid")]
+    let template = Template::parse(template).unwrap();
+
+    Channel::new(display_name, template, audience)
+}
+
+/// The team, class and kills the [`ChatStyle`] of a player shows, so it is only built again when
+/// they change.
+#[derive(Component, Default)]
+struct Styled(Option<(Team, Class, u32)>);
+
 #[derive(Component)]
 pub struct ChatModule;
 
 impl Module for ChatModule {
     fn module(world: &World) {
-        world.component::<ChatCooldown>().meta();
-
-        world
-            .component::<Player>()
-            .add_trait::<(flecs::With, ChatCooldown)>();
+        world.import::<hyperion_chat::ChatModule>();
+        world.import::<hyperion_permission::PermissionModule>();
+
+        world.get::<&mut Channels>(|channels| {
+            channels.insert(DEFAULT_CHANNEL, Channel {
+                cooldown: CHAT_COOLDOWN_TICKS,
+                ..channel(
+                    "Global",
+                    "§8<{prefix}{name}§8>§r {message}",
+                    Audience::Local,
+                )
+            });
 
-        system!("handle_chat_messages", world, &mut EventQueue<event::ChatMessage>($), &hyperion::net::Compose($))
-            .multi_threaded()
-            .each_iter(move |it: TableIter<'_, false>, _: usize, (event_queue, compose): (&mut EventQueue<event::ChatMessage>, &hyperion::net::Compose)| {
-                let world = it.world();
-                let span = info_span!("handle_chat_messages");
-                let _enter = span.enter();
+            channels.insert(TEAM_CHANNEL, Channel {
+                cooldown: CHAT_COOLDOWN_TICKS,
+                ..channel(
+                    "Team",
+                    "§8[§b{channel}§8] {prefix}{name}§8:§r {message}",
+                    Audience::Filter(teammates),
+                )
+            });
 
-                let system = it.system();
+            channels.insert(STAFF_CHANNEL, Channel {
+                cooldown: CHAT_COOLDOWN_TICKS,
+                ..channel(
+                    "Staff",
+                    "§8[§c{channel}§8] {prefix}{name}§8:§r {message}",
+                    Audience::Members,
+                )
+            });
+        });
 
-                let current_tick = compose.global().tick;
+        world.get::<&mut PermissionGroups>(|groups| {
+            groups.grant_default(Group::Moderator.name(), STAFF_PERMISSION);
+        });
 
-                for event::ChatMessage { msg, by } in event_queue.drain() {
-                    let msg = msg.get();
-                    let by = world.entity_from_id(by);
+        world.component::<Styled>();
 
-                    // todo: we should not need this; death should occur such that this is always valid
-                    if !by.is_alive() {
-                        continue;
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Styled)>();
+
+        system!(
+            "sync_chat",
+            world,
+            &mut Channels($),
+            &PermissionGroups($),
+            &mut ChatStyle,
+            &mut Styled,
+            &PlayerPermissions,
+            &Team,
+            &Class,
+            &KillCount,
+        )
+        .with_enum(PacketState::Play)
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each_iter(
+            |it, row, (channels, groups, style, styled, permissions, team, class, kills)| {
+                let player = it.entity(row).id();
+
+                if let Some(staff) = channels.get_mut(STAFF_CHANNEL) {
+                    let permitted = groups.is_permitted(permissions, STAFF_PERMISSION);
+
+                    if permitted && !staff.is_member(player) {
+                        staff.join(player);
+                    } else if !permitted && staff.is_member(player) {
+                        staff.leave(player);
                     }
+                }
 
-                    // Check cooldown
-                    // todo: try_get if entity is dead/not found what will happen?
-                    by.get::<(&Name, &Position, &mut ChatCooldown, &ConnectionId, &Team)>(|(name, position, cooldown, io, team)| {
-                        // Check if player is still on cooldown
-                        if cooldown.expires > current_tick {
-                            let remaining_ticks = cooldown.expires - current_tick;
-                            let remaining_secs = remaining_ticks as f32 / 20.0;
-
-                            let cooldown_msg = format!("§cPlease wait {remaining_secs:.2} seconds before sending another message").into_cow_text();
-
-                            let packet = play::GameMessageS2c {
-                                chat: cooldown_msg,
-                                overlay: false,
-                            };
-
-                            compose.unicast(&packet, *io, system).unwrap();
-                            return;
-                        }
-
-                        cooldown.expires = current_tick + CHAT_COOLDOWN_TICKS;
-
-                        let color_prefix = match team {
-                            Team::Blue => "§9",
-                            Team::Green => "§a",
-                            Team::Red => "§c",
-                            Team::Yellow => "§e",
-                        };
-
-                        let chat = format!("§8<{color_prefix}{name}§8>§r {msg}").into_cow_text();
-                        let packet = play::GameMessageS2c {
-                            chat,
-                            overlay: false,
-                        };
-
-                        let center = position.to_chunk();
-
-                        compose.broadcast_local(&packet, center, system)
-                            .send()
-                            .unwrap();
-                    });
+                let prefix = rank_prefix(&permissions.group);
+                if style.prefix != prefix {
+                    prefix.clone_into(&mut style.prefix);
                 }
-            });
+
+                let source = (*team, *class, kills.kill_count);
+                if styled.0 == Some(source) {
+                    return;
+                }
+
+                styled.0 = Some(source);
+                style.color = Some(Color::Named(team_color(*team)));
+                style.hover = format!(
+                    "§7Team: {team:?}\n§7Class: {class:?}\n§7Kills: {}",
+                    kills.kill_count
+                );
+            },
+        );
     }
 }
@@ -0,0 +1,107 @@
+use flecs_ecs::prelude::*;
+use hyperion::{
+    simulation::{Name, PacketState, Player, event},
+    storage::{EventBus, Priority},
+};
+use hyperion_rank_tree::Team;
+use hyperion_scoreboard::{
+    DisplaySlot, NameTagVisibility, ObjectiveRenderType, Scoreboard, Sidebar,
+    Team as ScoreboardTeam, TeamColor,
+};
+
+use crate::module::attack::KillCount;
+
+const KILLS: &str = "kills";
+
+const TEAMS: [Team; 4] = [Team::Blue, Team::Green, Team::Red, Team::Yellow];
+
+/// The name, display name and color of the scoreboard team of `team`.
+const fn scoreboard_team(team: Team) -> (&'static str, &'static str, TeamColor) {
+    match team {
+        Team::Blue => ("blue", "Blue", TeamColor::Blue),
+        Team::Green => ("green", "Green", TeamColor::Green),
+        Team::Red => ("red", "Red", TeamColor::Red),
+        Team::Yellow => ("yellow", "Yellow", TeamColor::Yellow),
+    }
+}
+
+#[derive(Component)]
+pub struct ScoreboardModule;
+
+impl Module for ScoreboardModule {
+    fn module(world: &World) {
+        world.import::<hyperion_scoreboard::ScoreboardModule>();
+
+        world
+            .component::<Player>()
+            .add_trait::<(flecs::With, Sidebar)>();
+
+        world.get::<&mut Scoreboard>(|scoreboard| {
+            scoreboard.set_objective(KILLS, "Kills", ObjectiveRenderType::Integer);
+            scoreboard.display(DisplaySlot::List, Some(KILLS));
+            scoreboard.display(DisplaySlot::BelowName, Some(KILLS));
+
+            for team in TEAMS {
+                let (name, display_name, color) = scoreboard_team(team);
+
+                // core sends no teams, so these alone hide the name tags of players
+                scoreboard.set_team(name, ScoreboardTeam {
+                    color,
+                    friendly_fire: false,
+                    name_tag_visibility: NameTagVisibility::Never,
+                    ..ScoreboardTeam::new(display_name)
+                });
+            }
+        });
+
+        world.get::<&mut EventBus>(|bus| {
+            bus.listen(
+                Priority::Monitor,
+                |quit: &mut event::PlayerQuit, context| {
+                    let world = context.world;
+
+                    world.entity_from_id(quit.player).try_get::<&Name>(|name| {
+                        world.get::<&mut Scoreboard>(|scoreboard| scoreboard.remove_entry(name));
+                    });
+                },
+            );
+        });
+
+        system!(
+            "sync_scoreboard",
+            world,
+            &mut Scoreboard($),
+            &mut Sidebar,
+            &Name,
+            &Team,
+            &KillCount,
+        )
+        .with_enum(PacketState::Play)
+        .kind::<flecs::pipeline::OnUpdate>()
+        .each(|(scoreboard, sidebar, name, team, kills)| {
+            let (team_name, display_name, _) = scoreboard_team(*team);
+
+            #[expect(
+                clippy::cast_possible_wrap,
+                reason = "nobody gets more than i32::MAX kills"
+            )]
+            let score = kills.kill_count as i32;
+
+            scoreboard.join_team(team_name, name);
+            scoreboard.set_score(KILLS, name, score);
+
+            let lines = [
+                format!("Team: {display_name}"),
+                format!("Kills: {}", kills.kill_count),
+            ];
+
+            // the sidebar is only resent when it changes
+            if sidebar.lines != lines {
+                *sidebar = Sidebar {
+                    title: "§6§lTag".to_owned(),
+                    lines: lines.to_vec(),
+                };
+            }
+        });
+    }
+}
@@ -0,0 +1,171 @@
+//! Maintenance of the database of a Hyperion server.
+//!
+//! `backup`, `list` and `dump` can run while the server is running. `compact` and `restore`
+//! replace the database file, so the server has to be stopped first.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, ensure};
+use clap::{Parser, Subcommand};
+use heed::{CompactionOption, types};
+use hyperion::{CONFIG_PATH, config::Config, storage::LocalDb};
+use tracing::info;
+
+/// The file LMDB keeps the data in, inside the database directory.
+const DATA_FILE: &str = "data.mdb";
+
+#[derive(Parser)]
+#[clap(version)]
+struct Params {
+    /// The server configuration, which has the location and size of the database
+    #[clap(long, default_value = CONFIG_PATH)]
+    config: PathBuf,
+
+    /// The database directory, overriding the one in the configuration
+    #[clap(long)]
+    path: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the named databases, e.g. `uuid-to-skins`, and how many entries they have
+    List,
+    /// Print the entries of a named database as hex, one `key value` pair per line
+    Dump {
+        name: String,
+        /// Stop after this many entries
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+    /// Write a compacted copy of the database to a file
+    Backup { file: PathBuf },
+    /// Rewrite the database without its free pages. The server must not be running.
+    Compact,
+    /// Replace the database with a backup. The server must not be running. The current data is
+    /// kept next to it as `data.mdb.old`.
+    Restore { file: PathBuf },
+}
+
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let params = Params::parse();
+
+    let mut database = if params.config.exists() {
+        let contents = std::fs::read_to_string(&params.config)
+            .with_context(|| format!("failed to read {}", params.config.display()))?;
+        Config::parse(&contents)?.database
+    } else {
+        Config::default().database
+    };
+
+    if let Some(path) = params.path {
+        database.path = path;
+    }
+
+    match params.command {
+        Command::List => list(&LocalDb::new(&database)?),
+        Command::Dump { name, limit } => dump(&LocalDb::new(&database)?, &name, limit),
+        Command::Backup { file } => backup(&LocalDb::new(&database)?, &file),
+        Command::Compact => compact(LocalDb::new(&database)?, &database.path),
+        Command::Restore { file } => restore(&file, &database.path),
+    }
+}
+
+fn list(db: &LocalDb) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+
+    db.read(|rtxn| {
+        // the names of the named databases are the keys of the unnamed one
+        let Some(main) = db.open_database::<types::Str, types::DecodeIgnore>(rtxn, None)? else {
+            return Ok(());
+        };
+
+        for entry in main.iter(rtxn)? {
+            let (name, ()) = entry?;
+
+            let Some(named) = db.open_database::<types::Bytes, types::Bytes>(rtxn, Some(name))?
+            else {
+                continue;
+            };
+
+            writeln!(out, "{name}\t{}", named.len(rtxn)?)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn dump(db: &LocalDb, name: &str, limit: Option<usize>) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+
+    db.read(|rtxn| {
+        let named = db
+            .open_database::<types::Bytes, types::Bytes>(rtxn, Some(name))?
+            .with_context(|| format!("there is no database named {name}"))?;
+
+        for entry in named.iter(rtxn)?.take(limit.unwrap_or(usize::MAX)) {
+            let (key, value) = entry?;
+            writeln!(out, "{} {}", hex::encode(key), hex::encode(value))?;
+        }
+
+        Ok(())
+    })
+}
+
+fn backup(db: &LocalDb, file: &Path) -> anyhow::Result<()> {
+    ensure!(!file.exists(), "{} already exists", file.display());
+
+    db.copy_to_path(file, CompactionOption::Enabled)?;
+
+    info!("backed up the database to {}", file.display());
+
+    Ok(())
+}
+
+fn compact(db: LocalDb, path: &Path) -> anyhow::Result<()> {
+    let data = path.join(DATA_FILE);
+    let compacted = path.join(format!("{DATA_FILE}.compact"));
+
+    let before = std::fs::metadata(&data)?.len();
+
+    backup(&db, &compacted)?;
+
+    // the file can only be replaced once the environment is closed
+    let closing = (*db).clone().prepare_for_closing();
+    drop(db);
+    closing.wait();
+
+    std::fs::rename(&compacted, &data)?;
+
+    let after = std::fs::metadata(&data)?.len();
+    info!("compacted the database from {before} to {after} bytes");
+
+    Ok(())
+}
+
+fn restore(file: &Path, path: &Path) -> anyhow::Result<()> {
+    ensure!(file.is_file(), "{} is not a backup file", file.display());
+
+    std::fs::create_dir_all(path)?;
+
+    let data = path.join(DATA_FILE);
+
+    if data.exists() {
+        let old = path.join(format!("{DATA_FILE}.old"));
+        std::fs::rename(&data, &old)?;
+        info!("moved the current database to {}", old.display());
+    }
+
+    std::fs::copy(file, &data)?;
+
+    info!("restored the database from {}", file.display());
+
+    Ok(())
+}